@@ -0,0 +1,318 @@
+//! # Cairo Native derive macros
+//!
+//! Derive macros for the `IntoValue` and `FromValue` traits of `cairo_native::typed`.
+//!
+//! Structs (with named, unnamed or no fields) map to Cairo structs with the same member order.
+//! Enums map to Cairo enums with the same variant order, where:
+//!   - Unit variants have a unit (`()`) payload.
+//!   - Variants with a single unnamed field use that field's type as their payload.
+//!   - Every other variant uses a struct with its fields as the payload.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, parse_quote, Data, DeriveInput, Fields, GenericParam, Generics, Ident,
+    Index, Type,
+};
+
+#[proc_macro_derive(IntoValue)]
+pub fn derive_into_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_into_value(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(FromValue)]
+pub fn derive_from_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_from_value(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_into_value(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let generics = add_trait_bounds(input.generics.clone(), quote!(IntoValue));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let (into_value, check_type) = match &input.data {
+        Data::Struct(data) => {
+            let bindings = field_bindings(&data.fields);
+            let pattern = destructure(quote!(Self), &data.fields, &bindings);
+
+            let into_value = quote! {
+                let #pattern = self;
+                __private::struct_value(::std::vec![#(IntoValue::into_value(#bindings)),*])
+            };
+            let check_type = check_fields(
+                quote!(IntoValue),
+                quote!(type_id),
+                &field_types(&data.fields),
+            );
+
+            (into_value, check_type)
+        }
+        Data::Enum(data) => {
+            let num_variants = data.variants.len();
+
+            let mut arms = Vec::with_capacity(num_variants);
+            let mut checks = Vec::with_capacity(num_variants);
+            for (tag, variant) in data.variants.iter().enumerate() {
+                let variant_name = &variant.ident;
+                let bindings = field_bindings(&variant.fields);
+                let pattern = destructure(quote!(Self::#variant_name), &variant.fields, &bindings);
+
+                let payload = match payload_kind(&variant.fields) {
+                    PayloadKind::Single => {
+                        let binding = &bindings[0];
+                        quote!(IntoValue::into_value(#binding))
+                    }
+                    PayloadKind::Struct => quote! {
+                        __private::struct_value(::std::vec![#(IntoValue::into_value(#bindings)),*])
+                    },
+                };
+                arms.push(quote! {
+                    #pattern => __private::enum_value(#tag, #payload),
+                });
+
+                let variant_type_id = quote!(&variants[#tag]);
+                checks.push(match payload_kind(&variant.fields) {
+                    PayloadKind::Single => {
+                        let ty = &field_types(&variant.fields)[0];
+                        quote!(<#ty as IntoValue>::check_type(registry, #variant_type_id)?;)
+                    }
+                    PayloadKind::Struct => check_fields(
+                        quote!(IntoValue),
+                        variant_type_id,
+                        &field_types(&variant.fields),
+                    ),
+                });
+            }
+
+            let into_value = quote! {
+                match self {
+                    #(#arms)*
+                }
+            };
+            let check_type = quote! {
+                let variants = __private::check_enum(registry, type_id, #num_variants)?;
+                #(#checks)*
+            };
+
+            (into_value, check_type)
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "IntoValue cannot be derived for unions",
+            ))
+        }
+    };
+
+    Ok(quote! {
+        const _: () = {
+            use ::cairo_native::typed::{IntoValue, __private};
+
+            impl #impl_generics IntoValue for #name #ty_generics #where_clause {
+                fn into_value(self) -> ::cairo_native::Value {
+                    #into_value
+                }
+
+                fn check_type(
+                    registry: &__private::ProgramRegistry,
+                    type_id: &__private::ConcreteTypeId,
+                ) -> ::cairo_native::error::Result<()> {
+                    #check_type
+                    ::std::result::Result::Ok(())
+                }
+            }
+        };
+    })
+}
+
+fn expand_from_value(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let name_str = name.to_string();
+    let generics = add_trait_bounds(input.generics.clone(), quote!(FromValue));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let (from_value, check_type) = match &input.data {
+        Data::Struct(data) => {
+            let from_value =
+                from_struct_value(quote!(Self), &name_str, quote!(value), &data.fields);
+            let check_type = check_fields(
+                quote!(FromValue),
+                quote!(type_id),
+                &field_types(&data.fields),
+            );
+
+            (from_value, check_type)
+        }
+        Data::Enum(data) => {
+            let num_variants = data.variants.len();
+
+            let mut arms = Vec::with_capacity(num_variants);
+            let mut checks = Vec::with_capacity(num_variants);
+            for (tag, variant) in data.variants.iter().enumerate() {
+                let variant_name = &variant.ident;
+                let variant_str = format!("{name_str}::{variant_name}");
+
+                let constructor = match payload_kind(&variant.fields) {
+                    PayloadKind::Single => quote! {
+                        ::std::result::Result::Ok(Self::#variant_name(FromValue::from_value(payload)?))
+                    },
+                    PayloadKind::Struct => from_struct_value(
+                        quote!(Self::#variant_name),
+                        &variant_str,
+                        quote!(payload),
+                        &variant.fields,
+                    ),
+                };
+                arms.push(quote! {
+                    #tag => { #constructor }
+                });
+
+                let variant_type_id = quote!(&variants[#tag]);
+                checks.push(match payload_kind(&variant.fields) {
+                    PayloadKind::Single => {
+                        let ty = &field_types(&variant.fields)[0];
+                        quote!(<#ty as FromValue>::check_type(registry, #variant_type_id)?;)
+                    }
+                    PayloadKind::Struct => check_fields(
+                        quote!(FromValue),
+                        variant_type_id,
+                        &field_types(&variant.fields),
+                    ),
+                });
+            }
+
+            let from_value = quote! {
+                let (tag, payload) = __private::enum_variant(value, #num_variants, #name_str)?;
+                match tag {
+                    #(#arms)*
+                    _ => ::std::unreachable!("the variant index has already been validated"),
+                }
+            };
+            let check_type = quote! {
+                let variants = __private::check_enum(registry, type_id, #num_variants)?;
+                #(#checks)*
+            };
+
+            (from_value, check_type)
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "FromValue cannot be derived for unions",
+            ))
+        }
+    };
+
+    Ok(quote! {
+        const _: () = {
+            use ::cairo_native::typed::{FromValue, __private};
+
+            impl #impl_generics FromValue for #name #ty_generics #where_clause {
+                fn from_value(value: ::cairo_native::Value) -> ::cairo_native::error::Result<Self> {
+                    #from_value
+                }
+
+                fn check_type(
+                    registry: &__private::ProgramRegistry,
+                    type_id: &__private::ConcreteTypeId,
+                ) -> ::cairo_native::error::Result<()> {
+                    #check_type
+                    ::std::result::Result::Ok(())
+                }
+            }
+        };
+    })
+}
+
+enum PayloadKind {
+    /// The payload is the variant's only unnamed field.
+    Single,
+    /// The payload is a struct containing the variant's fields (if any).
+    Struct,
+}
+
+fn payload_kind(fields: &Fields) -> PayloadKind {
+    match fields {
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => PayloadKind::Single,
+        _ => PayloadKind::Struct,
+    }
+}
+
+/// Add the given trait bound to every type parameter.
+fn add_trait_bounds(mut generics: Generics, bound: TokenStream2) -> Generics {
+    for param in &mut generics.params {
+        if let GenericParam::Type(type_param) = param {
+            type_param
+                .bounds
+                .push(parse_quote!(::cairo_native::typed::#bound));
+        }
+    }
+
+    generics
+}
+
+fn field_types(fields: &Fields) -> Vec<&Type> {
+    fields.iter().map(|field| &field.ty).collect()
+}
+
+/// Generate an identifier for every field, to be used when destructuring.
+fn field_bindings(fields: &Fields) -> Vec<Ident> {
+    (0..fields.len())
+        .map(|idx| format_ident!("__field{}", idx))
+        .collect()
+}
+
+/// Generate a pattern which binds every field to its identifier from `field_bindings`.
+fn destructure(path: TokenStream2, fields: &Fields, bindings: &[Ident]) -> TokenStream2 {
+    match fields {
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|field| &field.ident);
+            quote!(#path { #(#names: #bindings),* })
+        }
+        Fields::Unnamed(_) => quote!(#path(#(#bindings),*)),
+        Fields::Unit => quote!(#path),
+    }
+}
+
+/// Generate the code that checks a struct type with the given member types.
+fn check_fields(trait_name: TokenStream2, type_id: TokenStream2, types: &[&Type]) -> TokenStream2 {
+    let num_fields = types.len();
+    let indices = (0..num_fields).map(Index::from);
+
+    quote! {
+        let members = __private::check_struct(registry, #type_id, #num_fields)?;
+        #(<#types as #trait_name>::check_type(registry, &members[#indices])?;)*
+    }
+}
+
+/// Generate the code that builds `path` from a struct value.
+fn from_struct_value(
+    path: TokenStream2,
+    name: &str,
+    value: TokenStream2,
+    fields: &Fields,
+) -> TokenStream2 {
+    let num_fields = fields.len();
+    let bindings = field_bindings(fields);
+
+    let constructor = match fields {
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|field| &field.ident);
+            quote!(#path { #(#names: FromValue::from_value(#bindings)?),* })
+        }
+        Fields::Unnamed(_) => quote!(#path(#(FromValue::from_value(#bindings)?),*)),
+        Fields::Unit => quote!(#path),
+    };
+
+    quote! {
+        let [#(#bindings),*] = __private::struct_fields::<#num_fields>(#value, #name)?;
+        ::std::result::Result::Ok(#constructor)
+    }
+}
@@ -0,0 +1,322 @@
+//! # Runtime allocator
+//!
+//! Every heap allocation performed by the compiled programs and the runtime (arrays, boxes,
+//! nullables, `Felt252Dict` storage and syscall results) goes through an [`AllocatorVTable`]. By
+//! default it forwards to libc's `realloc` and `free`, but embedders can replace it for the
+//! duration of an invocation:
+//!
+//! ```ignore
+//! let arena = ArenaAllocator::new();
+//! let result = with_allocator(&arena, || {
+//!     executor.run(selector, EntryPointKind::External, &calldata, gas, None, &mut syscall_handler)
+//! })?;
+//! // Every allocation made by the execution is released here.
+//! drop(arena);
+//! ```
+//!
+//! The installed allocator is stored in a thread local and will also be used by any nested
+//! execution (for example, contract calls performed by the syscall handler) running on the same
+//! thread. Memory allocated by one allocator must never be freed by another one, therefore the
+//! allocator must remain installed until the invocation's results have been parsed, which the
+//! executors do before returning.
+
+use bumpalo::Bump;
+use std::{alloc::Layout, cell::Cell, ffi::c_void, marker::PhantomData, ptr};
+
+thread_local! {
+    static ALLOCATOR: Cell<AllocatorVTable> = const { Cell::new(AllocatorVTable::LIBC) };
+}
+
+/// An allocator usable by the compiled programs.
+///
+/// Implementations must return pointers aligned to at least 16 bytes, as libc's `malloc` does.
+pub trait NativeAllocator {
+    /// Resize the allocation at `ptr` to `len` bytes, preserving its contents. A null `ptr` must
+    /// behave as a new allocation. Returns the (possibly moved) pointer.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must either be null or have been returned by this same allocator.
+    unsafe fn realloc(&self, ptr: *mut c_void, len: usize) -> *mut c_void;
+
+    /// Release the allocation at `ptr`. Null pointers must be ignored.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must either be null or have been returned by this same allocator.
+    unsafe fn free(&self, ptr: *mut c_void);
+}
+
+/// The C-compatible representation of an allocator.
+///
+/// The `data` pointer is passed as the first argument to both functions.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct AllocatorVTable {
+    pub data: *mut c_void,
+    pub realloc: unsafe extern "C" fn(*mut c_void, *mut c_void, usize) -> *mut c_void,
+    pub free: unsafe extern "C" fn(*mut c_void, *mut c_void),
+}
+
+impl AllocatorVTable {
+    /// The default allocator, which forwards to libc.
+    pub const LIBC: Self = Self {
+        data: ptr::null_mut(),
+        realloc: libc_realloc_impl,
+        free: libc_free_impl,
+    };
+
+    /// Build a vtable which forwards to the given allocator.
+    ///
+    /// The vtable borrows the allocator without tracking its lifetime. Prefer
+    /// [`AllocatorGuard::install`], which does.
+    pub fn from_allocator<A>(allocator: &A) -> Self
+    where
+        A: NativeAllocator,
+    {
+        unsafe extern "C" fn realloc_impl<A: NativeAllocator>(
+            data: *mut c_void,
+            ptr: *mut c_void,
+            len: usize,
+        ) -> *mut c_void {
+            (*data.cast::<A>()).realloc(ptr, len)
+        }
+
+        unsafe extern "C" fn free_impl<A: NativeAllocator>(data: *mut c_void, ptr: *mut c_void) {
+            (*data.cast::<A>()).free(ptr)
+        }
+
+        Self {
+            data: (allocator as *const A).cast_mut().cast(),
+            realloc: realloc_impl::<A>,
+            free: free_impl::<A>,
+        }
+    }
+}
+
+unsafe extern "C" fn libc_realloc_impl(
+    _data: *mut c_void,
+    ptr: *mut c_void,
+    len: usize,
+) -> *mut c_void {
+    crate::utils::libc_realloc(ptr, len)
+}
+
+unsafe extern "C" fn libc_free_impl(_data: *mut c_void, ptr: *mut c_void) {
+    crate::utils::libc_free(ptr)
+}
+
+/// Installs an allocator for the current thread, restoring the previous one when dropped.
+#[derive(Debug)]
+pub struct AllocatorGuard<'a> {
+    previous: AllocatorVTable,
+    // The guard is tied to a thread local, therefore it shouldn't be sent to another thread.
+    phantom: PhantomData<(&'a (), *const ())>,
+}
+
+impl<'a> AllocatorGuard<'a> {
+    /// Install the given allocator until the guard is dropped.
+    pub fn install<A>(allocator: &'a A) -> Self
+    where
+        A: NativeAllocator,
+    {
+        unsafe { Self::install_vtable(AllocatorVTable::from_allocator(allocator)) }
+    }
+
+    /// Install a raw allocator vtable until the guard is dropped.
+    ///
+    /// # Safety
+    ///
+    /// The vtable's `data` must remain valid while the guard is alive.
+    pub unsafe fn install_vtable(vtable: AllocatorVTable) -> Self {
+        Self {
+            previous: ALLOCATOR.replace(vtable),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl Drop for AllocatorGuard<'_> {
+    fn drop(&mut self) {
+        ALLOCATOR.set(self.previous);
+    }
+}
+
+/// Run the closure with the given allocator installed for the current thread.
+pub fn with_allocator<A, T>(allocator: &A, f: impl FnOnce() -> T) -> T
+where
+    A: NativeAllocator,
+{
+    let _guard = AllocatorGuard::install(allocator);
+    f()
+}
+
+/// A bump arena allocator.
+///
+/// Frees are no-ops: every allocation is released at once when the arena is dropped or reset,
+/// which makes it suitable for per-transaction arenas.
+#[derive(Debug, Default)]
+pub struct ArenaAllocator {
+    bump: Bump,
+}
+
+impl ArenaAllocator {
+    /// Every allocation is prefixed by its size, padded to keep the data aligned.
+    const HEADER_SIZE: usize = 16;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the number of bytes currently allocated by the arena, including its overhead.
+    pub fn allocated_bytes(&self) -> usize {
+        self.bump.allocated_bytes()
+    }
+
+    /// Release every allocation while keeping the arena's largest chunk for reuse.
+    pub fn reset(&mut self) {
+        self.bump.reset();
+    }
+}
+
+impl NativeAllocator for ArenaAllocator {
+    unsafe fn realloc(&self, ptr: *mut c_void, len: usize) -> *mut c_void {
+        let old_len = if ptr.is_null() {
+            0
+        } else {
+            ptr.byte_sub(Self::HEADER_SIZE).cast::<usize>().read()
+        };
+        if !ptr.is_null() && len <= old_len {
+            return ptr;
+        }
+
+        let Some(layout) = len
+            .checked_add(Self::HEADER_SIZE)
+            .and_then(|size| Layout::from_size_align(size, Self::HEADER_SIZE).ok())
+        else {
+            return ptr::null_mut();
+        };
+        let Ok(new_ptr) = self.bump.try_alloc_layout(layout) else {
+            return ptr::null_mut();
+        };
+        let new_ptr = new_ptr.as_ptr();
+        new_ptr.cast::<usize>().write(len);
+        let new_ptr = new_ptr.byte_add(Self::HEADER_SIZE);
+
+        if !ptr.is_null() {
+            ptr::copy_nonoverlapping(ptr.cast::<u8>(), new_ptr, old_len);
+        }
+
+        new_ptr.cast()
+    }
+
+    unsafe fn free(&self, _ptr: *mut c_void) {}
+}
+
+/// Reallocate (or allocate, if null) a pointer using the current thread's allocator.
+pub(crate) unsafe fn native_realloc(ptr: *mut c_void, len: usize) -> *mut c_void {
+    let vtable = ALLOCATOR.get();
+    (vtable.realloc)(vtable.data, ptr, len)
+}
+
+/// Allocate memory using the current thread's allocator.
+pub(crate) unsafe fn native_malloc(len: usize) -> *mut c_void {
+    native_realloc(ptr::null_mut(), len)
+}
+
+/// Free a pointer using the current thread's allocator.
+pub(crate) unsafe fn native_free(ptr: *mut c_void) {
+    let vtable = ALLOCATOR.get();
+    (vtable.free)(vtable.data, ptr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{context::NativeContext, executor::JitNativeExecutor, utils::test::load_cairo};
+    use crate::{values::Value, OptLevel};
+    use starknet_types_core::felt::Felt;
+
+    #[test]
+    fn arena_realloc_preserves_contents() {
+        let arena = ArenaAllocator::new();
+
+        unsafe {
+            let ptr = arena.realloc(ptr::null_mut(), 4).cast::<u32>();
+            assert_eq!(ptr as usize % 16, 0);
+            ptr.write(0xDEADBEEF);
+
+            let ptr = arena.realloc(ptr.cast(), 64).cast::<u32>();
+            assert_eq!(ptr as usize % 16, 0);
+            assert_eq!(ptr.read(), 0xDEADBEEF);
+
+            arena.free(ptr.cast());
+        }
+    }
+
+    #[test]
+    fn arena_realloc_overflow() {
+        let arena = ArenaAllocator::new();
+
+        unsafe {
+            assert!(arena.realloc(ptr::null_mut(), usize::MAX).is_null());
+            assert!(arena
+                .realloc(ptr::null_mut(), isize::MAX as usize)
+                .is_null());
+        }
+    }
+
+    #[test]
+    fn guard_restores_previous_allocator() {
+        let arena = ArenaAllocator::new();
+
+        {
+            let _guard = AllocatorGuard::install(&arena);
+            unsafe { native_free(native_malloc(32)) };
+        }
+
+        assert!(arena.allocated_bytes() > 0);
+        assert!(ALLOCATOR.get().data.is_null());
+    }
+
+    #[test]
+    fn run_with_arena() {
+        let (module_name, program) = load_cairo! {
+            fn run_test() -> Array<felt252> {
+                let mut data = ArrayTrait::new();
+                let mut i = 0;
+                while i != 100 {
+                    data.append(i);
+                    i += 1;
+                }
+                data
+            }
+        };
+
+        let context = NativeContext::new();
+        let module = context
+            .compile(&program, false, Some(Default::default()))
+            .unwrap();
+        let executor = JitNativeExecutor::from_native_module(module, OptLevel::default()).unwrap();
+
+        let entry_point = format!("{0}::{0}::run_test", module_name);
+        let function_id = &program
+            .funcs
+            .iter()
+            .find(|f| f.id.debug_name.as_deref() == Some(entry_point.as_str()))
+            .unwrap()
+            .id;
+
+        let arena = ArenaAllocator::new();
+        let result = with_allocator(&arena, || {
+            executor.invoke_dynamic(function_id, &[], Some(u64::MAX))
+        })
+        .unwrap();
+
+        assert!(arena.allocated_bytes() > 0);
+        assert_eq!(
+            result.return_value,
+            Value::Array((0..100).map(Felt::from).map(Value::Felt252).collect()),
+        );
+    }
+}
@@ -1,9 +1,9 @@
 use crate::{
+    allocator::native_malloc,
     error::Result,
     native_panic,
     starknet::{ArrayAbi, Secp256k1Point, Secp256r1Point},
     types::TypeBuilder,
-    utils::libc_malloc,
     values::Value,
 };
 use bumpalo::Bump;
@@ -81,7 +81,7 @@ impl AbiArgument for ValueWithInfoWrapper<'_> {
 
                 let layout = self.registry.get_type(&info.ty)?.layout(self.registry)?;
                 let heap_ptr = unsafe {
-                    let heap_ptr = libc_malloc(layout.size());
+                    let heap_ptr = native_malloc(layout.size());
                     libc::memcpy(heap_ptr, ptr.as_ptr().cast(), layout.size());
                     heap_ptr
                 };
@@ -101,7 +101,7 @@ impl AbiArgument for ValueWithInfoWrapper<'_> {
 
                     let layout = self.registry.get_type(&info.ty)?.layout(self.registry)?;
                     let heap_ptr = unsafe {
-                        let heap_ptr = libc_malloc(layout.size());
+                        let heap_ptr = native_malloc(layout.size());
                         libc::memcpy(heap_ptr, ptr.as_ptr().cast(), layout.size());
                         heap_ptr
                     };
@@ -6,14 +6,15 @@ use cairo_lang_runner::short_string::as_cairo_short_string;
 use cairo_lang_sierra_to_casm::metadata::MetadataComputationConfig;
 use cairo_native::{
     context::NativeContext,
+    execution_result::ExecutionResult,
     executor::{AotNativeExecutor, JitNativeExecutor},
     metadata::gas::GasMetadata,
     starknet_stub::StubSyscallHandler,
 };
 use clap::{Parser, ValueEnum};
-use std::path::PathBuf;
+use std::{fs, path::PathBuf};
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
-use utils::{find_function, result_to_runresult};
+use utils::{find_function, result_to_runresult, FunctionArgs};
 
 mod utils;
 
@@ -23,6 +24,12 @@ enum RunMode {
     Jit,
 }
 
+#[derive(Clone, Debug, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
 /// Command line args parser.
 /// Exits with 1 if the compilation or run fails, otherwise 0.
 #[derive(Parser, Debug)]
@@ -45,6 +52,18 @@ struct Args {
     /// Optimization level, Valid: 0, 1, 2, 3. Values higher than 3 are considered as 3.
     #[arg(short = 'O', long, default_value_t = 0)]
     opt_level: u8,
+    /// The function to run, matched as a suffix of its full path.
+    #[arg(long, default_value = "::main")]
+    function: String,
+    /// The function's arguments as a JSON array of either `Value`s or Cairo-serialized felts.
+    #[arg(long, conflicts_with = "args_file")]
+    args: Option<String>,
+    /// Read the function's arguments from a JSON file, in the same format as `--args`.
+    #[arg(long)]
+    args_file: Option<PathBuf>,
+    /// How to print the execution result. The JSON output contains the whole `ExecutionResult`.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
 
     #[cfg(feature = "with-trace-dump")]
     #[arg(long)]
@@ -95,6 +114,15 @@ fn main() -> anyhow::Result<()> {
         .compile(&sierra_program, false, Some(Default::default()))
         .unwrap();
 
+    let func = find_function(&sierra_program, &args.function)?;
+    let function_args = match (&args.args, &args.args_file) {
+        (Some(input), _) => serde_json::from_str::<FunctionArgs>(input)?,
+        (None, Some(path)) => serde_json::from_str::<FunctionArgs>(&fs::read_to_string(path)?)?,
+        (None, None) => FunctionArgs::Values(Vec::new()),
+    }
+    .into_values(native_module.program_registry(), &func.signature)
+    .with_context(|| "Invalid function arguments.")?;
+
     let native_executor: Box<dyn Fn(_, _, _, &mut StubSyscallHandler) -> _> = match args.run_mode {
         RunMode::Aot => {
             let executor =
@@ -156,18 +184,45 @@ fn main() -> anyhow::Result<()> {
     let gas_metadata =
         GasMetadata::new(&sierra_program, Some(MetadataComputationConfig::default())).unwrap();
 
-    let func = find_function(&sierra_program, "::main")?;
-
     let initial_gas = gas_metadata
         .get_initial_available_gas(&func.id, args.available_gas)
         .with_context(|| "not enough gas to run")?;
 
     let mut syscall_handler = StubSyscallHandler::default();
 
-    let result = native_executor(&func.id, &[], Some(initial_gas), &mut syscall_handler)
-        .with_context(|| "Failed to run the function.")?;
+    let result = native_executor(
+        &func.id,
+        &function_args,
+        Some(initial_gas),
+        &mut syscall_handler,
+    )
+    .with_context(|| "Failed to run the function.")?;
+
+    match args.output_format {
+        OutputFormat::Text => print_result(&result)?,
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&result)?),
+    }
 
-    let run_result = result_to_runresult(&result)?;
+    #[cfg(feature = "with-trace-dump")]
+    if let Some(trace_output) = args.trace_output {
+        let traces = cairo_native::metadata::trace_dump::trace_dump_runtime::TRACE_DUMP
+            .lock()
+            .unwrap();
+        assert_eq!(traces.len(), 1);
+
+        let trace_dump = traces.values().next().unwrap();
+        serde_json::to_writer_pretty(
+            std::fs::File::create(trace_output).unwrap(),
+            &trace_dump.trace,
+        )
+        .unwrap();
+    }
+
+    Ok(())
+}
+
+fn print_result(result: &ExecutionResult) -> anyhow::Result<()> {
+    let run_result = result_to_runresult(result)?;
 
     match run_result {
         cairo_lang_runner::RunResultValue::Success(values) => {
@@ -188,20 +243,5 @@ fn main() -> anyhow::Result<()> {
         println!("Remaining gas: {gas}");
     }
 
-    #[cfg(feature = "with-trace-dump")]
-    if let Some(trace_output) = args.trace_output {
-        let traces = cairo_native::metadata::trace_dump::trace_dump_runtime::TRACE_DUMP
-            .lock()
-            .unwrap();
-        assert_eq!(traces.len(), 1);
-
-        let trace_dump = traces.values().next().unwrap();
-        serde_json::to_writer_pretty(
-            std::fs::File::create(trace_output).unwrap(),
-            &trace_dump.trace,
-        )
-        .unwrap();
-    }
-
     Ok(())
 }
@@ -0,0 +1,104 @@
+use anyhow::{bail, Context};
+use cairo_native::cache::{CompileClient, CompileServer, DiskProgramCache};
+use clap::{Parser, Subcommand};
+use std::{fs, io, os::unix::net::UnixListener, path::PathBuf, sync::Arc, time::Duration};
+use tracing_subscriber::{EnvFilter, FmtSubscriber};
+
+/// Compiles the Sierra programs and contract classes requested by other processes through a Unix
+/// domain socket, storing them in a shared on-disk cache. Clients receive the path of the compiled
+/// shared library once it's ready.
+#[derive(Parser, Debug)]
+#[clap(version, verbatim_doc_comment)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the server.
+    Serve {
+        /// The path of the socket to listen on.
+        socket: PathBuf,
+        /// The directory of the cache.
+        #[arg(long)]
+        cache_dir: PathBuf,
+        /// The maximum size of the cache in bytes. The least recently used entries are removed
+        /// when it's exceeded.
+        #[arg(long)]
+        max_cache_size: Option<u64>,
+        /// How long to wait for an entry being compiled by another process, in seconds.
+        #[arg(long, default_value_t = 900)]
+        lock_timeout: u64,
+        /// How long a lock has to remain unmodified to be considered stale and removed, in
+        /// seconds.
+        #[arg(long, default_value_t = 600)]
+        stale_lock_age: u64,
+    },
+    /// Print the statistics of a running server as JSON.
+    Stats {
+        /// The path of the server's socket.
+        socket: PathBuf,
+    },
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    tracing::subscriber::set_global_default(
+        FmtSubscriber::builder()
+            .with_env_filter(EnvFilter::from_default_env())
+            .finish(),
+    )?;
+
+    match args.command {
+        Command::Serve {
+            socket,
+            cache_dir,
+            max_cache_size,
+            lock_timeout,
+            stale_lock_age,
+        } => {
+            let mut cache = DiskProgramCache::new(&cache_dir)
+                .with_context(|| format!("Failed to open the cache at {}", cache_dir.display()))?;
+            if let Some(max_size) = max_cache_size {
+                cache = cache.with_max_size(max_size);
+            }
+
+            // A socket file left by a server which didn't shut down cleanly must be removed before
+            // binding to it again.
+            if socket.exists() {
+                if CompileClient::connect(&socket).is_ok() {
+                    bail!("a server is already listening at {}", socket.display());
+                }
+                match fs::remove_file(&socket) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                        return Err(e).context("Failed to remove the stale socket")
+                    }
+                    _ => {}
+                }
+            }
+
+            let listener = UnixListener::bind(&socket)
+                .with_context(|| format!("Failed to listen at {}", socket.display()))?;
+            tracing::info!(
+                "listening at {}, caching into {}",
+                socket.display(),
+                cache_dir.display()
+            );
+
+            let server = CompileServer::new(cache)
+                .with_lock_timeout(Duration::from_secs(lock_timeout))
+                .with_stale_lock_age(Duration::from_secs(stale_lock_age));
+            Arc::new(server).serve(listener)?;
+        }
+        Command::Stats { socket } => {
+            let stats = CompileClient::connect(&socket)
+                .with_context(|| format!("Failed to connect to {}", socket.display()))?
+                .stats()?;
+            println!("{}", serde_json::to_string_pretty(&stats)?);
+        }
+    }
+
+    Ok(())
+}
@@ -4,47 +4,52 @@
 //!
 //! ## Walkthrough
 //!
-//! Iterates through N rounds (specified as an argument), and in each round:
-//! - Generates an unique dummy starknet contract.
-//! - Compiles the program and inserts the compiled program into the cache.
-//! - Executes the program.
+//! Iterates through N rounds (specified as an argument), which are shared between compile threads
+//! and execute threads working concurrently. In each round:
+//! - A compile thread obtains the round's program from the selected workload (see `workload`).
+//! - It compiles the program and inserts the compiled program into the cache.
+//! - An execute thread receives the executor and executes the program.
 //!
-//! The cache is dropped at the end of the whole execution.
+//! The programs are compiled into a `DiskProgramCache` stored in `AOT_CACHE_DIR`. Locks left there
+//! by an interrupted run are removed at startup. Executors are dropped once executed, so that memory
+//! which isn't released shows up as a leak, unless asked to keep them loaded until the end of the
+//! whole execution.
 //!
-//! For documentation on the specific cache used, see `NaiveAotCache`.
+//! At the end of every round, the memory used (as tracked by the global allocator) and the
+//! resident set size are logged. When any of them grows monotonically during `leak_window` rounds
+//! by at least `leak_threshold` bytes, a possible leak is reported (see `memory`). Since memory
+//! grows by design when executors are kept, leak detection is disabled in that case.
 
-use cairo_lang_sierra::{
-    ids::FunctionId,
-    program::{GenericArg, Program},
-    program_registry::ProgramRegistry,
-};
-use cairo_lang_starknet::compile::compile_path;
+use cairo_lang_sierra::{ids::FunctionId, program::Program};
 use cairo_native::{
-    context::NativeContext,
-    executor::AotNativeExecutor,
-    metadata::gas::GasMetadata,
-    module_to_object, object_to_shared_lib,
-    starknet::DummySyscallHandler,
-    utils::{find_entry_point_by_idx, SHARED_LIBRARY_EXT},
+    cache::DiskProgramCache, executor::AotNativeExecutor, starknet_stub::StubSyscallHandler,
     OptLevel,
 };
 use clap::Parser;
-use libloading::Library;
-use num_bigint::BigInt;
+use memory::{resident_set_size, GrowthDetector};
+use starknet_types_core::felt::Felt;
 use stats_alloc::{Region, StatsAlloc, INSTRUMENTED_SYSTEM};
 use std::{
     alloc::System,
-    collections::HashMap,
-    fmt::{Debug, Display},
-    fs::{self, create_dir_all, read_dir, OpenOptions},
-    hash::Hash,
+    collections::HashSet,
+    fs::{read_dir, OpenOptions},
     io,
     path::{Path, PathBuf},
-    sync::Arc,
-    time::Instant,
+    process,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
 };
-use tracing::{debug, info, info_span, warn};
+use tracing::{debug, error, info, info_span, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+use workload::{build_workload, Workload, WorkloadKind, WorkloadProgram};
+
+mod memory;
+mod workload;
 
 #[global_allocator]
 static GLOBAL_ALLOC: &StatsAlloc<System> = &INSTRUMENTED_SYSTEM;
@@ -52,11 +57,6 @@ static GLOBAL_ALLOC: &StatsAlloc<System> = &INSTRUMENTED_SYSTEM;
 /// The directory used to store compiled native programs
 const AOT_CACHE_DIR: &str = ".aot-cache";
 
-/// An unique value hardcoded into the initial contract that it's
-/// used as an anchor point to safely modify it.
-/// It can be any value as long as it's unique in the contract.
-const UNIQUE_CONTRACT_VALUE: u32 = 835;
-
 /// A stress tester for Cairo Native
 ///
 /// It compiles Sierra programs with Cairo Native, caches, and executes them with AOT runner.
@@ -68,6 +68,60 @@ struct StressTestCommand {
     /// Output file for JSON formatted logs
     #[arg(short, long)]
     output: Option<PathBuf>,
+    /// Kind of programs to compile and execute
+    #[arg(short, long, value_enum, default_value_t = WorkloadKind::Dummy)]
+    workload: WorkloadKind,
+    /// Directory with the contract classes of the corpus workload
+    #[arg(long, required_if_eq("workload", "corpus"))]
+    corpus: Option<PathBuf>,
+    /// Amount of threads compiling programs
+    #[arg(long, default_value_t = 1)]
+    compile_threads: usize,
+    /// Amount of threads executing programs
+    #[arg(long, default_value_t = 1)]
+    execute_threads: usize,
+    /// Keep every executor loaded until the end, instead of dropping them once executed.
+    /// Disables leak detection, since memory grows by design
+    #[arg(long)]
+    keep_executors: bool,
+    /// Amount of consecutive rounds in which memory must grow to report a possible leak
+    #[arg(long, default_value_t = 100)]
+    leak_window: usize,
+    /// Minimum growth in bytes within the window to report a possible leak
+    #[arg(long, default_value_t = 16 * 1024 * 1024)]
+    leak_threshold: u64,
+    /// Exit with an error if a possible leak was reported
+    #[arg(long, conflicts_with = "keep_executors")]
+    fail_on_leak: bool,
+}
+
+/// A compiled program, waiting to be executed.
+struct CompiledRound {
+    round: u32,
+    before_round: Instant,
+    executor: AotNativeExecutor,
+    entry_point: FunctionId,
+    expected_result: Option<u64>,
+}
+
+/// The state shared between the compile and execute threads.
+struct StressTest<'a> {
+    args: &'a StressTestCommand,
+    workload: Box<dyn Workload>,
+    cache: DiskProgramCache,
+    global_region: Region<'a, System>,
+    next_round: AtomicU32,
+    program_keys: Mutex<HashSet<String>>,
+    kept_executors: Mutex<Vec<AotNativeExecutor>>,
+    leak_detector: Mutex<LeakDetector>,
+}
+
+/// Tracks the memory measurements of every round, looking for possible leaks.
+struct LeakDetector {
+    memory_used: GrowthDetector,
+    rss: GrowthDetector,
+    /// The number of rounds in which a possible leak was reported.
+    reports: u32,
 }
 
 fn main() {
@@ -80,244 +134,257 @@ fn main() {
     }
 
     // Generate initial program
-    let (entry_point, program) = {
+    let workload = {
         let before_generate = Instant::now();
-        let initial_program = generate_starknet_contract(UNIQUE_CONTRACT_VALUE);
+        let workload = build_workload(args.workload, args.corpus.as_deref());
         let elapsed = before_generate.elapsed().as_millis();
-        debug!(time = elapsed, "generated test program");
-        initial_program
+        debug!(time = elapsed, workload = ?args.workload, "generated workload");
+        workload
     };
 
-    let global_region = Region::new(GLOBAL_ALLOC);
+    // Initialize the cache
+    let cache = DiskProgramCache::new(AOT_CACHE_DIR).expect("failed to open aot cache");
+
+    // Programs are only compiled by this process, so any lock is a leftover of an interrupted run
+    // which would block its program forever.
+    let removed_locks = cache
+        .remove_stale_locks(Duration::ZERO)
+        .expect("failed to remove stale locks");
+    if removed_locks > 0 {
+        warn!("removed {removed_locks} stale locks from {AOT_CACHE_DIR}");
+    }
+
     let before_stress_test = Instant::now();
 
-    // Initialize context and cache
-    let native_context = NativeContext::new();
-    let mut cache = NaiveAotCache::new(&native_context);
+    let stress_test = StressTest {
+        args: &args,
+        workload,
+        cache,
+        global_region: Region::new(GLOBAL_ALLOC),
+        next_round: AtomicU32::new(0),
+        program_keys: Mutex::default(),
+        kept_executors: Mutex::default(),
+        leak_detector: Mutex::new(LeakDetector {
+            memory_used: GrowthDetector::new(args.leak_window, args.leak_threshold),
+            rss: GrowthDetector::new(args.leak_window, args.leak_threshold),
+            reports: 0,
+        }),
+    };
 
     info!("starting stress test");
 
-    for round in 0..args.rounds {
-        let _enter_round_span = info_span!("round", number = round).entered();
+    // The channel is bounded so that compile threads don't get too far ahead of the execute ones.
+    let (sender, receiver) = mpsc::sync_channel(args.execute_threads.max(1));
+    let receiver = Arc::new(Mutex::new(receiver));
+    thread::scope(|s| {
+        let stress_test = &stress_test;
+        for _ in 0..args.compile_threads.max(1) {
+            let sender = sender.clone();
+            s.spawn(move || stress_test.compile_rounds(sender));
+        }
+        // When every execute thread is gone, even if they panicked, the receiver is dropped and
+        // the compile threads stop.
+        for _ in 0..args.execute_threads.max(1) {
+            let receiver = receiver.clone();
+            s.spawn(move || stress_test.execute_rounds(receiver));
+        }
+        drop((sender, receiver));
+    });
 
-        let before_round = Instant::now();
+    let reports = stress_test.leak_detector.into_inner().unwrap().reports;
+    drop(stress_test.kept_executors);
 
-        let program = modify_starknet_contract(program.clone(), UNIQUE_CONTRACT_VALUE, round);
-        // TODO: use the program hash instead of round number.
-        let hash = round;
+    let elapsed = before_stress_test.elapsed().as_millis();
+    info!(
+        time = elapsed,
+        possible_leaks = reports,
+        "finished stress test"
+    );
 
-        debug!(hash, "obtained test program");
+    if args.fail_on_leak && reports > 0 {
+        error!("possible leaks were reported in {reports} rounds");
+        process::exit(1);
+    }
+}
 
-        if cache.get(&hash).is_some() {
-            panic!("all program keys should be different")
-        }
+impl StressTest<'_> {
+    /// Compiles the programs of the pending rounds, sending them to the execute threads.
+    fn compile_rounds(&self, sender: SyncSender<CompiledRound>) {
+        loop {
+            let round = self.next_round.fetch_add(1, Ordering::Relaxed);
+            if round >= self.args.rounds {
+                break;
+            }
 
-        // Compiles and caches the program
-        let executor = {
-            let before_compile = Instant::now();
-            let executor = cache.compile_and_insert(hash, &program, cairo_native::OptLevel::None);
-            let elapsed = before_compile.elapsed().as_millis();
-            debug!(time = elapsed, "compiled test program");
-            executor
-        };
+            let _enter_round_span = info_span!("round", number = round).entered();
 
-        // Executes the program
-        let execution_result = {
-            let now = Instant::now();
-            let execution_result = executor
-                .invoke_contract_dynamic(&entry_point, &[], Some(u64::MAX), DummySyscallHandler)
-                .expect("failed to execute contract");
-            let elapsed = now.elapsed().as_millis();
-            let result = execution_result.return_values[0];
-            debug!(time = elapsed, result = %result, "executed test program");
-            execution_result
-        };
+            let before_round = Instant::now();
 
-        assert!(
-            !execution_result.failure_flag,
-            "contract execution had failure flag set"
-        );
-
-        // Logs end of round
-        let elapsed = before_round.elapsed().as_millis();
-        let cache_disk_size =
-            directory_get_size(AOT_CACHE_DIR).expect("failed to calculate cache disk size");
-        let global_stats = global_region.change();
-        let memory_used = global_stats.bytes_allocated - global_stats.bytes_deallocated;
-        info!(
-            time = elapsed,
-            memory_used = memory_used,
-            cache_disk_size = cache_disk_size,
-            "finished round"
-        );
-    }
+            let Some(WorkloadProgram {
+                program,
+                entry_point,
+                expected_result,
+            }) = self.workload.generate(round)
+            else {
+                continue;
+            };
+            let hash = DiskProgramCache::key(&program, OptLevel::None);
 
-    let elapsed = before_stress_test.elapsed().as_millis();
-    info!(time = elapsed, "finished stress test");
-}
+            debug!(hash = %hash, "obtained test program");
 
-/// Generate a dummy starknet contract
-///
-/// The contract contains an external main function that returns `return_value`
-fn generate_starknet_contract(
-    return_value: u32,
-) -> (FunctionId, cairo_lang_sierra::program::Program) {
-    let program_str = format!(
-        "\
-#[starknet::contract]
-mod Contract {{
-    #[storage]
-    struct Storage {{}}
-
-    #[external(v0)]
-    fn main(self: @ContractState) -> felt252 {{
-        return {return_value};
-    }}
-}}
-"
-    );
+            if self.workload.is_unique() && !self.program_keys.lock().unwrap().insert(hash) {
+                panic!("all program keys should be different")
+            }
 
-    let mut program_file = tempfile::Builder::new()
-        .prefix("test_")
-        .suffix(".cairo")
-        .tempfile()
-        .expect("failed to create temporary file for cairo test program");
-    fs::write(&mut program_file, program_str).expect("failed to write cairo test file");
-
-    let contract_class = compile_path(program_file.path(), None, Default::default())
-        .expect("failed to compile cairo contract");
-
-    let program = contract_class
-        .extract_sierra_program()
-        .expect("failed to extract sierra program");
-
-    let entry_point_idx = contract_class
-        .entry_points_by_type
-        .external
-        .first()
-        .expect("contract should have at least one entrypoint")
-        .function_idx;
-
-    let entry_point = find_entry_point_by_idx(&program, entry_point_idx)
-        .expect("failed to find entrypoint")
-        .id
-        .clone();
-
-    (entry_point, program)
-}
+            // Compiles and caches the program
+            let executor = {
+                let before_compile = Instant::now();
+                let executor = self.compile_program(&program);
+                let elapsed = before_compile.elapsed().as_millis();
+                debug!(time = elapsed, "compiled test program");
+                executor
+            };
 
-/// Modifies the given contract by replacing the `old_value` with `new_value` in any type declaration
-///
-/// The contract must only contain the value `old_value` once
-fn modify_starknet_contract(mut program: Program, old_value: u32, new_value: u32) -> Program {
-    let mut old_value_counter = 0;
-
-    for type_declaration in &mut program.type_declarations {
-        for generic_arg in &mut type_declaration.long_id.generic_args {
-            let anchor = BigInt::from(old_value);
-
-            match generic_arg {
-                GenericArg::Value(return_value) if *return_value == anchor => {
-                    *return_value = BigInt::from(new_value);
-                    old_value_counter += 1;
-                }
-                _ => {}
+            let compiled_round = CompiledRound {
+                round,
+                before_round,
+                executor,
+                entry_point,
+                expected_result,
             };
+            if sender.send(compiled_round).is_err() {
+                break;
+            }
         }
     }
 
-    assert!(
-        old_value_counter == 1,
-        "old_value was not found exactly once"
-    );
-
-    program
-}
-
-/// A naive implementation of an AOT Program Cache
-///
-/// For each contract:
-/// - Compiles it to a shared library and stores it on disk (`AOT_CACHE_DIR`)
-/// - Loads the shared library into an executor and stores it on a hashmap with the given key.
-///
-/// The shared libraries are unloaded when the executor gets drop, AKA: when the cache gets dropped.
-/// The shared libraries on disk are never deleted
-///
-/// Possible improvements include:
-/// - Keeping only some executors on memory, while storing the remaining compiled shared libraries only on disk.
-/// - When restarting the program, reutilize already compiled programs from `AOT_CACHE_DIR`
-struct NaiveAotCache<'a, K>
-where
-    K: PartialEq + Eq + Hash + Display,
-{
-    context: &'a NativeContext,
-    cache: HashMap<K, Arc<AotNativeExecutor>>,
-}
+    fn compile_program(&self, program: &Program) -> AotNativeExecutor {
+        loop {
+            if let Some(executor) = self
+                .cache
+                .compile_and_insert(program, OptLevel::None)
+                .expect("failed to compile program")
+            {
+                return executor;
+            }
 
-impl<'a, K> NaiveAotCache<'a, K>
-where
-    K: PartialEq + Eq + Hash + Display,
-{
-    pub fn new(context: &'a NativeContext) -> Self {
-        Self {
-            context,
-            cache: Default::default(),
+            // The same program is being compiled by another thread, wait for it.
+            thread::sleep(Duration::from_millis(10));
+            if let Some(executor) = self
+                .cache
+                .get(program, OptLevel::None)
+                .expect("failed to load program")
+            {
+                return executor;
+            }
         }
     }
 
-    pub fn get(&self, key: &K) -> Option<Arc<AotNativeExecutor>> {
-        self.cache.get(key).cloned()
-    }
+    /// Executes the compiled programs until there are no more rounds.
+    fn execute_rounds(&self, receiver: Arc<Mutex<Receiver<CompiledRound>>>) {
+        loop {
+            let Ok(compiled_round) = receiver.lock().unwrap().recv() else {
+                break;
+            };
+            let CompiledRound {
+                round,
+                before_round,
+                executor,
+                entry_point,
+                expected_result,
+            } = compiled_round;
+
+            let _enter_round_span = info_span!("round", number = round).entered();
+
+            // Executes the program
+            let execution_result = {
+                let now = Instant::now();
+                let execution_result = executor
+                    .invoke_contract_dynamic(
+                        &entry_point,
+                        &[],
+                        Some(u64::MAX),
+                        &mut StubSyscallHandler::default(),
+                    )
+                    .expect("failed to execute contract");
+                let elapsed = now.elapsed().as_millis();
+                let result = execution_result.return_values.first();
+                debug!(
+                    time = elapsed,
+                    result = ?result,
+                    failure_flag = execution_result.failure_flag,
+                    "executed test program"
+                );
+                execution_result
+            };
 
-    /// Compiles and inserts a given program into the cache
-    ///
-    /// The dynamic library is stored in `AOT_CACHE_DIR` directory
-    pub fn compile_and_insert(
-        &mut self,
-        key: K,
-        program: &Program,
-        opt_level: OptLevel,
-    ) -> Arc<AotNativeExecutor> {
-        let native_module = self
-            .context
-            .compile(program, false, Some(Default::default()))
-            .expect("failed to compile program");
-
-        let registry = ProgramRegistry::new(program).expect("failed to get program registry");
-        let metadata = native_module
-            .metadata()
-            .get::<GasMetadata>()
-            .cloned()
-            .expect("module should have gas metadata");
-
-        let shared_library = {
-            let object_data = module_to_object(native_module.module(), opt_level)
-                .expect("failed to convert MLIR to object");
-
-            let shared_library_dir = Path::new(AOT_CACHE_DIR);
-            create_dir_all(shared_library_dir).expect("failed to create shared library directory");
-            let shared_library_name = format!("lib{key}{SHARED_LIBRARY_EXT}");
-            let shared_library_path = shared_library_dir.join(shared_library_name);
-
-            object_to_shared_lib(&object_data, &shared_library_path)
-                .expect("failed to link object into shared library");
-
-            unsafe {
-                Library::new(shared_library_path).expect("failed to load dynamic shared library")
+            if let Some(expected_result) = expected_result {
+                assert!(
+                    !execution_result.failure_flag,
+                    "contract execution had failure flag set"
+                );
+                assert_eq!(
+                    execution_result.return_values,
+                    [Felt::from(expected_result)],
+                    "contract execution returned an unexpected result"
+                );
             }
-        };
 
-        let executor = AotNativeExecutor::new(
-            shared_library,
-            registry,
-            metadata,
-            native_module.metadata().get().cloned().unwrap_or_default(),
-        );
-        let executor = Arc::new(executor);
+            if self.args.keep_executors {
+                self.kept_executors.lock().unwrap().push(executor);
+            } else {
+                drop(executor);
+            }
+
+            // Logs end of round
+            let elapsed = before_round.elapsed().as_millis();
+            let cache_disk_size =
+                directory_get_size(AOT_CACHE_DIR).expect("failed to calculate cache disk size");
+            let global_stats = self.global_region.change();
+            let memory_used = global_stats
+                .bytes_allocated
+                .saturating_sub(global_stats.bytes_deallocated);
+            let rss = resident_set_size();
+            info!(
+                time = elapsed,
+                memory_used = memory_used,
+                rss = rss,
+                cache_disk_size = cache_disk_size,
+                "finished round"
+            );
+
+            if !self.args.keep_executors {
+                self.leak_detector
+                    .lock()
+                    .unwrap()
+                    .record(memory_used as u64, rss);
+            }
+        }
+    }
+}
+
+impl LeakDetector {
+    fn record(&mut self, memory_used: u64, rss: Option<u64>) {
+        let mut reported = false;
 
-        self.cache.insert(key, executor.clone());
+        if let Some(growth) = self.memory_used.push(memory_used) {
+            warn!(
+                growth = growth,
+                "memory used grew monotonically, possible leak"
+            );
+            reported = true;
+        }
+        if let Some(growth) = rss.and_then(|rss| self.rss.push(rss)) {
+            warn!(
+                growth = growth,
+                "resident set size grew monotonically, possible leak"
+            );
+            reported = true;
+        }
 
-        executor
+        if reported {
+            self.reports += 1;
+        }
     }
 }
 
@@ -0,0 +1,101 @@
+//! Memory usage tracking and leak detection.
+
+use std::collections::VecDeque;
+
+/// Returns the resident set size of the current process in bytes
+///
+/// Only supported on Linux, returns `None` elsewhere.
+pub fn resident_set_size() -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        // The second field is the number of resident pages.
+        let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+        let resident_pages = statm.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+
+        Some(resident_pages * u64::try_from(page_size).ok()?)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    None
+}
+
+/// Detects when a memory measurement grows monotonically
+///
+/// Keeps the last `window` samples, and reports a growth when none of them is lower than the
+/// previous one and the difference between the first and the last is at least `min_growth`.
+/// Memory which grows for a while and then stabilizes, like caches warming up, is not reported
+/// once the window only contains stable samples.
+#[derive(Debug)]
+pub struct GrowthDetector {
+    window: usize,
+    min_growth: u64,
+    samples: VecDeque<u64>,
+}
+
+impl GrowthDetector {
+    pub fn new(window: usize, min_growth: u64) -> Self {
+        Self {
+            window: window.max(2),
+            min_growth,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Adds a sample, returning the growth within the window if it's monotonic.
+    pub fn push(&mut self, sample: u64) -> Option<u64> {
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+
+        if self.samples.len() < self.window {
+            return None;
+        }
+
+        let is_monotonic = self
+            .samples
+            .iter()
+            .zip(self.samples.iter().skip(1))
+            .all(|(prev, next)| next >= prev);
+        let growth = self.samples.back()? - self.samples.front()?;
+
+        (is_monotonic && growth >= self.min_growth).then_some(growth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_growth_detector() {
+        let mut detector = GrowthDetector::new(4, 10);
+
+        // Not enough samples yet.
+        for sample in [0, 10, 20] {
+            assert_eq!(detector.push(sample), None);
+        }
+        assert_eq!(detector.push(30), Some(30));
+
+        // Decreasing samples break the monotonic growth.
+        assert_eq!(detector.push(25), None);
+        for sample in [30, 35] {
+            assert_eq!(detector.push(sample), None);
+        }
+        assert_eq!(detector.push(40), Some(15));
+
+        // Small growths are ignored.
+        let mut detector = GrowthDetector::new(4, 10);
+        for sample in [40, 40, 41, 42] {
+            assert_eq!(detector.push(sample), None);
+        }
+    }
+
+    #[test]
+    fn test_resident_set_size() {
+        if cfg!(target_os = "linux") {
+            assert!(resident_set_size().unwrap() > 0);
+        }
+    }
+}
@@ -20,6 +20,7 @@ def canonicalize(event):
         "time": int(event["fields"]["time"]),
         "memory used": int(event["fields"]["memory_used"]) / 2**20,
         "cache disk size": int(event["fields"]["cache_disk_size"]) / 2**20,
+        "rss": int(event["fields"].get("rss", 0)) / 2**20,
     }
 
 
@@ -49,6 +50,7 @@ axes[0].set_title('Compilation Time')
 
 axes[1].plot("round", "memory used", data=dataset)
 axes[1].plot("round", "cache disk size", data=dataset)
+axes[1].plot("round", "rss", data=dataset)
 axes[1].set_xlabel("Round")
 axes[1].set_ylabel("Megabytes")
 axes[1].set_title('Space Usage')
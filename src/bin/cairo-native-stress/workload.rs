@@ -0,0 +1,304 @@
+//! Generators of the programs compiled and executed by the stress test.
+
+use cairo_lang_sierra::{
+    ids::FunctionId,
+    program::{GenericArg, Program},
+};
+use cairo_lang_starknet::compile::compile_path;
+use cairo_native::{executor::SierraContractClass, utils::find_entry_point_by_idx};
+use clap::ValueEnum;
+use itertools::chain;
+use num_bigint::BigInt;
+use std::{
+    fs::{self, read_dir},
+    io,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+/// An unique value hardcoded into the generated contracts that it's
+/// used as an anchor point to safely modify it.
+/// It can be any value as long as it's unique in the contract.
+const UNIQUE_CONTRACT_VALUE: u32 = 835;
+
+/// The number of elements inserted by the dictionary and array workloads.
+const COLLECTION_SIZE: u32 = 1000;
+
+/// The kinds of workloads available.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum WorkloadKind {
+    /// Dummy contracts which only return a value.
+    #[default]
+    Dummy,
+    /// Contracts which insert and read many entries of a dictionary.
+    Dict,
+    /// Contracts which append and read many elements of an array.
+    Array,
+    /// Real contract classes, read from a directory.
+    Corpus,
+}
+
+/// A program to compile and execute during a round.
+pub struct WorkloadProgram {
+    pub program: Program,
+    pub entry_point: FunctionId,
+    /// The value the entry point should return, if known.
+    pub expected_result: Option<u64>,
+}
+
+/// A generator of the programs compiled and executed by the stress test.
+pub trait Workload: Send + Sync {
+    /// Return the program of the given round, or `None` if the round should be skipped.
+    fn generate(&self, round: u32) -> Option<WorkloadProgram>;
+
+    /// Whether every round generates a different program.
+    fn is_unique(&self) -> bool;
+}
+
+/// Build the workload of the given kind.
+///
+/// The corpus workload requires the directory containing the contract classes.
+pub fn build_workload(kind: WorkloadKind, corpus: Option<&Path>) -> Box<dyn Workload> {
+    match kind {
+        WorkloadKind::Dummy => Box::new(TemplateWorkload::new("return {value};", |round| {
+            round.into()
+        })),
+        WorkloadKind::Dict => Box::new(TemplateWorkload::new(
+            "\
+        let mut dict: core::dict::Felt252Dict<felt252> = Default::default();
+        let mut i: felt252 = 0;
+        while i != {size} {
+            dict.insert(i, i);
+            i += 1;
+        };
+
+        let mut sum = 0;
+        let mut i: felt252 = 0;
+        while i != {size} {
+            sum += dict.get(i);
+            i += 1;
+        };
+        sum + {value}",
+            |round| collection_sum() + u64::from(round),
+        )),
+        WorkloadKind::Array => Box::new(TemplateWorkload::new(
+            "\
+        let mut array: Array<felt252> = ArrayTrait::new();
+        let mut i: felt252 = 0;
+        while i != {size} {
+            array.append(i);
+            i += 1;
+        };
+
+        let mut sum = 0;
+        let mut span = array.span();
+        loop {
+            match span.pop_front() {
+                Option::Some(x) => { sum += *x; },
+                Option::None => { break; },
+            }
+        };
+        sum + {value}",
+            |round| collection_sum() + u64::from(round),
+        )),
+        WorkloadKind::Corpus => Box::new(
+            CorpusWorkload::new(corpus.expect("the corpus workload requires a corpus directory"))
+                .expect("failed to read the corpus directory"),
+        ),
+    }
+}
+
+/// The sum of every element of the dictionary and array workloads.
+fn collection_sum() -> u64 {
+    (0..u64::from(COLLECTION_SIZE)).sum()
+}
+
+/// Generates unique contracts by modifying the anchor value of a template contract.
+struct TemplateWorkload {
+    entry_point: FunctionId,
+    program: Program,
+    expected_result: fn(u32) -> u64,
+}
+
+impl TemplateWorkload {
+    /// Compile a contract whose main function has the given body.
+    ///
+    /// In the body, `{value}` is replaced by the anchor value and `{size}` by the number of elements
+    /// of the collections.
+    fn new(body: &str, expected_result: fn(u32) -> u64) -> Self {
+        let body = body
+            .replace("{value}", &UNIQUE_CONTRACT_VALUE.to_string())
+            .replace("{size}", &COLLECTION_SIZE.to_string());
+        let (entry_point, program) = generate_starknet_contract(&body);
+
+        Self {
+            entry_point,
+            program,
+            expected_result,
+        }
+    }
+}
+
+impl Workload for TemplateWorkload {
+    fn generate(&self, round: u32) -> Option<WorkloadProgram> {
+        Some(WorkloadProgram {
+            program: modify_starknet_contract(self.program.clone(), UNIQUE_CONTRACT_VALUE, round),
+            entry_point: self.entry_point.clone(),
+            expected_result: Some((self.expected_result)(round)),
+        })
+    }
+
+    fn is_unique(&self) -> bool {
+        true
+    }
+}
+
+/// Cycles through the contract classes found in a directory, running a different entry point of
+/// each class every time it's reached again. Entry points are run without calldata, therefore
+/// their results aren't checked.
+struct CorpusWorkload {
+    paths: Vec<PathBuf>,
+}
+
+impl CorpusWorkload {
+    fn new(path: &Path) -> io::Result<Self> {
+        let mut paths = Vec::new();
+        for entry in read_dir(path)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|x| x == "json") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        if paths.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no contract classes found in the corpus directory",
+            ));
+        }
+
+        Ok(Self { paths })
+    }
+}
+
+impl Workload for CorpusWorkload {
+    fn generate(&self, round: u32) -> Option<WorkloadProgram> {
+        let path = &self.paths[round as usize % self.paths.len()];
+        let contract_class = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|x| SierraContractClass::from_json(&x).map_err(|e| e.to_string()));
+        let contract_class = match contract_class {
+            Ok(x) => x,
+            Err(e) => {
+                warn!(path = %path.display(), "failed to load contract class: {e}");
+                return None;
+            }
+        };
+
+        let entry_points = chain!(
+            contract_class.entry_points.constructor.iter(),
+            contract_class.entry_points.external.iter(),
+            contract_class.entry_points.l1_handler.iter(),
+        )
+        .collect::<Vec<_>>();
+        if entry_points.is_empty() {
+            warn!(path = %path.display(), "contract class has no entry points");
+            return None;
+        }
+        let entry_point =
+            entry_points[round as usize / self.paths.len() % entry_points.len()].function_idx;
+
+        Some(WorkloadProgram {
+            entry_point: FunctionId::new(entry_point as u64),
+            program: contract_class.program,
+            expected_result: None,
+        })
+    }
+
+    fn is_unique(&self) -> bool {
+        false
+    }
+}
+
+/// Generate a dummy starknet contract
+///
+/// The contract contains an external main function with the given body, which returns a felt252.
+fn generate_starknet_contract(body: &str) -> (FunctionId, Program) {
+    let program_str = format!(
+        "\
+#[starknet::contract]
+mod Contract {{
+    #[storage]
+    struct Storage {{}}
+
+    #[external(v0)]
+    fn main(self: @ContractState) -> felt252 {{
+        {body}
+    }}
+}}
+"
+    );
+
+    let mut program_file = tempfile::Builder::new()
+        .prefix("test_")
+        .suffix(".cairo")
+        .tempfile()
+        .expect("failed to create temporary file for cairo test program");
+    fs::write(&mut program_file, program_str).expect("failed to write cairo test file");
+
+    let contract_class = compile_path(program_file.path(), None, Default::default())
+        .expect("failed to compile cairo contract");
+
+    let program = contract_class
+        .extract_sierra_program()
+        .expect("failed to extract sierra program");
+
+    let entry_point_idx = contract_class
+        .entry_points_by_type
+        .external
+        .first()
+        .expect("contract should have at least one entrypoint")
+        .function_idx;
+
+    let entry_point = find_entry_point_by_idx(&program, entry_point_idx)
+        .expect("failed to find entrypoint")
+        .id
+        .clone();
+
+    (entry_point, program)
+}
+
+/// Modifies the given contract by replacing the `old_value` with `new_value` in any type or
+/// libfunc declaration
+///
+/// The contract must contain the value `old_value` at least once, and only where it's meant to be
+/// replaced
+fn modify_starknet_contract(mut program: Program, old_value: u32, new_value: u32) -> Program {
+    let mut old_value_counter = 0;
+    let anchor = BigInt::from(old_value);
+
+    let generic_args = chain!(
+        program
+            .type_declarations
+            .iter_mut()
+            .flat_map(|x| &mut x.long_id.generic_args),
+        program
+            .libfunc_declarations
+            .iter_mut()
+            .flat_map(|x| &mut x.long_id.generic_args),
+    );
+    for generic_arg in generic_args {
+        match generic_arg {
+            GenericArg::Value(return_value) if *return_value == anchor => {
+                *return_value = BigInt::from(new_value);
+                old_value_counter += 1;
+            }
+            _ => {}
+        };
+    }
+
+    assert!(old_value_counter > 0, "old_value was not found");
+
+    program
+}
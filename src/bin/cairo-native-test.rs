@@ -11,10 +11,10 @@ use cairo_lang_test_plugin::{
 };
 use clap::Parser;
 use colored::Colorize;
-use std::path::PathBuf;
+use std::{io, path::PathBuf};
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
 use utils::{
-    test::{display_tests_summary, filter_test_cases, run_tests},
+    test::{display_tests_summary, filter_test_cases, run_tests, write_reports, ReportFormat},
     RunArgs, RunMode,
 };
 
@@ -58,6 +58,17 @@ struct Args {
     /// Optimization level, Valid: 0, 1, 2, 3. Values higher than 3 are considered as 3.
     #[arg(short = 'O', long, default_value_t = 0)]
     opt_level: u8,
+    /// The number of tests to run in parallel.
+    #[arg(short, long, default_value_t = 1)]
+    jobs: usize,
+    /// The format of the test results. Reports other than text are written to stdout once every
+    /// test has run.
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    format: ReportFormat,
+    /// Also run every test with the Cairo VM, failing the tests whose return values, panic data
+    /// or remaining gas differ from native.
+    #[arg(long, default_value_t = false)]
+    compare_vm: bool,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -119,19 +130,30 @@ fn main() -> anyhow::Result<()> {
         compiled.metadata.named_tests,
         compiled.sierra_program.program,
         compiled.metadata.function_set_costs,
+        compiled.metadata.contracts_info,
         RunArgs {
             run_mode: args.run_mode.clone(),
             opt_level: args.opt_level,
+            jobs: args.jobs,
+            format: args.format,
+            compare_vm: args.compare_vm,
         },
     )?;
 
-    display_tests_summary(&summary, filtered_out);
-    if !summary.failed.is_empty() {
+    if args.format == ReportFormat::Text {
+        display_tests_summary(&summary, filtered_out);
+    }
+    let suites = [(args.path.display().to_string(), summary)];
+    write_reports(args.format, &suites, io::stdout().lock())?;
+
+    let [(_, summary)] = &suites;
+    if !summary.failed.is_empty() || !summary.mismatched.is_empty() {
         bail!(
-            "test result: {}. {} passed; {} failed; {} ignored",
+            "test result: {}. {} passed; {} failed; {} mismatched; {} ignored",
             "FAILED".bright_red(),
             summary.passed.len(),
             summary.failed.len(),
+            summary.mismatched.len(),
             summary.ignored.len()
         );
     }
@@ -1,12 +1,16 @@
-use anyhow::Context;
+use anyhow::{bail, Context};
 use cairo_lang_sierra::program::VersionedProgram;
 use cairo_lang_test_plugin::{TestCompilation, TestCompilationMetadata};
 use clap::{Parser, ValueEnum};
+use colored::Colorize;
 use scarb_metadata::{Metadata, MetadataCommand, ScarbCommand};
 use scarb_ui::args::PackagesFilter;
-use std::{collections::HashSet, env, fs, path::Path};
+use std::{collections::HashSet, env, fs, io, path::Path};
 use utils::{
-    test::{display_tests_summary, filter_test_cases, find_testable_targets, run_tests},
+    test::{
+        display_tests_summary, filter_test_cases, find_testable_targets, run_tests, write_reports,
+        ReportFormat, TestsSummary,
+    },
     RunArgs, RunMode,
 };
 
@@ -38,6 +42,17 @@ struct Args {
     /// Optimization level, Valid: 0, 1, 2, 3. Values higher than 3 are considered as 3.
     #[arg(short = 'O', long, default_value_t = 0)]
     opt_level: u8,
+    /// The number of tests to run in parallel.
+    #[arg(short, long, default_value_t = 1)]
+    jobs: usize,
+    /// The format of the test results. Reports other than text are written to stdout once every
+    /// test has run.
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    format: ReportFormat,
+    /// Also run every test with the Cairo VM, failing the tests whose return values, panic data
+    /// or remaining gas differ from native.
+    #[arg(long, default_value_t = false)]
+    compare_vm: bool,
 }
 
 #[derive(ValueEnum, Clone, Debug, Default)]
@@ -104,8 +119,11 @@ fn main() -> anyhow::Result<()> {
         .join(profile);
 
     let mut deduplicator = TargetGroupDeduplicator::default();
+    let mut suites = Vec::new();
     for package in matched {
-        println!("testing {} ...", package.name);
+        if args.format == ReportFormat::Text {
+            println!("testing {} ...", package.name);
+        }
 
         // Iterate over the filtered targets.
         for target in find_testable_targets(&package) {
@@ -135,16 +153,39 @@ fn main() -> anyhow::Result<()> {
                 compiled.metadata.named_tests,
                 compiled.sierra_program.program,
                 compiled.metadata.function_set_costs,
+                compiled.metadata.contracts_info,
                 RunArgs {
                     run_mode: args.run_mode.clone(),
                     opt_level: args.opt_level,
+                    jobs: args.jobs,
+                    format: args.format,
+                    compare_vm: args.compare_vm,
                 },
             )?;
 
-            display_tests_summary(&summary, filtered_out);
+            if args.format == ReportFormat::Text {
+                display_tests_summary(&summary, filtered_out);
+            }
+            suites.push((format!("{}::{name}", package.name), summary));
         }
     }
 
+    write_reports(args.format, &suites, io::stdout().lock())?;
+
+    let count = |f: fn(&TestsSummary) -> usize| suites.iter().map(|(_, x)| f(x)).sum::<usize>();
+    let failed = count(|x| x.failed.len());
+    let mismatched = count(|x| x.mismatched.len());
+    if failed != 0 || mismatched != 0 {
+        bail!(
+            "test result: {}. {} passed; {} failed; {} mismatched; {} ignored",
+            "FAILED".bright_red(),
+            count(|x| x.passed.len()),
+            failed,
+            mismatched,
+            count(|x| x.ignored.len())
+        );
+    }
+
     Ok(())
 }
 
@@ -0,0 +1,475 @@
+//! # Batch compilation
+//!
+//! Every contract class is compiled into `<output>/<class hash>.so` (along with its contract info),
+//! and the outcome is appended to `<output>/manifest.jsonl` as soon as it's known. Classes which
+//! appear more than once in the input are only compiled the first time.
+//!
+//! When a batch is run again, classes with a successful entry whose library still exists are
+//! skipped, as well as failed ones unless asked to retry them. Lines left incomplete by an
+//! interruption are ignored, and once every class has been processed the manifest is rewritten
+//! with a single entry per class.
+//!
+//! The output directory must not be shared with other batches running at the same time, since the
+//! lock files left behind by interrupted compilations are removed when starting.
+
+use anyhow::{bail, Context};
+use cairo_native::{
+    executor::{AotContractExecutor, SierraContractClass},
+    utils::SHARED_LIBRARY_EXT,
+    OptLevel,
+};
+use serde::{Deserialize, Serialize};
+use starknet_types_core::felt::Felt;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Instant,
+};
+
+const MANIFEST_FILE: &str = "manifest.jsonl";
+
+pub struct BatchArgs {
+    /// A directory containing the contract class files, or a file listing their paths.
+    pub input: PathBuf,
+    pub output_dir: PathBuf,
+    pub opt_level: u8,
+    /// The number of classes to compile in parallel, or the number of CPUs if missing.
+    pub jobs: Option<usize>,
+    /// Whether to compile again the classes which failed in a previous batch.
+    pub retry_failed: bool,
+}
+
+/// The outcome of compiling a contract class, as recorded in the manifest.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// The contract class file.
+    pub source: PathBuf,
+    /// The class hash. Missing if the class couldn't be loaded.
+    pub class_hash: Option<Felt>,
+    /// The optimization level the class was compiled with.
+    pub opt_level: usize,
+    /// The shared library, relative to the output directory. Missing if the compilation failed.
+    pub library: Option<PathBuf>,
+    /// The compilation time in seconds. Missing if the library was found already built.
+    pub compile_time: Option<f64>,
+    /// The size of the shared library in bytes.
+    pub object_size: Option<u64>,
+    /// Why the class couldn't be compiled.
+    pub error: Option<String>,
+}
+
+/// Identifies a class in the manifest. Classes which couldn't be loaded don't have a class hash,
+/// therefore they're identified by their file instead.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum ManifestKey {
+    ClassHash(Felt),
+    Source(PathBuf),
+}
+
+impl ManifestEntry {
+    fn key(&self) -> ManifestKey {
+        match self.class_hash {
+            Some(class_hash) => ManifestKey::ClassHash(class_hash),
+            None => ManifestKey::Source(self.source.clone()),
+        }
+    }
+}
+
+/// What happened to a class of the batch.
+enum Outcome {
+    /// The class has been processed, and its entry should be recorded.
+    Recorded(ManifestEntry),
+    /// The class was compiled by a previous batch.
+    Reused,
+    /// The class failed in a previous batch, and retrying it wasn't requested.
+    PreviouslyFailed,
+    /// The same class appears earlier in the input.
+    Duplicate,
+}
+
+struct Batch<'a> {
+    args: &'a BatchArgs,
+    opt_level: usize,
+    entries: Mutex<BTreeMap<ManifestKey, ManifestEntry>>,
+    /// The classes processed by this batch, to skip duplicates.
+    seen: Mutex<HashSet<Felt>>,
+    /// The manifest, opened for appending.
+    journal: Mutex<File>,
+}
+
+/// Compile every contract class of the input into the output directory.
+pub fn run(args: BatchArgs) -> anyhow::Result<()> {
+    fs::create_dir_all(&args.output_dir).with_context(|| {
+        format!(
+            "Failed to create the output directory {}",
+            args.output_dir.display()
+        )
+    })?;
+    remove_stale_locks(&args.output_dir)?;
+
+    let inputs = collect_inputs(&args.input, &args.output_dir)?;
+
+    // Rewrite the manifest before appending to it, to get rid of incomplete lines.
+    let manifest_path = args.output_dir.join(MANIFEST_FILE);
+    let entries = read_manifest(&manifest_path)?;
+    write_manifest(&manifest_path, entries.values())?;
+    let journal = OpenOptions::new()
+        .append(true)
+        .open(&manifest_path)
+        .context("Failed to open the manifest")?;
+
+    let batch = Batch {
+        args: &args,
+        opt_level: usize::from(OptLevel::from(args.opt_level)),
+        entries: Mutex::new(entries),
+        seen: Mutex::new(HashSet::new()),
+        journal: Mutex::new(journal),
+    };
+
+    println!("compiling {} contract classes", inputs.len());
+
+    let jobs = args.jobs.unwrap_or_else(|| {
+        thread::available_parallelism()
+            .map(usize::from)
+            .unwrap_or(1)
+    });
+    let next_input = AtomicUsize::new(0);
+    let [compiled, failed, reused, previously_failed] = thread::scope(|s| {
+        let workers = (0..jobs.clamp(1, inputs.len().max(1)))
+            .map(|_| {
+                s.spawn(|| {
+                    let mut counts = [0usize; 4];
+                    loop {
+                        let index = next_input.fetch_add(1, Ordering::Relaxed);
+                        let Some(source) = inputs.get(index) else {
+                            break;
+                        };
+
+                        match batch.process(source) {
+                            Outcome::Recorded(entry) => {
+                                match &entry.error {
+                                    None => {
+                                        counts[0] += 1;
+                                        println!("{} ... ok", source.display());
+                                    }
+                                    Some(error) => {
+                                        counts[1] += 1;
+                                        println!("{} ... failed: {error}", source.display());
+                                    }
+                                }
+                                batch.record(entry)?;
+                            }
+                            Outcome::Reused => counts[2] += 1,
+                            Outcome::PreviouslyFailed => counts[3] += 1,
+                            Outcome::Duplicate => {}
+                        }
+                    }
+                    anyhow::Ok(counts)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        workers.into_iter().try_fold([0; 4], |acc, worker| {
+            let counts = worker.join().unwrap()?;
+            anyhow::Ok([0, 1, 2, 3].map(|i| acc[i] + counts[i]))
+        })
+    })?;
+
+    write_manifest(&manifest_path, batch.entries.into_inner().unwrap().values())?;
+
+    println!(
+        "compiled {compiled} classes; {failed} failed; {reused} already compiled; {previously_failed} failed previously;"
+    );
+    if failed > 0 {
+        bail!(
+            "{failed} classes failed to compile, see {} for details",
+            manifest_path.display()
+        );
+    }
+
+    Ok(())
+}
+
+impl Batch<'_> {
+    fn process(&self, source: &Path) -> Outcome {
+        let failed = |class_hash, error: anyhow::Error| {
+            Outcome::Recorded(ManifestEntry {
+                source: source.to_path_buf(),
+                class_hash,
+                opt_level: self.opt_level,
+                library: None,
+                compile_time: None,
+                object_size: None,
+                error: Some(format!("{error:#}")),
+            })
+        };
+
+        let contract_class = match fs::read_to_string(source)
+            .context("Error reading Sierra file.")
+            .and_then(|x| {
+                SierraContractClass::from_json(&x).context("Error loading the contract class.")
+            }) {
+            Ok(x) => x,
+            Err(e) => return failed(None, e),
+        };
+        let class_hash = contract_class.class_hash;
+        if !self.seen.lock().unwrap().insert(class_hash) {
+            return Outcome::Duplicate;
+        }
+
+        let library = PathBuf::from(format!(
+            "{}.{SHARED_LIBRARY_EXT}",
+            hex_class_hash(class_hash)
+        ));
+        let library_path = self.args.output_dir.join(&library);
+
+        let previous = self
+            .entries
+            .lock()
+            .unwrap()
+            .get(&ManifestKey::ClassHash(class_hash))
+            .cloned();
+        match previous {
+            Some(previous) if previous.opt_level == self.opt_level => {
+                if previous.error.is_none() && library_path.exists() {
+                    return Outcome::Reused;
+                }
+                if previous.error.is_some() && !self.args.retry_failed {
+                    return Outcome::PreviouslyFailed;
+                }
+            }
+            Some(_) => {}
+            // The batch was interrupted after building the library, but before recording it.
+            None if library_path.exists() && library_path.with_extension("json").exists() => {
+                return Outcome::Recorded(ManifestEntry {
+                    source: source.to_path_buf(),
+                    class_hash: Some(class_hash),
+                    opt_level: self.opt_level,
+                    object_size: fs::metadata(&library_path).map(|x| x.len()).ok(),
+                    library: Some(library),
+                    compile_time: None,
+                    error: None,
+                });
+            }
+            None => {}
+        }
+
+        let start = Instant::now();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            AotContractExecutor::new_into(
+                &contract_class.program,
+                &contract_class.entry_points,
+                contract_class.sierra_version,
+                &library_path,
+                self.args.opt_level.into(),
+            )
+        }));
+        let compile_time = start.elapsed().as_secs_f64();
+
+        let error = match result {
+            Ok(Ok(Some(_))) => None,
+            Ok(Ok(None)) => Some(anyhow::anyhow!(
+                "Failed to take lock on path {}",
+                library_path.display()
+            )),
+            Ok(Err(e)) => Some(anyhow::Error::new(e).context("Error compiling Sierra program.")),
+            Err(_) => Some(anyhow::anyhow!("Compilation panicked.")),
+        };
+        if let Some(error) = error {
+            return failed(Some(class_hash), error);
+        }
+
+        Outcome::Recorded(ManifestEntry {
+            source: source.to_path_buf(),
+            class_hash: Some(class_hash),
+            opt_level: self.opt_level,
+            object_size: fs::metadata(&library_path).map(|x| x.len()).ok(),
+            library: Some(library),
+            compile_time: Some(compile_time),
+            error: None,
+        })
+    }
+
+    /// Append an entry to the manifest.
+    fn record(&self, entry: ManifestEntry) -> anyhow::Result<()> {
+        // Every line is written at once, so that an interruption can only leave the last one
+        // incomplete.
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+
+        let mut journal = self.journal.lock().unwrap();
+        journal
+            .write_all(line.as_bytes())
+            .and_then(|_| journal.flush())
+            .context("Failed to write to the manifest")?;
+        drop(journal);
+
+        self.entries.lock().unwrap().insert(entry.key(), entry);
+        Ok(())
+    }
+}
+
+/// Find the contract class files of the input, which is either a directory (searched recursively
+/// for JSON files, except for the output directory) or a file listing them, one per line.
+fn collect_inputs(input: &Path, output_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    if !input.is_dir() {
+        let list = fs::read_to_string(input)
+            .with_context(|| format!("Failed to read the list of classes {}", input.display()))?;
+        return Ok(list
+            .lines()
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .map(PathBuf::from)
+            .collect());
+    }
+
+    let output_dir = output_dir.canonicalize()?;
+    let mut inputs = Vec::new();
+    let mut pending = vec![input.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let read_dir = fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read the directory {}", dir.display()))?;
+        for entry in read_dir {
+            let path = entry?.path();
+            if path.is_dir() {
+                if path.canonicalize()? != output_dir {
+                    pending.push(path);
+                }
+            } else if path.extension().is_some_and(|x| x == "json") {
+                inputs.push(path);
+            }
+        }
+    }
+
+    inputs.sort();
+    Ok(inputs)
+}
+
+/// Remove the lock files of compilations which were interrupted.
+fn remove_stale_locks(output_dir: &Path) -> anyhow::Result<()> {
+    for entry in fs::read_dir(output_dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|x| x == "lock") {
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove the lock file {}", path.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Read the entries of a manifest, keeping only the last one of every class. Invalid lines, which
+/// may have been left by an interrupted batch, are ignored.
+fn read_manifest(path: &Path) -> anyhow::Result<BTreeMap<ManifestKey, ManifestEntry>> {
+    let data = match fs::read_to_string(path) {
+        Ok(x) => x,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e).context("Failed to read the manifest"),
+    };
+
+    let mut entries = BTreeMap::new();
+    for (index, line) in data.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str::<ManifestEntry>(line) {
+            Ok(entry) => {
+                entries.insert(entry.key(), entry);
+            }
+            Err(e) => eprintln!("ignoring invalid manifest line {}: {e}", index + 1),
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Replace the manifest with the given entries.
+fn write_manifest<'a>(
+    path: &Path,
+    entries: impl IntoIterator<Item = &'a ManifestEntry>,
+) -> anyhow::Result<()> {
+    let tmp_path = path.with_extension("jsonl.tmp");
+
+    let mut data = String::new();
+    for entry in entries {
+        data.push_str(&serde_json::to_string(entry)?);
+        data.push('\n');
+    }
+    fs::write(&tmp_path, data).context("Failed to write the manifest")?;
+    fs::rename(&tmp_path, path).context("Failed to write the manifest")?;
+
+    Ok(())
+}
+
+fn hex_class_hash(class_hash: Felt) -> String {
+    let digits = class_hash
+        .to_bytes_be()
+        .iter()
+        .map(|x| format!("{x:02x}"))
+        .collect::<String>();
+    format!("0x{digits}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(MANIFEST_FILE);
+
+        let entry = |class_hash: u64, error: Option<&str>| ManifestEntry {
+            source: PathBuf::from(format!("class_{class_hash}.json")),
+            class_hash: Some(Felt::from(class_hash)),
+            opt_level: 0,
+            library: error
+                .is_none()
+                .then(|| PathBuf::from(format!("{}.so", hex_class_hash(class_hash.into())))),
+            compile_time: Some(1.5),
+            object_size: Some(1024),
+            error: error.map(String::from),
+        };
+
+        // Later entries replace earlier ones, and the incomplete last line is ignored.
+        let mut data = String::new();
+        for entry in [entry(1, Some("oops")), entry(2, None), entry(1, None)] {
+            data.push_str(&serde_json::to_string(&entry).unwrap());
+            data.push('\n');
+        }
+        data.push_str(r#"{"source":"class_3.json","class_ha"#);
+        fs::write(&path, data).unwrap();
+
+        let entries = read_manifest(&path).unwrap();
+        assert_eq!(
+            entries.into_values().collect::<Vec<_>>(),
+            [entry(1, None), entry(2, None)]
+        );
+
+        write_manifest(&path, &[entry(2, None)]).unwrap();
+        assert_eq!(
+            read_manifest(&path)
+                .unwrap()
+                .into_values()
+                .collect::<Vec<_>>(),
+            [entry(2, None)]
+        );
+    }
+
+    #[test]
+    fn test_hex_class_hash() {
+        assert_eq!(
+            hex_class_hash(Felt::from(0xabcu64)),
+            format!("0x{}abc", "0".repeat(61))
+        );
+    }
+}
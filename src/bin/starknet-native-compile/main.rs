@@ -0,0 +1,74 @@
+use anyhow::Context;
+use std::path::PathBuf;
+
+use cairo_native::executor::{AotContractExecutor, SierraContractClass};
+use clap::Parser;
+use starknet_types_core::felt::Felt;
+
+mod batch;
+
+/// Given a Sierra file (as saved in Starknet's contract tree), extracts the sierra_program from
+/// felts into readable Sierra code, compiles it to native, and saves the result to the given output
+/// path.
+///
+/// In batch mode, compiles every contract class found in a directory (or listed in a file, one path
+/// per line) into the output directory, naming each shared library after its class hash. The
+/// results are recorded in the directory's `manifest.jsonl`, and classes which are already in it
+/// are skipped, so an interrupted batch can be resumed by running it again.
+#[derive(Parser, Debug)]
+#[clap(version, verbatim_doc_comment)]
+struct Args {
+    /// The path of the Sierra file to compile. In batch mode, a directory containing the contract
+    /// class files or a file listing their paths.
+    path: PathBuf,
+    /// Optimization level, Valid: 0, 1, 2, 3. Values higher than 3 are considered as 3.
+    #[arg(short = 'O', long, default_value_t = 0)]
+    opt_level: u8,
+    /// The output file path. In batch mode, the output directory.
+    output: PathBuf,
+    /// The expected class hash. Compilation fails if the class doesn't match it.
+    #[arg(long, value_parser = |x: &str| Felt::from_hex(x).map_err(|e| e.to_string()), conflicts_with = "batch")]
+    class_hash: Option<Felt>,
+    /// Compile many contract classes at once.
+    #[arg(long)]
+    batch: bool,
+    /// The number of classes to compile in parallel. Defaults to the number of CPUs.
+    #[arg(short, long, requires = "batch")]
+    jobs: Option<usize>,
+    /// Compile again the classes whose compilation failed in a previous batch.
+    #[arg(long, requires = "batch")]
+    retry_failed: bool,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    if args.batch {
+        return batch::run(batch::BatchArgs {
+            input: args.path,
+            output_dir: args.output,
+            opt_level: args.opt_level,
+            jobs: args.jobs,
+            retry_failed: args.retry_failed,
+        });
+    }
+
+    let raw_contract_class =
+        std::fs::read_to_string(&args.path).context("Error reading Sierra file.")?;
+    let contract_class = SierraContractClass::from_json(&raw_contract_class)
+        .context("Error loading the contract class.")?;
+    if let Some(class_hash) = args.class_hash {
+        contract_class.validate_class_hash(class_hash)?;
+    }
+
+    AotContractExecutor::new_into(
+        &contract_class.program,
+        &contract_class.entry_points,
+        contract_class.sierra_version,
+        args.output.clone(),
+        args.opt_level.into(),
+    )
+    .context("Error compiling Sierra program.")?
+    .with_context(|| format!("Failed to take lock on path {}", args.output.display()))?;
+    Ok(())
+}
@@ -3,7 +3,16 @@
 
 use anyhow::bail;
 use cairo_lang_runner::{casm_run::format_next_item, RunResultValue};
-use cairo_lang_sierra::program::{Function, Program};
+use cairo_lang_sierra::{
+    extensions::{
+        circuit::CircuitTypeConcrete,
+        core::{CoreLibfunc, CoreType, CoreTypeConcrete},
+        starknet::StarknetTypeConcrete,
+    },
+    ids::ConcreteTypeId,
+    program::{Function, FunctionSignature, Program},
+    program_registry::ProgramRegistry,
+};
 use cairo_native::{
     execution_result::ExecutionResult,
     starknet::{Secp256k1Point, Secp256r1Point},
@@ -11,6 +20,7 @@ use cairo_native::{
 };
 use clap::ValueEnum;
 use itertools::Itertools;
+use serde::Deserialize;
 use starknet_types_core::felt::Felt;
 use std::vec::IntoIter;
 
@@ -19,6 +29,11 @@ pub mod test;
 pub(super) struct RunArgs {
     pub run_mode: RunMode,
     pub opt_level: u8,
+    /// The number of tests to run in parallel.
+    pub jobs: usize,
+    pub format: test::ReportFormat,
+    /// Whether to also run the tests with the Cairo VM and compare their results.
+    pub compare_vm: bool,
 }
 
 #[derive(Clone, Debug, ValueEnum)]
@@ -111,6 +126,57 @@ pub fn result_to_runresult(result: &ExecutionResult) -> anyhow::Result<RunResult
     })
 }
 
+/// The arguments of a function, either as JSON-encoded [`Value`]s or as their Cairo
+/// serialization.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum FunctionArgs {
+    Values(Vec<Value>),
+    Felts(Vec<Felt>),
+}
+
+impl FunctionArgs {
+    /// Convert the arguments into the values expected by the function's signature. Builtins are
+    /// provided by the executors, therefore they're not part of the arguments.
+    pub fn into_values(
+        self,
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        signature: &FunctionSignature,
+    ) -> anyhow::Result<Vec<Value>> {
+        match self {
+            Self::Values(values) => Ok(values),
+            Self::Felts(felts) => Ok(Value::deserialize_seq_from_felts(
+                &felts,
+                signature
+                    .param_types
+                    .iter()
+                    .filter(|type_id| !is_builtin(registry, type_id)),
+                registry,
+            )?),
+        }
+    }
+}
+
+fn is_builtin(registry: &ProgramRegistry<CoreType, CoreLibfunc>, type_id: &ConcreteTypeId) -> bool {
+    matches!(
+        registry.get_type(type_id),
+        Ok(CoreTypeConcrete::Bitwise(_)
+            | CoreTypeConcrete::EcOp(_)
+            | CoreTypeConcrete::GasBuiltin(_)
+            | CoreTypeConcrete::BuiltinCosts(_)
+            | CoreTypeConcrete::RangeCheck(_)
+            | CoreTypeConcrete::RangeCheck96(_)
+            | CoreTypeConcrete::Pedersen(_)
+            | CoreTypeConcrete::Poseidon(_)
+            | CoreTypeConcrete::Coupon(_)
+            | CoreTypeConcrete::Starknet(StarknetTypeConcrete::System(_))
+            | CoreTypeConcrete::SegmentArena(_)
+            | CoreTypeConcrete::Circuit(
+                CircuitTypeConcrete::AddMod(_) | CircuitTypeConcrete::MulMod(_)
+            ))
+    )
+}
+
 /// Convert a JIT value to a felt.
 fn jitvalue_to_felt(value: &Value) -> Vec<Felt> {
     let mut felts = Vec::new();
@@ -221,6 +287,65 @@ mod tests {
         assert!(find_function(&ProgramParser::new().parse("").unwrap(), "Func2").is_err());
     }
 
+    #[test]
+    fn test_function_args_from_felts() {
+        let program = ProgramParser::new()
+            .parse(
+                r#"
+                    type RangeCheck = RangeCheck;
+                    type felt252 = felt252;
+                    type u8 = u8;
+                    type i8 = i8;
+                    type Array<felt252> = Array<felt252>;
+                    type Tuple<u8, i8> = Struct<ut@Tuple, u8, i8>;
+                    type Unit = Struct<ut@Tuple>;
+                    type core::bool = Enum<ut@core::bool, Unit, Unit>;
+
+                    return();
+
+                    Func@0(a: RangeCheck, b: Array<felt252>, c: Tuple<u8, i8>, d: core::bool) -> ();
+                "#,
+            )
+            .unwrap();
+        let registry = ProgramRegistry::new(&program).unwrap();
+        let signature = &program.funcs[0].signature;
+
+        // The last felt of the tuple is -1.
+        let args: FunctionArgs =
+            serde_json::from_str(r#"["2", "10", "20", "7", "0x800000000000011000000000000000000000000000000000000000000000000", "1"]"#).unwrap();
+        assert_eq!(
+            args.into_values(&registry, signature).unwrap(),
+            [
+                Value::Array(vec![Value::Felt252(10.into()), Value::Felt252(20.into())]),
+                Value::Struct {
+                    fields: vec![Value::Uint8(7), Value::Sint8(-1)],
+                    debug_name: Some("Tuple<u8, i8>".to_string()),
+                },
+                Value::Enum {
+                    tag: 1,
+                    value: Box::new(Value::Struct {
+                        fields: Vec::new(),
+                        debug_name: Some("Unit".to_string()),
+                    }),
+                    debug_name: Some("core::bool".to_string()),
+                },
+            ]
+        );
+
+        // Missing and unused felts are rejected.
+        let args: FunctionArgs = serde_json::from_str(r#"["2", "10"]"#).unwrap();
+        assert!(args.into_values(&registry, signature).is_err());
+        let args: FunctionArgs = serde_json::from_str(r#"["0", "7", "1", "0", "0"]"#).unwrap();
+        assert!(args.into_values(&registry, signature).is_err());
+
+        // Values are passed through.
+        let args: FunctionArgs = serde_json::from_str(r#"[{"Uint8": 7}]"#).unwrap();
+        assert_eq!(
+            args.into_values(&registry, signature).unwrap(),
+            [Value::Uint8(7)]
+        );
+    }
+
     #[test]
     fn test_result_to_runresult_enum_nonpanic() {
         // Tests the conversion of a non-panic enum result to a `RunResultValue::Success`.
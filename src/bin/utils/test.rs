@@ -1,8 +1,9 @@
 use super::{find_function, format_for_panic, result_to_runresult, RunArgs, RunMode};
 use anyhow::Context;
-use cairo_lang_runner::RunResultValue;
+use cairo_lang_runner::{RunResultValue, SierraCasmRunner, StarknetState};
 use cairo_lang_sierra::{extensions::gas::CostTokenType, ids::FunctionId, program::Program};
 use cairo_lang_sierra_to_casm::metadata::MetadataComputationConfig;
+use cairo_lang_starknet::contract::ContractInfo;
 use cairo_lang_test_plugin::{
     test_config::{PanicExpectation, TestExpectation},
     TestConfig,
@@ -13,14 +14,40 @@ use cairo_native::{
     context::NativeContext,
     executor::{AotNativeExecutor, JitNativeExecutor},
     metadata::gas::GasMetadata,
+    starknet::{BlockInfo, ExecutionInfoV2, TxV2Info},
     starknet_stub::StubSyscallHandler,
+    utils::decode_error_message,
 };
+use clap::ValueEnum;
 use colored::Colorize;
 use itertools::Itertools;
 use num_traits::ToPrimitive;
 #[cfg(feature = "scarb")]
 use scarb_metadata::{PackageMetadata, TargetMetadata};
-use std::sync::Mutex;
+use serde::Serialize;
+use starknet_types_core::felt::Felt;
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The format of the test results.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// Human readable progress and summary.
+    #[default]
+    Text,
+    /// A JSON array with the reports of every test suite.
+    Json,
+    /// A JUnit XML document.
+    Junit,
+}
 
 /// Summary data of the ran tests.
 pub struct TestsSummary {
@@ -28,6 +55,76 @@ pub struct TestsSummary {
     pub failed: Vec<String>,
     pub ignored: Vec<String>,
     pub failed_run_results: Vec<RunResultValue>,
+    /// The tests whose results differ between native and the Cairo VM.
+    pub mismatched: Vec<String>,
+    pub vm_mismatches: Vec<Vec<VmMismatch>>,
+    /// The reports of every test, in their original order.
+    pub reports: Vec<TestReport>,
+}
+
+/// The machine-readable report of a test.
+#[derive(Debug, Serialize)]
+pub struct TestReport {
+    pub name: String,
+    pub status: TestReportStatus,
+    /// The panic data, if the test panicked.
+    pub panic_data: Option<Vec<Felt>>,
+    /// The panic data decoded as a string.
+    pub panic_message: Option<String>,
+    /// The gas made available to the test, if any.
+    pub available_gas: Option<usize>,
+    /// The estimated gas usage of the test.
+    pub gas_usage: Option<i64>,
+    /// The wall time spent running the test, in seconds.
+    pub wall_time: f64,
+    /// The differences between the results of native and the Cairo VM.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub vm_mismatches: Vec<VmMismatch>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestReportStatus {
+    Passed,
+    Failed,
+    Ignored,
+    /// Native and the Cairo VM returned different results.
+    Mismatch,
+}
+
+/// A difference between the results of running a test with native and with the Cairo VM.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct VmMismatch {
+    pub kind: VmMismatchKind,
+    /// The result of native, formatted for display.
+    pub native: String,
+    /// The result of the Cairo VM, formatted for display.
+    pub vm: String,
+}
+
+/// The part of the results which differs between native and the Cairo VM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VmMismatchKind {
+    /// One of them finished successfully while the other panicked.
+    Outcome,
+    /// Both finished successfully, but returned different values.
+    ReturnValues,
+    /// Both panicked, but with different panic data.
+    PanicData,
+    /// The gas left after running the test.
+    RemainingGas,
+}
+
+impl VmMismatchKind {
+    fn description(self) -> &'static str {
+        match self {
+            Self::Outcome => "outcome",
+            Self::ReturnValues => "return values",
+            Self::PanicData => "panic data",
+            Self::RemainingGas => "remaining gas",
+        }
+    }
 }
 
 /// The result of a ran test.
@@ -36,12 +133,18 @@ struct TestResult {
     status: TestStatus,
     /// The gas usage of the run if relevant.
     gas_usage: Option<i64>,
+    /// The panic data, if the test panicked.
+    panic_data: Option<Vec<Felt>>,
+    /// The time spent running the test.
+    wall_time: Duration,
 }
 
 /// The status of a ran test.
 enum TestStatus {
     Success,
     Fail(RunResultValue),
+    /// The results of native and the Cairo VM differ.
+    Mismatch(Vec<VmMismatch>),
 }
 
 /// Find all testable targets in the Scarb package.
@@ -98,7 +201,7 @@ pub fn filter_test_cases(
 
 /// Display the summary of the ran tests.
 pub fn display_tests_summary(summary: &TestsSummary, filtered_out: usize) {
-    if summary.failed.is_empty() {
+    if summary.failed.is_empty() && summary.mismatched.is_empty() {
         println!(
             "test result: {}. {} passed; {} failed; {} ignored; {filtered_out} filtered out;",
             "ok".bright_green(),
@@ -107,33 +210,156 @@ pub fn display_tests_summary(summary: &TestsSummary, filtered_out: usize) {
             summary.ignored.len()
         );
     } else {
-        println!("failures:");
-        for (failure, run_result) in summary
-            .failed
-            .iter()
-            .zip_eq(summary.failed_run_results.clone())
-        {
-            print!("   {failure} - ");
-            match run_result {
-                RunResultValue::Success(_) => {
-                    println!("expected panic but finished successfully.");
+        if !summary.failed.is_empty() {
+            println!("failures:");
+            for (failure, run_result) in summary
+                .failed
+                .iter()
+                .zip_eq(summary.failed_run_results.clone())
+            {
+                print!("   {failure} - ");
+                match run_result {
+                    RunResultValue::Success(_) => {
+                        println!("expected panic but finished successfully.");
+                    }
+                    RunResultValue::Panic(values) => {
+                        println!("{}", format_for_panic(values.into_iter()));
+                    }
                 }
-                RunResultValue::Panic(values) => {
-                    println!("{}", format_for_panic(values.into_iter()));
+            }
+            println!();
+        }
+        if !summary.mismatched.is_empty() {
+            println!("native/VM mismatches:");
+            for (name, mismatches) in summary.mismatched.iter().zip_eq(&summary.vm_mismatches) {
+                println!("   {name}:");
+                for mismatch in mismatches {
+                    println!("      {}:", mismatch.kind.description());
+                    println!("         native: {}", mismatch.native);
+                    println!("         vm:     {}", mismatch.vm);
                 }
             }
+            println!();
         }
-        println!();
     }
 }
 
+/// The execution info seen by the tests run with the Cairo VM, which can't be configured. Every
+/// field is zero or empty.
+fn vm_execution_info() -> ExecutionInfoV2 {
+    ExecutionInfoV2 {
+        block_info: BlockInfo {
+            block_number: 0,
+            block_timestamp: 0,
+            sequencer_address: Felt::ZERO,
+        },
+        tx_info: TxV2Info {
+            version: Felt::ZERO,
+            account_contract_address: Felt::ZERO,
+            max_fee: 0,
+            signature: vec![],
+            transaction_hash: Felt::ZERO,
+            chain_id: Felt::ZERO,
+            nonce: Felt::ZERO,
+            resource_bounds: vec![],
+            tip: 0,
+            paymaster_data: vec![],
+            nonce_data_availability_mode: 0,
+            fee_data_availability_mode: 0,
+            account_deployment_data: vec![],
+        },
+        caller_address: Felt::ZERO,
+        contract_address: Felt::ZERO,
+        entry_point_selector: Felt::ZERO,
+    }
+}
+
+/// Compare the results of running a test with native and with the Cairo VM.
+///
+/// Return values and panic data are compared as their serialized felts, therefore a difference
+/// in both the outcome and the values is only reported as a different outcome.
+fn find_vm_mismatches(
+    native_result: &RunResultValue,
+    native_remaining_gas: Option<u64>,
+    vm_result: &RunResultValue,
+    vm_remaining_gas: Option<Felt>,
+) -> Vec<VmMismatch> {
+    fn format_result(result: &RunResultValue) -> String {
+        match result {
+            RunResultValue::Success(values) => format!("success [{}]", values.iter().join(", ")),
+            RunResultValue::Panic(values) => format_for_panic(values.clone().into_iter()),
+        }
+    }
+
+    fn format_gas<T: ToString>(gas: Option<T>) -> String {
+        gas.map_or_else(|| "none".to_string(), |x| x.to_string())
+    }
+
+    let mut mismatches = Vec::new();
+    let kind = match (native_result, vm_result) {
+        (RunResultValue::Success(_), RunResultValue::Panic(_))
+        | (RunResultValue::Panic(_), RunResultValue::Success(_)) => Some(VmMismatchKind::Outcome),
+        (RunResultValue::Success(native), RunResultValue::Success(vm)) if native != vm => {
+            Some(VmMismatchKind::ReturnValues)
+        }
+        (RunResultValue::Panic(native), RunResultValue::Panic(vm)) if native != vm => {
+            Some(VmMismatchKind::PanicData)
+        }
+        _ => None,
+    };
+    if let Some(kind) = kind {
+        mismatches.push(VmMismatch {
+            kind,
+            native: format_result(native_result),
+            vm: format_result(vm_result),
+        });
+    }
+
+    if native_remaining_gas.map(Felt::from) != vm_remaining_gas {
+        mismatches.push(VmMismatch {
+            kind: VmMismatchKind::RemainingGas,
+            native: format_gas(native_remaining_gas),
+            vm: format_gas(vm_remaining_gas),
+        });
+    }
+
+    mismatches
+}
+
 /// Runs the tests and process the results for a summary.
+///
+/// The tests are distributed between `args.jobs` threads which share the same compiled executor.
+/// Progress is only printed when using the text report format.
+///
+/// When `args.compare_vm` is set, every test is also run with the Cairo VM and any difference in
+/// their results fails the test as a mismatch, regardless of its expectation.
 pub fn run_tests(
     named_tests: Vec<(String, TestConfig)>,
     sierra_program: Program,
     function_set_costs: OrderedHashMap<FunctionId, OrderedHashMap<CostTokenType, i32>>,
+    contracts_info: OrderedHashMap<Felt, ContractInfo>,
     args: RunArgs,
 ) -> anyhow::Result<TestsSummary> {
+    let vm_runner = if args.compare_vm {
+        Some(
+            SierraCasmRunner::new(
+                sierra_program.clone(),
+                Some(MetadataComputationConfig {
+                    function_set_costs: function_set_costs.clone(),
+                    linear_ap_change_solver: true,
+                    linear_gas_solver: true,
+                    skip_non_linear_solver_comparisons: false,
+                    compute_runtime_costs: false,
+                }),
+                contracts_info,
+                None,
+            )
+            .context("Failed setting up the Cairo VM runner.")?,
+        )
+    } else {
+        None
+    };
+
     let native_context = NativeContext::new();
 
     // Compile the sierra program into a MLIR module.
@@ -141,32 +367,33 @@ pub fn run_tests(
         .compile(&sierra_program, false, Some(Default::default()))
         .unwrap();
 
-    let native_executor: Box<dyn Fn(_, _, _, &mut StubSyscallHandler) -> _> = match args.run_mode {
-        RunMode::Aot => {
-            let executor =
-                AotNativeExecutor::from_native_module(native_module, args.opt_level.into())?;
-            Box::new(move |function_id, args, gas, syscall_handler| {
-                executor.invoke_dynamic_with_syscall_handler(
-                    function_id,
-                    args,
-                    gas,
-                    syscall_handler,
-                )
-            })
-        }
-        RunMode::Jit => {
-            let executor =
-                JitNativeExecutor::from_native_module(native_module, args.opt_level.into())?;
-            Box::new(move |function_id, args, gas, syscall_handler| {
-                executor.invoke_dynamic_with_syscall_handler(
-                    function_id,
-                    args,
-                    gas,
-                    syscall_handler,
-                )
-            })
-        }
-    };
+    let native_executor: Box<dyn Fn(_, _, _, &mut StubSyscallHandler) -> _ + Sync> =
+        match args.run_mode {
+            RunMode::Aot => {
+                let executor =
+                    AotNativeExecutor::from_native_module(native_module, args.opt_level.into())?;
+                Box::new(move |function_id, args, gas, syscall_handler| {
+                    executor.invoke_dynamic_with_syscall_handler(
+                        function_id,
+                        args,
+                        gas,
+                        syscall_handler,
+                    )
+                })
+            }
+            RunMode::Jit => {
+                let executor =
+                    JitNativeExecutor::from_native_module(native_module, args.opt_level.into())?;
+                Box::new(move |function_id, args, gas, syscall_handler| {
+                    executor.invoke_dynamic_with_syscall_handler(
+                        function_id,
+                        args,
+                        gas,
+                        syscall_handler,
+                    )
+                })
+            }
+        };
 
     let gas_metadata = GasMetadata::new(
         &sierra_program,
@@ -180,103 +407,600 @@ pub fn run_tests(
     )
     .unwrap();
 
-    println!("running {} tests", named_tests.len());
-    let wrapped_summary = Mutex::new(Ok(TestsSummary {
-        passed: vec![],
-        failed: vec![],
-        ignored: vec![],
-        failed_run_results: vec![],
-    }));
-    named_tests
-        .into_iter()
-        .map(
-            |(name, test)| -> anyhow::Result<(String, Option<TestResult>)> {
-                if test.ignored {
-                    return Ok((name, None));
-                }
-                tracing::trace!("running test {name:?}");
+    let run_test = |name: &str, test: &TestConfig| -> anyhow::Result<Option<TestResult>> {
+        if test.ignored {
+            return Ok(None);
+        }
+        tracing::trace!("running test {name:?}");
 
-                let func = find_function(&sierra_program, name.as_str())?;
+        let func = find_function(&sierra_program, name)?;
+
+        let initial_gas = test.available_gas.map(|x| x.try_into().unwrap());
+
+        // Both runs must see the same execution info for their results to be comparable.
+        let mut syscall_handler = StubSyscallHandler::default();
+        if vm_runner.is_some() {
+            syscall_handler.execution_info = vm_execution_info();
+        }
 
-                let initial_gas = test.available_gas.map(|x| x.try_into().unwrap());
+        let start = Instant::now();
+        let result = native_executor(&func.id, &[], initial_gas, &mut syscall_handler)
+            .with_context(|| format!("Failed to run the function `{name}`."))?;
+        let wall_time = start.elapsed();
 
-                let result = native_executor(
-                    &func.id,
-                    &[],
-                    initial_gas,
-                    &mut StubSyscallHandler::default(),
+        let run_result = result_to_runresult(&result)?;
+
+        let vm_mismatches = match &vm_runner {
+            Some(vm_runner) => {
+                let vm_result = vm_runner
+                    .run_function_with_starknet_context(
+                        func,
+                        vec![],
+                        test.available_gas,
+                        StarknetState::default(),
+                    )
+                    .with_context(|| {
+                        format!("Failed to run the function `{name}` with the Cairo VM.")
+                    })?;
+
+                find_vm_mismatches(
+                    &run_result,
+                    result.remaining_gas,
+                    &vm_result.value,
+                    vm_result.gas_counter,
                 )
-                .with_context(|| format!("Failed to run the function `{}`.", name.as_str()))?;
+            }
+            None => Vec::new(),
+        };
 
-                let run_result = result_to_runresult(&result)?;
-                Ok((
-                    name,
-                    Some(TestResult {
-                        status: match &run_result {
-                            RunResultValue::Success(_) => match test.expectation {
-                                TestExpectation::Success => TestStatus::Success,
-                                TestExpectation::Panics(_) => TestStatus::Fail(run_result),
-                            },
-                            RunResultValue::Panic(value) => match test.expectation {
-                                TestExpectation::Success => TestStatus::Fail(run_result),
-                                TestExpectation::Panics(panic_expectation) => {
-                                    match panic_expectation {
-                                        PanicExpectation::Exact(expected) if value != &expected => {
-                                            TestStatus::Fail(run_result)
-                                        }
-                                        _ => TestStatus::Success,
-                                    }
-                                }
-                            },
-                        },
-                        gas_usage: test
-                            .available_gas
-                            .zip(result.remaining_gas)
-                            .map(|(before, after)| {
-                                before.into_or_panic::<i64>() - after.to_i64().unwrap()
-                            })
-                            .or_else(|| {
-                                gas_metadata
-                                    .initial_required_gas(&func.id)
-                                    .map(|gas| gas.try_into().unwrap())
-                            }),
-                    }),
-                ))
+        Ok(Some(TestResult {
+            panic_data: match &run_result {
+                RunResultValue::Success(_) => None,
+                RunResultValue::Panic(values) => Some(values.clone()),
             },
-        )
-        .for_each(|r| {
+            status: match &run_result {
+                _ if !vm_mismatches.is_empty() => TestStatus::Mismatch(vm_mismatches),
+                RunResultValue::Success(_) => match &test.expectation {
+                    TestExpectation::Success => TestStatus::Success,
+                    TestExpectation::Panics(_) => TestStatus::Fail(run_result),
+                },
+                RunResultValue::Panic(value) => match &test.expectation {
+                    TestExpectation::Success => TestStatus::Fail(run_result),
+                    TestExpectation::Panics(panic_expectation) => match panic_expectation {
+                        PanicExpectation::Exact(expected) if value != expected => {
+                            TestStatus::Fail(run_result)
+                        }
+                        _ => TestStatus::Success,
+                    },
+                },
+            },
+            gas_usage: test
+                .available_gas
+                .zip(result.remaining_gas)
+                .map(|(before, after)| before.into_or_panic::<i64>() - after.to_i64().unwrap())
+                .or_else(|| {
+                    gas_metadata
+                        .initial_required_gas(&func.id)
+                        .map(|gas| gas.try_into().unwrap())
+                }),
+            wall_time,
+        }))
+    };
+
+    let print_progress = args.format == ReportFormat::Text;
+    if print_progress {
+        println!("running {} tests", named_tests.len());
+    }
+    let wrapped_summary = Mutex::new(Ok(TestsSummary {
+        passed: vec![],
+        failed: vec![],
+        ignored: vec![],
+        failed_run_results: vec![],
+        mismatched: vec![],
+        vm_mismatches: vec![],
+        reports: vec![],
+    }));
+    let reports = Mutex::new(Vec::with_capacity(named_tests.len()));
+    let record_result =
+        |index: usize, name: &str, test: &TestConfig, r: anyhow::Result<Option<TestResult>>| {
             let mut wrapped_summary = wrapped_summary.lock().unwrap();
             if wrapped_summary.is_err() {
                 return;
             }
-            let (name, status) = match r {
-                Ok((name, status)) => (name, status),
+            let status = match r {
+                Ok(status) => status,
                 Err(err) => {
                     *wrapped_summary = Err(err);
                     return;
                 }
             };
             let summary = wrapped_summary.as_mut().unwrap();
+            let report = TestReport::new(name.to_string(), test, status.as_ref());
             let (res_type, status_str, gas_usage) = match status {
                 Some(TestResult {
                     status: TestStatus::Success,
                     gas_usage,
+                    ..
                 }) => (&mut summary.passed, "ok".bright_green(), gas_usage),
                 Some(TestResult {
                     status: TestStatus::Fail(run_result),
                     gas_usage,
+                    ..
                 }) => {
                     summary.failed_run_results.push(run_result);
                     (&mut summary.failed, "fail".bright_red(), gas_usage)
                 }
+                Some(TestResult {
+                    status: TestStatus::Mismatch(mismatches),
+                    gas_usage,
+                    ..
+                }) => {
+                    summary.vm_mismatches.push(mismatches);
+                    (&mut summary.mismatched, "mismatch".bright_red(), gas_usage)
+                }
                 None => (&mut summary.ignored, "ignored".bright_yellow(), None),
             };
-            if let Some(gas_usage) = gas_usage {
-                println!("test {name} ... {status_str} (gas usage est.: {gas_usage})");
-            } else {
-                println!("test {name} ... {status_str}");
+            if print_progress {
+                if let Some(gas_usage) = gas_usage {
+                    println!("test {name} ... {status_str} (gas usage est.: {gas_usage})");
+                } else {
+                    println!("test {name} ... {status_str}");
+                }
             }
-            res_type.push(name);
+            res_type.push(name.to_string());
+            reports.lock().unwrap().push((index, report));
+        };
+
+    // Every thread takes the next pending test until there are none left.
+    let next_test = AtomicUsize::new(0);
+    thread::scope(|s| {
+        for _ in 0..args.jobs.clamp(1, named_tests.len().max(1)) {
+            s.spawn(|| loop {
+                let index = next_test.fetch_add(1, Ordering::Relaxed);
+                let Some((name, test)) = named_tests.get(index) else {
+                    break;
+                };
+
+                record_result(index, name, test, run_test(name, test));
+            });
+        }
+    });
+
+    let mut summary = wrapped_summary.into_inner().unwrap()?;
+    summary.reports = reports
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .sorted_by_key(|(index, _)| *index)
+        .map(|(_, report)| report)
+        .collect();
+
+    Ok(summary)
+}
+
+impl TestReport {
+    fn new(name: String, test: &TestConfig, result: Option<&TestResult>) -> Self {
+        let panic_data = result.and_then(|x| x.panic_data.clone());
+        let panic_message = panic_data.as_ref().map(|felts| {
+            let bytes = felts
+                .iter()
+                .flat_map(|felt| felt.to_bytes_be())
+                // remove null chars
+                .filter(|b| *b != 0)
+                .collect::<Vec<_>>();
+            decode_error_message(&bytes)
         });
-    wrapped_summary.into_inner().unwrap()
+
+        Self {
+            name,
+            status: match result {
+                Some(TestResult {
+                    status: TestStatus::Success,
+                    ..
+                }) => TestReportStatus::Passed,
+                Some(TestResult {
+                    status: TestStatus::Fail(_),
+                    ..
+                }) => TestReportStatus::Failed,
+                Some(TestResult {
+                    status: TestStatus::Mismatch(_),
+                    ..
+                }) => TestReportStatus::Mismatch,
+                None => TestReportStatus::Ignored,
+            },
+            panic_data,
+            panic_message,
+            available_gas: test.available_gas,
+            gas_usage: result.and_then(|x| x.gas_usage),
+            wall_time: result.map_or(0.0, |x| x.wall_time.as_secs_f64()),
+            vm_mismatches: match result {
+                Some(TestResult {
+                    status: TestStatus::Mismatch(mismatches),
+                    ..
+                }) => mismatches.clone(),
+                _ => Vec::new(),
+            },
+        }
+    }
+
+    /// Describe why the test failed.
+    fn failure_message(&self) -> String {
+        if !self.vm_mismatches.is_empty() {
+            return format!(
+                "native and the Cairo VM differ in their {}.",
+                self.vm_mismatches
+                    .iter()
+                    .map(|x| x.kind.description())
+                    .join(", ")
+            );
+        }
+
+        match &self.panic_data {
+            None => "expected panic but finished successfully.".to_string(),
+            Some(panic_data) => format_for_panic(panic_data.clone().into_iter()),
+        }
+    }
+}
+
+/// Write the reports of the ran test suites, each identified by its name.
+///
+/// Nothing is written for the text format, since its progress is printed while running.
+pub fn write_reports(
+    format: ReportFormat,
+    suites: &[(String, TestsSummary)],
+    mut output: impl Write,
+) -> io::Result<()> {
+    match format {
+        ReportFormat::Text => Ok(()),
+        ReportFormat::Json => {
+            #[derive(Serialize)]
+            struct SuiteReport<'a> {
+                name: &'a str,
+                tests: &'a [TestReport],
+            }
+
+            let suites = suites
+                .iter()
+                .map(|(name, summary)| SuiteReport {
+                    name,
+                    tests: &summary.reports,
+                })
+                .collect::<Vec<_>>();
+            serde_json::to_writer_pretty(&mut output, &suites)?;
+            writeln!(output)
+        }
+        ReportFormat::Junit => {
+            output.write_all(junit_report(suites).as_bytes())?;
+            output.flush()
+        }
+    }
+}
+
+/// Build a JUnit XML document with a `testsuite` for every suite.
+fn junit_report(suites: &[(String, TestsSummary)]) -> String {
+    let count = |summary: &TestsSummary, status| {
+        summary
+            .reports
+            .iter()
+            .filter(|x| x.status == status)
+            .count()
+    };
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        xml,
+        r#"<testsuites tests="{}" failures="{}" skipped="{}">"#,
+        suites.iter().map(|(_, x)| x.reports.len()).sum::<usize>(),
+        suites
+            .iter()
+            .map(|(_, x)| x.failed.len() + x.mismatched.len())
+            .sum::<usize>(),
+        suites.iter().map(|(_, x)| x.ignored.len()).sum::<usize>(),
+    )
+    .unwrap();
+
+    for (suite_name, summary) in suites {
+        writeln!(
+            xml,
+            r#"  <testsuite name="{}" tests="{}" failures="{}" skipped="{}" time="{:.6}">"#,
+            xml_escape(suite_name),
+            summary.reports.len(),
+            count(summary, TestReportStatus::Failed) + count(summary, TestReportStatus::Mismatch),
+            count(summary, TestReportStatus::Ignored),
+            summary.reports.iter().map(|x| x.wall_time).sum::<f64>(),
+        )
+        .unwrap();
+
+        for report in &summary.reports {
+            writeln!(
+                xml,
+                r#"    <testcase name="{}" classname="{}" time="{:.6}">"#,
+                xml_escape(&report.name),
+                xml_escape(suite_name),
+                report.wall_time,
+            )
+            .unwrap();
+
+            if report.available_gas.is_some() || report.gas_usage.is_some() {
+                xml.push_str("      <properties>\n");
+                if let Some(available_gas) = report.available_gas {
+                    writeln!(
+                        xml,
+                        r#"        <property name="available_gas" value="{available_gas}"/>"#
+                    )
+                    .unwrap();
+                }
+                if let Some(gas_usage) = report.gas_usage {
+                    writeln!(
+                        xml,
+                        r#"        <property name="gas_usage" value="{gas_usage}"/>"#
+                    )
+                    .unwrap();
+                }
+                xml.push_str("      </properties>\n");
+            }
+
+            match report.status {
+                TestReportStatus::Passed => {}
+                TestReportStatus::Failed => {
+                    writeln!(
+                        xml,
+                        r#"      <failure message="{}">{}</failure>"#,
+                        xml_escape(&report.failure_message()),
+                        xml_escape(report.panic_message.as_deref().unwrap_or_default()),
+                    )
+                    .unwrap();
+                }
+                TestReportStatus::Mismatch => {
+                    writeln!(
+                        xml,
+                        r#"      <failure type="vm_mismatch" message="{}">{}</failure>"#,
+                        xml_escape(&report.failure_message()),
+                        xml_escape(
+                            &report
+                                .vm_mismatches
+                                .iter()
+                                .map(|x| format!(
+                                    "{}: native = {}, vm = {}",
+                                    x.kind.description(),
+                                    x.native,
+                                    x.vm
+                                ))
+                                .join("\n")
+                        ),
+                    )
+                    .unwrap();
+                }
+                TestReportStatus::Ignored => xml.push_str("      <skipped/>\n"),
+            }
+
+            xml.push_str("    </testcase>\n");
+        }
+
+        xml.push_str("  </testsuite>\n");
+    }
+
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .chars()
+        .fold(String::with_capacity(value.len()), |mut acc, ch| {
+            match ch {
+                '&' => acc.push_str("&amp;"),
+                '<' => acc.push_str("&lt;"),
+                '>' => acc.push_str("&gt;"),
+                '"' => acc.push_str("&quot;"),
+                '\'' => acc.push_str("&apos;"),
+                // Control characters aren't allowed in XML 1.0.
+                ch if ch.is_control() && !matches!(ch, '\n' | '\r' | '\t') => {
+                    write!(acc, "\\x{:02x}", ch as u32).unwrap()
+                }
+                ch => acc.push(ch),
+            }
+            acc
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cairo_lang_compiler::{
+        db::RootDatabase, diagnostics::DiagnosticsReporter, project::setup_project,
+    };
+    use cairo_lang_filesystem::cfg::{Cfg, CfgSet};
+    use cairo_lang_test_plugin::{
+        compile_test_prepared_db, test_plugin_suite, TestsCompilationConfig,
+    };
+
+    fn summary() -> TestsSummary {
+        TestsSummary {
+            passed: vec!["test_ok".into()],
+            failed: vec!["test_<fail>".into()],
+            ignored: vec!["test_ignored".into()],
+            failed_run_results: Vec::new(),
+            mismatched: Vec::new(),
+            vm_mismatches: Vec::new(),
+            reports: vec![
+                TestReport {
+                    name: "test_ok".into(),
+                    status: TestReportStatus::Passed,
+                    panic_data: None,
+                    panic_message: None,
+                    available_gas: Some(1000),
+                    gas_usage: Some(100),
+                    wall_time: 0.5,
+                    vm_mismatches: Vec::new(),
+                },
+                TestReport {
+                    name: "test_<fail>".into(),
+                    status: TestReportStatus::Failed,
+                    panic_data: Some(vec![Felt::from_bytes_be_slice(b"oops")]),
+                    panic_message: Some("oops".into()),
+                    available_gas: None,
+                    gas_usage: None,
+                    wall_time: 0.25,
+                    vm_mismatches: Vec::new(),
+                },
+                TestReport {
+                    name: "test_ignored".into(),
+                    status: TestReportStatus::Ignored,
+                    panic_data: None,
+                    panic_message: None,
+                    available_gas: None,
+                    gas_usage: None,
+                    wall_time: 0.0,
+                    vm_mismatches: Vec::new(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_json_report() {
+        let mut output = Vec::new();
+        write_reports(
+            ReportFormat::Json,
+            &[("suite".into(), summary())],
+            &mut output,
+        )
+        .unwrap();
+
+        let report: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        let tests = &report[0]["tests"];
+        assert_eq!(report[0]["name"], "suite");
+        assert_eq!(tests[0]["status"], "passed");
+        assert_eq!(tests[0]["gas_usage"], 100);
+        assert_eq!(tests[1]["status"], "failed");
+        assert_eq!(tests[1]["panic_message"], "oops");
+        assert_eq!(tests[2]["status"], "ignored");
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let report = junit_report(&[("suite".into(), summary())]);
+
+        assert!(report.contains(r#"<testsuites tests="3" failures="1" skipped="1">"#));
+        assert!(report.contains(
+            r#"<testsuite name="suite" tests="3" failures="1" skipped="1" time="0.750000">"#
+        ));
+        assert!(report
+            .contains(r#"<testcase name="test_&lt;fail&gt;" classname="suite" time="0.250000">"#));
+        assert!(
+            report.contains(r#"<failure message="Panicked with &quot;oops&quot;.">oops</failure>"#)
+        );
+        assert!(report.contains(r#"<property name="gas_usage" value="100"/>"#));
+        assert!(report.contains("<skipped/>"));
+    }
+
+    #[test]
+    fn test_find_vm_mismatches() {
+        let success = RunResultValue::Success(vec![Felt::from(1)]);
+        let panic = |data: &[u8]| RunResultValue::Panic(vec![Felt::from_bytes_be_slice(data)]);
+
+        assert!(find_vm_mismatches(&success, Some(10), &success, Some(Felt::from(10))).is_empty());
+        assert!(find_vm_mismatches(&panic(b"oops"), None, &panic(b"oops"), None).is_empty());
+
+        let kinds = |mismatches: Vec<VmMismatch>| {
+            mismatches.into_iter().map(|x| x.kind).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            kinds(find_vm_mismatches(&success, None, &panic(b"oops"), None)),
+            [VmMismatchKind::Outcome]
+        );
+        assert_eq!(
+            kinds(find_vm_mismatches(
+                &success,
+                None,
+                &RunResultValue::Success(vec![Felt::from(2)]),
+                None
+            )),
+            [VmMismatchKind::ReturnValues]
+        );
+        assert_eq!(
+            kinds(find_vm_mismatches(
+                &panic(b"oops"),
+                Some(10),
+                &panic(b"fail"),
+                Some(Felt::from(20))
+            )),
+            [VmMismatchKind::PanicData, VmMismatchKind::RemainingGas]
+        );
+
+        let mismatches = find_vm_mismatches(&panic(b"oops"), None, &panic(b"fail"), None);
+        assert_eq!(mismatches[0].native, "Panicked with \"oops\".");
+        assert_eq!(mismatches[0].vm, "Panicked with \"fail\".");
+    }
+
+    #[test]
+    fn test_compare_vm_execution_info() {
+        // The panic data contains the execution info, so any difference is a mismatch.
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("execution_info.cairo");
+        std::fs::write(
+            &path,
+            r#"
+                #[test]
+                #[should_panic]
+                fn read_execution_info() {
+                    let info = starknet::get_execution_info().unbox();
+                    let block_info = info.block_info.unbox();
+                    let tx_info = info.tx_info.unbox();
+                    core::panic(array![
+                        block_info.sequencer_address.into(),
+                        info.caller_address.into(),
+                        info.contract_address.into(),
+                        info.entry_point_selector,
+                        tx_info.chain_id,
+                        tx_info.transaction_hash,
+                        tx_info.signature.len().into(),
+                    ]);
+                }
+            "#,
+        )
+        .unwrap();
+
+        let db = &mut {
+            let mut b = RootDatabase::builder();
+            b.detect_corelib();
+            b.with_cfg(CfgSet::from_iter([Cfg::name("test")]));
+            b.with_default_plugin_suite(test_plugin_suite());
+            b.build().unwrap()
+        };
+        let main_crate_ids = setup_project(db, &path).unwrap();
+        let compiled = compile_test_prepared_db(
+            &db.snapshot(),
+            TestsCompilationConfig {
+                starknet: false,
+                add_statements_functions: false,
+                add_statements_code_locations: false,
+                contract_declarations: None,
+                contract_crate_ids: None,
+                executable_crate_ids: None,
+            },
+            main_crate_ids.clone(),
+            DiagnosticsReporter::stderr().with_crates(&main_crate_ids),
+        )
+        .unwrap();
+
+        let summary = run_tests(
+            compiled.metadata.named_tests,
+            compiled.sierra_program.program,
+            compiled.metadata.function_set_costs,
+            compiled.metadata.contracts_info,
+            RunArgs {
+                run_mode: RunMode::Jit,
+                opt_level: 0,
+                jobs: 1,
+                format: ReportFormat::Json,
+                compare_vm: true,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(summary.passed.len(), 1);
+        assert!(summary.mismatched.is_empty());
+    }
 }
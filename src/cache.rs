@@ -1,8 +1,19 @@
-pub use self::{aot::AotProgramCache, jit::JitProgramCache};
+pub use self::{
+    aot::AotProgramCache,
+    concurrent::{ConcurrentJitProgramCache, ConcurrentProgramCache, RetryPolicy},
+    disk::DiskProgramCache,
+    jit::JitProgramCache,
+    queue::{CompilationHandle, CompilationQueue, FallbackExecutor, NoFallback},
+    server::{CompileClient, CompileServer, ServerStats},
+};
 use std::hash::Hash;
 
 pub mod aot;
+pub mod concurrent;
+pub mod disk;
 pub mod jit;
+pub mod queue;
+pub mod server;
 
 #[derive(Debug)]
 pub enum ProgramCache<'a, K>
@@ -0,0 +1,392 @@
+//! # Concurrent program caches
+//!
+//! Thread-safe caches which can be shared between threads without wrapping them in a mutex. Reads
+//! only take a shared lock, and compilations run outside of any lock so programs with different
+//! keys are compiled in parallel.
+//!
+//! When multiple threads request the same uncompiled key, only the first one compiles it while the
+//! rest wait for the result. Failed compilations are remembered too, and are only attempted again
+//! according to the cache's [`RetryPolicy`].
+//!
+//! There's a cache for AOT executors, [`ConcurrentProgramCache`], and one for JIT executors,
+//! [`ConcurrentJitProgramCache`]. JIT executors borrow the [`NativeContext`] they were compiled
+//! with, so the JIT cache can't outlive its context. Use the AOT cache when the cache has to be
+//! `'static`, for example to hand it to a [`CompilationQueue`](super::CompilationQueue).
+
+use crate::{
+    context::NativeContext,
+    error::{Error, Result},
+    executor::{AotNativeExecutor, JitNativeExecutor},
+    OptLevel,
+};
+use cairo_lang_sierra::program::Program;
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug},
+    hash::Hash,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+/// When to attempt compiling a program again after it has failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of compilation attempts, including the first one.
+    pub max_attempts: u32,
+    /// The time to wait after a failure before attempting it again.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Never retry failed compilations.
+    pub const NEVER: Self = Self {
+        max_attempts: 1,
+        backoff: Duration::ZERO,
+    };
+
+    fn should_retry(&self, attempts: u32, since_failure: Duration) -> bool {
+        attempts < self.max_attempts && since_failure >= self.backoff
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::NEVER
+    }
+}
+
+/// A thread-safe cache of AOT compiled programs.
+///
+/// Please look at the [module level docs](self).
+pub struct ConcurrentProgramCache<K>
+where
+    K: PartialEq + Eq + Hash,
+{
+    opt_level: OptLevel,
+    entries: Entries<K, AotNativeExecutor>,
+}
+
+impl<K> ConcurrentProgramCache<K>
+where
+    K: PartialEq + Eq + Hash,
+{
+    pub fn new(opt_level: OptLevel) -> Self {
+        Self {
+            opt_level,
+            entries: Entries::default(),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.entries.retry_policy = retry_policy;
+        self
+    }
+
+    /// Return the executor of an already compiled program.
+    pub fn get(&self, key: &K) -> Option<Arc<AotNativeExecutor>> {
+        self.entries.get(key)
+    }
+
+    /// Return the executor of a program, compiling it if it's not in the cache.
+    ///
+    /// If the program is being compiled by another thread, this function will block until it's
+    /// done. Failures are cached, and will be returned as [`Error::CachedCompilationFailure`]
+    /// until the retry policy allows compiling the program again.
+    pub fn get_or_compile(&self, key: K, program: &Program) -> Result<Arc<AotNativeExecutor>> {
+        self.entries.get_or_compile(key, || {
+            let module = NativeContext::new().compile(program, false, Some(Default::default()))?;
+            AotNativeExecutor::from_native_module(module, self.opt_level)
+        })
+    }
+}
+
+impl<K> Debug for ConcurrentProgramCache<K>
+where
+    K: PartialEq + Eq + Hash,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ConcurrentProgramCache")
+    }
+}
+
+/// A thread-safe cache of JIT compiled programs.
+///
+/// Please look at the [module level docs](self).
+pub struct ConcurrentJitProgramCache<'a, K>
+where
+    K: PartialEq + Eq + Hash,
+{
+    context: &'a NativeContext,
+    opt_level: OptLevel,
+    entries: Entries<K, JitNativeExecutor<'a>>,
+}
+
+impl<'a, K> ConcurrentJitProgramCache<'a, K>
+where
+    K: PartialEq + Eq + Hash,
+{
+    pub fn new(context: &'a NativeContext, opt_level: OptLevel) -> Self {
+        Self {
+            context,
+            opt_level,
+            entries: Entries::default(),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.entries.retry_policy = retry_policy;
+        self
+    }
+
+    /// Return the executor of an already compiled program.
+    pub fn get(&self, key: &K) -> Option<Arc<JitNativeExecutor<'a>>> {
+        self.entries.get(key)
+    }
+
+    /// Return the executor of a program, compiling it if it's not in the cache.
+    ///
+    /// Behaves like [`ConcurrentProgramCache::get_or_compile`].
+    pub fn get_or_compile(&self, key: K, program: &Program) -> Result<Arc<JitNativeExecutor<'a>>> {
+        self.entries.get_or_compile(key, || {
+            let module = self
+                .context
+                .compile(program, false, Some(Default::default()))?;
+            JitNativeExecutor::from_native_module(module, self.opt_level)
+        })
+    }
+}
+
+impl<K> Debug for ConcurrentJitProgramCache<'_, K>
+where
+    K: PartialEq + Eq + Hash,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ConcurrentJitProgramCache")
+    }
+}
+
+/// The entries of a concurrent cache, shared by both kinds of executors.
+struct Entries<K, E> {
+    retry_policy: RetryPolicy,
+    cache: RwLock<HashMap<K, Arc<CacheEntry<E>>>>,
+}
+
+impl<K, E> Default for Entries<K, E> {
+    fn default() -> Self {
+        Self {
+            retry_policy: RetryPolicy::default(),
+            cache: Default::default(),
+        }
+    }
+}
+
+struct CacheEntry<E> {
+    state: Mutex<EntryState<E>>,
+    // Notified whenever a compilation finishes, either successfully or not.
+    compiled: Condvar,
+}
+
+impl<E> Default for CacheEntry<E> {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(EntryState::default()),
+            compiled: Condvar::new(),
+        }
+    }
+}
+
+enum EntryState<E> {
+    /// Not compiled yet, or the last compilation failed.
+    Idle {
+        attempts: u32,
+        last_error: Option<(String, Instant)>,
+    },
+    Compiling,
+    Compiled(Arc<E>),
+}
+
+impl<E> Default for EntryState<E> {
+    fn default() -> Self {
+        Self::Idle {
+            attempts: 0,
+            last_error: None,
+        }
+    }
+}
+
+impl<K, E> Entries<K, E>
+where
+    K: PartialEq + Eq + Hash,
+{
+    fn get(&self, key: &K) -> Option<Arc<E>> {
+        let entry = self.cache.read().unwrap().get(key).cloned()?;
+        let state = entry.state.lock().unwrap();
+
+        match &*state {
+            EntryState::Compiled(executor) => Some(executor.clone()),
+            _ => None,
+        }
+    }
+
+    fn get_or_compile(&self, key: K, compile: impl FnOnce() -> Result<E>) -> Result<Arc<E>> {
+        let entry = self.cache.read().unwrap().get(&key).cloned();
+        let entry = match entry {
+            Some(entry) => entry,
+            None => self.cache.write().unwrap().entry(key).or_default().clone(),
+        };
+
+        let mut state = entry.state.lock().unwrap();
+        let attempts = loop {
+            match &*state {
+                EntryState::Compiled(executor) => return Ok(executor.clone()),
+                EntryState::Compiling => {}
+                EntryState::Idle {
+                    attempts,
+                    last_error,
+                } => match last_error {
+                    Some((message, failed_at))
+                        if !self
+                            .retry_policy
+                            .should_retry(*attempts, failed_at.elapsed()) =>
+                    {
+                        return Err(Error::CachedCompilationFailure(message.clone()))
+                    }
+                    _ => break *attempts,
+                },
+            }
+
+            state = entry.compiled.wait(state).unwrap();
+        };
+
+        // Compile without holding the lock, so that other threads can check the entry's state.
+        *state = EntryState::Compiling;
+        drop(state);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| compile().map(Arc::new)));
+
+        let mut state = entry.state.lock().unwrap();
+        *state = match &result {
+            Ok(Ok(executor)) => EntryState::Compiled(executor.clone()),
+            Ok(Err(e)) => EntryState::Idle {
+                attempts: attempts + 1,
+                last_error: Some((e.to_string(), Instant::now())),
+            },
+            Err(_) => EntryState::Idle {
+                attempts: attempts + 1,
+                last_error: Some(("compilation panicked".to_string(), Instant::now())),
+            },
+        };
+        drop(state);
+        entry.compiled.notify_all();
+
+        result.unwrap_or_else(|e| panic::resume_unwind(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{utils::test::load_cairo, values::Value};
+    use cairo_lang_sierra::ids::GenericLibfuncId;
+    use starknet_types_core::felt::Felt;
+    use std::thread;
+
+    #[test]
+    fn test_concurrent_get_or_compile() {
+        let cache = ConcurrentProgramCache::new(OptLevel::None);
+
+        let (_, program) = load_cairo! {
+            fn run_test() -> felt252 {
+                42
+            }
+        };
+        let function_id = &program.funcs.first().expect("should have a function").id;
+
+        assert!(cache.get(&()).is_none());
+
+        // Every thread receives the executor compiled by the first one.
+        let executors = thread::scope(|s| {
+            let handles = (0..8)
+                .map(|_| s.spawn(|| cache.get_or_compile((), &program).unwrap()))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|x| x.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert!(executors.iter().all(|x| Arc::ptr_eq(x, &executors[0])));
+        assert!(Arc::ptr_eq(&cache.get(&()).unwrap(), &executors[0]));
+
+        let res = executors[0]
+            .invoke_dynamic(function_id, &[], Some(u64::MAX))
+            .expect("should run");
+        assert_eq!(res.return_value, Value::Felt252(Felt::from(42)));
+    }
+
+    #[test]
+    fn test_concurrent_retry_policy() {
+        let (_, mut program) = load_cairo! {
+            fn run_test() -> felt252 {
+                42
+            }
+        };
+        program.libfunc_declarations[0].long_id.generic_id =
+            GenericLibfuncId::from_string("unknown_libfunc");
+
+        let is_compilation_error = |result: Result<_>| matches!(result, Err(e) if !matches!(e, Error::CachedCompilationFailure(_)));
+
+        // Failures are cached.
+        let cache = ConcurrentProgramCache::new(OptLevel::None);
+        assert!(is_compilation_error(cache.get_or_compile((), &program)));
+        assert!(matches!(
+            cache.get_or_compile((), &program),
+            Err(Error::CachedCompilationFailure(_))
+        ));
+
+        // Until the retry policy allows compiling them again.
+        let cache = ConcurrentProgramCache::new(OptLevel::None).with_retry_policy(RetryPolicy {
+            max_attempts: 2,
+            backoff: Duration::ZERO,
+        });
+        for _ in 0..2 {
+            assert!(is_compilation_error(cache.get_or_compile((), &program)));
+        }
+        assert!(matches!(
+            cache.get_or_compile((), &program),
+            Err(Error::CachedCompilationFailure(_))
+        ));
+    }
+
+    #[test]
+    fn test_concurrent_jit_get_or_compile() {
+        let context = NativeContext::new();
+        let cache = ConcurrentJitProgramCache::new(&context, OptLevel::None);
+
+        let (_, program) = load_cairo! {
+            fn run_test() -> felt252 {
+                42
+            }
+        };
+        let function_id = &program.funcs.first().expect("should have a function").id;
+
+        assert!(cache.get(&()).is_none());
+
+        let executors = thread::scope(|s| {
+            let handles = (0..8)
+                .map(|_| s.spawn(|| cache.get_or_compile((), &program).unwrap()))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|x| x.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert!(executors.iter().all(|x| Arc::ptr_eq(x, &executors[0])));
+        assert!(Arc::ptr_eq(&cache.get(&()).unwrap(), &executors[0]));
+
+        let res = executors[0]
+            .invoke_dynamic(function_id, &[], Some(u64::MAX))
+            .expect("should run");
+        assert_eq!(res.return_value, Value::Felt252(Felt::from(42)));
+    }
+}
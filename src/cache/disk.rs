@@ -0,0 +1,627 @@
+//! # Persistent AOT program cache
+//!
+//! Compiled programs are stored in a directory as shared libraries, along with a JSON file holding
+//! everything else required to load them back. Entries are content-addressed by a hash of the
+//! Sierra program, the optimization level, the target (triple, CPU and its features) and the
+//! version of Cairo Native, which allows them to survive restarts and to be shared between
+//! processes.
+//!
+//! Entries are built using the same lockfile protocol as [`AotContractExecutor::new_into`], so
+//! multiple processes can safely share the same directory.
+//!
+//! When the cache has a maximum size, the least recently used entries are removed after inserting
+//! new ones until it fits. Every entry also stores a checksum of its shared library, which is
+//! verified when loading it. Entries which fail the check are removed from the cache.
+//!
+//! Contract classes can be stored too, keyed by their class hash instead of their program. They
+//! share the cache's maximum size with the programs.
+//!
+//! [`AotContractExecutor::new_into`]: crate::executor::AotContractExecutor::new_into
+
+use crate::{
+    context::NativeContext,
+    error::{Error, Result},
+    executor::{
+        AotContractExecutor, AotNativeExecutor, LockFile, NativeContractInfo, SierraContractClass,
+    },
+    ffi::{get_host_cpu, get_target_triple},
+    metadata::{felt252_dict::Felt252DictOverrides, gas::GasMetadata},
+    module::NativeModule,
+    utils::{library_checksum, SHARED_LIBRARY_EXT},
+    OptLevel,
+};
+use cairo_lang_sierra::{ids::ConcreteTypeId, program::Program, program_registry::ProgramRegistry};
+use libloading::Library;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use starknet_types_core::felt::Felt;
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// A persistent cache of AOT compiled programs.
+///
+/// Please look at the [module level docs](self).
+#[derive(Debug, Clone)]
+pub struct DiskProgramCache {
+    path: PathBuf,
+    max_size: Option<u64>,
+}
+
+/// Everything required to load an entry, other than its shared library and the program.
+#[derive(Debug, Serialize, Deserialize)]
+struct EntryInfo {
+    /// The SHA-256 of the shared library.
+    checksum: String,
+    /// The types with a dictionary drop override, along with the symbol of their drop function.
+    dict_overrides: Vec<(u64, String)>,
+}
+
+/// A complete entry found in the cache directory.
+#[derive(Debug)]
+struct Entry {
+    key: String,
+    size: u64,
+    last_used: SystemTime,
+}
+
+impl DiskProgramCache {
+    /// Open the cache stored in the given directory, creating it if it doesn't exist.
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        fs::create_dir_all(&path)?;
+
+        Ok(Self {
+            path,
+            max_size: None,
+        })
+    }
+
+    /// Limit the total size of the cache's entries, in bytes.
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Return the key under which a program compiled with the given optimization level is stored.
+    pub fn key(program: &Program, opt_level: OptLevel) -> String {
+        let mut hasher = target_hasher(opt_level);
+        hasher.update(program.to_string().as_bytes());
+
+        to_hex(&hasher.finalize())
+    }
+
+    /// Return the key under which a contract class compiled with the given optimization level is
+    /// stored.
+    pub fn contract_key(class_hash: Felt, opt_level: OptLevel) -> String {
+        let mut hasher = target_hasher(opt_level);
+        hasher.update(b"contract class\0");
+        hasher.update(class_hash.to_bytes_be());
+
+        to_hex(&hasher.finalize())
+    }
+
+    /// Return the path of the shared library of a program, whether it's in the cache or not.
+    pub fn program_path(&self, program: &Program, opt_level: OptLevel) -> PathBuf {
+        self.library_path(&Self::key(program, opt_level))
+    }
+
+    /// Return the path of the shared library of a contract class, whether it's in the cache or
+    /// not. It can be loaded using [`AotContractExecutor::from_path`].
+    pub fn contract_path(&self, class_hash: Felt, opt_level: OptLevel) -> PathBuf {
+        self.library_path(&Self::contract_key(class_hash, opt_level))
+    }
+
+    /// Load a program from the cache.
+    ///
+    /// Returns `Ok(None)` if the program is not in the cache, it's still being compiled, its entry
+    /// was corrupted or it was replaced while being loaded.
+    pub fn get(&self, program: &Program, opt_level: OptLevel) -> Result<Option<AotNativeExecutor>> {
+        let key = Self::key(program, opt_level);
+        let library_path = self.library_path(&key);
+        let info_path = library_path.with_extension("json");
+
+        // The info is written before the library is moved into place, so a missing library means
+        // that the entry is either missing or still being built.
+        let library_data = match fs::read(&library_path) {
+            Ok(x) => x,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let info = fs::read_to_string(&info_path)
+            .ok()
+            .and_then(|x| serde_json::from_str::<EntryInfo>(&x).ok())
+            .filter(|x| x.checksum == library_checksum(&library_data));
+        let Some(info) = info else {
+            tracing::warn!(key, "removing corrupted cache entry");
+            self.remove_entry(&key)?;
+            return Ok(None);
+        };
+
+        // Mark the entry as recently used.
+        File::options()
+            .append(true)
+            .open(&info_path)?
+            .set_modified(SystemTime::now())?;
+
+        let dict_overrides = info
+            .dict_overrides
+            .into_iter()
+            .map(|(id, symbol)| (ConcreteTypeId::new(id), symbol))
+            .collect();
+
+        let Some(library) = load_library(&library_path, &library_data)? else {
+            return Ok(None);
+        };
+
+        Ok(Some(AotNativeExecutor::new(
+            library,
+            ProgramRegistry::new(program)?,
+            GasMetadata::new(program, Some(Default::default()))?,
+            dict_overrides,
+        )))
+    }
+
+    /// Compile a program and insert it into the cache, then evict entries if it has grown past
+    /// its maximum size.
+    ///
+    /// An attempt to compile a program which is already being compiled by another process will
+    /// result in `Ok(None)` being returned. When this happens, the user should wait until the lock
+    /// is released, at which point they can use [`get`](Self::get) to load it.
+    pub fn compile_and_insert(
+        &self,
+        program: &Program,
+        opt_level: OptLevel,
+    ) -> Result<Option<AotNativeExecutor>> {
+        let key = Self::key(program, opt_level);
+        let library_path = self.library_path(&key);
+        let lock_file = match LockFile::new(&library_path)? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+
+        let context = NativeContext::new();
+        let NativeModule {
+            module,
+            registry,
+            mut metadata,
+        } = context.compile(program, false, Some(Default::default()))?;
+
+        // Build the shared library into the lockfile, to avoid using a tmp file.
+        let object_data = crate::module_to_object(&module, opt_level)?;
+        crate::object_to_shared_lib(&object_data, &lock_file.0)?;
+
+        let dict_overrides: Felt252DictOverrides = metadata.remove().unwrap_or_default();
+        fs::write(
+            library_path.with_extension("json"),
+            serde_json::to_string(&EntryInfo {
+                checksum: library_checksum(&fs::read(&lock_file.0)?),
+                dict_overrides: dict_overrides
+                    .drop_fns()
+                    .map(|(type_id, symbol)| (type_id.id, symbol.to_string()))
+                    .collect(),
+            })?,
+        )?;
+
+        // Atomically move the built shared library to the correct path, making the entry visible
+        // to other processes.
+        lock_file.rename(&library_path)?;
+
+        if let Some(max_size) = self.max_size {
+            self.evict_until(max_size, Some(&key))?;
+        }
+
+        Ok(Some(AotNativeExecutor::new(
+            unsafe { Library::new(&library_path)? },
+            registry,
+            metadata.remove().ok_or(Error::MissingMetadata)?,
+            dict_overrides,
+        )))
+    }
+
+    /// Load a contract class from the cache.
+    ///
+    /// Returns `Ok(None)` if the contract class is not in the cache, it's still being compiled, its
+    /// entry was corrupted or it was replaced while being loaded.
+    pub fn get_contract(
+        &self,
+        class_hash: Felt,
+        opt_level: OptLevel,
+    ) -> Result<Option<AotContractExecutor>> {
+        self.get_contract_entry(&Self::contract_key(class_hash, opt_level))
+    }
+
+    /// Load the contract class stored under the given key, verifying its checksum like
+    /// [`get_contract`](Self::get_contract).
+    pub(crate) fn get_contract_entry(&self, key: &str) -> Result<Option<AotContractExecutor>> {
+        let library_path = self.library_path(key);
+        let info_path = library_path.with_extension("json");
+
+        let library_data = match fs::read(&library_path) {
+            Ok(x) => x,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let contract_info = fs::read_to_string(&info_path)
+            .ok()
+            .and_then(|x| serde_json::from_str::<NativeContractInfo>(&x).ok())
+            .filter(|x| x.checksum.as_ref() == Some(&library_checksum(&library_data)));
+        let Some(contract_info) = contract_info else {
+            tracing::warn!(key, "removing corrupted cache entry");
+            self.remove_entry(key)?;
+            return Ok(None);
+        };
+
+        // Mark the entry as recently used.
+        File::options()
+            .append(true)
+            .open(&info_path)?
+            .set_modified(SystemTime::now())?;
+
+        let Some(library) = load_library(&library_path, &library_data)? else {
+            return Ok(None);
+        };
+
+        Ok(Some(AotContractExecutor::from_library(
+            library,
+            library_path,
+            contract_info,
+        )))
+    }
+
+    /// Compile a contract class and insert it into the cache, then evict entries if it has grown
+    /// past its maximum size.
+    ///
+    /// Like [`compile_and_insert`](Self::compile_and_insert), returns `Ok(None)` if the contract
+    /// class is already being compiled by another process.
+    pub fn compile_contract_and_insert(
+        &self,
+        contract_class: &SierraContractClass,
+        opt_level: OptLevel,
+    ) -> Result<Option<AotContractExecutor>> {
+        let key = Self::contract_key(contract_class.class_hash, opt_level);
+        let executor = AotContractExecutor::new_into(
+            &contract_class.program,
+            &contract_class.entry_points,
+            contract_class.sierra_version,
+            self.library_path(&key),
+            opt_level,
+        )?;
+
+        if executor.is_some() {
+            if let Some(max_size) = self.max_size {
+                self.evict_until(max_size, Some(&key))?;
+            }
+        }
+
+        Ok(executor)
+    }
+
+    /// Remove the lock of an entry if it hasn't been modified for `max_age`, which means that the
+    /// process compiling it most likely died. Returns whether it was removed.
+    pub fn remove_stale_lock(&self, key: &str, max_age: Duration) -> Result<bool> {
+        let lock_path = self.library_path(key).with_extension("lock");
+        let modified = match fs::metadata(&lock_path).and_then(|x| x.modified()) {
+            Ok(x) => x,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        if modified.elapsed().unwrap_or_default() < max_age {
+            return Ok(false);
+        }
+
+        tracing::warn!(key, "removing stale lock");
+        match fs::remove_file(&lock_path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(true),
+        }
+    }
+
+    /// Remove every lock which hasn't been modified for `max_age`.
+    ///
+    /// Returns the number of removed locks.
+    pub fn remove_stale_locks(&self, max_age: Duration) -> Result<usize> {
+        let mut num_removed = 0;
+        for dir_entry in fs::read_dir(&self.path)? {
+            let lock_path = dir_entry?.path();
+            let key = match lock_path.extension().zip(lock_path.file_stem()) {
+                Some((ext, stem)) if ext == "lock" => stem.to_string_lossy(),
+                _ => continue,
+            };
+            let Some(key) = key.strip_prefix("lib") else {
+                continue;
+            };
+
+            if self.remove_stale_lock(key, max_age)? {
+                num_removed += 1;
+            }
+        }
+
+        Ok(num_removed)
+    }
+
+    /// Return the total size of the cache's entries, in bytes.
+    pub fn size(&self) -> Result<u64> {
+        Ok(self.entries()?.iter().map(|x| x.size).sum())
+    }
+
+    /// Remove the least recently used entries until the cache fits within its maximum size.
+    ///
+    /// Returns the number of removed entries.
+    pub fn evict(&self) -> Result<usize> {
+        match self.max_size {
+            Some(max_size) => self.evict_until(max_size, None),
+            None => Ok(0),
+        }
+    }
+
+    fn evict_until(&self, max_size: u64, keep: Option<&str>) -> Result<usize> {
+        let mut entries = self.entries()?;
+        entries.sort_by_key(|x| x.last_used);
+
+        let mut size = entries.iter().map(|x| x.size).sum::<u64>();
+        let mut num_removed = 0;
+        for entry in entries {
+            if size <= max_size {
+                break;
+            }
+            if Some(entry.key.as_str()) == keep {
+                continue;
+            }
+
+            self.remove_entry(&entry.key)?;
+            size -= entry.size;
+            num_removed += 1;
+        }
+
+        Ok(num_removed)
+    }
+
+    /// List the complete entries, skipping the ones being built.
+    fn entries(&self) -> Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        for dir_entry in fs::read_dir(&self.path)? {
+            let info_path = dir_entry?.path();
+            let key = match info_path.extension().zip(info_path.file_stem()) {
+                Some((ext, stem)) if ext == "json" => stem.to_string_lossy(),
+                _ => continue,
+            };
+            let Some(key) = key.strip_prefix("lib") else {
+                continue;
+            };
+
+            // Entries may be removed by other processes while listing them.
+            let (Ok(info_metadata), Ok(library_metadata)) = (
+                fs::metadata(&info_path),
+                fs::metadata(self.library_path(key)),
+            ) else {
+                continue;
+            };
+
+            entries.push(Entry {
+                key: key.to_string(),
+                size: info_metadata.len() + library_metadata.len(),
+                last_used: info_metadata.modified()?,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    fn remove_entry(&self, key: &str) -> Result<()> {
+        // The library goes first, since entries without a library are ignored.
+        let library_path = self.library_path(key);
+        for path in [library_path.clone(), library_path.with_extension("json")] {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn library_path(&self, key: &str) -> PathBuf {
+        self.path.join(format!("lib{key}.{SHARED_LIBRARY_EXT}"))
+    }
+}
+
+/// Load a shared library whose contents have already been verified.
+///
+/// The library is read again once loaded, since it may have been replaced in between. Returns
+/// `Ok(None)` if it has, in which case the entry should be looked up again.
+fn load_library(library_path: &Path, library_data: &[u8]) -> Result<Option<Library>> {
+    let library = unsafe { Library::new(library_path)? };
+
+    match fs::read(library_path) {
+        Ok(x) if x == library_data => Ok(Some(library)),
+        Ok(_) => {
+            tracing::warn!(path = %library_path.display(), "cache entry replaced while loading");
+            Ok(None)
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Return a hasher fed with everything which makes a compiled library specific to the current
+/// target, Cairo Native version and optimization level.
+fn target_hasher(opt_level: OptLevel) -> Sha256 {
+    let (cpu_name, cpu_features) = get_host_cpu();
+
+    let mut hasher = Sha256::new();
+    for part in [
+        env!("CARGO_PKG_VERSION"),
+        &get_target_triple(),
+        &cpu_name,
+        &cpu_features,
+    ] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.update([usize::from(opt_level) as u8]);
+
+    hasher
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{x:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        utils::test::{load_cairo, load_starknet_contract},
+        values::Value,
+    };
+    use starknet_types_core::felt::Felt;
+
+    #[test]
+    fn test_disk_compile_and_insert() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskProgramCache::new(dir.path()).unwrap();
+
+        let (_, program) = load_cairo! {
+            fn run_test() -> felt252 {
+                42
+            }
+        };
+        let function_id = &program.funcs.first().expect("should have a function").id;
+
+        assert!(cache.get(&program, OptLevel::None).unwrap().is_none());
+        cache
+            .compile_and_insert(&program, OptLevel::None)
+            .unwrap()
+            .unwrap();
+
+        // The program can be loaded back by another cache using the same directory.
+        let executor = DiskProgramCache::new(dir.path())
+            .unwrap()
+            .get(&program, OptLevel::None)
+            .unwrap()
+            .unwrap();
+        let res = executor
+            .invoke_dynamic(function_id, &[], Some(u64::MAX))
+            .expect("should run");
+        assert_eq!(res.return_value, Value::Felt252(Felt::from(42)));
+
+        // Other optimization levels are stored separately.
+        assert!(cache.get(&program, OptLevel::Default).unwrap().is_none());
+
+        // Programs being compiled by someone else can't be compiled again.
+        let key = DiskProgramCache::key(&program, OptLevel::Less);
+        let lock_file = LockFile::new(cache.library_path(&key)).unwrap().unwrap();
+        assert!(cache
+            .compile_and_insert(&program, OptLevel::Less)
+            .unwrap()
+            .is_none());
+        assert!(cache.get(&program, OptLevel::Less).unwrap().is_none());
+
+        // Locks are only stale once they haven't been modified for a while.
+        assert!(!cache
+            .remove_stale_lock(&key, Duration::from_secs(3600))
+            .unwrap());
+        File::options()
+            .append(true)
+            .open(&lock_file.0)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(7200))
+            .unwrap();
+        assert_eq!(
+            cache.remove_stale_locks(Duration::from_secs(3600)).unwrap(),
+            1
+        );
+        assert!(cache
+            .compile_and_insert(&program, OptLevel::Less)
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn test_disk_integrity_and_eviction() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskProgramCache::new(dir.path()).unwrap();
+
+        let (_, program_a) = load_cairo! {
+            fn run_test() -> felt252 {
+                1
+            }
+        };
+        let (_, program_b) = load_cairo! {
+            fn run_test() -> felt252 {
+                2
+            }
+        };
+
+        // Corrupted entries are removed when loading them.
+        cache
+            .compile_and_insert(&program_a, OptLevel::None)
+            .unwrap();
+        let key_a = DiskProgramCache::key(&program_a, OptLevel::None);
+        fs::write(cache.library_path(&key_a), b"corrupted").unwrap();
+        assert!(cache.get(&program_a, OptLevel::None).unwrap().is_none());
+        assert_eq!(cache.size().unwrap(), 0);
+
+        // The least recently used entries are evicted to make room for new ones.
+        cache
+            .compile_and_insert(&program_a, OptLevel::None)
+            .unwrap();
+        let max_size = cache.size().unwrap();
+        let cache = cache.with_max_size(max_size);
+        cache
+            .compile_and_insert(&program_b, OptLevel::None)
+            .unwrap();
+        assert!(cache.get(&program_a, OptLevel::None).unwrap().is_none());
+        assert!(cache.get(&program_b, OptLevel::None).unwrap().is_some());
+        assert_eq!(cache.evict().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_disk_contract_integrity() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskProgramCache::new(dir.path()).unwrap();
+
+        let (_, contract) = load_starknet_contract! {
+            #[starknet::contract]
+            mod contract {
+                #[storage]
+                struct Storage {}
+
+                #[external(v0)]
+                fn get(self: @ContractState) -> felt252 {
+                    42
+                }
+            }
+        };
+        let contract_class = SierraContractClass::from_contract_class(&contract).unwrap();
+        let class_hash = contract_class.class_hash;
+
+        cache
+            .compile_contract_and_insert(&contract_class, OptLevel::None)
+            .unwrap()
+            .unwrap();
+        assert!(cache
+            .get_contract(class_hash, OptLevel::None)
+            .unwrap()
+            .is_some());
+
+        // Corrupted entries are removed when loading them.
+        let library_path = cache.contract_path(class_hash, OptLevel::None);
+        let library_data = fs::read(&library_path).unwrap();
+        fs::write(&library_path, &library_data[..library_data.len() / 2]).unwrap();
+        assert!(cache
+            .get_contract(class_hash, OptLevel::None)
+            .unwrap()
+            .is_none());
+        assert!(!library_path.exists());
+        assert_eq!(cache.size().unwrap(), 0);
+    }
+}
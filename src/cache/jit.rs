@@ -1,13 +1,95 @@
+//! # JIT program cache
+//!
+//! Every [`JitNativeExecutor`] keeps its MLIR module and execution engine alive, therefore the
+//! cache can optionally be bounded by a number of entries and/or an amount of memory. When it
+//! exceeds its budget, the least recently used executors are evicted.
+//!
+//! Evicted executors may still be in use by whoever obtained them from the cache. Their engines
+//! are dropped once the last [`Arc`] is released, and until then they're still included in
+//! [`JitProgramCache::total_memory_usage`].
+//!
+//! Measuring the code size of an executor requires running the code generation once more, so
+//! it's only done when its memory usage is requested or when the cache is bounded by memory.
+
 use crate::error::Result;
 use crate::{context::NativeContext, executor::JitNativeExecutor, OptLevel};
-use cairo_lang_sierra::program::Program;
+use cairo_lang_sierra::{
+    extensions::core::{CoreConcreteLibfunc, CoreTypeConcrete},
+    program::{Function, Program},
+};
 use std::{
     collections::HashMap,
     fmt::{self, Debug},
     hash::Hash,
-    sync::Arc,
+    mem,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, OnceLock, Weak,
+    },
 };
 
+/// The memory used by a compiled executor, in bytes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutorMemoryUsage {
+    /// The size of the machine code generated from the module.
+    pub code_size: usize,
+    /// An estimation of the size of the MLIR module, from its textual representation.
+    pub module_size: usize,
+    /// An estimation of the size of the program registry.
+    pub registry_size: usize,
+}
+
+impl ExecutorMemoryUsage {
+    pub const fn total(&self) -> usize {
+        self.code_size + self.module_size + self.registry_size
+    }
+}
+
+/// The memory usage of a cached executor, whose code and module sizes are measured the first time
+/// they're needed.
+struct MemoryUsage {
+    registry_size: usize,
+    opt_level: OptLevel,
+    code_size: OnceLock<usize>,
+    module_size: OnceLock<usize>,
+}
+
+impl MemoryUsage {
+    fn new(program: &Program, opt_level: OptLevel) -> Self {
+        Self {
+            registry_size: program.type_declarations.len() * mem::size_of::<CoreTypeConcrete>()
+                + program.libfunc_declarations.len() * mem::size_of::<CoreConcreteLibfunc>()
+                + program.funcs.len() * mem::size_of::<Function>(),
+            opt_level,
+            code_size: OnceLock::new(),
+            module_size: OnceLock::new(),
+        }
+    }
+
+    fn measure(&self, executor: &JitNativeExecutor) -> Result<ExecutorMemoryUsage> {
+        let code_size = match self.code_size.get() {
+            Some(code_size) => *code_size,
+            None => {
+                // The execution engine doesn't expose its code size, but it generates the same
+                // object code from the module.
+                let code_size =
+                    crate::ffi::module_to_object(executor.module(), self.opt_level)?.len();
+                *self.code_size.get_or_init(|| code_size)
+            }
+        };
+
+        let module_size = *self
+            .module_size
+            .get_or_init(|| executor.module().as_operation().to_string().len());
+
+        Ok(ExecutorMemoryUsage {
+            code_size,
+            module_size,
+            registry_size: self.registry_size,
+        })
+    }
+}
+
 /// A Cache for programs with the same context.
 pub struct JitProgramCache<'a, K>
 where
@@ -17,7 +99,20 @@ where
     // Since we already hold a reference to the Context, it doesn't make sense to use thread-safe
     // reference counting. Using a Arc<RwLock<T>> here is useless because NativeExecutor is neither
     // Send nor Sync.
-    cache: HashMap<K, Arc<JitNativeExecutor<'a>>>,
+    cache: HashMap<K, CacheEntry<'a>>,
+    // Executors which have been evicted while still in use.
+    evicted: Vec<(Weak<JitNativeExecutor<'a>>, Arc<MemoryUsage>)>,
+
+    max_entries: Option<usize>,
+    max_memory: Option<usize>,
+    // Incremented on every access, used to find the least recently used entries.
+    clock: AtomicU64,
+}
+
+struct CacheEntry<'a> {
+    executor: Arc<JitNativeExecutor<'a>>,
+    memory_usage: Arc<MemoryUsage>,
+    last_used: AtomicU64,
 }
 
 impl<'a, K> JitProgramCache<'a, K>
@@ -28,16 +123,80 @@ where
         Self {
             context,
             cache: Default::default(),
+            evicted: Vec::new(),
+            max_entries: None,
+            max_memory: None,
+            clock: AtomicU64::new(0),
         }
     }
 
+    /// Limit the number of cached executors.
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    /// Limit the memory used by the cached executors, in bytes, as reported by
+    /// [`ExecutorMemoryUsage::total`].
+    ///
+    /// The code size of every executor is measured when it's inserted. The execution engine
+    /// doesn't expose it, so each insert runs a second full code generation of the module, which
+    /// roughly doubles the time spent compiling.
+    pub fn with_max_memory(mut self, max_memory: usize) -> Self {
+        self.max_memory = Some(max_memory);
+        self
+    }
+
     // Return the native context.
     pub const fn context(&self) -> &'a NativeContext {
         self.context
     }
 
     pub fn get(&self, key: &K) -> Option<Arc<JitNativeExecutor<'a>>> {
-        self.cache.get(key).cloned()
+        let entry = self.cache.get(key)?;
+        entry.last_used.store(self.tick(), Ordering::Relaxed);
+
+        Some(entry.executor.clone())
+    }
+
+    /// Return the memory used by a cached executor.
+    pub fn memory_usage(&self, key: &K) -> Result<Option<ExecutorMemoryUsage>> {
+        self.cache
+            .get(key)
+            .map(|x| x.memory_usage.measure(&x.executor))
+            .transpose()
+    }
+
+    /// Return the memory used by every cached executor, plus the evicted ones which are still
+    /// in use.
+    pub fn total_memory_usage(&self) -> Result<usize> {
+        let mut total = self.cached_memory_usage()?;
+        for (executor, memory_usage) in &self.evicted {
+            if let Some(executor) = executor.upgrade() {
+                total += memory_usage.measure(&executor)?.total();
+            }
+        }
+
+        Ok(total)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Remove an executor from the cache. Its engine will be dropped once it's no longer in use.
+    pub fn remove(&mut self, key: &K) -> Option<Arc<JitNativeExecutor<'a>>> {
+        let entry = self.cache.remove(key)?;
+        self.evicted
+            .push((Arc::downgrade(&entry.executor), entry.memory_usage));
+        self.evicted
+            .retain(|(executor, _)| executor.strong_count() > 0);
+
+        Some(entry.executor)
     }
 
     pub fn compile_and_insert(
@@ -45,17 +204,77 @@ where
         key: K,
         program: &Program,
         opt_level: OptLevel,
-    ) -> Result<Arc<JitNativeExecutor<'a>>> {
+    ) -> Result<Arc<JitNativeExecutor<'a>>>
+    where
+        K: Clone,
+    {
         let module = self
             .context
             .compile(program, false, Some(Default::default()))?;
+        let memory_usage = Arc::new(MemoryUsage::new(program, opt_level));
         let executor = JitNativeExecutor::from_native_module(module, opt_level)?;
 
         let executor = Arc::new(executor);
-        self.cache.insert(key, executor.clone());
+        if let Some(entry) = self.cache.insert(
+            key.clone(),
+            CacheEntry {
+                executor: executor.clone(),
+                memory_usage,
+                last_used: AtomicU64::new(self.tick()),
+            },
+        ) {
+            self.evicted
+                .push((Arc::downgrade(&entry.executor), entry.memory_usage));
+        }
+        self.evict(&key)?;
 
         Ok(executor)
     }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn cached_memory_usage(&self) -> Result<usize> {
+        self.cache
+            .values()
+            .map(|x| Ok(x.memory_usage.measure(&x.executor)?.total()))
+            .sum()
+    }
+
+    /// Evict the least recently used entries until the cache is within its budget. The entry
+    /// which has just been inserted is never evicted.
+    fn evict(&mut self, inserted: &K) -> Result<()>
+    where
+        K: Clone,
+    {
+        let exceeds_budget = |cache: &Self| -> Result<bool> {
+            Ok(cache.max_entries.is_some_and(|x| cache.cache.len() > x)
+                || match cache.max_memory {
+                    Some(x) => cache.cached_memory_usage()? > x,
+                    None => false,
+                })
+        };
+
+        while exceeds_budget(self)? {
+            let Some(key) = self
+                .cache
+                .iter()
+                .filter(|(key, _)| *key != inserted)
+                .min_by_key(|(_, entry)| entry.last_used.load(Ordering::Relaxed))
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+
+            self.remove(&key);
+        }
+
+        self.evicted
+            .retain(|(executor, _)| executor.strong_count() > 0);
+
+        Ok(())
+    }
 }
 
 impl<K> Debug for JitProgramCache<'_, K>
@@ -114,4 +333,76 @@ mod test {
 
         assert!(diff_2 < diff_1);
     }
+
+    #[test]
+    fn test_cache_eviction() {
+        let programs = [
+            load_cairo!(
+                fn main(lhs: felt252, rhs: felt252) -> felt252 {
+                    lhs + rhs
+                }
+            ),
+            load_cairo!(
+                fn main(lhs: felt252, rhs: felt252) -> felt252 {
+                    lhs - rhs
+                }
+            ),
+            load_cairo!(
+                fn main(lhs: felt252, rhs: felt252) -> felt252 {
+                    lhs * rhs
+                }
+            ),
+        ]
+        .map(|(_, program)| program);
+
+        let context = NativeContext::new();
+        let mut cache = JitProgramCache::new(&context).with_max_entries(2);
+
+        let executor = cache
+            .compile_and_insert(0, &programs[0], Default::default())
+            .unwrap();
+        cache
+            .compile_and_insert(1, &programs[1], Default::default())
+            .unwrap();
+
+        // The code size is only measured when requested.
+        assert!(cache.cache[&0].memory_usage.code_size.get().is_none());
+        let memory_usage = cache.memory_usage(&0).unwrap().unwrap();
+        assert!(memory_usage.code_size > 0);
+        assert!(memory_usage.module_size > 0);
+        assert_eq!(
+            memory_usage.total(),
+            memory_usage.code_size + memory_usage.module_size + memory_usage.registry_size
+        );
+        assert!(memory_usage.registry_size > 0);
+
+        // The least recently used entry is evicted.
+        cache.get(&0).unwrap();
+        cache
+            .compile_and_insert(2, &programs[2], Default::default())
+            .unwrap();
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&0).is_some());
+        assert!(cache.get(&1).is_none());
+
+        // Evicted executors are accounted for until they're no longer in use.
+        let total_memory_usage = cache.total_memory_usage().unwrap();
+        cache.remove(&0);
+        assert_eq!(cache.total_memory_usage().unwrap(), total_memory_usage);
+        drop(executor);
+        assert_eq!(
+            cache.total_memory_usage().unwrap(),
+            cache.memory_usage(&2).unwrap().unwrap().total()
+        );
+
+        // Memory budgets keep at least the last executor.
+        let mut cache = JitProgramCache::new(&context).with_max_memory(1);
+        for (i, program) in programs.iter().enumerate() {
+            cache
+                .compile_and_insert(i, program, Default::default())
+                .unwrap();
+            assert_eq!(cache.len(), 1);
+            assert!(cache.get(&i).is_some());
+        }
+    }
 }
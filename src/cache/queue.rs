@@ -0,0 +1,638 @@
+//! # Background compilation queue
+//!
+//! Compiling a program may take several seconds, which is often too long to block on. The
+//! [`CompilationQueue`] accepts programs into a prioritized pool of worker threads and returns a
+//! [`CompilationHandle`] which can be polled, waited on or awaited as a future.
+//!
+//! Compiled executors are inserted into the queue's [`ConcurrentProgramCache`], therefore they're
+//! available to anyone sharing the cache as soon as they're done.
+//!
+//! While a program is still being compiled (or if its compilation failed), invocations through
+//! the queue run on a pluggable [`FallbackExecutor`] instead. When no fallback is configured, the
+//! invocations block until the compilation finishes.
+//!
+//! ```ignore
+//! let cache = Arc::new(ConcurrentProgramCache::new(OptLevel::Default));
+//! let queue = CompilationQueue::new(cache, 4).with_fallback(SierraEmuFallback);
+//!
+//! queue.submit(class_hash, program, 10);
+//!
+//! // Runs on the emulator until the executor is ready, and natively afterwards.
+//! let result = queue.invoke_contract_dynamic(&class_hash, function_id, &calldata, gas, handler)?;
+//! ```
+
+#[cfg(feature = "with-sierra-emu")]
+pub use self::emu::SierraEmuFallback;
+
+use super::ConcurrentProgramCache;
+use crate::{
+    error::{Error, Result},
+    execution_result::ContractExecutionResult,
+    executor::AotNativeExecutor,
+    starknet::StarknetSyscallHandler,
+};
+use cairo_lang_sierra::{ids::FunctionId, program::Program};
+use starknet_types_core::felt::Felt;
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    fmt::{self, Debug},
+    future::Future,
+    hash::Hash,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex},
+    task::{Context, Poll, Waker},
+    thread::{self, JoinHandle},
+};
+
+#[cfg(feature = "with-sierra-emu")]
+mod emu;
+
+/// An executor used to run programs which haven't been compiled yet.
+pub trait FallbackExecutor: Send + Sync {
+    /// Invoke the given function of a contract program, with the same semantics as
+    /// [`AotNativeExecutor::invoke_contract_dynamic`].
+    fn invoke_contract_dynamic(
+        &self,
+        program: &Arc<Program>,
+        function_id: &FunctionId,
+        args: &[Felt],
+        gas: Option<u64>,
+        syscall_handler: impl StarknetSyscallHandler,
+    ) -> Result<ContractExecutionResult>;
+}
+
+/// The absence of a fallback executor. Invocations will wait for the compilation to finish.
+#[derive(Debug, Clone, Copy)]
+pub enum NoFallback {}
+
+impl FallbackExecutor for NoFallback {
+    fn invoke_contract_dynamic(
+        &self,
+        _program: &Arc<Program>,
+        _function_id: &FunctionId,
+        _args: &[Felt],
+        _gas: Option<u64>,
+        _syscall_handler: impl StarknetSyscallHandler,
+    ) -> Result<ContractExecutionResult> {
+        match *self {}
+    }
+}
+
+/// A pool of worker threads compiling programs in the background.
+///
+/// Please look at the [module level docs](self).
+pub struct CompilationQueue<K, F = NoFallback>
+where
+    K: Clone + PartialEq + Eq + Hash + Send + Sync + 'static,
+{
+    shared: Arc<Shared<K>>,
+    fallback: Option<F>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+struct Shared<K>
+where
+    K: PartialEq + Eq + Hash,
+{
+    cache: Arc<ConcurrentProgramCache<K>>,
+    state: Mutex<QueueState<K>>,
+    // Notified whenever a job is pushed or the queue is shutting down.
+    job_available: Condvar,
+}
+
+struct QueueState<K> {
+    jobs: BinaryHeap<Job<K>>,
+    // Programs which have been submitted but aren't in the cache yet, used by the fallback.
+    submissions: HashMap<K, Submission>,
+    next_sequence: u64,
+    shutdown: bool,
+}
+
+#[derive(Clone)]
+struct Submission {
+    program: Arc<Program>,
+    handle: CompilationHandle,
+}
+
+struct Job<K> {
+    priority: u32,
+    sequence: u64,
+    key: K,
+    program: Arc<Program>,
+    handle: CompilationHandle,
+}
+
+impl<K> PartialEq for Job<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<K> Eq for Job<K> {}
+
+impl<K> PartialOrd for Job<K> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K> Ord for Job<K> {
+    /// Higher priorities go first. Jobs with the same priority are processed in submission order.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl<K> CompilationQueue<K>
+where
+    K: Clone + PartialEq + Eq + Hash + Send + Sync + 'static,
+{
+    /// Spawn `num_workers` threads which will insert the compiled programs into `cache`.
+    pub fn new(cache: Arc<ConcurrentProgramCache<K>>, num_workers: usize) -> Self {
+        let shared = Arc::new(Shared {
+            cache,
+            state: Mutex::new(QueueState {
+                jobs: BinaryHeap::new(),
+                submissions: HashMap::new(),
+                next_sequence: 0,
+                shutdown: false,
+            }),
+            job_available: Condvar::new(),
+        });
+
+        let workers = (0..num_workers.max(1))
+            .map(|i| {
+                let shared = shared.clone();
+                thread::Builder::new()
+                    .name(format!("cairo-native-compiler-{i}"))
+                    .spawn(move || shared.run_worker())
+                    .expect("failed to spawn a compilation worker")
+            })
+            .collect();
+
+        Self {
+            shared,
+            fallback: None,
+            workers,
+        }
+    }
+}
+
+impl<K, F> CompilationQueue<K, F>
+where
+    K: Clone + PartialEq + Eq + Hash + Send + Sync + 'static,
+    F: FallbackExecutor,
+{
+    /// Run invocations of pending programs on `fallback` instead of waiting for them.
+    pub fn with_fallback<G>(mut self, fallback: G) -> CompilationQueue<K, G>
+    where
+        G: FallbackExecutor,
+    {
+        CompilationQueue {
+            shared: self.shared.clone(),
+            fallback: Some(fallback),
+            workers: std::mem::take(&mut self.workers),
+        }
+    }
+
+    /// Return the cache where compiled programs are inserted into.
+    pub fn cache(&self) -> &Arc<ConcurrentProgramCache<K>> {
+        &self.shared.cache
+    }
+
+    /// Return the number of programs waiting for a worker.
+    pub fn pending(&self) -> usize {
+        self.shared.state.lock().unwrap().jobs.len()
+    }
+
+    /// Queue a program for compilation. Programs with a higher priority are compiled first.
+    ///
+    /// Submitting a key which is already queued or compiled returns a handle to the existing
+    /// compilation. Keys whose compilation failed are queued again, subject to the cache's
+    /// [`RetryPolicy`](super::RetryPolicy).
+    pub fn submit(&self, key: K, program: Arc<Program>, priority: u32) -> CompilationHandle {
+        if let Some(executor) = self.shared.cache.get(&key) {
+            return CompilationHandle::finished(HandleResult::Compiled(executor));
+        }
+
+        let mut state = self.shared.state.lock().unwrap();
+        if let Some(submission) = state.submissions.get(&key) {
+            if !submission.handle.is_finished() {
+                return submission.handle.clone();
+            }
+        }
+
+        let handle = CompilationHandle::default();
+        state.submissions.insert(
+            key.clone(),
+            Submission {
+                program: program.clone(),
+                handle: handle.clone(),
+            },
+        );
+
+        let sequence = state.next_sequence;
+        state.next_sequence += 1;
+        state.jobs.push(Job {
+            priority,
+            sequence,
+            key,
+            program,
+            handle: handle.clone(),
+        });
+        drop(state);
+
+        self.shared.job_available.notify_one();
+        handle
+    }
+
+    /// Invoke a function of a submitted program.
+    ///
+    /// Compiled programs run natively. Otherwise, the fallback executor is used if there's one, or
+    /// the invocation blocks until the compilation finishes.
+    pub fn invoke_contract_dynamic(
+        &self,
+        key: &K,
+        function_id: &FunctionId,
+        args: &[Felt],
+        gas: Option<u64>,
+        syscall_handler: impl StarknetSyscallHandler,
+    ) -> Result<ContractExecutionResult> {
+        let submission = self
+            .shared
+            .state
+            .lock()
+            .unwrap()
+            .submissions
+            .get(key)
+            .cloned();
+
+        // The cache is checked after the submission, since the workers insert into the cache
+        // before removing the submission.
+        if let Some(executor) = self.shared.cache.get(key) {
+            return executor.invoke_contract_dynamic(function_id, args, gas, syscall_handler);
+        }
+
+        let Some(submission) = submission else {
+            return Err(Error::ProgramNotSubmitted);
+        };
+
+        match &self.fallback {
+            Some(fallback) => fallback.invoke_contract_dynamic(
+                &submission.program,
+                function_id,
+                args,
+                gas,
+                syscall_handler,
+            ),
+            None => submission.handle.wait()?.invoke_contract_dynamic(
+                function_id,
+                args,
+                gas,
+                syscall_handler,
+            ),
+        }
+    }
+}
+
+impl<K, F> Drop for CompilationQueue<K, F>
+where
+    K: Clone + PartialEq + Eq + Hash + Send + Sync + 'static,
+{
+    fn drop(&mut self) {
+        // The queue may have been moved into another one by `with_fallback`.
+        if self.workers.is_empty() {
+            return;
+        }
+
+        let mut state = self.shared.state.lock().unwrap();
+        state.shutdown = true;
+        for job in state.jobs.drain() {
+            job.handle.finish(HandleResult::Cancelled);
+        }
+        drop(state);
+        self.shared.job_available.notify_all();
+
+        // Compilations in progress are allowed to finish.
+        for worker in self.workers.drain(..) {
+            worker.join().ok();
+        }
+    }
+}
+
+impl<K, F> Debug for CompilationQueue<K, F>
+where
+    K: Clone + PartialEq + Eq + Hash + Send + Sync + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CompilationQueue")
+            .field("workers", &self.workers.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K> Shared<K>
+where
+    K: Clone + PartialEq + Eq + Hash,
+{
+    fn run_worker(&self) {
+        loop {
+            let mut state = self.state.lock().unwrap();
+            let job = loop {
+                if state.shutdown {
+                    return;
+                }
+                if let Some(job) = state.jobs.pop() {
+                    break job;
+                }
+                state = self.job_available.wait(state).unwrap();
+            };
+            drop(state);
+
+            // The cache catches panics, but they're resumed afterwards. Keep the worker alive
+            // while making sure the handle is finished regardless.
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                self.cache.get_or_compile(job.key.clone(), &job.program)
+            }));
+
+            let result = match result {
+                Ok(Ok(executor)) => {
+                    let mut state = self.state.lock().unwrap();
+                    state.submissions.remove(&job.key);
+                    HandleResult::Compiled(executor)
+                }
+                Ok(Err(e)) => HandleResult::Failed(e.to_string()),
+                Err(_) => HandleResult::Failed("compilation panicked".to_string()),
+            };
+            job.handle.finish(result);
+        }
+    }
+}
+
+/// A handle to a program queued for compilation.
+///
+/// Handles can be cloned, polled with [`CompilationHandle::try_get`], waited on with
+/// [`CompilationHandle::wait`] or awaited as futures.
+#[derive(Clone, Default)]
+pub struct CompilationHandle(Arc<HandleState>);
+
+#[derive(Default)]
+struct HandleState {
+    inner: Mutex<HandleInner>,
+    // Notified when the compilation finishes.
+    finished: Condvar,
+}
+
+#[derive(Default)]
+struct HandleInner {
+    result: Option<HandleResult>,
+    wakers: Vec<Waker>,
+}
+
+#[derive(Clone)]
+enum HandleResult {
+    Compiled(Arc<AotNativeExecutor>),
+    Failed(String),
+    Cancelled,
+}
+
+impl HandleResult {
+    fn to_result(&self) -> Result<Arc<AotNativeExecutor>> {
+        match self {
+            Self::Compiled(executor) => Ok(executor.clone()),
+            Self::Failed(message) => Err(Error::CompilationFailed(message.clone())),
+            Self::Cancelled => Err(Error::CompilationCancelled),
+        }
+    }
+}
+
+impl CompilationHandle {
+    fn finished(result: HandleResult) -> Self {
+        let handle = Self::default();
+        handle.finish(result);
+        handle
+    }
+
+    fn finish(&self, result: HandleResult) {
+        let mut inner = self.0.inner.lock().unwrap();
+        inner.result = Some(result);
+        let wakers = std::mem::take(&mut inner.wakers);
+        drop(inner);
+
+        self.0.finished.notify_all();
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    /// Return whether the compilation has finished, either successfully or not.
+    pub fn is_finished(&self) -> bool {
+        self.0.inner.lock().unwrap().result.is_some()
+    }
+
+    /// Return the compilation's result without blocking, or `None` if it's still pending.
+    pub fn try_get(&self) -> Option<Result<Arc<AotNativeExecutor>>> {
+        self.0
+            .inner
+            .lock()
+            .unwrap()
+            .result
+            .as_ref()
+            .map(HandleResult::to_result)
+    }
+
+    /// Block until the compilation finishes and return its result.
+    pub fn wait(&self) -> Result<Arc<AotNativeExecutor>> {
+        let inner = self
+            .0
+            .finished
+            .wait_while(self.0.inner.lock().unwrap(), |x| x.result.is_none())
+            .unwrap();
+
+        inner.result.as_ref().unwrap().to_result()
+    }
+}
+
+impl Future for CompilationHandle {
+    type Output = Result<Arc<AotNativeExecutor>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut inner = self.0.inner.lock().unwrap();
+        match &inner.result {
+            Some(result) => Poll::Ready(result.to_result()),
+            None => {
+                if !inner.wakers.iter().any(|x| x.will_wake(cx.waker())) {
+                    inner.wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Debug for CompilationHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CompilationHandle")
+            .field("finished", &self.is_finished())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{starknet::DummySyscallHandler, utils::test::load_starknet, OptLevel};
+    use cairo_lang_sierra::ids::GenericLibfuncId;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Returns the number of times it has been invoked.
+    #[derive(Default)]
+    struct CountingFallback(AtomicUsize);
+
+    impl FallbackExecutor for CountingFallback {
+        fn invoke_contract_dynamic(
+            &self,
+            _program: &Arc<Program>,
+            _function_id: &FunctionId,
+            _args: &[Felt],
+            _gas: Option<u64>,
+            _syscall_handler: impl StarknetSyscallHandler,
+        ) -> Result<ContractExecutionResult> {
+            let count = self.0.fetch_add(1, Ordering::Relaxed) + 1;
+            Ok(ContractExecutionResult {
+                remaining_gas: 0,
+                failure_flag: false,
+                return_values: vec![Felt::from(count)],
+                error_msg: None,
+            })
+        }
+    }
+
+    fn program() -> (Arc<Program>, FunctionId) {
+        let (_, program) = load_starknet! {
+            #[starknet::interface]
+            trait ISimpleStorage<TContractState> {
+                fn get(self: @TContractState) -> u128;
+            }
+
+            #[starknet::contract]
+            mod contract {
+                #[storage]
+                struct Storage {}
+
+                #[abi(embed_v0)]
+                impl ISimpleStorageImpl of super::ISimpleStorage<ContractState> {
+                    fn get(self: @ContractState) -> u128 {
+                        42
+                    }
+                }
+            }
+        };
+        let function_id = program
+            .funcs
+            .iter()
+            .find(|f| {
+                f.id.debug_name
+                    .as_ref()
+                    .is_some_and(|name| name.contains("__wrapper__ISimpleStorageImpl__get"))
+            })
+            .expect("should have a function")
+            .id
+            .clone();
+
+        (Arc::new(program), function_id)
+    }
+
+    #[test]
+    fn test_queue_compiles_in_background() {
+        let (program, function_id) = program();
+        let function_id = &function_id;
+
+        let cache = Arc::new(ConcurrentProgramCache::new(OptLevel::None));
+        let queue = CompilationQueue::new(cache.clone(), 2);
+
+        let handle = queue.submit(0, program.clone(), 0);
+        let executor = handle.wait().expect("should compile");
+        assert!(handle.is_finished());
+
+        // The executor has been promoted into the cache.
+        assert!(Arc::ptr_eq(&cache.get(&0).unwrap(), &executor));
+        assert!(Arc::ptr_eq(
+            &queue.submit(0, program, 0).try_get().unwrap().unwrap(),
+            &executor
+        ));
+
+        let result = queue
+            .invoke_contract_dynamic(&0, function_id, &[], Some(u64::MAX), DummySyscallHandler)
+            .expect("should run");
+        assert_eq!(result.return_values, vec![Felt::from(42)]);
+
+        assert!(matches!(
+            queue.invoke_contract_dynamic(&1, function_id, &[], None, DummySyscallHandler),
+            Err(Error::ProgramNotSubmitted)
+        ));
+    }
+
+    #[test]
+    fn test_queue_fallback() {
+        let (program, function_id) = program();
+        let function_id = &function_id;
+        let mut program = Arc::unwrap_or_clone(program);
+        program.libfunc_declarations[0].long_id.generic_id =
+            GenericLibfuncId::from_string("unknown_libfunc");
+        let program = Arc::new(program);
+
+        let cache = Arc::new(ConcurrentProgramCache::new(OptLevel::None));
+        let queue = CompilationQueue::new(cache, 1).with_fallback(CountingFallback::default());
+
+        let handle = queue.submit((), program, 0);
+        assert!(matches!(handle.wait(), Err(Error::CompilationFailed(_))));
+
+        // Programs which fail to compile keep running on the fallback.
+        for i in 1..=2 {
+            let result = queue
+                .invoke_contract_dynamic(&(), function_id, &[], None, DummySyscallHandler)
+                .expect("should run");
+            assert_eq!(result.return_values, vec![Felt::from(i)]);
+        }
+    }
+
+    #[test]
+    fn test_queue_priority() {
+        let high = Job {
+            priority: 1,
+            sequence: 1,
+            key: (),
+            program: program().0,
+            handle: CompilationHandle::default(),
+        };
+        let low = Job {
+            priority: 0,
+            sequence: 0,
+            ..high.clone_job()
+        };
+        let late = Job {
+            sequence: 2,
+            ..high.clone_job()
+        };
+
+        let mut jobs = BinaryHeap::from([low, late, high]);
+        let order = std::iter::from_fn(|| jobs.pop())
+            .map(|x| (x.priority, x.sequence))
+            .collect::<Vec<_>>();
+        assert_eq!(order, [(1, 1), (1, 2), (0, 0)]);
+    }
+
+    impl Job<()> {
+        fn clone_job(&self) -> Self {
+            Self {
+                priority: self.priority,
+                sequence: self.sequence,
+                key: (),
+                program: self.program.clone(),
+                handle: self.handle.clone(),
+            }
+        }
+    }
+}
@@ -0,0 +1,553 @@
+//! A [`FallbackExecutor`] backed by the Sierra emulator.
+
+use super::FallbackExecutor;
+use crate::{
+    error::{Error, Result},
+    execution_result::ContractExecutionResult,
+    metadata::gas::GasMetadataError,
+    starknet::{self as native, StarknetSyscallHandler},
+};
+use cairo_lang_sierra::{
+    extensions::{
+        circuit::CircuitTypeConcrete, core::CoreTypeConcrete, starknet::StarknetTypeConcrete,
+    },
+    ids::FunctionId,
+    program::Program,
+    program_registry::ProgramRegistryError,
+};
+use sierra_emu::{
+    starknet::{self as emu, SyscallResult},
+    Value, VirtualMachine,
+};
+use starknet_types_core::felt::Felt;
+use std::sync::Arc;
+
+/// Runs programs on [`sierra_emu::VirtualMachine`].
+///
+/// The emulator is much slower than native execution, but it's ready to run immediately.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SierraEmuFallback;
+
+impl FallbackExecutor for SierraEmuFallback {
+    fn invoke_contract_dynamic(
+        &self,
+        program: &Arc<Program>,
+        function_id: &FunctionId,
+        args: &[Felt],
+        gas: Option<u64>,
+        mut syscall_handler: impl StarknetSyscallHandler,
+    ) -> Result<ContractExecutionResult> {
+        let mut vm = VirtualMachine::new(program.clone());
+
+        let function = program
+            .funcs
+            .iter()
+            .find(|x| &x.id == function_id)
+            .ok_or_else(|| Box::new(ProgramRegistryError::MissingFunction(function_id.clone())))?;
+
+        // Same as the native executors.
+        let available_gas = match (gas, vm.gas.initial_required_gas(function_id)) {
+            (Some(gas), Some(required_gas)) => {
+                gas.checked_sub(required_gas)
+                    .ok_or(GasMetadataError::NotEnoughGas {
+                        gas: Box::new((required_gas, gas)),
+                    })?
+            }
+            _ => 0,
+        };
+
+        let params = function
+            .signature
+            .param_types
+            .iter()
+            .map(|type_id| {
+                Ok(match vm.registry().get_type(type_id)? {
+                    CoreTypeConcrete::GasBuiltin(_) => Value::U64(available_gas),
+                    CoreTypeConcrete::BuiltinCosts(_) => Value::BuiltinCosts(Default::default()),
+                    CoreTypeConcrete::Starknet(StarknetTypeConcrete::System(_))
+                    | CoreTypeConcrete::RangeCheck(_)
+                    | CoreTypeConcrete::RangeCheck96(_)
+                    | CoreTypeConcrete::Circuit(
+                        CircuitTypeConcrete::MulMod(_) | CircuitTypeConcrete::AddMod(_),
+                    )
+                    | CoreTypeConcrete::Pedersen(_)
+                    | CoreTypeConcrete::Poseidon(_)
+                    | CoreTypeConcrete::Bitwise(_)
+                    | CoreTypeConcrete::EcOp(_)
+                    | CoreTypeConcrete::SegmentArena(_) => Value::Unit,
+                    // The calldata: `Span<felt252>`.
+                    CoreTypeConcrete::Struct(info) => {
+                        let felt_ty = match vm.registry().get_type(&info.members[0])? {
+                            CoreTypeConcrete::Snapshot(info) => {
+                                match vm.registry().get_type(&info.ty)? {
+                                    CoreTypeConcrete::Array(info) => info.ty.clone(),
+                                    _ => return Err(Error::make_missing_parameter(type_id)),
+                                }
+                            }
+                            _ => return Err(Error::make_missing_parameter(type_id)),
+                        };
+
+                        Value::Struct(vec![Value::Array {
+                            ty: felt_ty,
+                            data: args.iter().copied().map(Value::Felt).collect(),
+                        }])
+                    }
+                    _ => return Err(Error::make_missing_parameter(type_id)),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        vm.push_frame(function_id.clone(), params);
+        let result = vm
+            .run(&mut EmuSyscallHandler(&mut syscall_handler))
+            .ok_or_else(|| Error::UnexpectedValue("a contract execution result".to_string()))?;
+
+        Ok(ContractExecutionResult {
+            remaining_gas: result.remaining_gas,
+            failure_flag: result.failure_flag,
+            return_values: result.return_values,
+            error_msg: result.error_msg,
+        })
+    }
+}
+
+/// Exposes a native syscall handler to the emulator.
+struct EmuSyscallHandler<'a, T>(&'a mut T);
+
+fn u256_to_native(value: emu::U256) -> native::U256 {
+    native::U256 {
+        lo: value.lo,
+        hi: value.hi,
+    }
+}
+
+fn u256_to_emu(value: native::U256) -> emu::U256 {
+    emu::U256 {
+        lo: value.lo,
+        hi: value.hi,
+    }
+}
+
+// The emulator represents the point at infinity as `(0, 0)`.
+fn secp256k1_to_native(p: emu::Secp256k1Point) -> native::Secp256k1Point {
+    native::Secp256k1Point {
+        x: u256_to_native(p.x),
+        y: u256_to_native(p.y),
+        is_infinity: p.x == emu::U256::default() && p.y == emu::U256::default(),
+    }
+}
+
+fn secp256k1_to_emu(p: native::Secp256k1Point) -> emu::Secp256k1Point {
+    match p.is_infinity {
+        true => emu::Secp256k1Point {
+            x: Default::default(),
+            y: Default::default(),
+        },
+        false => emu::Secp256k1Point {
+            x: u256_to_emu(p.x),
+            y: u256_to_emu(p.y),
+        },
+    }
+}
+
+fn secp256r1_to_native(p: emu::Secp256r1Point) -> native::Secp256r1Point {
+    native::Secp256r1Point {
+        x: u256_to_native(p.x),
+        y: u256_to_native(p.y),
+        is_infinity: p.x == emu::U256::default() && p.y == emu::U256::default(),
+    }
+}
+
+fn secp256r1_to_emu(p: native::Secp256r1Point) -> emu::Secp256r1Point {
+    match p.is_infinity {
+        true => emu::Secp256r1Point {
+            x: Default::default(),
+            y: Default::default(),
+        },
+        false => emu::Secp256r1Point {
+            x: u256_to_emu(p.x),
+            y: u256_to_emu(p.y),
+        },
+    }
+}
+
+fn block_info_to_emu(value: native::BlockInfo) -> emu::BlockInfo {
+    emu::BlockInfo {
+        block_number: value.block_number,
+        block_timestamp: value.block_timestamp,
+        sequencer_address: value.sequencer_address,
+    }
+}
+
+impl<T> emu::StarknetSyscallHandler for EmuSyscallHandler<'_, T>
+where
+    T: StarknetSyscallHandler,
+{
+    fn get_block_hash(
+        &mut self,
+        block_number: u64,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        self.0.get_block_hash(block_number, remaining_gas)
+    }
+
+    fn get_execution_info(&mut self, remaining_gas: &mut u64) -> SyscallResult<emu::ExecutionInfo> {
+        let info = self.0.get_execution_info(remaining_gas)?;
+        Ok(emu::ExecutionInfo {
+            block_info: block_info_to_emu(info.block_info),
+            tx_info: emu::TxInfo {
+                version: info.tx_info.version,
+                account_contract_address: info.tx_info.account_contract_address,
+                max_fee: info.tx_info.max_fee,
+                signature: info.tx_info.signature,
+                transaction_hash: info.tx_info.transaction_hash,
+                chain_id: info.tx_info.chain_id,
+                nonce: info.tx_info.nonce,
+            },
+            caller_address: info.caller_address,
+            contract_address: info.contract_address,
+            entry_point_selector: info.entry_point_selector,
+        })
+    }
+
+    fn get_execution_info_v2(
+        &mut self,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<emu::ExecutionInfoV2> {
+        let info = self.0.get_execution_info_v2(remaining_gas)?;
+        Ok(emu::ExecutionInfoV2 {
+            block_info: block_info_to_emu(info.block_info),
+            tx_info: emu::TxV2Info {
+                version: info.tx_info.version,
+                account_contract_address: info.tx_info.account_contract_address,
+                max_fee: info.tx_info.max_fee,
+                signature: info.tx_info.signature,
+                transaction_hash: info.tx_info.transaction_hash,
+                chain_id: info.tx_info.chain_id,
+                nonce: info.tx_info.nonce,
+                resource_bounds: info
+                    .tx_info
+                    .resource_bounds
+                    .into_iter()
+                    .map(|x| emu::ResourceBounds {
+                        resource: x.resource,
+                        max_amount: x.max_amount,
+                        max_price_per_unit: x.max_price_per_unit,
+                    })
+                    .collect(),
+                tip: info.tx_info.tip,
+                paymaster_data: info.tx_info.paymaster_data,
+                nonce_data_availability_mode: info.tx_info.nonce_data_availability_mode,
+                fee_data_availability_mode: info.tx_info.fee_data_availability_mode,
+                account_deployment_data: info.tx_info.account_deployment_data,
+            },
+            caller_address: info.caller_address,
+            contract_address: info.contract_address,
+            entry_point_selector: info.entry_point_selector,
+        })
+    }
+
+    fn deploy(
+        &mut self,
+        class_hash: Felt,
+        contract_address_salt: Felt,
+        calldata: Vec<Felt>,
+        deploy_from_zero: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(Felt, Vec<Felt>)> {
+        self.0.deploy(
+            class_hash,
+            contract_address_salt,
+            &calldata,
+            deploy_from_zero,
+            remaining_gas,
+        )
+    }
+
+    fn replace_class(&mut self, class_hash: Felt, remaining_gas: &mut u64) -> SyscallResult<()> {
+        self.0.replace_class(class_hash, remaining_gas)
+    }
+
+    fn library_call(
+        &mut self,
+        class_hash: Felt,
+        function_selector: Felt,
+        calldata: Vec<Felt>,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        self.0
+            .library_call(class_hash, function_selector, &calldata, remaining_gas)
+    }
+
+    fn call_contract(
+        &mut self,
+        address: Felt,
+        entry_point_selector: Felt,
+        calldata: Vec<Felt>,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        self.0
+            .call_contract(address, entry_point_selector, &calldata, remaining_gas)
+    }
+
+    fn storage_read(
+        &mut self,
+        address_domain: u32,
+        address: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        self.0.storage_read(address_domain, address, remaining_gas)
+    }
+
+    fn storage_write(
+        &mut self,
+        address_domain: u32,
+        address: Felt,
+        value: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        self.0
+            .storage_write(address_domain, address, value, remaining_gas)
+    }
+
+    fn emit_event(
+        &mut self,
+        keys: Vec<Felt>,
+        data: Vec<Felt>,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        self.0.emit_event(&keys, &data, remaining_gas)
+    }
+
+    fn send_message_to_l1(
+        &mut self,
+        to_address: Felt,
+        payload: Vec<Felt>,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        self.0
+            .send_message_to_l1(to_address, &payload, remaining_gas)
+    }
+
+    fn keccak(&mut self, input: Vec<u64>, remaining_gas: &mut u64) -> SyscallResult<emu::U256> {
+        self.0.keccak(&input, remaining_gas).map(u256_to_emu)
+    }
+
+    fn secp256k1_new(
+        &mut self,
+        x: emu::U256,
+        y: emu::U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<emu::Secp256k1Point>> {
+        self.0
+            .secp256k1_new(u256_to_native(x), u256_to_native(y), remaining_gas)
+            .map(|x| x.map(secp256k1_to_emu))
+    }
+
+    fn secp256k1_add(
+        &mut self,
+        p0: emu::Secp256k1Point,
+        p1: emu::Secp256k1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<emu::Secp256k1Point> {
+        self.0
+            .secp256k1_add(
+                secp256k1_to_native(p0),
+                secp256k1_to_native(p1),
+                remaining_gas,
+            )
+            .map(secp256k1_to_emu)
+    }
+
+    fn secp256k1_mul(
+        &mut self,
+        p: emu::Secp256k1Point,
+        m: emu::U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<emu::Secp256k1Point> {
+        self.0
+            .secp256k1_mul(secp256k1_to_native(p), u256_to_native(m), remaining_gas)
+            .map(secp256k1_to_emu)
+    }
+
+    fn secp256k1_get_point_from_x(
+        &mut self,
+        x: emu::U256,
+        y_parity: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<emu::Secp256k1Point>> {
+        self.0
+            .secp256k1_get_point_from_x(u256_to_native(x), y_parity, remaining_gas)
+            .map(|x| x.map(secp256k1_to_emu))
+    }
+
+    fn secp256k1_get_xy(
+        &mut self,
+        p: emu::Secp256k1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(emu::U256, emu::U256)> {
+        self.0
+            .secp256k1_get_xy(secp256k1_to_native(p), remaining_gas)
+            .map(|(x, y)| (u256_to_emu(x), u256_to_emu(y)))
+    }
+
+    fn secp256r1_new(
+        &mut self,
+        x: emu::U256,
+        y: emu::U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<emu::Secp256r1Point>> {
+        self.0
+            .secp256r1_new(u256_to_native(x), u256_to_native(y), remaining_gas)
+            .map(|x| x.map(secp256r1_to_emu))
+    }
+
+    fn secp256r1_add(
+        &mut self,
+        p0: emu::Secp256r1Point,
+        p1: emu::Secp256r1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<emu::Secp256r1Point> {
+        self.0
+            .secp256r1_add(
+                secp256r1_to_native(p0),
+                secp256r1_to_native(p1),
+                remaining_gas,
+            )
+            .map(secp256r1_to_emu)
+    }
+
+    fn secp256r1_mul(
+        &mut self,
+        p: emu::Secp256r1Point,
+        m: emu::U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<emu::Secp256r1Point> {
+        self.0
+            .secp256r1_mul(secp256r1_to_native(p), u256_to_native(m), remaining_gas)
+            .map(secp256r1_to_emu)
+    }
+
+    fn secp256r1_get_point_from_x(
+        &mut self,
+        x: emu::U256,
+        y_parity: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<emu::Secp256r1Point>> {
+        self.0
+            .secp256r1_get_point_from_x(u256_to_native(x), y_parity, remaining_gas)
+            .map(|x| x.map(secp256r1_to_emu))
+    }
+
+    fn secp256r1_get_xy(
+        &mut self,
+        p: emu::Secp256r1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(emu::U256, emu::U256)> {
+        self.0
+            .secp256r1_get_xy(secp256r1_to_native(p), remaining_gas)
+            .map(|(x, y)| (u256_to_emu(x), u256_to_emu(y)))
+    }
+
+    fn sha256_process_block(
+        &mut self,
+        mut prev_state: [u32; 8],
+        current_block: [u32; 16],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<[u32; 8]> {
+        self.0
+            .sha256_process_block(&mut prev_state, &current_block, remaining_gas)?;
+        Ok(prev_state)
+    }
+
+    fn meta_tx_v0(
+        &mut self,
+        address: Felt,
+        entry_point_selector: Felt,
+        calldata: Vec<Felt>,
+        signature: Vec<Felt>,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        self.0.meta_tx_v0(
+            address,
+            entry_point_selector,
+            &calldata,
+            &signature,
+            remaining_gas,
+        )
+    }
+
+    fn get_class_hash_at(
+        &mut self,
+        contract_address: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        self.0.get_class_hash_at(contract_address, remaining_gas)
+    }
+
+    #[cfg(feature = "with-cheatcode")]
+    fn cheatcode(&mut self, selector: Felt, input: Vec<Felt>) -> Vec<Felt> {
+        self.0.cheatcode(selector, &input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{starknet_stub::StubSyscallHandler, utils::test::load_starknet};
+
+    #[test]
+    fn test_emu_fallback() {
+        let (_, program) = load_starknet! {
+            #[starknet::interface]
+            trait ICounter<TContractState> {
+                fn increment(ref self: TContractState, amount: u128) -> u128;
+            }
+
+            #[starknet::contract]
+            mod contract {
+                #[storage]
+                struct Storage {
+                    value: u128,
+                }
+
+                #[abi(embed_v0)]
+                impl ICounterImpl of super::ICounter<ContractState> {
+                    fn increment(ref self: ContractState, amount: u128) -> u128 {
+                        let value = self.value.read() + amount;
+                        self.value.write(value);
+                        value
+                    }
+                }
+            }
+        };
+        let program = Arc::new(program);
+        let function_id = &program
+            .funcs
+            .iter()
+            .find(|f| {
+                f.id.debug_name
+                    .as_ref()
+                    .is_some_and(|name| name.contains("__wrapper__ICounterImpl__increment"))
+            })
+            .expect("should have a function")
+            .id;
+
+        // Storage is accessed through the native syscall handler.
+        let mut syscall_handler = StubSyscallHandler::default();
+        for expected in [5, 10] {
+            let result = SierraEmuFallback
+                .invoke_contract_dynamic(
+                    &program,
+                    function_id,
+                    &[Felt::from(5)],
+                    Some(u64::MAX),
+                    &mut syscall_handler,
+                )
+                .unwrap();
+
+            assert!(!result.failure_flag);
+            assert_eq!(result.return_values, vec![Felt::from(expected)]);
+        }
+    }
+}
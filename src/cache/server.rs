@@ -0,0 +1,548 @@
+//! # Compilation server
+//!
+//! A [`CompileServer`] owns a [`DiskProgramCache`] and compiles the programs and contract classes
+//! requested by the other processes of the same machine, which talk to it through a Unix domain
+//! socket using a [`CompileClient`]. Once an entry is ready the server replies with the path of its
+//! shared library, which the client then loads from the cache directory by itself.
+//!
+//! Requests for an entry which is already being compiled wait for that compilation instead of
+//! starting another one. Entries locked by other processes sharing the cache directory are waited
+//! for too, up to a timeout. Locks which haven't been modified for a while are considered stale,
+//! since the process which created them most likely died, and are removed.
+//!
+//! Every connection carries a sequence of requests, each of them followed by its response. Both
+//! are JSON objects written on a single line.
+
+use crate::{
+    cache::DiskProgramCache,
+    error::{Error, Result},
+    executor::{AotContractExecutor, AotNativeExecutor, SierraContractClass},
+    OptLevel,
+};
+use cairo_lang_sierra::program::Program;
+use serde::{Deserialize, Serialize};
+use starknet_types_core::felt::Felt;
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug},
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How often to check whether an entry locked by another process is ready.
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long to wait for an entry locked by another process by default.
+const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+/// How long a lock has to remain unmodified to be considered stale by default.
+const DEFAULT_STALE_LOCK_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// A request to the compilation server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    /// Compile a Sierra program, which can then be loaded using [`DiskProgramCache::get`].
+    CompileProgram { program: Program, opt_level: u8 },
+    /// Compile a contract class given its JSON representation, optionally checking its class hash.
+    CompileContract {
+        contract_class: String,
+        class_hash: Option<Felt>,
+        opt_level: u8,
+    },
+    /// Return the statistics of the server.
+    Stats,
+}
+
+/// A response of the compilation server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    /// The shared library of the requested entry is ready.
+    Compiled {
+        path: PathBuf,
+    },
+    Stats(ServerStats),
+    Error {
+        message: String,
+    },
+}
+
+/// The statistics of a compilation server.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ServerStats {
+    /// The number of compilation requests received.
+    pub requests: u64,
+    /// The number of requests whose entry was already in the cache.
+    pub cache_hits: u64,
+    /// The number of requests which waited for a compilation started by another request.
+    pub deduplicated: u64,
+    /// The number of compilations performed by the server.
+    pub compilations: u64,
+    /// The number of requests which failed.
+    pub failures: u64,
+    /// The number of entries being compiled.
+    pub in_progress: u64,
+    /// The time spent compiling, in seconds.
+    pub compile_time: f64,
+    /// The time since the server started, in seconds.
+    pub uptime: f64,
+}
+
+/// A server which compiles programs and contract classes into a shared cache.
+///
+/// Please look at the [module level docs](self).
+pub struct CompileServer {
+    cache: DiskProgramCache,
+    /// The compilations in progress by their cache key, along with their result once done.
+    in_progress: Mutex<HashMap<String, Arc<OnceLock<std::result::Result<(), String>>>>>,
+    stats: Mutex<ServerStats>,
+    started_at: Instant,
+
+    lock_timeout: Duration,
+    stale_lock_age: Duration,
+}
+
+impl CompileServer {
+    pub fn new(cache: DiskProgramCache) -> Self {
+        Self {
+            cache,
+            in_progress: Mutex::default(),
+            stats: Mutex::default(),
+            started_at: Instant::now(),
+            lock_timeout: DEFAULT_LOCK_TIMEOUT,
+            stale_lock_age: DEFAULT_STALE_LOCK_AGE,
+        }
+    }
+
+    /// Set how long to wait for an entry locked by another process before failing the request.
+    pub fn with_lock_timeout(mut self, lock_timeout: Duration) -> Self {
+        self.lock_timeout = lock_timeout;
+        self
+    }
+
+    /// Set how long a lock has to remain unmodified to be considered stale and removed.
+    pub fn with_stale_lock_age(mut self, stale_lock_age: Duration) -> Self {
+        self.stale_lock_age = stale_lock_age;
+        self
+    }
+
+    pub fn cache(&self) -> &DiskProgramCache {
+        &self.cache
+    }
+
+    pub fn stats(&self) -> ServerStats {
+        ServerStats {
+            in_progress: self.in_progress.lock().unwrap().len() as u64,
+            uptime: self.started_at.elapsed().as_secs_f64(),
+            ..self.stats.lock().unwrap().clone()
+        }
+    }
+
+    /// Accept connections until the listener fails, serving each of them in its own thread.
+    pub fn serve(self: Arc<Self>, listener: UnixListener) -> Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = self.clone();
+            thread::spawn(move || {
+                if let Err(e) = server.serve_connection(stream) {
+                    tracing::warn!("compilation server connection failed: {e}");
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    fn serve_connection(&self, stream: UnixStream) -> io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str(&line) {
+                Ok(request) => self.handle(request),
+                Err(e) => Response::Error {
+                    message: format!("invalid request: {e}"),
+                },
+            };
+
+            let mut data = serde_json::to_vec(&response)?;
+            data.push(b'\n');
+            writer.write_all(&data)?;
+        }
+
+        Ok(())
+    }
+
+    /// Handle a single request.
+    pub fn handle(&self, request: Request) -> Response {
+        if !matches!(request, Request::Stats) {
+            self.stats.lock().unwrap().requests += 1;
+        }
+
+        let result = match request {
+            Request::CompileProgram { program, opt_level } => {
+                let opt_level = OptLevel::from(opt_level);
+                let path = self.cache.program_path(&program, opt_level);
+                self.ensure_compiled(DiskProgramCache::key(&program, opt_level), &path, || {
+                    self.cache
+                        .compile_and_insert(&program, opt_level)
+                        .map(|x| x.is_some())
+                })
+                .map(|_| path)
+            }
+            Request::CompileContract {
+                contract_class,
+                class_hash,
+                opt_level,
+            } => {
+                let opt_level = OptLevel::from(opt_level);
+                SierraContractClass::from_json(&contract_class)
+                    .and_then(|contract_class| {
+                        if let Some(class_hash) = class_hash {
+                            contract_class.validate_class_hash(class_hash)?;
+                        }
+                        Ok(contract_class)
+                    })
+                    .and_then(|contract_class| {
+                        let class_hash = contract_class.class_hash;
+                        let path = self.cache.contract_path(class_hash, opt_level);
+                        self.ensure_compiled(
+                            DiskProgramCache::contract_key(class_hash, opt_level),
+                            &path,
+                            || {
+                                self.cache
+                                    .compile_contract_and_insert(&contract_class, opt_level)
+                                    .map(|x| x.is_some())
+                            },
+                        )
+                        .map(|_| path)
+                    })
+            }
+            Request::Stats => return Response::Stats(self.stats()),
+        };
+
+        match result {
+            Ok(path) => Response::Compiled { path },
+            Err(e) => {
+                self.stats.lock().unwrap().failures += 1;
+                Response::Error {
+                    message: e.to_string(),
+                }
+            }
+        }
+    }
+
+    /// Make sure that the entry with the given key is in the cache.
+    ///
+    /// The entry is compiled unless its library already exists or another request is compiling it,
+    /// in which case this function waits for it. The compilation function returns `false` if the
+    /// entry is locked by another process, which is waited for until the lock timeout.
+    fn ensure_compiled(
+        &self,
+        key: String,
+        library_path: &Path,
+        compile: impl Fn() -> Result<bool>,
+    ) -> Result<()> {
+        let compilation = {
+            let mut in_progress = self.in_progress.lock().unwrap();
+            match in_progress.get(&key) {
+                Some(compilation) => {
+                    self.stats.lock().unwrap().deduplicated += 1;
+                    compilation.clone()
+                }
+                None => {
+                    let compilation = Arc::new(OnceLock::new());
+                    in_progress.insert(key.clone(), compilation.clone());
+                    compilation
+                }
+            }
+        };
+
+        compilation
+            .get_or_init(|| {
+                let result = self.compile_entry(&key, library_path, compile);
+                self.in_progress.lock().unwrap().remove(&key);
+                result.map_err(|e| e.to_string())
+            })
+            .clone()
+            .map_err(Error::CompilationFailed)
+    }
+
+    fn compile_entry(
+        &self,
+        key: &str,
+        library_path: &Path,
+        compile: impl Fn() -> Result<bool>,
+    ) -> Result<()> {
+        let waiting_since = Instant::now();
+        loop {
+            if library_path.exists() {
+                self.stats.lock().unwrap().cache_hits += 1;
+                return Ok(());
+            }
+
+            let start = Instant::now();
+            if compile()? {
+                let mut stats = self.stats.lock().unwrap();
+                stats.compilations += 1;
+                stats.compile_time += start.elapsed().as_secs_f64();
+                return Ok(());
+            }
+
+            // Another process is compiling it, wait until it's done unless its lock is stale.
+            if self.cache.remove_stale_lock(key, self.stale_lock_age)? {
+                continue;
+            }
+            if waiting_since.elapsed() >= self.lock_timeout {
+                return Err(Error::CompileServer(format!(
+                    "timed out waiting for {} to be compiled by another process",
+                    library_path.display()
+                )));
+            }
+            thread::sleep(LOCK_POLL_INTERVAL);
+        }
+    }
+}
+
+impl Debug for CompileServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CompileServer")
+            .field("cache", &self.cache)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A client of a [`CompileServer`].
+///
+/// Please look at the [module level docs](self).
+#[derive(Debug)]
+pub struct CompileClient {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl CompileClient {
+    /// Connect to the server listening at the given socket.
+    pub fn connect(socket_path: impl AsRef<Path>) -> Result<Self> {
+        let writer = UnixStream::connect(socket_path)?;
+        Ok(Self {
+            reader: BufReader::new(writer.try_clone()?),
+            writer,
+        })
+    }
+
+    /// Send a request and wait for its response.
+    pub fn request(&mut self, request: &Request) -> Result<Response> {
+        let mut data = serde_json::to_vec(request)?;
+        data.push(b'\n');
+        self.writer.write_all(&data)?;
+
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(Error::CompileServer(
+                "the server closed the connection".to_string(),
+            ));
+        }
+
+        match serde_json::from_str(&line)? {
+            Response::Error { message } => Err(Error::CompileServer(message)),
+            response => Ok(response),
+        }
+    }
+
+    /// Compile a program, returning the path of its shared library.
+    pub fn compile_program(&mut self, program: &Program, opt_level: OptLevel) -> Result<PathBuf> {
+        self.request_compilation(&Request::CompileProgram {
+            program: program.clone(),
+            opt_level: usize::from(opt_level) as u8,
+        })
+    }
+
+    /// Compile a contract class given its JSON representation, returning the path of its shared
+    /// library.
+    pub fn compile_contract(
+        &mut self,
+        contract_class: &str,
+        class_hash: Option<Felt>,
+        opt_level: OptLevel,
+    ) -> Result<PathBuf> {
+        self.request_compilation(&Request::CompileContract {
+            contract_class: contract_class.to_string(),
+            class_hash,
+            opt_level: usize::from(opt_level) as u8,
+        })
+    }
+
+    /// Compile a program and load it from the server's cache.
+    pub fn load_program(
+        &mut self,
+        program: &Program,
+        opt_level: OptLevel,
+    ) -> Result<AotNativeExecutor> {
+        let path = self.compile_program(program, opt_level)?;
+        cache_of(&path)?
+            .get(program, opt_level)?
+            .ok_or_else(|| Error::CompileServer(format!("{} is not loadable", path.display())))
+    }
+
+    /// Compile a contract class and load it from the server's cache.
+    pub fn load_contract(
+        &mut self,
+        contract_class: &str,
+        class_hash: Option<Felt>,
+        opt_level: OptLevel,
+    ) -> Result<AotContractExecutor> {
+        let path = self.compile_contract(contract_class, class_hash, opt_level)?;
+        let key = path
+            .file_stem()
+            .and_then(|x| x.to_str())
+            .and_then(|x| x.strip_prefix("lib"))
+            .ok_or_else(|| {
+                Error::CompileServer(format!("invalid library path {}", path.display()))
+            })?;
+        cache_of(&path)?
+            .get_contract_entry(key)?
+            .ok_or_else(|| Error::CompileServer(format!("{} is not loadable", path.display())))
+    }
+
+    /// Return the statistics of the server.
+    pub fn stats(&mut self) -> Result<ServerStats> {
+        match self.request(&Request::Stats)? {
+            Response::Stats(stats) => Ok(stats),
+            response => Err(unexpected_response(response)),
+        }
+    }
+
+    fn request_compilation(&mut self, request: &Request) -> Result<PathBuf> {
+        match self.request(request)? {
+            Response::Compiled { path } => Ok(path),
+            response => Err(unexpected_response(response)),
+        }
+    }
+}
+
+/// Open the cache containing the given library.
+fn cache_of(library_path: &Path) -> Result<DiskProgramCache> {
+    let cache_path = library_path.parent().ok_or_else(|| {
+        Error::CompileServer(format!("invalid library path {}", library_path.display()))
+    })?;
+    DiskProgramCache::new(cache_path)
+}
+
+fn unexpected_response(response: Response) -> Error {
+    Error::CompileServer(format!("unexpected response: {response:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{utils::test::load_cairo, values::Value};
+
+    #[test]
+    fn test_compile_server() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("server.sock");
+
+        let server = Arc::new(CompileServer::new(
+            DiskProgramCache::new(dir.path().join("cache")).unwrap(),
+        ));
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        thread::spawn({
+            let server = server.clone();
+            move || server.serve(listener)
+        });
+
+        let (_, program) = load_cairo! {
+            fn run_test() -> felt252 {
+                42
+            }
+        };
+        let function_id = &program.funcs.first().expect("should have a function").id;
+
+        // Concurrent requests for the same program are compiled once.
+        let paths = thread::scope(|s| {
+            let handles = (0..4)
+                .map(|_| {
+                    s.spawn(|| {
+                        CompileClient::connect(&socket_path)
+                            .unwrap()
+                            .compile_program(&program, OptLevel::None)
+                            .unwrap()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|x| x.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert!(paths.iter().all(|x| x == &paths[0]));
+
+        let mut client = CompileClient::connect(&socket_path).unwrap();
+        let executor = client.load_program(&program, OptLevel::None).unwrap();
+        let res = executor
+            .invoke_dynamic(function_id, &[], Some(u64::MAX))
+            .expect("should run");
+        assert_eq!(res.return_value, Value::Felt252(Felt::from(42)));
+
+        assert!(matches!(
+            client.compile_contract("{}", None, OptLevel::None),
+            Err(Error::CompileServer(_))
+        ));
+
+        let stats = client.stats().unwrap();
+        assert_eq!(stats.requests, 6);
+        assert_eq!(stats.compilations, 1);
+        assert_eq!(stats.cache_hits + stats.deduplicated, 4);
+        assert_eq!(stats.failures, 1);
+        assert_eq!(stats.in_progress, 0);
+        assert_eq!(
+            stats,
+            ServerStats {
+                uptime: stats.uptime,
+                ..server.stats()
+            }
+        );
+    }
+
+    #[test]
+    fn test_compile_server_locks() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskProgramCache::new(dir.path()).unwrap();
+
+        let (_, program) = load_cairo! {
+            fn run_test() -> felt252 {
+                42
+            }
+        };
+        let request = Request::CompileProgram {
+            program: program.clone(),
+            opt_level: 0,
+        };
+
+        // A lock left by another process is waited for until the timeout.
+        let lock_path = cache
+            .program_path(&program, OptLevel::None)
+            .with_extension("lock");
+        std::fs::File::create(&lock_path).unwrap();
+
+        let server =
+            CompileServer::new(cache.clone()).with_lock_timeout(Duration::from_millis(200));
+        assert!(matches!(
+            server.handle(request.clone()),
+            Response::Error { message } if message.contains("timed out")
+        ));
+        assert_eq!(server.stats().failures, 1);
+
+        // Stale locks are removed.
+        let server = CompileServer::new(cache).with_stale_lock_age(Duration::ZERO);
+        assert!(matches!(server.handle(request), Response::Compiled { .. }));
+        assert!(!lock_path.exists());
+    }
+}
@@ -1,11 +1,12 @@
 //! Various error types used thorough the crate.
-use crate::metadata::gas::GasMetadataError;
+use crate::{executor::EntryPointKind, metadata::gas::GasMetadataError};
 use cairo_lang_sierra::extensions::modules::utils::Range;
 use cairo_lang_sierra::{
     edit_state::EditStateError, ids::ConcreteTypeId, program_registry::ProgramRegistryError,
 };
 use num_bigint::BigInt;
 use panic::NativeAssertError;
+use starknet_types_core::felt::Felt;
 use std::{alloc::LayoutError, num::TryFromIntError};
 use thiserror::Error;
 
@@ -19,12 +20,27 @@ pub enum Error {
     #[error(transparent)]
     MlirError(#[from] melior::Error),
 
+    #[error("type mismatch: {0}")]
+    TypeMismatch(String),
+
     #[error("missing parameter of type '{0}'")]
     MissingParameter(String),
 
+    #[error("too many arguments, expected {expected} but got {actual}")]
+    ArgumentCount { expected: usize, actual: usize },
+
+    #[error("values of type '{0}' are not supported")]
+    UnsupportedType(String),
+
     #[error("unexpected value, expected value of type '{0}'")]
     UnexpectedValue(String),
 
+    #[error("invalid value at '{path}': {reason}")]
+    InvalidValue { path: String, reason: String },
+
+    #[error("felt serialization error: {0}")]
+    FeltSerialization(String),
+
     #[error("a syscall handler was expected but was not provided")]
     MissingSyscallHandler,
 
@@ -79,6 +95,58 @@ pub enum Error {
     #[error("selector not found in the AotContractExecutor mappings")]
     SelectorNotFound,
 
+    #[error("entry point {selector:#x} is of kind {actual:?}, but {requested:?} was requested")]
+    EntryPointKindMismatch {
+        selector: Felt,
+        requested: EntryPointKind,
+        actual: EntryPointKind,
+    },
+
+    #[error("no contract is deployed at address {0:#x}")]
+    ContractNotDeployed(Felt),
+
+    #[error("a contract is already deployed at address {0:#x}")]
+    ContractAlreadyDeployed(Felt),
+
+    #[error("class hash {0:#x} has not been declared")]
+    ClassNotDeclared(Felt),
+
+    #[error(
+        "invalid nonce for account {contract_address:#x}: expected {expected:#x}, got {actual:#x}"
+    )]
+    InvalidNonce {
+        contract_address: Felt,
+        expected: Felt,
+        actual: Felt,
+    },
+
+    #[error("class hash mismatch: expected {expected:#x}, got {actual:#x}")]
+    ClassHashMismatch { expected: Felt, actual: Felt },
+
+    #[error("the program failed to compile previously: {0}")]
+    CachedCompilationFailure(String),
+
+    #[error("program compilation failed: {0}")]
+    CompilationFailed(String),
+
+    #[error("the compilation was cancelled")]
+    CompilationCancelled,
+
+    #[error("no program has been submitted with the given key")]
+    ProgramNotSubmitted,
+
+    #[error("compilation server error: {0}")]
+    CompileServer(String),
+
+    #[error("{0} expected events were not emitted")]
+    ExpectedEventsNotEmitted(usize),
+
+    #[error("syscall replay diverged: {0}")]
+    ReplayDivergence(String),
+
+    #[error("contract ABI error: {0}")]
+    Abi(String),
+
     #[error(transparent)]
     IoError(#[from] std::io::Error),
 
@@ -105,6 +173,10 @@ impl Error {
                 .unwrap_or_default(),
         )
     }
+
+    pub fn make_unsupported_type(ty: &ConcreteTypeId) -> Self {
+        Self::UnsupportedType(ty.to_string())
+    }
 }
 
 #[derive(Error, Debug)]
@@ -3,8 +3,15 @@
 //! This module provides methods to execute the programs, either via JIT or compiled ahead
 //! of time. It also provides a cache to avoid recompiling previously compiled programs.
 
-pub use self::{aot::AotNativeExecutor, contract::AotContractExecutor, jit::JitNativeExecutor};
+pub(crate) use self::contract::{LockFile, NativeContractInfo};
+pub use self::{
+    aot::AotNativeExecutor,
+    contract::{AotContractExecutor, EntryPointKind, PreparedContractCall},
+    contract_class::SierraContractClass,
+    jit::JitNativeExecutor,
+};
 use crate::{
+    allocator::native_free,
     arch::{AbiArgument, ValueWithInfoWrapper},
     error::{panic::ToNativeAssertError, Error},
     execution_result::{BuiltinStats, ExecutionResult},
@@ -12,7 +19,7 @@ use crate::{
     runtime::BUILTIN_COSTS,
     starknet::{handler::StarknetSyscallHandlerCallbacks, StarknetSyscallHandler},
     types::TypeBuilder,
-    utils::{libc_free, BuiltinCosts, RangeExt},
+    utils::{BuiltinCosts, RangeExt},
     values::Value,
 };
 use bumpalo::Bump;
@@ -34,6 +41,7 @@ use std::{alloc::Layout, arch::global_asm, ptr::NonNull};
 
 mod aot;
 mod contract;
+mod contract_class;
 mod jit;
 
 #[cfg(target_arch = "aarch64")]
@@ -75,6 +83,10 @@ fn invoke_dynamic(
     find_dict_drop_override: impl Copy + Fn(&ConcreteTypeId) -> Option<extern "C" fn(*mut c_void)>,
 ) -> Result<ExecutionResult, Error> {
     tracing::info!("Invoking function with signature: {function_signature:?}.");
+
+    // Marshalling assumes the values match their types, therefore they must be validated first.
+    validate_args(registry, function_signature, args)?;
+
     let arena = Bump::new();
     let mut invoke_data = Vec::<u8>::new();
 
@@ -374,6 +386,39 @@ impl Drop for BuiltinCostsGuard {
     }
 }
 
+/// Check that every argument matches its parameter type, and that there are no more arguments
+/// than parameters. Builtins and zero-sized parameters are skipped, since they don't consume an
+/// argument.
+fn validate_args(
+    registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    function_signature: &FunctionSignature,
+    args: &[Value],
+) -> Result<(), Error> {
+    let mut args_iter = args.iter().enumerate();
+    let mut num_params = 0;
+    for type_id in &function_signature.param_types {
+        let type_info = registry.get_type(type_id)?;
+        if type_info.is_builtin() || type_info.is_zst(registry)? {
+            continue;
+        }
+
+        let (idx, value) = args_iter
+            .next()
+            .ok_or_else(|| Error::make_missing_parameter(type_id))?;
+        value.validate_at(&mut format!("arg[{idx}]"), type_id, registry)?;
+        num_params += 1;
+    }
+
+    if args.len() > num_params {
+        return Err(Error::ArgumentCount {
+            expected: num_params,
+            actual: args.len(),
+        });
+    }
+
+    Ok(())
+}
+
 /// Parses the result by reading from the return ptr the given type.
 fn parse_result(
     type_id: &ConcreteTypeId,
@@ -410,7 +455,7 @@ fn parse_result(
             let ptr =
                 return_ptr.unwrap_or_else(|| NonNull::new_unchecked(ret_registers[0] as *mut ()));
             let value = Value::from_ptr(ptr, &info.ty, registry, true)?;
-            libc_free(ptr.cast().as_ptr());
+            native_free(ptr.cast().as_ptr());
             Ok(value)
         },
         CoreTypeConcrete::EcPoint(_) | CoreTypeConcrete::EcState(_) => Ok(Value::from_ptr(
@@ -533,7 +578,7 @@ fn parse_result(
             } else {
                 let ptr = NonNull::new_unchecked(ptr);
                 let value = Value::from_ptr(ptr, &info.ty, registry, true)?;
-                libc_free(ptr.as_ptr().cast());
+                native_free(ptr.as_ptr().cast());
                 Ok(value)
             }
         },
@@ -815,4 +860,97 @@ mod tests {
 
         assert_eq!(result.return_values, vec![Felt::from(42)]);
     }
+
+    #[test]
+    fn test_invoke_dynamic_invalid_args() {
+        let (_, program) = load_cairo! {
+            #[derive(Drop)]
+            struct Pair {
+                a: felt252,
+                b: Option<u8>,
+            }
+
+            fn run_test(x: u32, pair: Pair) -> u32 {
+                let _ = pair;
+                x
+            }
+        };
+
+        let native_context = NativeContext::new();
+        let module = native_context
+            .compile(&program, false, None)
+            .expect("failed to compile context");
+        let executor = JitNativeExecutor::from_native_module(module, OptLevel::default()).unwrap();
+        let entrypoint_function_id = &program
+            .funcs
+            .iter()
+            .find(|f| {
+                f.id.debug_name
+                    .as_deref()
+                    .is_some_and(|name| name.ends_with("::run_test"))
+            })
+            .expect("should have a function")
+            .id;
+
+        let pair = |tag, payload| Value::Struct {
+            fields: vec![
+                Value::Felt252(Felt::ONE),
+                Value::Enum {
+                    tag,
+                    value: Box::new(payload),
+                    debug_name: None,
+                },
+            ],
+            debug_name: None,
+        };
+
+        let result = executor
+            .invoke_dynamic(
+                entrypoint_function_id,
+                &[Value::Uint32(7), pair(0, Value::Uint8(1))],
+                Some(u64::MAX),
+            )
+            .unwrap();
+        assert_eq!(result.return_value, Value::Uint32(7));
+
+        let error = executor
+            .invoke_dynamic(
+                entrypoint_function_id,
+                &[Value::Uint32(7), pair(5, Value::Uint8(1))],
+                Some(u64::MAX),
+            )
+            .unwrap_err();
+        assert!(
+            matches!(error, Error::InvalidValue { path, .. } if path == "arg[1].fields[1].tag")
+        );
+
+        let error = executor
+            .invoke_dynamic(
+                entrypoint_function_id,
+                &[Value::Felt252(Felt::ONE), pair(0, Value::Uint8(1))],
+                Some(u64::MAX),
+            )
+            .unwrap_err();
+        assert!(matches!(error, Error::InvalidValue { path, .. } if path == "arg[0]"));
+
+        let error = executor
+            .invoke_dynamic(entrypoint_function_id, &[Value::Uint32(7)], Some(u64::MAX))
+            .unwrap_err();
+        assert!(matches!(error, Error::MissingParameter(_)));
+
+        let error = executor
+            .invoke_dynamic(
+                entrypoint_function_id,
+                &[Value::Uint32(7), pair(0, Value::Uint8(1)), Value::Uint32(8)],
+                Some(u64::MAX),
+            )
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::ArgumentCount {
+                expected: 2,
+                actual: 3
+            }
+        ));
+    }
 }
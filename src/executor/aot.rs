@@ -6,6 +6,7 @@ use crate::{
     },
     module::NativeModule,
     starknet::{DummySyscallHandler, StarknetSyscallHandler},
+    typed::{FromValue, IntoArgs, TypedExecutionResult, TypedFunction},
     utils::generate_function_name,
     values::Value,
     OptLevel,
@@ -87,6 +88,10 @@ impl AotNativeExecutor {
         ))
     }
 
+    pub const fn program_registry(&self) -> &ProgramRegistry<CoreType, CoreLibfunc> {
+        &self.registry
+    }
+
     pub fn invoke_dynamic(
         &self,
         function_id: &FunctionId,
@@ -132,6 +137,32 @@ impl AotNativeExecutor {
         )
     }
 
+    /// Bind a function, validating its signature against the given argument and return types.
+    pub fn bind<Args, Ret>(
+        &self,
+        function_id: &FunctionId,
+    ) -> Result<TypedFunction<'_, Self, Args, Ret>, Error>
+    where
+        Args: IntoArgs,
+        Ret: FromValue,
+    {
+        TypedFunction::new(self, function_id)
+    }
+
+    /// Execute a program with the given typed params.
+    pub fn invoke<Args, Ret>(
+        &self,
+        function_id: &FunctionId,
+        args: Args,
+        gas: Option<u64>,
+    ) -> Result<TypedExecutionResult<Ret>, Error>
+    where
+        Args: IntoArgs,
+        Ret: FromValue,
+    {
+        self.bind(function_id)?.invoke(args, gas)
+    }
+
     pub fn invoke_contract_dynamic(
         &self,
         function_id: &FunctionId,
@@ -30,20 +30,27 @@
 //!
 //! When loading, passing the "program.so" path will make it load the program and the "program.json" alongside it.
 //!
+//! ## Batch invocations:
+//!
+//! When the same entry point is called many times, [`AotContractExecutor::prepare`] resolves its
+//! selector and function pointer once and returns a [`PreparedContractCall`], which keeps the
+//! argument buffers alive between calls and can run whole batches of calldata.
+//!
 
 use crate::{
+    allocator::{native_free, native_malloc},
     arch::AbiArgument,
     context::NativeContext,
     error::{panic::ToNativeAssertError, Error, Result},
     execution_result::{BuiltinStats, ContractExecutionResult},
-    executor::{invoke_trampoline, BuiltinCostsGuard},
+    executor::{invoke_trampoline, BuiltinCostsGuard, SierraContractClass},
     metadata::runtime_bindings::setup_runtime,
     module::NativeModule,
     native_assert, native_panic,
     starknet::{handler::StarknetSyscallHandlerCallbacks, StarknetSyscallHandler},
     types::TypeBuilder,
     utils::{
-        decode_error_message, generate_function_name, get_integer_layout, libc_free, libc_malloc,
+        decode_error_message, generate_function_name, get_integer_layout, library_checksum,
         BuiltinCosts,
     },
     OptLevel,
@@ -61,7 +68,7 @@ use cairo_lang_sierra::{
     program_registry::ProgramRegistry,
 };
 use cairo_lang_sierra_to_casm::metadata::MetadataComputationConfig;
-use cairo_lang_starknet_classes::contract_class::ContractEntryPoints;
+use cairo_lang_starknet_classes::contract_class::{ContractClass, ContractEntryPoints};
 use cairo_lang_starknet_classes::{
     casm_contract_class::ENTRY_POINT_COST, compiler_version::VersionId,
 };
@@ -97,17 +104,36 @@ pub struct AotContractExecutor {
 pub struct NativeContractInfo {
     pub version: ContractInfoVersion,
     pub entry_points: BTreeMap<Felt, EntryPointInfo>,
+    /// The SHA-256 of the shared library. Missing in contract infos written by older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ContractInfoVersion {
+    /// Entry points don't record their kind nor their debug name.
     V0,
+    V1,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub struct EntryPointInfo {
     pub function_id: u64,
     pub builtins: Vec<BuiltinType>,
+    /// The kind of the entry point. Missing when loaded from a [`ContractInfoVersion::V0`] contract
+    /// info, in which case the entry point can be run as any kind.
+    #[serde(default)]
+    pub kind: Option<EntryPointKind>,
+    #[serde(default)]
+    pub debug_name: Option<String>,
+}
+
+/// The kinds of contract entry points, each of which is dispatched differently by the sequencer.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EntryPointKind {
+    Constructor,
+    External,
+    L1Handler,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
@@ -159,6 +185,49 @@ impl AotContractExecutor {
         Ok(executor)
     }
 
+    /// Compile and load a contract class using a temporary shared library.
+    pub fn from_contract_class(
+        contract_class: &SierraContractClass,
+        opt_level: OptLevel,
+    ) -> Result<Self> {
+        Self::new(
+            &contract_class.program,
+            &contract_class.entry_points,
+            contract_class.sierra_version,
+            opt_level,
+        )
+    }
+
+    /// Compile and load a contract class given its JSON representation, using a temporary shared
+    /// library. The class hash is checked if one is expected.
+    pub fn from_contract_class_json(
+        json: &str,
+        class_hash: Option<Felt>,
+        opt_level: OptLevel,
+    ) -> Result<Self> {
+        let contract_class = SierraContractClass::from_json(json)?;
+        if let Some(class_hash) = class_hash {
+            contract_class.validate_class_hash(class_hash)?;
+        }
+
+        Self::from_contract_class(&contract_class, opt_level)
+    }
+
+    /// Compile and load a [`ContractClass`] using a temporary shared library. The class hash is
+    /// checked if one is expected.
+    pub fn from_cairo_contract_class(
+        contract_class: &ContractClass,
+        class_hash: Option<Felt>,
+        opt_level: OptLevel,
+    ) -> Result<Self> {
+        let contract_class = SierraContractClass::from_contract_class(contract_class)?;
+        if let Some(class_hash) = class_hash {
+            contract_class.validate_class_hash(class_hash)?;
+        }
+
+        Self::from_contract_class(&contract_class, opt_level)
+    }
+
     /// Compile and load a program into a shared library.
     ///
     /// This function uses a lockfile to support cache sharing between multiple processes. An
@@ -180,45 +249,31 @@ impl AotContractExecutor {
 
         let context = NativeContext::new();
 
-        let no_eq_solver = match sierra_version.major.cmp(&1) {
-            Ordering::Less => false,
-            Ordering::Equal => sierra_version.minor >= 4,
-            Ordering::Greater => true,
-        };
-
         // Compile the Sierra program.
         let NativeModule {
             module, registry, ..
         } = context.compile(
             program,
             true,
-            Some(MetadataComputationConfig {
-                function_set_costs: chain!(
-                    entry_points.constructor.iter(),
-                    entry_points.external.iter(),
-                    entry_points.l1_handler.iter(),
-                )
-                .map(|x| {
-                    (
-                        FunctionId::new(x.function_idx as u64),
-                        [(CostTokenType::Const, ENTRY_POINT_COST)].into(),
-                    )
-                })
-                .collect(),
-                linear_gas_solver: no_eq_solver,
-                linear_ap_change_solver: no_eq_solver,
-                skip_non_linear_solver_comparisons: false,
-                compute_runtime_costs: false,
-            }),
+            Some(contract_metadata_config(entry_points, sierra_version)),
         )?;
 
         // Generate mappings between the entry point's selectors and their function indexes.
         let entry_point_mappings = chain!(
-            entry_points.constructor.iter(),
-            entry_points.external.iter(),
-            entry_points.l1_handler.iter(),
+            entry_points
+                .constructor
+                .iter()
+                .map(|x| (x, EntryPointKind::Constructor)),
+            entry_points
+                .external
+                .iter()
+                .map(|x| (x, EntryPointKind::External)),
+            entry_points
+                .l1_handler
+                .iter()
+                .map(|x| (x, EntryPointKind::L1Handler)),
         )
-        .map(|x| {
+        .map(|(x, kind)| {
             let function_id = x.function_idx as u64;
             let function = registry
                 .get_function(&FunctionId::new(function_id))
@@ -231,6 +286,8 @@ impl AotContractExecutor {
                 EntryPointInfo {
                     function_id: x.function_idx as u64,
                     builtins,
+                    kind: Some(kind),
+                    debug_name: function.id.debug_name.as_ref().map(|x| x.to_string()),
                 },
             ))
         })
@@ -245,8 +302,9 @@ impl AotContractExecutor {
         fs::write(
             output_path.with_extension("json"),
             serde_json::to_string(&NativeContractInfo {
-                version: ContractInfoVersion::V0,
+                version: ContractInfoVersion::V1,
                 entry_points: entry_point_mappings,
+                checksum: Some(library_checksum(&fs::read(&lock_file.0)?)),
             })?,
         )?;
 
@@ -267,12 +325,21 @@ impl AotContractExecutor {
 
         // Note: Library should load first, otherwise there could theoretically be a race condition.
         //   See the `new_into` function's code for details.
-        let library = Arc::new(unsafe { Library::new(&path)? });
+        let library = unsafe { Library::new(&path)? };
         let contract_info =
             serde_json::from_str(&fs::read_to_string(path.with_extension("json"))?)?;
 
+        Ok(Some(Self::from_library(library, path, contract_info)))
+    }
+
+    /// Build an executor from an already loaded shared library and its contract info.
+    pub(crate) fn from_library(
+        library: Library,
+        path: PathBuf,
+        contract_info: NativeContractInfo,
+    ) -> Self {
         let executor = Self {
-            library,
+            library: Arc::new(library),
             path,
             contract_info,
         };
@@ -285,12 +352,13 @@ impl AotContractExecutor {
         #[cfg(feature = "with-trace-dump")]
         crate::metadata::trace_dump::setup_runtime(|name| executor.find_symbol_ptr(name));
 
-        Ok(Some(executor))
+        executor
     }
 
     /// Runs the entry point by the given selector.
     ///
     /// - selector: The selector of the entry point to run.
+    /// - kind: The kind of the entry point, which must match the kind it was compiled as.
     /// - args: The calldata.
     /// - gas: The gas for the execution.
     /// - builtin_costs: An optional argument to customize the costs of the builtins.
@@ -300,37 +368,190 @@ impl AotContractExecutor {
     pub fn run(
         &self,
         selector: Felt,
+        kind: EntryPointKind,
         args: &[Felt],
         gas: u64,
         builtin_costs: Option<BuiltinCosts>,
-        mut syscall_handler: impl StarknetSyscallHandler,
+        syscall_handler: impl StarknetSyscallHandler,
     ) -> Result<ContractExecutionResult> {
-        let arena = Bump::new();
-        let mut invoke_data = Vec::<u8>::new();
+        self.prepare(selector, kind)?
+            .run(args, gas, builtin_costs, syscall_handler)
+    }
+
+    /// Resolve the entry point by the given selector and kind, and return a
+    /// [`PreparedContractCall`] which can be used to invoke it many times, reusing its buffers
+    /// between calls.
+    pub fn prepare(
+        &self,
+        selector: Felt,
+        kind: EntryPointKind,
+    ) -> Result<PreparedContractCall<'_>> {
+        let entry_point = self
+            .contract_info
+            .entry_points
+            .get(&selector)
+            .ok_or(Error::SelectorNotFound)?;
+
+        if let Some(actual) = entry_point.kind.filter(|x| *x != kind) {
+            return Err(Error::EntryPointKindMismatch {
+                selector,
+                requested: kind,
+                actual,
+            });
+        }
 
         let function_id = FunctionId {
-            id: self
-                .contract_info
-                .entry_points
-                .get(&selector)
-                .ok_or(Error::SelectorNotFound)?
-                .function_id,
+            id: entry_point.function_id,
             debug_name: None,
         };
         let function_ptr = self.find_function_ptr(&function_id, true)?;
 
+        Ok(PreparedContractCall {
+            entry_point,
+            function_ptr,
+            arena: Bump::new(),
+            invoke_data: Vec::new(),
+        })
+    }
+
+    pub fn find_function_ptr(
+        &self,
+        function_id: &FunctionId,
+        is_for_contract_executor: bool,
+    ) -> Result<*mut c_void> {
+        let function_name = generate_function_name(function_id, is_for_contract_executor);
+        let function_name = format!("_mlir_ciface_{function_name}");
+
+        // Arguments and return values are hardcoded since they'll be handled by the trampoline.
+        Ok(unsafe {
+            self.library
+                .get::<extern "C" fn()>(function_name.as_bytes())?
+                .into_raw()
+                .into_raw()
+        })
+    }
+
+    pub fn find_symbol_ptr(&self, name: &str) -> Option<*mut c_void> {
+        unsafe {
+            self.library
+                .get::<*mut ()>(name.as_bytes())
+                .ok()
+                .map(|x| x.into_raw().into_raw())
+        }
+    }
+}
+
+/// An entry point whose selector and function pointer have already been resolved, created using
+/// [`AotContractExecutor::prepare`].
+///
+/// The argument arena and the invoke data buffer are kept alive between calls. When running
+/// batches, the syscall handler callbacks and the builtin costs are also initialized only once.
+#[derive(Debug)]
+pub struct PreparedContractCall<'a> {
+    entry_point: &'a EntryPointInfo,
+    function_ptr: *mut c_void,
+
+    arena: Bump,
+    invoke_data: Vec<u8>,
+}
+
+impl PreparedContractCall<'_> {
+    /// Runs the prepared entry point.
+    ///
+    /// Check out [`AotContractExecutor::run`] for details on the arguments.
+    pub fn run(
+        &mut self,
+        args: &[Felt],
+        gas: u64,
+        builtin_costs: Option<BuiltinCosts>,
+        mut syscall_handler: impl StarknetSyscallHandler,
+    ) -> Result<ContractExecutionResult> {
         // Initialize syscall handler and builtin costs.
         // We may be inside a recursive contract, save the possible saved builtin costs to restore it after our call.
         let mut syscall_handler = StarknetSyscallHandlerCallbacks::new(&mut syscall_handler);
         let builtin_costs = builtin_costs.unwrap_or_default();
-        let builtin_costs_guard = BuiltinCostsGuard::install(builtin_costs);
+        let _builtin_costs_guard = BuiltinCostsGuard::install(builtin_costs);
+
+        self.invoke(args, gas, builtin_costs, &mut syscall_handler)
+    }
+
+    /// Runs the prepared entry point once for every calldata set, returning the results in the
+    /// same order.
+    ///
+    /// Every call starts with the same amount of gas and shares the syscall handler. The batch
+    /// stops at the first error.
+    pub fn run_batch<A>(
+        &mut self,
+        calldata: &[A],
+        gas: u64,
+        builtin_costs: Option<BuiltinCosts>,
+        syscall_handler: impl StarknetSyscallHandler,
+    ) -> Result<Vec<ContractExecutionResult>>
+    where
+        A: AsRef<[Felt]>,
+    {
+        let mut results = Vec::with_capacity(calldata.len());
+        self.run_batch_with(
+            calldata,
+            gas,
+            builtin_costs,
+            syscall_handler,
+            |_, result| results.push(result),
+        )?;
+
+        Ok(results)
+    }
+
+    /// Runs the prepared entry point once for every calldata set, streaming each result (along with
+    /// the index of its calldata) through the callback as soon as it's available.
+    ///
+    /// Every call starts with the same amount of gas and shares the syscall handler. The batch
+    /// stops at the first error.
+    pub fn run_batch_with<A>(
+        &mut self,
+        calldata: &[A],
+        gas: u64,
+        builtin_costs: Option<BuiltinCosts>,
+        mut syscall_handler: impl StarknetSyscallHandler,
+        mut callback: impl FnMut(usize, ContractExecutionResult),
+    ) -> Result<()>
+    where
+        A: AsRef<[Felt]>,
+    {
+        let mut syscall_handler = StarknetSyscallHandlerCallbacks::new(&mut syscall_handler);
+        let builtin_costs = builtin_costs.unwrap_or_default();
+        let _builtin_costs_guard = BuiltinCostsGuard::install(builtin_costs);
+
+        for (idx, args) in calldata.iter().enumerate() {
+            let result = self.invoke(args.as_ref(), gas, builtin_costs, &mut syscall_handler)?;
+            callback(idx, result);
+        }
+
+        Ok(())
+    }
+
+    fn invoke<T>(
+        &mut self,
+        args: &[Felt],
+        gas: u64,
+        builtin_costs: BuiltinCosts,
+        syscall_handler: &mut StarknetSyscallHandlerCallbacks<T>,
+    ) -> Result<ContractExecutionResult>
+    where
+        T: StarknetSyscallHandler,
+    {
+        let Self {
+            entry_point,
+            function_ptr,
+            arena,
+            invoke_data,
+        } = self;
+
+        arena.reset();
+        invoke_data.clear();
 
         //  it can vary from contract to contract thats why we need to store/ load it.
-        let builtins_size: usize = self.contract_info.entry_points[&selector]
-            .builtins
-            .iter()
-            .map(|x| x.size_in_bytes())
-            .sum();
+        let builtins_size: usize = entry_point.builtins.iter().map(|x| x.size_in_bytes()).sum();
 
         // There is always a return ptr because contracts always return more than 1 thing (builtin counters, syscall, enum)
         let return_ptr = arena.alloc_layout(unsafe {
@@ -340,22 +561,22 @@ impl AotContractExecutor {
 
         return_ptr
             .as_ptr()
-            .to_bytes(&mut invoke_data, |_| unreachable!())?;
+            .to_bytes(invoke_data, |_| unreachable!())?;
 
-        for b in &self.contract_info.entry_points[&selector].builtins {
+        for b in &entry_point.builtins {
             match b {
                 BuiltinType::Gas => {
-                    gas.to_bytes(&mut invoke_data, |_| unreachable!())?;
+                    gas.to_bytes(invoke_data, |_| unreachable!())?;
                 }
                 BuiltinType::BuiltinCosts => {
-                    builtin_costs.to_bytes(&mut invoke_data, |_| unreachable!())?;
+                    builtin_costs.to_bytes(invoke_data, |_| unreachable!())?;
                 }
                 BuiltinType::System => {
-                    (&mut syscall_handler as *mut StarknetSyscallHandlerCallbacks<_>)
-                        .to_bytes(&mut invoke_data, |_| unreachable!())?;
+                    (syscall_handler as *mut StarknetSyscallHandlerCallbacks<_>)
+                        .to_bytes(invoke_data, |_| unreachable!())?;
                 }
                 _ => {
-                    0u64.to_bytes(&mut invoke_data, |_| unreachable!())?;
+                    0u64.to_bytes(invoke_data, |_| unreachable!())?;
                 }
             }
         }
@@ -371,7 +592,7 @@ impl AotContractExecutor {
             0 => std::ptr::null_mut(),
             _ => unsafe {
                 let array_ptr: *mut () =
-                    libc_malloc(felt_layout.size() * args.len() + refcount_offset).cast();
+                    native_malloc(felt_layout.size() * args.len() + refcount_offset).cast();
 
                 // Write reference count.
                 array_ptr.cast::<(u32, u32)>().write((1, len_u32));
@@ -395,21 +616,21 @@ impl AotContractExecutor {
             ptr::null_mut()
         } else {
             unsafe {
-                let array_ptr_ptr = libc_malloc(size_of::<*mut ()>()).cast::<*mut ()>();
+                let array_ptr_ptr = native_malloc(size_of::<*mut ()>()).cast::<*mut ()>();
                 array_ptr_ptr.write(array_ptr);
                 array_ptr_ptr
             }
         };
 
-        array_ptr_ptr.to_bytes(&mut invoke_data, |_| unreachable!())?;
+        array_ptr_ptr.to_bytes(invoke_data, |_| unreachable!())?;
         if cfg!(target_arch = "aarch64") {
-            0u32.to_bytes(&mut invoke_data, |_| unreachable!())?; // start
-            len_u32.to_bytes(&mut invoke_data, |_| unreachable!())?; // end
-            len_u32.to_bytes(&mut invoke_data, |_| unreachable!())?; // cap
+            0u32.to_bytes(invoke_data, |_| unreachable!())?; // start
+            len_u32.to_bytes(invoke_data, |_| unreachable!())?; // end
+            len_u32.to_bytes(invoke_data, |_| unreachable!())?; // cap
         } else if cfg!(target_arch = "x86_64") {
-            (0u32 as u64).to_bytes(&mut invoke_data, |_| unreachable!())?; // start
-            (len_u32 as u64).to_bytes(&mut invoke_data, |_| unreachable!())?; // end
-            (len_u32 as u64).to_bytes(&mut invoke_data, |_| unreachable!())?; // cap
+            (0u32 as u64).to_bytes(invoke_data, |_| unreachable!())?; // start
+            (len_u32 as u64).to_bytes(invoke_data, |_| unreachable!())?; // end
+            (len_u32 as u64).to_bytes(invoke_data, |_| unreachable!())?; // cap
         } else {
             unreachable!("unsupported architecture");
         }
@@ -435,7 +656,7 @@ impl AotContractExecutor {
         #[allow(unused_mut)]
         let mut run_trampoline = || unsafe {
             invoke_trampoline(
-                function_ptr,
+                *function_ptr,
                 invoke_data.as_ptr().cast(),
                 invoke_data.len() >> 3,
                 ret_registers.as_mut_ptr(),
@@ -463,7 +684,7 @@ impl AotContractExecutor {
 
         let return_ptr = &mut return_ptr.cast();
 
-        for b in &self.contract_info.entry_points[&selector].builtins {
+        for b in &entry_point.builtins {
             match b {
                 BuiltinType::Gas => {
                     remaining_gas = unsafe { *read_value::<u64>(return_ptr) };
@@ -541,8 +762,8 @@ impl AotContractExecutor {
                     array_ptr.cast::<u32>().read() == 1,
                     "return array should have a reference count of 1"
                 );
-                libc_free(array_ptr.as_ptr().cast());
-                libc_free(array_ptr_ptr.cast());
+                native_free(array_ptr.as_ptr().cast());
+                native_free(array_ptr_ptr.cast());
             }
         }
 
@@ -560,9 +781,6 @@ impl AotContractExecutor {
             }
         };
 
-        // Restore the original builtin costs pointer.
-        drop(builtin_costs_guard);
-
         #[cfg(feature = "with-mem-tracing")]
         crate::utils::mem_tracing::report_stats();
 
@@ -573,31 +791,37 @@ impl AotContractExecutor {
             error_msg,
         })
     }
+}
 
-    pub fn find_function_ptr(
-        &self,
-        function_id: &FunctionId,
-        is_for_contract_executor: bool,
-    ) -> Result<*mut c_void> {
-        let function_name = generate_function_name(function_id, is_for_contract_executor);
-        let function_name = format!("_mlir_ciface_{function_name}");
+/// Return the gas metadata configuration for compiling a contract: entry points are charged the
+/// entry point cost, and the linear solvers are used from Sierra 1.4.0 onwards.
+pub(crate) fn contract_metadata_config(
+    entry_points: &ContractEntryPoints,
+    sierra_version: VersionId,
+) -> MetadataComputationConfig {
+    let no_eq_solver = match sierra_version.major.cmp(&1) {
+        Ordering::Less => false,
+        Ordering::Equal => sierra_version.minor >= 4,
+        Ordering::Greater => true,
+    };
 
-        // Arguments and return values are hardcoded since they'll be handled by the trampoline.
-        Ok(unsafe {
-            self.library
-                .get::<extern "C" fn()>(function_name.as_bytes())?
-                .into_raw()
-                .into_raw()
+    MetadataComputationConfig {
+        function_set_costs: chain!(
+            entry_points.constructor.iter(),
+            entry_points.external.iter(),
+            entry_points.l1_handler.iter(),
+        )
+        .map(|x| {
+            (
+                FunctionId::new(x.function_idx as u64),
+                [(CostTokenType::Const, ENTRY_POINT_COST)].into(),
+            )
         })
-    }
-
-    pub fn find_symbol_ptr(&self, name: &str) -> Option<*mut c_void> {
-        unsafe {
-            self.library
-                .get::<*mut ()>(name.as_bytes())
-                .ok()
-                .map(|x| x.into_raw().into_raw())
-        }
+        .collect(),
+        linear_gas_solver: no_eq_solver,
+        linear_ap_change_solver: no_eq_solver,
+        skip_non_linear_solver_comparisons: false,
+        compute_runtime_costs: false,
     }
 }
 
@@ -643,8 +867,11 @@ fn find_entrypoint_builtins(
         .try_collect()
 }
 
+/// A lockfile which marks an output path as being built.
+///
+/// The output is built into the lockfile itself, then atomically renamed to the output path.
 #[derive(Debug)]
-struct LockFile(PathBuf);
+pub(crate) struct LockFile(pub(crate) PathBuf);
 
 impl LockFile {
     pub fn new(path: impl Into<PathBuf>) -> io::Result<Option<Self>> {
@@ -798,6 +1025,7 @@ mod tests {
             let result = executor
                 .run(
                     Felt::from(&selector),
+                    EntryPointKind::External,
                     &[n.into()],
                     u64::MAX,
                     None,
@@ -835,6 +1063,7 @@ mod tests {
         let result = executor
             .run(
                 Felt::from(&selector),
+                EntryPointKind::External,
                 &[2.into()],
                 u64::MAX,
                 None,
@@ -843,6 +1072,128 @@ mod tests {
             .unwrap();
 
         assert_eq!(result.return_values, vec![Felt::from(2), Felt::from(4)]);
+
+        assert!(matches!(
+            executor.run(
+                Felt::from(&selector),
+                EntryPointKind::L1Handler,
+                &[2.into()],
+                u64::MAX,
+                None,
+                &mut StubSyscallHandler::default(),
+            ),
+            Err(Error::EntryPointKindMismatch {
+                requested: EntryPointKind::L1Handler,
+                actual: EntryPointKind::External,
+                ..
+            })
+        ));
+    }
+
+    #[rstest]
+    fn test_contract_executor_from_json(starknet_program_empty: ContractClass) {
+        let class_hash = SierraContractClass::from_contract_class(&starknet_program_empty)
+            .unwrap()
+            .class_hash;
+        let json = serde_json::to_string(&starknet_program_empty).unwrap();
+
+        AotContractExecutor::from_contract_class_json(&json, Some(class_hash), OptLevel::None)
+            .unwrap();
+        AotContractExecutor::from_cairo_contract_class(
+            &starknet_program_empty,
+            None,
+            OptLevel::None,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            AotContractExecutor::from_contract_class_json(&json, Some(Felt::ONE), OptLevel::None),
+            Err(Error::ClassHashMismatch { expected, actual })
+                if expected == Felt::ONE && actual == class_hash
+        ));
+        assert!(matches!(
+            AotContractExecutor::from_cairo_contract_class(
+                &starknet_program_empty,
+                Some(Felt::ONE),
+                OptLevel::None
+            ),
+            Err(Error::ClassHashMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn load_v0_contract_info() {
+        let contract_info: NativeContractInfo = serde_json::from_str(
+            r#"{"version":"V0","entry_points":{"0x1":{"function_id":3,"builtins":["Gas","System"]}}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(contract_info.version, ContractInfoVersion::V0);
+        assert_eq!(
+            contract_info.entry_points[&Felt::ONE],
+            EntryPointInfo {
+                function_id: 3,
+                builtins: vec![BuiltinType::Gas, BuiltinType::System],
+                kind: None,
+                debug_name: None,
+            }
+        );
+    }
+
+    #[rstest]
+    #[case(OptLevel::Default)]
+    fn test_contract_executor_batch(starknet_program: ContractClass, #[case] optlevel: OptLevel) {
+        let (sierra_version, _) =
+            version_id_from_serialized_sierra_program(&starknet_program.sierra_program).unwrap();
+        let executor = AotContractExecutor::new(
+            &starknet_program.extract_sierra_program().unwrap(),
+            &starknet_program.entry_points_by_type,
+            sierra_version,
+            optlevel,
+        )
+        .unwrap();
+
+        // The last function in the program is the `get` wrapper function.
+        let selector = starknet_program
+            .entry_points_by_type
+            .external
+            .last()
+            .unwrap()
+            .selector
+            .clone();
+
+        let calldata = (0..50).map(|n| vec![Felt::from(n)]).collect::<Vec<_>>();
+        let mut prepared_call = executor
+            .prepare(Felt::from(&selector), EntryPointKind::External)
+            .unwrap();
+
+        let results = prepared_call
+            .run_batch(
+                &calldata,
+                u64::MAX,
+                None,
+                &mut StubSyscallHandler::default(),
+            )
+            .unwrap();
+        assert_eq!(results.len(), calldata.len());
+        for (n, result) in results.iter().enumerate() {
+            assert_eq!(result.return_values, vec![Felt::from(n), Felt::from(n * 2)]);
+        }
+
+        let mut indices = Vec::new();
+        prepared_call
+            .run_batch_with(
+                &calldata,
+                u64::MAX,
+                None,
+                &mut StubSyscallHandler::default(),
+                |idx, result| {
+                    assert_eq!(result.return_values[0], Felt::from(idx));
+                    indices.push(idx);
+                },
+            )
+            .unwrap();
+        assert_eq!(indices, (0..calldata.len()).collect::<Vec<_>>());
     }
 
     #[rstest]
@@ -874,6 +1225,7 @@ mod tests {
         let result = executor
             .run(
                 Felt::from(&selector),
+                EntryPointKind::External,
                 &[10.into()],
                 u64::MAX,
                 None,
@@ -914,6 +1266,7 @@ mod tests {
         let result = executor
             .run(
                 Felt::from(&selector),
+                EntryPointKind::External,
                 &[],
                 u64::MAX,
                 None,
@@ -0,0 +1,322 @@
+//! # Contract classes
+//!
+//! Loading of Starknet contract classes into everything required to compile them: the decompressed
+//! Sierra program, its entry points and the Sierra version. Classes can be loaded either from a
+//! [`ContractClass`] or from their JSON representation, as produced by the compiler or as served
+//! by Starknet nodes (where the ABI is a string).
+//!
+//! The class hash is computed while loading, which allows checking that a program matches the
+//! class hash it has been declared with before compiling it. The ABI is hashed as a string: the
+//! original one when the class comes from a node, otherwise the one Starknet tooling declares
+//! compiler output with (Python's `json.dumps` style, see [`abi_to_string`]).
+
+use crate::error::{Error, Result};
+use cairo_lang_sierra::{ids::FunctionId, program::Program};
+use cairo_lang_starknet::contract::starknet_keccak;
+use cairo_lang_starknet_classes::{
+    compiler_version::VersionId,
+    contract_class::{
+        version_id_from_serialized_sierra_program, ContractClass, ContractEntryPoint,
+        ContractEntryPoints,
+    },
+};
+use itertools::chain;
+use serde::Serialize;
+use serde_json::ser::Formatter;
+use starknet_types_core::{
+    felt::Felt,
+    hash::{Poseidon, StarkHash},
+};
+use std::io;
+
+/// A contract class, ready to be compiled.
+#[derive(Debug, Clone)]
+pub struct SierraContractClass {
+    pub program: Program,
+    pub entry_points: ContractEntryPoints,
+    pub sierra_version: VersionId,
+    pub class_hash: Felt,
+}
+
+impl SierraContractClass {
+    /// Load a contract class.
+    ///
+    /// Since the original ABI string is not available, the ABI is serialized the way it is when
+    /// declaring compiler output (see [`abi_to_string`]) to compute the class hash.
+    pub fn from_contract_class(contract_class: &ContractClass) -> Result<Self> {
+        let abi = match &contract_class.abi {
+            Some(abi) => abi_to_string(abi)?,
+            None => String::new(),
+        };
+
+        Self::load(contract_class, &abi)
+    }
+
+    /// Load a contract class from its JSON representation.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let mut contract_class: serde_json::Value = serde_json::from_str(json)?;
+
+        // Starknet nodes serve the ABI as a string, which is hashed as is.
+        let abi = match contract_class.get_mut("abi") {
+            Some(serde_json::Value::String(abi)) => {
+                let abi = abi.clone();
+                contract_class["abi"] = if abi.is_empty() {
+                    serde_json::Value::Null
+                } else {
+                    serde_json::from_str(&abi)?
+                };
+                Some(abi)
+            }
+            _ => None,
+        };
+
+        let contract_class: ContractClass = serde_json::from_value(contract_class)?;
+        match abi {
+            Some(abi) => Self::load(&contract_class, &abi),
+            None => Self::from_contract_class(&contract_class),
+        }
+    }
+
+    fn load(contract_class: &ContractClass, abi: &str) -> Result<Self> {
+        let (sierra_version, _) =
+            version_id_from_serialized_sierra_program(&contract_class.sierra_program)
+                .map_err(|e| Error::ProgramParser(e.to_string()))?;
+        let program = contract_class
+            .extract_sierra_program()
+            .map_err(|e| Error::ProgramParser(e.to_string()))?;
+
+        Ok(Self {
+            program,
+            entry_points: contract_class.entry_points_by_type.clone(),
+            sierra_version,
+            class_hash: compute_class_hash(contract_class, abi),
+        })
+    }
+
+    /// Check that the class hash matches the expected one.
+    pub fn validate_class_hash(&self, class_hash: Felt) -> Result<()> {
+        if self.class_hash != class_hash {
+            return Err(Error::ClassHashMismatch {
+                expected: class_hash,
+                actual: self.class_hash,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Return the function of an entry point, of any kind, given its selector.
+    pub fn find_entry_point(&self, selector: Felt) -> Option<FunctionId> {
+        chain!(
+            self.entry_points.constructor.iter(),
+            self.entry_points.external.iter(),
+            self.entry_points.l1_handler.iter(),
+        )
+        .find(|x| Felt::from(&x.selector) == selector)
+        .map(|x| FunctionId::new(x.function_idx as u64))
+    }
+}
+
+/// Serialize an ABI the way it is declared when it comes from compiler output.
+///
+/// Both the official `cairo-lang` CLI and `starknet-rs` based tools (like `starkli`) flatten the
+/// ABI using Python's default `json.dumps` style: `", "` and `": "` as separators and non-ASCII
+/// characters escaped as `\uXXXX`. Any other formatting results in a different class hash.
+fn abi_to_string(abi: &impl Serialize) -> Result<String> {
+    let mut buffer = Vec::new();
+    abi.serialize(&mut serde_json::Serializer::with_formatter(
+        &mut buffer,
+        PythonicFormatter,
+    ))?;
+
+    // The formatter only ever writes valid UTF-8.
+    String::from_utf8(buffer).map_err(|e| Error::Abi(e.to_string()))
+}
+
+/// A JSON formatter which mimics the output of Python's `json.dumps` with its default arguments.
+struct PythonicFormatter;
+
+impl Formatter for PythonicFormatter {
+    fn begin_array_value<W>(&mut self, writer: &mut W, first: bool) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if first {
+            Ok(())
+        } else {
+            writer.write_all(b", ")
+        }
+    }
+
+    fn begin_object_key<W>(&mut self, writer: &mut W, first: bool) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if first {
+            Ok(())
+        } else {
+            writer.write_all(b", ")
+        }
+    }
+
+    fn begin_object_value<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        writer.write_all(b": ")
+    }
+
+    fn write_string_fragment<W>(&mut self, writer: &mut W, fragment: &str) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        for c in fragment.chars() {
+            if c.is_ascii() {
+                writer.write_all(&[c as u8])?;
+            } else {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    write!(writer, "\\u{unit:04x}")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Compute the hash of a Sierra contract class given the string of its ABI.
+fn compute_class_hash(contract_class: &ContractClass, abi: &str) -> Felt {
+    fn hash_entry_points(entry_points: &[ContractEntryPoint]) -> Felt {
+        Poseidon::hash_array(
+            &entry_points
+                .iter()
+                .flat_map(|x| [Felt::from(&x.selector), Felt::from(x.function_idx)])
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    let version = format!("CONTRACT_CLASS_V{}", contract_class.contract_class_version);
+    let sierra_program = contract_class
+        .sierra_program
+        .iter()
+        .map(|x| Felt::from(&x.value))
+        .collect::<Vec<_>>();
+
+    Poseidon::hash_array(&[
+        Felt::from_bytes_be_slice(version.as_bytes()),
+        hash_entry_points(&contract_class.entry_points_by_type.external),
+        hash_entry_points(&contract_class.entry_points_by_type.l1_handler),
+        hash_entry_points(&contract_class.entry_points_by_type.constructor),
+        Felt::from_bytes_be_slice(&starknet_keccak(abi.as_bytes()).to_bytes_be()),
+        Poseidon::hash_array(&sierra_program),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        context::NativeContext,
+        executor::{AotContractExecutor, EntryPointKind, JitNativeExecutor},
+        starknet::selector_from_name,
+        starknet_stub::StubSyscallHandler,
+        utils::test::load_starknet_contract,
+        OptLevel,
+    };
+
+    #[test]
+    fn load_contract_class() {
+        let (_, contract_class) = load_starknet_contract! {
+            #[starknet::interface]
+            trait IDouble<TContractState> {
+                fn double(self: @TContractState, value: felt252) -> felt252;
+            }
+
+            #[starknet::contract]
+            mod double {
+                #[storage]
+                struct Storage {}
+
+                #[abi(embed_v0)]
+                impl DoubleImpl of super::IDouble<ContractState> {
+                    fn double(self: @ContractState, value: felt252) -> felt252 {
+                        value * 2
+                    }
+                }
+            }
+        };
+
+        let class = SierraContractClass::from_contract_class(&contract_class).unwrap();
+
+        // Compiler output is hashed as it would be declared.
+        let json_class =
+            SierraContractClass::from_json(&serde_json::to_string(&contract_class).unwrap())
+                .unwrap();
+        assert_eq!(json_class.class_hash, class.class_hash);
+        assert_eq!(json_class.program, class.program);
+
+        // Classes served by nodes are hashed with their original ABI string.
+        let abi = abi_to_string(&contract_class.abi).unwrap();
+        let mut json = serde_json::to_value(&contract_class).unwrap();
+        json["abi"] = abi.into();
+        let json_class = SierraContractClass::from_json(&json.to_string()).unwrap();
+        assert_eq!(json_class.class_hash, class.class_hash);
+
+        let abi = serde_json::to_string(&contract_class.abi).unwrap();
+        let mut json = serde_json::to_value(&contract_class).unwrap();
+        json["abi"] = abi.clone().into();
+        let json_class = SierraContractClass::from_json(&json.to_string()).unwrap();
+        assert_ne!(json_class.class_hash, class.class_hash);
+        assert_eq!(
+            json_class.class_hash,
+            compute_class_hash(&contract_class, &abi)
+        );
+        assert_eq!(json_class.program, class.program);
+
+        class.validate_class_hash(class.class_hash).unwrap();
+        assert!(matches!(
+            class.validate_class_hash(Felt::ONE),
+            Err(Error::ClassHashMismatch { .. })
+        ));
+
+        let selector = selector_from_name("double");
+        let aot = AotContractExecutor::from_contract_class(&class, OptLevel::Default).unwrap();
+        let result = aot
+            .run(
+                selector,
+                EntryPointKind::External,
+                &[21.into()],
+                u64::MAX,
+                None,
+                &mut StubSyscallHandler::default(),
+            )
+            .unwrap();
+        assert_eq!(result.return_values, vec![42.into()]);
+
+        let context = NativeContext::new();
+        let jit =
+            JitNativeExecutor::from_contract_class(&context, &class, OptLevel::Default).unwrap();
+        let result = jit
+            .invoke_contract_dynamic(
+                &class.find_entry_point(selector).unwrap(),
+                &[21.into()],
+                Some(u64::MAX),
+                &mut StubSyscallHandler::default(),
+            )
+            .unwrap();
+        assert_eq!(result.return_values, vec![42.into()]);
+    }
+
+    #[test]
+    fn abi_to_string_pythonic() {
+        let abi = serde_json::json!([
+            { "type": "function", "inputs": [], "name": "caf\u{e9} \u{1f980}" },
+            { "type": "event", "kind": "struct", "members": [1, 2] },
+        ]);
+
+        assert_eq!(
+            abi_to_string(&abi).unwrap(),
+            r#"[{"inputs": [], "name": "caf\u00e9 \ud83e\udd80", "type": "function"}, {"kind": "struct", "members": [1, 2], "type": "event"}]"#
+        );
+    }
+}
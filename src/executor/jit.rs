@@ -1,4 +1,6 @@
+use super::{contract::contract_metadata_config, SierraContractClass};
 use crate::{
+    context::NativeContext,
     error::Error,
     execution_result::{ContractExecutionResult, ExecutionResult},
     metadata::{
@@ -6,6 +8,7 @@ use crate::{
     },
     module::NativeModule,
     starknet::{DummySyscallHandler, StarknetSyscallHandler},
+    typed::{FromValue, IntoArgs, TypedExecutionResult, TypedFunction},
     utils::{create_engine, generate_function_name},
     values::Value,
     OptLevel,
@@ -16,6 +19,7 @@ use cairo_lang_sierra::{
     program::FunctionSignature,
     program_registry::ProgramRegistry,
 };
+use cairo_lang_starknet_classes::contract_class::ContractClass;
 use libc::c_void;
 use melior::{ir::Module, ExecutionEngine};
 use starknet_types_core::felt::Felt;
@@ -74,6 +78,59 @@ impl<'m> JitNativeExecutor<'m> {
         Ok(executor)
     }
 
+    /// Compile a contract class. Its entry points can be found using
+    /// [`SierraContractClass::find_entry_point`], then run with
+    /// [`invoke_contract_dynamic`](Self::invoke_contract_dynamic).
+    pub fn from_contract_class(
+        context: &'m NativeContext,
+        contract_class: &SierraContractClass,
+        opt_level: OptLevel,
+    ) -> Result<Self, Error> {
+        let native_module = context.compile(
+            &contract_class.program,
+            false,
+            Some(contract_metadata_config(
+                &contract_class.entry_points,
+                contract_class.sierra_version,
+            )),
+        )?;
+
+        Self::from_native_module(native_module, opt_level)
+    }
+
+    /// Compile a contract class given its JSON representation, like
+    /// [`from_contract_class`](Self::from_contract_class). The class hash is checked if one is
+    /// expected.
+    pub fn from_contract_class_json(
+        context: &'m NativeContext,
+        json: &str,
+        class_hash: Option<Felt>,
+        opt_level: OptLevel,
+    ) -> Result<Self, Error> {
+        let contract_class = SierraContractClass::from_json(json)?;
+        if let Some(class_hash) = class_hash {
+            contract_class.validate_class_hash(class_hash)?;
+        }
+
+        Self::from_contract_class(context, &contract_class, opt_level)
+    }
+
+    /// Compile a [`ContractClass`], like [`from_contract_class`](Self::from_contract_class). The
+    /// class hash is checked if one is expected.
+    pub fn from_cairo_contract_class(
+        context: &'m NativeContext,
+        contract_class: &ContractClass,
+        class_hash: Option<Felt>,
+        opt_level: OptLevel,
+    ) -> Result<Self, Error> {
+        let contract_class = SierraContractClass::from_contract_class(contract_class)?;
+        if let Some(class_hash) = class_hash {
+            contract_class.validate_class_hash(class_hash)?;
+        }
+
+        Self::from_contract_class(context, &contract_class, opt_level)
+    }
+
     pub const fn program_registry(&self) -> &ProgramRegistry<CoreType, CoreLibfunc> {
         &self.registry
     }
@@ -129,6 +186,32 @@ impl<'m> JitNativeExecutor<'m> {
         )
     }
 
+    /// Bind a function, validating its signature against the given argument and return types.
+    pub fn bind<Args, Ret>(
+        &self,
+        function_id: &FunctionId,
+    ) -> Result<TypedFunction<'_, Self, Args, Ret>, Error>
+    where
+        Args: IntoArgs,
+        Ret: FromValue,
+    {
+        TypedFunction::new(self, function_id)
+    }
+
+    /// Execute a program with the given typed params.
+    pub fn invoke<Args, Ret>(
+        &self,
+        function_id: &FunctionId,
+        args: Args,
+        gas: Option<u64>,
+    ) -> Result<TypedExecutionResult<Ret>, Error>
+    where
+        Args: IntoArgs,
+        Ret: FromValue,
+    {
+        self.bind(function_id)?.invoke(args, gas)
+    }
+
     pub fn invoke_contract_dynamic(
         &self,
         function_id: &FunctionId,
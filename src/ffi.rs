@@ -312,6 +312,21 @@ pub fn get_target_triple() -> String {
     target_triple
 }
 
+/// Gets the name and the features of the host CPU, which the generated code targets.
+pub fn get_host_cpu() -> (String, String) {
+    unsafe {
+        let name = LLVMGetHostCPUName();
+        let features = LLVMGetHostCPUFeatures();
+        let host_cpu = (
+            CStr::from_ptr(name).to_string_lossy().into_owned(),
+            CStr::from_ptr(features).to_string_lossy().into_owned(),
+        );
+        LLVMDisposeMessage(name);
+        LLVMDisposeMessage(features);
+        host_cpu
+    }
+}
+
 /// Gets the data layout reprrsentation as a string, to be given to the MLIR module.
 /// LLVM uses this to know the proper alignments for the given sizes, etc.
 /// This function gets the data layout of the host target triple.
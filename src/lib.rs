@@ -15,6 +15,9 @@ pub use self::{
     values::Value,
 };
 
+extern crate self as cairo_native;
+
+pub mod allocator;
 mod arch;
 pub mod cache;
 mod compiler;
@@ -31,6 +34,7 @@ pub mod module;
 mod runtime;
 pub mod starknet;
 pub mod starknet_stub;
+pub mod typed;
 mod types;
 pub mod utils;
 mod values;
@@ -173,7 +173,9 @@ pub fn build_span_from_tuple<'ctx, 'this>(
     metadata: &mut MetadataStorage,
     info: &SignatureAndTypeConcreteLibfunc,
 ) -> Result<()> {
-    metadata.get_or_insert_with(|| ReallocBindingsMeta::new(context, helper));
+    if metadata.get::<ReallocBindingsMeta>().is_none() {
+        metadata.insert(ReallocBindingsMeta::new(context, helper)?);
+    }
 
     let tuple_len = {
         let CoreTypeConcrete::Struct(info) = registry.get_type(&info.ty)? else {
@@ -285,7 +287,9 @@ pub fn build_tuple_from_span<'ctx, 'this>(
     metadata: &mut MetadataStorage,
     info: &SignatureAndTypeConcreteLibfunc,
 ) -> Result<()> {
-    metadata.get_or_insert_with(|| ReallocBindingsMeta::new(context, helper));
+    if metadata.get::<ReallocBindingsMeta>().is_none() {
+        metadata.insert(ReallocBindingsMeta::new(context, helper)?);
+    }
 
     let elem_id = {
         let CoreTypeConcrete::Snapshot(info) =
@@ -489,7 +493,9 @@ pub fn build_append<'ctx, 'this>(
     metadata: &mut MetadataStorage,
     info: &SignatureAndTypeConcreteLibfunc,
 ) -> Result<()> {
-    metadata.get_or_insert_with(|| ReallocBindingsMeta::new(context, helper));
+    if metadata.get::<ReallocBindingsMeta>().is_none() {
+        metadata.insert(ReallocBindingsMeta::new(context, helper)?);
+    }
 
     let self_ty = registry.build_type(
         context,
@@ -749,7 +755,9 @@ fn build_pop<'ctx, 'this, const CONSUME: bool, const REVERSE: bool>(
     metadata: &mut MetadataStorage,
     info: PopInfo,
 ) -> Result<()> {
-    metadata.get_or_insert_with(|| ReallocBindingsMeta::new(context, helper));
+    if metadata.get::<ReallocBindingsMeta>().is_none() {
+        metadata.insert(ReallocBindingsMeta::new(context, helper)?);
+    }
 
     let ptr_ty = llvm::r#type::pointer(context, 0);
     let len_ty = IntegerType::new(context, 32).into();
@@ -66,7 +66,7 @@ pub fn build_into_box<'ctx, 'this>(
     info: &SignatureAndTypeConcreteLibfunc,
 ) -> Result<()> {
     if metadata.get::<ReallocBindingsMeta>().is_none() {
-        metadata.insert(ReallocBindingsMeta::new(context, helper));
+        metadata.insert(ReallocBindingsMeta::new(context, helper)?);
     }
 
     let inner_type = registry.get_type(&info.ty)?;
@@ -126,7 +126,7 @@ pub fn build_unbox<'ctx, 'this>(
     let inner_layout = inner_type.layout(registry)?;
 
     if metadata.get::<ReallocBindingsMeta>().is_none() {
-        metadata.insert(ReallocBindingsMeta::new(context, helper));
+        metadata.insert(ReallocBindingsMeta::new(context, helper)?);
     }
 
     // Load the boxed value from memory.
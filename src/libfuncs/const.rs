@@ -60,7 +60,7 @@ pub fn build_const_as_box<'ctx, 'this>(
     info: &ConstAsBoxConcreteLibfunc,
 ) -> Result<()> {
     if metadata.get::<ReallocBindingsMeta>().is_none() {
-        metadata.insert(ReallocBindingsMeta::new(context, helper));
+        metadata.insert(ReallocBindingsMeta::new(context, helper)?);
     }
 
     let const_type_outer = registry.get_type(&info.const_type)?;
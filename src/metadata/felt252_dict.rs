@@ -23,11 +23,26 @@ pub struct Felt252DictOverrides {
     drop_overrides: HashMap<ConcreteTypeId, String>,
 }
 
+impl FromIterator<(ConcreteTypeId, String)> for Felt252DictOverrides {
+    fn from_iter<T: IntoIterator<Item = (ConcreteTypeId, String)>>(iter: T) -> Self {
+        Self {
+            drop_overrides: iter.into_iter().collect(),
+        }
+    }
+}
+
 impl Felt252DictOverrides {
     pub fn get_drop_fn(&self, type_id: &ConcreteTypeId) -> Option<&str> {
         self.drop_overrides.get(type_id).map(String::as_str)
     }
 
+    /// Return every overridden type along with the symbol of its drop function.
+    pub fn drop_fns(&self) -> impl Iterator<Item = (&ConcreteTypeId, &str)> {
+        self.drop_overrides
+            .iter()
+            .map(|(type_id, symbol)| (type_id, symbol.as_str()))
+    }
+
     pub fn build_drop_fn<'ctx>(
         &mut self,
         context: &'ctx Context,
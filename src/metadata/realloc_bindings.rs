@@ -1,61 +1,68 @@
 //! # Memory allocation external bindings
 //!
-//! This metadata ensures that the bindings to the C function `realloc` exist in the current
+//! This metadata ensures that the bindings to the memory allocation functions exist in the current
 //! compilation context.
+//!
+//! The generated code never calls libc's `realloc` and `free` directly. Instead, two internal
+//! wrappers (`cairo_native__realloc` and `cairo_native__free`) are generated, which forward the call
+//! to the runtime function pointers stored in the `cairo_native__alloc__realloc` and
+//! `cairo_native__alloc__free` globals. Those globals are initialized by `setup_runtime` and
+//! dispatch to the allocator installed using [`AllocatorGuard`](crate::allocator::AllocatorGuard).
 
+use crate::{
+    error::{Error, Result},
+    utils::BlockExt,
+};
 use melior::{
-    dialect::llvm,
+    dialect::{llvm, ods},
     ir::{
         attribute::{FlatSymbolRefAttribute, StringAttribute, TypeAttribute},
         operation::OperationBuilder,
         r#type::IntegerType,
-        BlockLike, Identifier, Location, Module, Operation, Region, Value,
+        Attribute, Block, BlockLike, Identifier, Location, Module, Operation, Region, Type, Value,
     },
-    Context, Error,
+    Context,
 };
 
+/// Symbol of the global which holds the runtime's `realloc` function pointer.
+pub(crate) const REALLOC_GLOBAL_SYMBOL: &str = "cairo_native__alloc__realloc";
+/// Symbol of the global which holds the runtime's `free` function pointer.
+pub(crate) const FREE_GLOBAL_SYMBOL: &str = "cairo_native__alloc__free";
+
+const REALLOC_WRAPPER_SYMBOL: &str = "cairo_native__realloc";
+const FREE_WRAPPER_SYMBOL: &str = "cairo_native__free";
+
 /// Memory allocation `realloc` metadata.
 #[derive(Debug)]
 pub struct ReallocBindingsMeta;
 
 impl ReallocBindingsMeta {
-    /// Register the bindings to the `realloc` C function and return the metadata.
-    pub fn new(context: &Context, module: &Module) -> Self {
-        module.body().append_operation(llvm::func(
+    /// Register the bindings to the `realloc` and `free` runtime functions and return the
+    /// metadata.
+    pub fn new(context: &Context, module: &Module) -> Result<Self> {
+        let location = Location::unknown(context);
+        let ptr_ty = llvm::r#type::pointer(context, 0);
+
+        build_wrapper(
             context,
-            StringAttribute::new(context, "realloc"),
-            TypeAttribute::new(llvm::r#type::function(
-                llvm::r#type::pointer(context, 0),
-                &[
-                    llvm::r#type::pointer(context, 0),
-                    IntegerType::new(context, 64).into(),
-                ],
-                false,
-            )),
-            Region::new(),
-            &[(
-                Identifier::new(context, "sym_visibility"),
-                StringAttribute::new(context, "private").into(),
-            )],
-            Location::unknown(context),
-        ));
-        module.body().append_operation(llvm::func(
+            module,
+            REALLOC_WRAPPER_SYMBOL,
+            REALLOC_GLOBAL_SYMBOL,
+            &[ptr_ty, IntegerType::new(context, 64).into()],
+            Some(ptr_ty),
+            location,
+        )?;
+        build_wrapper(
             context,
-            StringAttribute::new(context, "free"),
-            TypeAttribute::new(llvm::r#type::function(
-                llvm::r#type::void(context),
-                &[llvm::r#type::pointer(context, 0)],
-                false,
-            )),
-            Region::new(),
-            &[(
-                Identifier::new(context, "sym_visibility"),
-                StringAttribute::new(context, "private").into(),
-            )],
-            Location::unknown(context),
-        ));
+            module,
+            FREE_WRAPPER_SYMBOL,
+            FREE_GLOBAL_SYMBOL,
+            &[ptr_ty],
+            None,
+            location,
+        )?;
 
-        Self
+        Ok(Self)
     }
 
     /// Calls the `realloc` function, returns a op with 1 result: an opaque pointer.
@@ -64,15 +71,15 @@ impl ReallocBindingsMeta {
         ptr: Value<'c, 'a>,
         len: Value<'c, 'a>,
         location: Location<'c>,
-    ) -> Result<Operation<'c>, Error> {
-        OperationBuilder::new("llvm.call", location)
+    ) -> Result<Operation<'c>> {
+        Ok(OperationBuilder::new("llvm.call", location)
             .add_attributes(&[(
                 Identifier::new(context, "callee"),
-                FlatSymbolRefAttribute::new(context, "realloc").into(),
+                FlatSymbolRefAttribute::new(context, REALLOC_WRAPPER_SYMBOL).into(),
             )])
             .add_operands(&[ptr, len])
             .add_results(&[llvm::r#type::pointer(context, 0)])
-            .build()
+            .build()?)
     }
 
     /// Calls the `free` function.
@@ -80,13 +87,102 @@ impl ReallocBindingsMeta {
         context: &'c Context,
         ptr: Value<'c, '_>,
         location: Location<'c>,
-    ) -> Result<Operation<'c>, Error> {
-        OperationBuilder::new("llvm.call", location)
+    ) -> Result<Operation<'c>> {
+        Ok(OperationBuilder::new("llvm.call", location)
             .add_attributes(&[(
                 Identifier::new(context, "callee"),
-                FlatSymbolRefAttribute::new(context, "free").into(),
+                FlatSymbolRefAttribute::new(context, FREE_WRAPPER_SYMBOL).into(),
             )])
             .add_operands(&[ptr])
-            .build()
+            .build()?)
     }
 }
+
+/// Declare the weak global `global_symbol`, which will hold a function pointer, and generate a
+/// private function named `wrapper_symbol` that forwards its arguments to it.
+fn build_wrapper<'c>(
+    context: &'c Context,
+    module: &Module<'c>,
+    wrapper_symbol: &str,
+    global_symbol: &str,
+    arg_types: &[Type<'c>],
+    ret_type: Option<Type<'c>>,
+    location: Location<'c>,
+) -> Result<()> {
+    let ptr_ty = llvm::r#type::pointer(context, 0);
+
+    module.body().append_operation(
+        ods::llvm::mlir_global(
+            context,
+            Region::new(),
+            TypeAttribute::new(ptr_ty),
+            StringAttribute::new(context, global_symbol),
+            Attribute::parse(context, "#llvm.linkage<weak>").ok_or(Error::ParseAttributeError)?,
+            location,
+        )
+        .into(),
+    );
+
+    let region = Region::new();
+    let entry = region.append_block(Block::new(
+        &arg_types
+            .iter()
+            .map(|ty| (*ty, location))
+            .collect::<Vec<_>>(),
+    ));
+
+    let global_address = entry.append_op_result(
+        ods::llvm::mlir_addressof(
+            context,
+            ptr_ty,
+            FlatSymbolRefAttribute::new(context, global_symbol),
+            location,
+        )
+        .into(),
+    )?;
+    let function_ptr = entry.load(context, location, global_address, ptr_ty)?;
+
+    let args = (0..arg_types.len())
+        .map(|idx| entry.arg(idx))
+        .collect::<Result<Vec<_>>>()?;
+    let call_op = entry.append_operation(
+        OperationBuilder::new("llvm.call", location)
+            .add_operands(&[function_ptr])
+            .add_operands(&args)
+            .add_results(ret_type.as_slice())
+            .build()?,
+    );
+
+    entry.append_operation(llvm::r#return(
+        match ret_type {
+            Some(_) => Some(call_op.result(0)?.into()),
+            None => None,
+        },
+        location,
+    ));
+
+    module.body().append_operation(llvm::func(
+        context,
+        StringAttribute::new(context, wrapper_symbol),
+        TypeAttribute::new(llvm::r#type::function(
+            ret_type.unwrap_or_else(|| llvm::r#type::void(context)),
+            arg_types,
+            false,
+        )),
+        region,
+        &[
+            (
+                Identifier::new(context, "sym_visibility"),
+                StringAttribute::new(context, "private").into(),
+            ),
+            (
+                Identifier::new(context, "linkage"),
+                Attribute::parse(context, "#llvm.linkage<private>")
+                    .ok_or(Error::ParseAttributeError)?,
+            ),
+        ],
+        location,
+    ));
+
+    Ok(())
+}
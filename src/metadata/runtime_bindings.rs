@@ -3,6 +3,7 @@
 //! This metadata ensures that the bindings to the runtime functions exist in the current
 //! compilation context.
 
+use super::realloc_bindings::{FREE_GLOBAL_SYMBOL, REALLOC_GLOBAL_SYMBOL};
 use crate::{
     error::{Error, Result},
     libfuncs::LibfuncHelper,
@@ -41,6 +42,8 @@ enum RuntimeBinding {
     DictDup,
     GetCostsBuiltin,
     DebugPrint,
+    AllocRealloc,
+    AllocFree,
     #[cfg(feature = "with-cheatcode")]
     VtableCheatcode,
 }
@@ -65,6 +68,8 @@ impl RuntimeBinding {
             RuntimeBinding::DictDrop => "cairo_native__dict_drop",
             RuntimeBinding::DictDup => "cairo_native__dict_dup",
             RuntimeBinding::GetCostsBuiltin => "cairo_native__get_costs_builtin",
+            RuntimeBinding::AllocRealloc => REALLOC_GLOBAL_SYMBOL,
+            RuntimeBinding::AllocFree => FREE_GLOBAL_SYMBOL,
             #[cfg(feature = "with-cheatcode")]
             RuntimeBinding::VtableCheatcode => "cairo_native__vtable_cheatcode",
         }
@@ -109,6 +114,10 @@ impl RuntimeBinding {
             RuntimeBinding::GetCostsBuiltin => {
                 crate::runtime::cairo_native__get_costs_builtin as *const ()
             }
+            RuntimeBinding::AllocRealloc => {
+                crate::runtime::cairo_native__alloc__realloc as *const ()
+            }
+            RuntimeBinding::AllocFree => crate::runtime::cairo_native__alloc__free as *const (),
             #[cfg(feature = "with-cheatcode")]
             RuntimeBinding::VtableCheatcode => {
                 crate::starknet::cairo_native__vtable_cheatcode as *const ()
@@ -682,6 +691,8 @@ pub fn setup_runtime(find_symbol_ptr: impl Fn(&str) -> Option<*mut c_void>) {
         RuntimeBinding::DictDup,
         RuntimeBinding::GetCostsBuiltin,
         RuntimeBinding::DebugPrint,
+        RuntimeBinding::AllocRealloc,
+        RuntimeBinding::AllocFree,
         #[cfg(feature = "with-cheatcode")]
         RuntimeBinding::VtableCheatcode,
     ] {
@@ -1,6 +1,9 @@
 #![allow(non_snake_case)]
 
-use crate::utils::BuiltinCosts;
+use crate::{
+    allocator::{native_free, native_realloc},
+    utils::BuiltinCosts,
+};
 use cairo_lang_sierra_gas::core_libfunc_cost::{
     DICT_SQUASH_REPEATED_ACCESS_COST, DICT_SQUASH_UNIQUE_KEY_COST,
 };
@@ -15,7 +18,7 @@ use starknet_types_core::{
     hash::StarkHash,
 };
 use std::{
-    alloc::{dealloc, realloc, Layout},
+    alloc::Layout,
     cell::Cell,
     collections::{hash_map::Entry, HashMap},
     ffi::{c_int, c_void},
@@ -171,15 +174,7 @@ impl Drop for FeltDict {
 
         // Free the value data.
         if !self.elements.is_null() {
-            unsafe {
-                dealloc(
-                    self.elements.cast(),
-                    Layout::from_size_align_unchecked(
-                        self.layout.pad_to_align().size() * self.mappings.capacity(),
-                        self.layout.align(),
-                    ),
-                )
-            };
+            unsafe { native_free(self.elements.cast()) };
         }
     }
 }
@@ -272,12 +267,8 @@ pub unsafe extern "C" fn cairo_native__dict_get(
 
     // Maybe realloc (conditions: !has_capacity && !is_present).
     if !has_capacity && !is_present {
-        dict.elements = realloc(
+        dict.elements = native_realloc(
             dict.elements.cast(),
-            Layout::from_size_align_unchecked(
-                dict.layout.pad_to_align().size() * dict.mappings.len(),
-                dict.layout.align(),
-            ),
             dict.layout.pad_to_align().size() * dict.mappings.capacity(),
         )
         .cast();
@@ -548,6 +539,26 @@ pub extern "C" fn cairo_native__get_costs_builtin() -> *const [u64; 7] {
     BUILTIN_COSTS.with(|x| x.as_ptr()) as *const [u64; 7]
 }
 
+/// Reallocate (or allocate, if null) a pointer using the currently installed allocator.
+///
+/// # Safety
+///
+/// This function is intended to be called from MLIR, deals with pointers, and is therefore
+/// definitely unsafe to use manually.
+pub unsafe extern "C" fn cairo_native__alloc__realloc(ptr: *mut c_void, len: u64) -> *mut c_void {
+    native_realloc(ptr, len as usize)
+}
+
+/// Free a pointer using the currently installed allocator.
+///
+/// # Safety
+///
+/// This function is intended to be called from MLIR, deals with pointers, and is therefore
+/// definitely unsafe to use manually.
+pub unsafe extern "C" fn cairo_native__alloc__free(ptr: *mut c_void) {
+    native_free(ptr)
+}
+
 // Utility methods for the print runtime function
 
 /// Formats the given felts as a debug string.
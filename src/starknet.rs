@@ -1,8 +1,20 @@
 //! Starknet related code for `cairo_native`
 
+pub use self::abi::{
+    byte_array_from_str, byte_array_to_string, selector_from_name, ContractAbi, DecodedEvent,
+};
+pub use self::gas_costs::{SyscallGasCost, SyscallGasCosts, SyscallGasMeter, OUT_OF_GAS_ERROR};
+pub use self::replay::{
+    RecordingSyscallHandler, ReplaySyscallHandler, SyscallRecord, SyscallRequest, SyscallResponse,
+    SyscallTranscript,
+};
 use serde::{Deserialize, Serialize};
 use starknet_types_core::felt::Felt;
 
+mod abi;
+mod gas_costs;
+mod replay;
+
 pub type SyscallResult<T> = std::result::Result<T, Vec<Felt>>;
 
 #[repr(C)]
@@ -560,7 +572,7 @@ impl StarknetSyscallHandler for DummySyscallHandler {
 // TODO: Move to the correct place or remove if unused.
 pub(crate) mod handler {
     use super::*;
-    use crate::utils::{libc_free, libc_malloc};
+    use crate::allocator::{native_free, native_malloc};
     use std::{
         alloc::Layout,
         fmt::Debug,
@@ -944,7 +956,7 @@ pub(crate) mod handler {
                 _ => {
                     let refcount_offset =
                         crate::types::array::calc_data_prefix_offset(Layout::new::<E>());
-                    let ptr = libc_malloc(
+                    let ptr = native_malloc(
                         Layout::array::<E>(data.len()).unwrap().size() + refcount_offset,
                     ) as *mut E;
 
@@ -957,7 +969,7 @@ pub(crate) mod handler {
                         ptr.add(i).write(val.clone());
                     }
 
-                    let ptr_ptr = libc_malloc(size_of::<*mut ()>()).cast::<*mut E>();
+                    let ptr_ptr = native_malloc(size_of::<*mut ()>()).cast::<*mut E>();
                     ptr_ptr.write(ptr);
 
                     ArrayAbi {
@@ -980,8 +992,8 @@ pub(crate) mod handler {
             let ptr = data.ptr.read().byte_sub(refcount_offset);
             match ptr.cast::<u32>().read() {
                 1 => {
-                    libc_free(ptr.cast());
-                    libc_free(data.ptr.cast());
+                    native_free(ptr.cast());
+                    native_free(data.ptr.cast());
                 }
                 n => ptr.cast::<u32>().write(n - 1),
             }
@@ -1030,19 +1042,20 @@ pub(crate) mod handler {
                     ok: ManuallyDrop::new(SyscallResultAbiOk {
                         tag: 0u8,
                         payload: unsafe {
-                            let mut block_info_ptr = NonNull::new(libc_malloc(
-                                size_of::<BlockInfoAbi>(),
-                            )
-                                as *mut BlockInfoAbi)
-                            .unwrap();
+                            let mut block_info_ptr =
+                                NonNull::new(
+                                    native_malloc(size_of::<BlockInfoAbi>()) as *mut BlockInfoAbi
+                                )
+                                .unwrap();
                             block_info_ptr.as_mut().block_number = x.block_info.block_number;
                             block_info_ptr.as_mut().block_timestamp = x.block_info.block_timestamp;
                             block_info_ptr.as_mut().sequencer_address =
                                 Felt252Abi(x.block_info.sequencer_address.to_bytes_le());
 
-                            let mut tx_info_ptr =
-                                NonNull::new(libc_malloc(size_of::<TxInfoAbi>()) as *mut TxInfoAbi)
-                                    .unwrap();
+                            let mut tx_info_ptr = NonNull::new(
+                                native_malloc(size_of::<TxInfoAbi>()) as *mut TxInfoAbi,
+                            )
+                            .unwrap();
                             tx_info_ptr.as_mut().version =
                                 Felt252Abi(x.tx_info.version.to_bytes_le());
                             tx_info_ptr.as_mut().account_contract_address =
@@ -1062,7 +1075,7 @@ pub(crate) mod handler {
                             tx_info_ptr.as_mut().nonce = Felt252Abi(x.tx_info.nonce.to_bytes_le());
 
                             let mut execution_info_ptr =
-                                NonNull::new(libc_malloc(size_of::<ExecutionInfoAbi>())
+                                NonNull::new(native_malloc(size_of::<ExecutionInfoAbi>())
                                     as *mut ExecutionInfoAbi)
                                 .unwrap();
                             execution_info_ptr.as_mut().block_info = block_info_ptr;
@@ -1095,23 +1108,24 @@ pub(crate) mod handler {
                         tag: 0u8,
                         payload: unsafe {
                             let mut execution_info_ptr =
-                                NonNull::new(libc_malloc(size_of::<ExecutionInfoV2Abi>())
+                                NonNull::new(native_malloc(size_of::<ExecutionInfoV2Abi>())
                                     as *mut ExecutionInfoV2Abi)
                                 .unwrap();
 
-                            let mut block_info_ptr = NonNull::new(libc_malloc(
-                                size_of::<BlockInfoAbi>(),
-                            )
-                                as *mut BlockInfoAbi)
-                            .unwrap();
+                            let mut block_info_ptr =
+                                NonNull::new(
+                                    native_malloc(size_of::<BlockInfoAbi>()) as *mut BlockInfoAbi
+                                )
+                                .unwrap();
                             block_info_ptr.as_mut().block_number = x.block_info.block_number;
                             block_info_ptr.as_mut().block_timestamp = x.block_info.block_timestamp;
                             block_info_ptr.as_mut().sequencer_address =
                                 Felt252Abi(x.block_info.sequencer_address.to_bytes_le());
 
-                            let mut tx_info_ptr = NonNull::new(
-                                libc_malloc(size_of::<TxInfoV2Abi>()) as *mut TxInfoV2Abi,
+                            let mut tx_info_ptr = NonNull::new(native_malloc(
+                                size_of::<TxInfoV2Abi>(),
                             )
+                                as *mut TxInfoV2Abi)
                             .unwrap();
                             tx_info_ptr.as_mut().version =
                                 Felt252Abi(x.tx_info.version.to_bytes_le());
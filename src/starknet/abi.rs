@@ -0,0 +1,870 @@
+//! # Contract ABI
+//!
+//! Encoding of calldata and decoding of return data and events driven by the ABI of a contract
+//! class. Values are checked against their ABI types while encoding, and map to them as follows:
+//!   - Felts, contract addresses and class hashes map to [`Value::Felt252`], integers to their
+//!     matching [`Value`] variant and `bytes31` to [`Value::Bytes31`].
+//!   - Structs (including `u256` and `ByteArray`) and tuples map to [`Value::Struct`]. Decoded
+//!     structs are named after their ABI type.
+//!   - Enums (including `bool` and `Option`) map to [`Value::Enum`].
+//!   - Arrays and spans map to [`Value::Array`].
+
+use crate::{
+    error::{Error, Result},
+    values::Value,
+};
+use cairo_lang_starknet::contract::starknet_keccak;
+use cairo_lang_starknet_classes::contract_class::ContractClass;
+use serde::Deserialize;
+use starknet_types_core::felt::Felt;
+use std::{
+    collections::{HashMap, HashSet},
+    slice,
+};
+
+const BYTE_ARRAY: &str = "core::byte_array::ByteArray";
+
+/// Compute the selector of an entry point or event from its name, using `sn_keccak`.
+pub fn selector_from_name(name: &str) -> Felt {
+    Felt::from_bytes_be_slice(&starknet_keccak(name.as_bytes()).to_bytes_be())
+}
+
+/// Build the value of a `ByteArray` holding the given string.
+pub fn byte_array_from_str(string: &str) -> Value {
+    let words = string.as_bytes().chunks_exact(31);
+    let pending_word = words.remainder();
+
+    Value::Struct {
+        fields: vec![
+            Value::Array(
+                words
+                    .map(|word| {
+                        // A `bytes31` value holds the little endian bytes of the word.
+                        let mut data: [u8; 31] = word.try_into().unwrap();
+                        data.reverse();
+                        Value::Bytes31(data)
+                    })
+                    .collect(),
+            ),
+            Value::Felt252(Felt::from_bytes_be_slice(pending_word)),
+            Value::Uint32(pending_word.len() as u32),
+        ],
+        debug_name: Some(BYTE_ARRAY.to_string()),
+    }
+}
+
+/// Extract the string held by the value of a `ByteArray`, if it's valid UTF-8.
+pub fn byte_array_to_string(value: &Value) -> Option<String> {
+    let Value::Struct { fields, .. } = value else {
+        return None;
+    };
+    let [Value::Array(words), Value::Felt252(pending_word), Value::Uint32(pending_word_len)] =
+        fields.as_slice()
+    else {
+        return None;
+    };
+
+    let mut bytes = Vec::new();
+    for word in words {
+        let Value::Bytes31(data) = word else {
+            return None;
+        };
+        bytes.extend(data.iter().rev());
+    }
+
+    let pending_word_len = *pending_word_len as usize;
+    if pending_word_len > 31 {
+        return None;
+    }
+    bytes.extend_from_slice(&pending_word.to_bytes_be()[32 - pending_word_len..]);
+
+    String::from_utf8(bytes).ok()
+}
+
+/// An event decoded from its keys and data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedEvent {
+    /// The ABI type of the event.
+    pub name: String,
+    /// The event members, by name.
+    pub fields: Vec<(String, Value)>,
+}
+
+/// The ABI of a contract class.
+#[derive(Debug, Clone, Default)]
+pub struct ContractAbi {
+    functions: HashMap<String, AbiFunction>,
+    structs: HashMap<String, Vec<AbiMember>>,
+    enums: HashMap<String, Vec<AbiMember>>,
+    events: HashMap<String, AbiEventKind>,
+    // Events which aren't a variant of another event.
+    root_events: Vec<String>,
+}
+
+impl ContractAbi {
+    /// Parse the ABI from its JSON representation.
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(Self::from_items(serde_json::from_str(json)?))
+    }
+
+    /// Extract the ABI of a contract class.
+    pub fn from_contract_class(contract_class: &ContractClass) -> Result<Self> {
+        let abi = contract_class
+            .abi
+            .as_ref()
+            .ok_or_else(|| Error::Abi("the contract class has no ABI".to_string()))?;
+
+        Ok(Self::from_items(serde_json::from_value(
+            serde_json::to_value(abi)?,
+        )?))
+    }
+
+    fn from_items(items: Vec<AbiItem>) -> Self {
+        let mut abi = Self::default();
+        abi.insert_items(items);
+
+        // These are usually part of the ABI, but may be missing if they're not used directly.
+        abi.structs
+            .entry("core::integer::u256".to_string())
+            .or_insert_with(|| {
+                vec![
+                    AbiMember::new("low", "core::integer::u128"),
+                    AbiMember::new("high", "core::integer::u128"),
+                ]
+            });
+        abi.structs
+            .entry(BYTE_ARRAY.to_string())
+            .or_insert_with(|| {
+                vec![
+                    AbiMember::new("data", "core::array::Array::<core::bytes_31::bytes31>"),
+                    AbiMember::new("pending_word", "core::felt252"),
+                    AbiMember::new("pending_word_len", "core::integer::u32"),
+                ]
+            });
+        abi.enums
+            .entry("core::bool".to_string())
+            .or_insert_with(|| vec![AbiMember::new("False", "()"), AbiMember::new("True", "()")]);
+
+        let nested_events = abi
+            .events
+            .values()
+            .flat_map(|kind| match kind {
+                AbiEventKind::Struct { .. } => &[][..],
+                AbiEventKind::Enum { variants } => variants.as_slice(),
+            })
+            .map(|variant| variant.ty.as_str())
+            .collect::<HashSet<_>>();
+        let mut root_events = abi
+            .events
+            .keys()
+            .filter(|name| !nested_events.contains(name.as_str()))
+            .cloned()
+            .collect::<Vec<_>>();
+        root_events.sort();
+
+        abi.root_events = root_events;
+        abi
+    }
+
+    fn insert_items(&mut self, items: Vec<AbiItem>) {
+        for item in items {
+            match item {
+                AbiItem::Function(function)
+                | AbiItem::Constructor(function)
+                | AbiItem::L1Handler(function) => {
+                    self.functions.insert(function.name.clone(), function);
+                }
+                AbiItem::Interface { items } => self.insert_items(items),
+                AbiItem::Struct { name, members } => {
+                    self.structs.insert(name, members);
+                }
+                AbiItem::Enum { name, variants } => {
+                    self.enums.insert(name, variants);
+                }
+                AbiItem::Event(event) => {
+                    self.events.insert(event.name, event.kind);
+                }
+                AbiItem::Other => {}
+            }
+        }
+    }
+
+    /// Encode the calldata of a function from its arguments, by name. Return the selector of the
+    /// function along with the calldata.
+    pub fn encode_calldata(
+        &self,
+        function: &str,
+        args: &[(&str, Value)],
+    ) -> Result<(Felt, Vec<Felt>)> {
+        let function = self.function(function)?;
+
+        if let Some((name, _)) = args
+            .iter()
+            .find(|(name, _)| !function.inputs.iter().any(|input| input.name == *name))
+        {
+            return Err(Error::Abi(format!(
+                "function '{}' has no argument '{name}'",
+                function.name
+            )));
+        }
+
+        let mut calldata = Vec::new();
+        for input in &function.inputs {
+            let (_, value) = args
+                .iter()
+                .find(|(name, _)| *name == input.name)
+                .ok_or_else(|| {
+                    Error::Abi(format!(
+                        "missing argument '{}' of function '{}'",
+                        input.name, function.name
+                    ))
+                })?;
+
+            self.encode_into(&input.ty, value, &mut calldata)?;
+        }
+
+        Ok((selector_from_name(&function.name), calldata))
+    }
+
+    /// Decode the values returned by a function.
+    pub fn decode_return_values(
+        &self,
+        function: &str,
+        return_values: &[Felt],
+    ) -> Result<Vec<Value>> {
+        let function = self.function(function)?;
+
+        let mut felts = return_values.iter();
+        let values = function
+            .outputs
+            .iter()
+            .map(|output| self.decode_from(&output.ty, &mut felts))
+            .collect::<Result<Vec<_>>>()?;
+        check_consumed(&felts, &function.name)?;
+
+        Ok(values)
+    }
+
+    /// Encode a value of the given ABI type.
+    pub fn encode_value(&self, ty: &str, value: &Value) -> Result<Vec<Felt>> {
+        let mut felts = Vec::new();
+        self.encode_into(ty, value, &mut felts)?;
+        Ok(felts)
+    }
+
+    /// Decode a value of the given ABI type.
+    pub fn decode_value(&self, ty: &str, felts: &[Felt]) -> Result<Value> {
+        let mut iter = felts.iter();
+        let value = self.decode_from(ty, &mut iter)?;
+        check_consumed(&iter, ty)?;
+
+        Ok(value)
+    }
+
+    /// Decode an event emitted by the contract from its keys and data.
+    pub fn decode_event(&self, keys: &[Felt], data: &[Felt]) -> Result<DecodedEvent> {
+        for name in &self.root_events {
+            let (mut keys, mut data) = (keys.iter(), data.iter());
+            if let Ok(event) = self.decode_event_from(name, &mut keys, &mut data) {
+                if keys.len() == 0 && data.len() == 0 {
+                    return Ok(event);
+                }
+            }
+        }
+
+        Err(Error::Abi(
+            "the event doesn't match any event of the ABI".to_string(),
+        ))
+    }
+
+    fn function(&self, name: &str) -> Result<&AbiFunction> {
+        self.functions
+            .get(name)
+            .ok_or_else(|| Error::Abi(format!("function '{name}' not found")))
+    }
+
+    fn resolve<'a>(&'a self, ty: &'a str) -> Result<AbiType<'a>> {
+        if let Some(scalar) = Scalar::from_type(ty) {
+            return Ok(AbiType::Scalar(scalar));
+        }
+        if let Some(inner) =
+            generic_arg(ty, "core::array::Array").or_else(|| generic_arg(ty, "core::array::Span"))
+        {
+            return Ok(AbiType::Array(inner));
+        }
+        if let Some(types) = ty.strip_prefix('(').and_then(|x| x.strip_suffix(')')) {
+            return Ok(AbiType::Tuple(split_types(types)));
+        }
+
+        if let Some(members) = self.structs.get(ty) {
+            Ok(AbiType::Struct(members))
+        } else if let Some(variants) = self.enums.get(ty) {
+            Ok(AbiType::Enum(variants))
+        } else {
+            Err(Error::Abi(format!("unknown type '{ty}'")))
+        }
+    }
+
+    fn encode_into(&self, ty: &str, value: &Value, felts: &mut Vec<Felt>) -> Result<()> {
+        match (self.resolve(ty)?, value) {
+            (AbiType::Scalar(scalar), _) => {
+                felts.push(scalar.encode(value).ok_or_else(|| mismatch(ty, value))?)
+            }
+            (AbiType::Array(inner), Value::Array(values)) => {
+                felts.push(values.len().into());
+                for value in values {
+                    self.encode_into(inner, value, felts)?;
+                }
+            }
+            (AbiType::Tuple(types), Value::Struct { fields, .. })
+                if types.len() == fields.len() =>
+            {
+                for (ty, value) in types.into_iter().zip(fields) {
+                    self.encode_into(ty, value, felts)?;
+                }
+            }
+            (AbiType::Struct(members), Value::Struct { fields, .. })
+                if members.len() == fields.len() =>
+            {
+                for (member, value) in members.iter().zip(fields) {
+                    self.encode_into(&member.ty, value, felts)?;
+                }
+            }
+            (AbiType::Enum(variants), Value::Enum { tag, value, .. }) if *tag < variants.len() => {
+                felts.push((*tag).into());
+                self.encode_into(&variants[*tag].ty, value, felts)?;
+            }
+            _ => return Err(mismatch(ty, value)),
+        }
+
+        Ok(())
+    }
+
+    fn decode_from(&self, ty: &str, felts: &mut slice::Iter<Felt>) -> Result<Value> {
+        Ok(match self.resolve(ty)? {
+            AbiType::Scalar(scalar) => scalar
+                .decode(next_felt(felts, ty)?)
+                .ok_or_else(|| out_of_range(ty))?,
+            AbiType::Array(inner) => {
+                let len: usize = next_felt(felts, ty)?
+                    .to_biguint()
+                    .try_into()
+                    .map_err(|_| out_of_range(ty))?;
+
+                // Avoid trusting the length prefix when reserving memory.
+                let mut values = Vec::with_capacity(len.min(felts.len()));
+                for _ in 0..len {
+                    values.push(self.decode_from(inner, felts)?);
+                }
+
+                Value::Array(values)
+            }
+            AbiType::Tuple(types) => Value::Struct {
+                fields: types
+                    .into_iter()
+                    .map(|ty| self.decode_from(ty, felts))
+                    .collect::<Result<_>>()?,
+                debug_name: None,
+            },
+            AbiType::Struct(members) => Value::Struct {
+                fields: members
+                    .iter()
+                    .map(|member| self.decode_from(&member.ty, felts))
+                    .collect::<Result<_>>()?,
+                debug_name: Some(ty.to_string()),
+            },
+            AbiType::Enum(variants) => {
+                let tag: usize = next_felt(felts, ty)?
+                    .to_biguint()
+                    .try_into()
+                    .map_err(|_| out_of_range(ty))?;
+                let variant = variants.get(tag).ok_or_else(|| {
+                    Error::FeltSerialization(format!("variant index {tag} out of range for '{ty}'"))
+                })?;
+
+                Value::Enum {
+                    tag,
+                    value: Box::new(self.decode_from(&variant.ty, felts)?),
+                    debug_name: Some(ty.to_string()),
+                }
+            }
+        })
+    }
+
+    fn decode_event_from(
+        &self,
+        name: &str,
+        keys: &mut slice::Iter<Felt>,
+        data: &mut slice::Iter<Felt>,
+    ) -> Result<DecodedEvent> {
+        let kind = self
+            .events
+            .get(name)
+            .ok_or_else(|| Error::Abi(format!("event '{name}' not found")))?;
+
+        match kind {
+            AbiEventKind::Struct { members } => Ok(DecodedEvent {
+                name: name.to_string(),
+                fields: members
+                    .iter()
+                    .map(|member| {
+                        let felts = match member.kind {
+                            AbiEventMemberKind::Key => &mut *keys,
+                            _ => &mut *data,
+                        };
+                        Ok((member.name.clone(), self.decode_from(&member.ty, felts)?))
+                    })
+                    .collect::<Result<_>>()?,
+            }),
+            AbiEventKind::Enum { variants } => {
+                for variant in variants {
+                    let (mut variant_keys, mut variant_data) = (keys.clone(), data.clone());
+
+                    // Nested variants are prefixed by their selector, while flat ones are not.
+                    match variant.kind {
+                        AbiEventMemberKind::Nested => {
+                            if variant_keys.next() != Some(&selector_from_name(&variant.name)) {
+                                continue;
+                            }
+                        }
+                        AbiEventMemberKind::Flat => {}
+                        AbiEventMemberKind::Key | AbiEventMemberKind::Data => continue,
+                    }
+
+                    if let Ok(event) =
+                        self.decode_event_from(&variant.ty, &mut variant_keys, &mut variant_data)
+                    {
+                        *keys = variant_keys;
+                        *data = variant_data;
+                        return Ok(event);
+                    }
+                }
+
+                Err(Error::Abi(format!(
+                    "the event doesn't match any variant of '{name}'"
+                )))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AbiItem {
+    Function(AbiFunction),
+    Constructor(AbiFunction),
+    L1Handler(AbiFunction),
+    Interface {
+        items: Vec<AbiItem>,
+    },
+    Struct {
+        name: String,
+        members: Vec<AbiMember>,
+    },
+    Enum {
+        name: String,
+        variants: Vec<AbiMember>,
+    },
+    Event(AbiEvent),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct AbiFunction {
+    name: String,
+    inputs: Vec<AbiMember>,
+    #[serde(default)]
+    outputs: Vec<AbiOutput>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct AbiMember {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+impl AbiMember {
+    fn new(name: &str, ty: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ty: ty.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct AbiOutput {
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AbiEvent {
+    name: String,
+    #[serde(flatten)]
+    kind: AbiEventKind,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum AbiEventKind {
+    Struct { members: Vec<AbiEventMember> },
+    Enum { variants: Vec<AbiEventMember> },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct AbiEventMember {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+    kind: AbiEventMemberKind,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AbiEventMemberKind {
+    Key,
+    Data,
+    Nested,
+    Flat,
+}
+
+enum AbiType<'a> {
+    Scalar(Scalar),
+    Array(&'a str),
+    Tuple(Vec<&'a str>),
+    Struct(&'a [AbiMember]),
+    Enum(&'a [AbiMember]),
+}
+
+/// Types encoded as a single felt.
+#[derive(Clone, Copy)]
+enum Scalar {
+    Felt252,
+    Bytes31,
+    Uint8,
+    Uint16,
+    Uint32,
+    Uint64,
+    Uint128,
+    Sint8,
+    Sint16,
+    Sint32,
+    Sint64,
+    Sint128,
+}
+
+impl Scalar {
+    fn from_type(ty: &str) -> Option<Self> {
+        Some(match ty {
+            "core::felt252"
+            | "core::starknet::contract_address::ContractAddress"
+            | "core::starknet::class_hash::ClassHash"
+            | "core::starknet::storage_access::StorageAddress"
+            | "core::starknet::storage_access::StorageBaseAddress" => Self::Felt252,
+            "core::bytes_31::bytes31" => Self::Bytes31,
+            "core::integer::u8" => Self::Uint8,
+            "core::integer::u16" => Self::Uint16,
+            "core::integer::u32" => Self::Uint32,
+            "core::integer::u64" => Self::Uint64,
+            "core::integer::u128" => Self::Uint128,
+            "core::integer::i8" => Self::Sint8,
+            "core::integer::i16" => Self::Sint16,
+            "core::integer::i32" => Self::Sint32,
+            "core::integer::i64" => Self::Sint64,
+            "core::integer::i128" => Self::Sint128,
+            _ => return None,
+        })
+    }
+
+    fn encode(self, value: &Value) -> Option<Felt> {
+        Some(match (self, value) {
+            (Self::Felt252, Value::Felt252(value)) => *value,
+            (Self::Bytes31, Value::Bytes31(data)) => Felt::from_bytes_le_slice(data),
+            (Self::Uint8, Value::Uint8(value)) => (*value).into(),
+            (Self::Uint16, Value::Uint16(value)) => (*value).into(),
+            (Self::Uint32, Value::Uint32(value)) => (*value).into(),
+            (Self::Uint64, Value::Uint64(value)) => (*value).into(),
+            (Self::Uint128, Value::Uint128(value)) => (*value).into(),
+            (Self::Sint8, Value::Sint8(value)) => (*value).into(),
+            (Self::Sint16, Value::Sint16(value)) => (*value).into(),
+            (Self::Sint32, Value::Sint32(value)) => (*value).into(),
+            (Self::Sint64, Value::Sint64(value)) => (*value).into(),
+            (Self::Sint128, Value::Sint128(value)) => (*value).into(),
+            _ => return None,
+        })
+    }
+
+    fn decode(self, felt: Felt) -> Option<Value> {
+        Some(match self {
+            Self::Felt252 => Value::Felt252(felt),
+            Self::Bytes31 => {
+                let data = felt.to_bytes_le();
+                if data[31] != 0 {
+                    return None;
+                }
+
+                Value::Bytes31(data[..31].try_into().ok()?)
+            }
+            Self::Uint8 => Value::Uint8(felt.to_biguint().try_into().ok()?),
+            Self::Uint16 => Value::Uint16(felt.to_biguint().try_into().ok()?),
+            Self::Uint32 => Value::Uint32(felt.to_biguint().try_into().ok()?),
+            Self::Uint64 => Value::Uint64(felt.to_biguint().try_into().ok()?),
+            Self::Uint128 => Value::Uint128(felt.to_biguint().try_into().ok()?),
+            Self::Sint8 => Value::Sint8(felt.to_bigint().try_into().ok()?),
+            Self::Sint16 => Value::Sint16(felt.to_bigint().try_into().ok()?),
+            Self::Sint32 => Value::Sint32(felt.to_bigint().try_into().ok()?),
+            Self::Sint64 => Value::Sint64(felt.to_bigint().try_into().ok()?),
+            Self::Sint128 => Value::Sint128(felt.to_bigint().try_into().ok()?),
+        })
+    }
+}
+
+/// Return the generic argument of `ty` if it's an instance of `base`.
+fn generic_arg<'a>(ty: &'a str, base: &str) -> Option<&'a str> {
+    ty.strip_prefix(base)?
+        .strip_prefix("::<")?
+        .strip_suffix('>')
+}
+
+/// Split a comma separated list of types, ignoring the commas of nested types.
+fn split_types(types: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (index, char) in types.char_indices() {
+        match char {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(types[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+
+    let last = types[start..].trim();
+    if !last.is_empty() {
+        result.push(last);
+    }
+
+    result
+}
+
+fn mismatch(ty: &str, value: &Value) -> Error {
+    Error::TypeMismatch(format!("expected a value of type '{ty}', got {value:?}"))
+}
+
+fn out_of_range(ty: &str) -> Error {
+    Error::FeltSerialization(format!("value out of range for '{ty}'"))
+}
+
+fn next_felt(felts: &mut slice::Iter<Felt>, ty: &str) -> Result<Felt> {
+    felts.next().copied().ok_or_else(|| {
+        Error::FeltSerialization(format!(
+            "unexpected end of input while deserializing '{ty}'"
+        ))
+    })
+}
+
+fn check_consumed(felts: &slice::Iter<Felt>, context: &str) -> Result<()> {
+    if felts.len() != 0 {
+        return Err(Error::FeltSerialization(format!(
+            "{} trailing felts after deserializing '{context}'",
+            felts.len(),
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        executor::{AotContractExecutor, EntryPointKind},
+        starknet_stub::StubSyscallHandler,
+        utils::test::{jit_enum, jit_struct, load_starknet_contract},
+        OptLevel,
+    };
+    use cairo_lang_starknet_classes::contract_class::version_id_from_serialized_sierra_program;
+
+    #[test]
+    fn byte_array_roundtrip() {
+        for string in [
+            "",
+            "short",
+            "a byte array longer than thirty-one bytes, twice over!!",
+        ] {
+            let value = byte_array_from_str(string);
+            assert_eq!(byte_array_to_string(&value).as_deref(), Some(string));
+        }
+    }
+
+    #[test]
+    fn split_nested_types() {
+        assert_eq!(
+            split_types("core::felt252, (core::integer::u8, core::array::Array::<(a, b)>)"),
+            [
+                "core::felt252",
+                "(core::integer::u8, core::array::Array::<(a, b)>)"
+            ]
+        );
+        assert!(split_types("").is_empty());
+    }
+
+    #[test]
+    fn call_contract_through_abi() {
+        let (_, contract) = load_starknet_contract! {
+            #[derive(Drop, Serde)]
+            struct Point {
+                x: u32,
+                y: i64,
+            }
+
+            #[derive(Drop, Serde)]
+            enum Shape {
+                Dot: Point,
+                Line: (Point, Point),
+            }
+
+            #[starknet::interface]
+            trait IShapes<TContractState> {
+                fn describe(
+                    ref self: TContractState,
+                    name: ByteArray,
+                    shape: Shape,
+                    weights: Array<u256>,
+                    flag: bool,
+                ) -> (ByteArray, u256, Option<u8>);
+            }
+
+            #[starknet::contract]
+            mod shapes {
+                use super::{Point, Shape};
+
+                #[storage]
+                struct Storage {}
+
+                #[event]
+                #[derive(Drop, starknet::Event)]
+                enum Event {
+                    Described: Described,
+                }
+
+                #[derive(Drop, starknet::Event)]
+                struct Described {
+                    #[key]
+                    name: ByteArray,
+                    points: u8,
+                }
+
+                #[abi(embed_v0)]
+                impl ShapesImpl of super::IShapes<ContractState> {
+                    fn describe(
+                        ref self: ContractState,
+                        name: ByteArray,
+                        shape: Shape,
+                        weights: Array<u256>,
+                        flag: bool,
+                    ) -> (ByteArray, u256, Option<u8>) {
+                        let points: u8 = match shape {
+                            Shape::Dot(_) => 1,
+                            Shape::Line(_) => 2,
+                        };
+
+                        let mut total = 0_u256;
+                        for weight in weights {
+                            total += weight;
+                        };
+
+                        self.emit(Described { name: name.clone(), points });
+                        (name, total, if flag { Option::Some(points) } else { Option::None })
+                    }
+                }
+            }
+        };
+
+        let abi = ContractAbi::from_contract_class(&contract).unwrap();
+
+        let point = |x: u32, y: i64| jit_struct!(Value::Uint32(x), Value::Sint64(y));
+        let u256 = |lo: u128, hi: u128| jit_struct!(Value::Uint128(lo), Value::Uint128(hi));
+        let name = byte_array_from_str("a line longer than thirty-one bytes");
+
+        // Arguments are matched by name.
+        let (selector, calldata) = abi
+            .encode_calldata(
+                "describe",
+                &[
+                    ("flag", jit_enum!(1, jit_struct!())),
+                    (
+                        "shape",
+                        jit_enum!(1, jit_struct!(point(1, -2), point(3, 4))),
+                    ),
+                    ("name", name.clone()),
+                    ("weights", Value::Array(vec![u256(5, 1), u256(7, 2)])),
+                ],
+            )
+            .unwrap();
+        assert_eq!(selector, selector_from_name("describe"));
+
+        assert!(matches!(
+            abi.encode_calldata("describe", &[("name", Value::Felt252(1.into()))]),
+            Err(Error::TypeMismatch(_))
+        ));
+        assert!(matches!(
+            abi.encode_calldata("describe", &[("name", name.clone())]),
+            Err(Error::Abi(_))
+        ));
+
+        let (sierra_version, _) =
+            version_id_from_serialized_sierra_program(&contract.sierra_program).unwrap();
+        let executor = AotContractExecutor::new(
+            &contract.extract_sierra_program().unwrap(),
+            &contract.entry_points_by_type,
+            sierra_version,
+            OptLevel::Default,
+        )
+        .unwrap();
+
+        let mut syscall_handler = StubSyscallHandler::default();
+        let result = executor
+            .run(
+                selector,
+                EntryPointKind::External,
+                &calldata,
+                u64::MAX,
+                None,
+                &mut syscall_handler,
+            )
+            .unwrap();
+        assert!(!result.failure_flag);
+
+        let return_values = abi
+            .decode_return_values("describe", &result.return_values)
+            .unwrap();
+        assert_eq!(
+            return_values,
+            [jit_struct!(
+                name.clone(),
+                u256(12, 3),
+                jit_enum!(0, Value::Uint8(2))
+            )]
+        );
+
+        let event = &syscall_handler.events[0];
+        let event = abi.decode_event(&event.keys, &event.data).unwrap();
+        assert!(event.name.ends_with("::shapes::Described"));
+        assert_eq!(
+            event.fields,
+            [
+                ("name".to_string(), name),
+                ("points".to_string(), Value::Uint8(2)),
+            ]
+        );
+    }
+}
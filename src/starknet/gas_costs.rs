@@ -0,0 +1,672 @@
+//! # Syscall gas costs
+//!
+//! Syscalls are charged by the syscall handler, not by the compiled program. The
+//! [`SyscallGasMeter`] wraps a syscall handler and charges every syscall according to a
+//! [`SyscallGasCosts`] schedule before forwarding it.
+//!
+//! There are presets for Starknet v0.13.0 up to v0.13.2. Later versions changed how syscalls are
+//! priced, and don't have a preset yet.
+//!
+//! Syscalls which can't be afforded fail with the standard `Out of gas` error, without reaching the
+//! inner handler and without consuming any gas.
+
+use super::{
+    ExecutionInfo, ExecutionInfoV2, Secp256k1Point, Secp256r1Point, StarknetSyscallHandler,
+    SyscallResult, U256,
+};
+use serde::{Deserialize, Serialize};
+use starknet_types_core::felt::Felt;
+
+/// The error returned by syscalls when there isn't enough gas, `'Out of gas'`.
+pub const OUT_OF_GAS_ERROR: Felt = Felt::from_hex_unchecked("0x4f7574206f6620676173");
+
+/// The number of 64-bit words absorbed by every keccak round.
+const KECCAK_FULL_RATE_IN_WORDS: usize = 17;
+
+/// The gas costs of the resources syscalls are priced in.
+const STEP: u64 = 100;
+const RANGE_CHECK: u64 = 70;
+const BITWISE: u64 = 594;
+const MEMORY_HOLE: u64 = 10;
+
+/// The gas reserved for every entry point, which calls and deployments have to pay for.
+const ENTRY_POINT_INITIAL_BUDGET: u64 = 100 * STEP;
+const ENTRY_POINT: u64 = ENTRY_POINT_INITIAL_BUDGET + 500 * STEP;
+const CALL_CONTRACT: u64 = 10 * STEP + ENTRY_POINT;
+
+/// The cost of a syscall, made of a fixed part plus a part which grows linearly with its input.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SyscallGasCost {
+    pub base: u64,
+    pub linear_factor: u64,
+}
+
+impl SyscallGasCost {
+    pub const fn new(base: u64, linear_factor: u64) -> Self {
+        Self {
+            base,
+            linear_factor,
+        }
+    }
+
+    pub const fn fixed(base: u64) -> Self {
+        Self::new(base, 0)
+    }
+
+    /// Return the cost for an input of `n` elements.
+    pub fn cost(&self, n: usize) -> u64 {
+        self.linear_factor
+            .saturating_mul(n as u64)
+            .saturating_add(self.base)
+    }
+}
+
+/// The gas cost of every syscall.
+///
+/// Linear factors apply to the length of the calldata for calls and deployments, the number of keys
+/// and data elements for events, the length of the payload for L1 messages and the number of rounds
+/// for keccak.
+///
+/// Costs don't include the syscall base cost, since it is charged by the compiled program itself.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SyscallGasCosts {
+    pub get_block_hash: SyscallGasCost,
+    pub get_execution_info: SyscallGasCost,
+    pub deploy: SyscallGasCost,
+    pub replace_class: SyscallGasCost,
+    pub library_call: SyscallGasCost,
+    pub call_contract: SyscallGasCost,
+    pub storage_read: SyscallGasCost,
+    pub storage_write: SyscallGasCost,
+    pub emit_event: SyscallGasCost,
+    pub send_message_to_l1: SyscallGasCost,
+    pub keccak: SyscallGasCost,
+    pub secp256k1_new: SyscallGasCost,
+    pub secp256k1_add: SyscallGasCost,
+    pub secp256k1_mul: SyscallGasCost,
+    pub secp256k1_get_point_from_x: SyscallGasCost,
+    pub secp256k1_get_xy: SyscallGasCost,
+    pub secp256r1_new: SyscallGasCost,
+    pub secp256r1_add: SyscallGasCost,
+    pub secp256r1_mul: SyscallGasCost,
+    pub secp256r1_get_point_from_x: SyscallGasCost,
+    pub secp256r1_get_xy: SyscallGasCost,
+    pub sha256_process_block: SyscallGasCost,
+    pub get_class_hash_at: SyscallGasCost,
+    pub meta_tx_v0: SyscallGasCost,
+}
+
+impl SyscallGasCosts {
+    /// Every syscall is free, which is what the [`StubSyscallHandler`] charges.
+    ///
+    /// [`StubSyscallHandler`]: crate::starknet_stub::StubSyscallHandler
+    pub fn free() -> Self {
+        Self::default()
+    }
+
+    /// Return the preset of a Starknet version, like `"0.13.1"`.
+    ///
+    /// Only versions from v0.13.0 up to v0.13.2 have a preset, others return `None`.
+    pub fn for_version(version: &str) -> Option<Self> {
+        Some(match version {
+            "0.13.0" => Self::v0_13_0(),
+            "0.13.1" | "0.13.1.1" => Self::v0_13_1(),
+            "0.13.2" => Self::v0_13_2(),
+            _ => return None,
+        })
+    }
+
+    /// The costs charged by the Cairo runner when running tests with the VM.
+    ///
+    /// They match the Starknet v0.13.2 ones except for secp256k1 and secp256r1 syscalls, which the
+    /// runner prices with different memory hole costs.
+    pub fn cairo_runner() -> Self {
+        Self {
+            secp256k1_new: SyscallGasCost::fixed(475 * STEP + 35 * RANGE_CHECK + 40000),
+            secp256k1_mul: SyscallGasCost::fixed(76501 * STEP + 7045 * RANGE_CHECK),
+            secp256k1_get_point_from_x: SyscallGasCost::fixed(
+                391 * STEP + 30 * RANGE_CHECK + 20000,
+            ),
+            secp256k1_get_xy: SyscallGasCost::fixed(239 * STEP + 11 * RANGE_CHECK + 40000),
+            secp256r1_new: SyscallGasCost::fixed(594 * STEP + 49 * RANGE_CHECK + 40000),
+            secp256r1_mul: SyscallGasCost::fixed(125340 * STEP + 13961 * RANGE_CHECK),
+            secp256r1_get_point_from_x: SyscallGasCost::fixed(
+                510 * STEP + 44 * RANGE_CHECK + 20000,
+            ),
+            secp256r1_get_xy: SyscallGasCost::fixed(241 * STEP + 11 * RANGE_CHECK + 40000),
+            ..Self::v0_13_2()
+        }
+    }
+
+    /// The costs of Starknet v0.13.0.
+    ///
+    /// Up to v0.13.2 the gas of a syscall doesn't depend on the size of its input, except for
+    /// keccak rounds: calldata, events and L1 messages are paid for through the transaction
+    /// resources instead. Therefore, the rest of the linear factors are 0.
+    pub fn v0_13_0() -> Self {
+        Self {
+            get_block_hash: SyscallGasCost::fixed(50 * STEP),
+            get_execution_info: SyscallGasCost::fixed(10 * STEP),
+            deploy: SyscallGasCost::fixed(200 * STEP + ENTRY_POINT),
+            replace_class: SyscallGasCost::fixed(50 * STEP),
+            library_call: SyscallGasCost::fixed(CALL_CONTRACT),
+            call_contract: SyscallGasCost::fixed(CALL_CONTRACT),
+            storage_read: SyscallGasCost::fixed(50 * STEP),
+            storage_write: SyscallGasCost::fixed(50 * STEP),
+            emit_event: SyscallGasCost::fixed(10 * STEP),
+            send_message_to_l1: SyscallGasCost::fixed(50 * STEP),
+            keccak: SyscallGasCost::new(0, 180000),
+            secp256k1_new: SyscallGasCost::fixed(475 * STEP + 35 * RANGE_CHECK + 40 * MEMORY_HOLE),
+            secp256k1_add: SyscallGasCost::fixed(406 * STEP + 29 * RANGE_CHECK),
+            secp256k1_mul: SyscallGasCost::fixed(76401 * STEP + 7045 * RANGE_CHECK),
+            secp256k1_get_point_from_x: SyscallGasCost::fixed(
+                391 * STEP + 30 * RANGE_CHECK + 20 * MEMORY_HOLE,
+            ),
+            secp256k1_get_xy: SyscallGasCost::fixed(
+                239 * STEP + 11 * RANGE_CHECK + 40 * MEMORY_HOLE,
+            ),
+            secp256r1_new: SyscallGasCost::fixed(594 * STEP + 49 * RANGE_CHECK + 40 * MEMORY_HOLE),
+            secp256r1_add: SyscallGasCost::fixed(589 * STEP + 57 * RANGE_CHECK),
+            secp256r1_mul: SyscallGasCost::fixed(125240 * STEP + 13961 * RANGE_CHECK),
+            secp256r1_get_point_from_x: SyscallGasCost::fixed(
+                510 * STEP + 44 * RANGE_CHECK + 20 * MEMORY_HOLE,
+            ),
+            secp256r1_get_xy: SyscallGasCost::fixed(
+                241 * STEP + 11 * RANGE_CHECK + 40 * MEMORY_HOLE,
+            ),
+            // Not available yet.
+            sha256_process_block: SyscallGasCost::fixed(0),
+            // Not available in any of the presets, charged like the Cairo runner does.
+            get_class_hash_at: SyscallGasCost::fixed(50 * STEP),
+            meta_tx_v0: SyscallGasCost::fixed(CALL_CONTRACT),
+        }
+    }
+
+    /// The costs of Starknet v0.13.1 (and v0.13.1.1), which made secp multiplications pricier.
+    pub fn v0_13_1() -> Self {
+        Self {
+            secp256k1_mul: SyscallGasCost::fixed(
+                76501 * STEP + 7045 * RANGE_CHECK + 2 * MEMORY_HOLE,
+            ),
+            secp256r1_mul: SyscallGasCost::fixed(
+                125340 * STEP + 13961 * RANGE_CHECK + 2 * MEMORY_HOLE,
+            ),
+            ..Self::v0_13_0()
+        }
+    }
+
+    /// The costs of Starknet v0.13.2, which introduced the `sha256_process_block` syscall.
+    pub fn v0_13_2() -> Self {
+        Self {
+            sha256_process_block: SyscallGasCost::fixed(
+                1852 * STEP + 65 * RANGE_CHECK + 1115 * BITWISE,
+            ),
+            ..Self::v0_13_1()
+        }
+    }
+}
+
+/// A syscall handler which charges every syscall before forwarding it to the inner handler.
+///
+/// Please look at the [module level docs](self).
+#[derive(Debug)]
+pub struct SyscallGasMeter<H> {
+    handler: H,
+    costs: SyscallGasCosts,
+}
+
+impl<H> SyscallGasMeter<H>
+where
+    H: StarknetSyscallHandler,
+{
+    pub fn new(handler: H, costs: SyscallGasCosts) -> Self {
+        Self { handler, costs }
+    }
+
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    pub fn costs(&self) -> &SyscallGasCosts {
+        &self.costs
+    }
+
+    pub fn into_inner(self) -> H {
+        self.handler
+    }
+}
+
+/// Deduct the cost of a syscall, or fail without consuming anything if it can't be afforded.
+fn charge(remaining_gas: &mut u64, cost: u64) -> SyscallResult<()> {
+    match remaining_gas.checked_sub(cost) {
+        Some(gas) => {
+            *remaining_gas = gas;
+            Ok(())
+        }
+        None => Err(vec![OUT_OF_GAS_ERROR]),
+    }
+}
+
+impl<H> StarknetSyscallHandler for &mut SyscallGasMeter<H>
+where
+    H: StarknetSyscallHandler,
+{
+    fn get_block_hash(
+        &mut self,
+        block_number: u64,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        charge(remaining_gas, self.costs.get_block_hash.cost(0))?;
+        self.handler.get_block_hash(block_number, remaining_gas)
+    }
+
+    fn get_execution_info(&mut self, remaining_gas: &mut u64) -> SyscallResult<ExecutionInfo> {
+        charge(remaining_gas, self.costs.get_execution_info.cost(0))?;
+        self.handler.get_execution_info(remaining_gas)
+    }
+
+    fn get_execution_info_v2(&mut self, remaining_gas: &mut u64) -> SyscallResult<ExecutionInfoV2> {
+        charge(remaining_gas, self.costs.get_execution_info.cost(0))?;
+        self.handler.get_execution_info_v2(remaining_gas)
+    }
+
+    fn deploy(
+        &mut self,
+        class_hash: Felt,
+        contract_address_salt: Felt,
+        calldata: &[Felt],
+        deploy_from_zero: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(Felt, Vec<Felt>)> {
+        charge(remaining_gas, self.costs.deploy.cost(calldata.len()))?;
+        self.handler.deploy(
+            class_hash,
+            contract_address_salt,
+            calldata,
+            deploy_from_zero,
+            remaining_gas,
+        )
+    }
+
+    fn replace_class(&mut self, class_hash: Felt, remaining_gas: &mut u64) -> SyscallResult<()> {
+        charge(remaining_gas, self.costs.replace_class.cost(0))?;
+        self.handler.replace_class(class_hash, remaining_gas)
+    }
+
+    fn library_call(
+        &mut self,
+        class_hash: Felt,
+        function_selector: Felt,
+        calldata: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        charge(remaining_gas, self.costs.library_call.cost(calldata.len()))?;
+        self.handler
+            .library_call(class_hash, function_selector, calldata, remaining_gas)
+    }
+
+    fn call_contract(
+        &mut self,
+        address: Felt,
+        entry_point_selector: Felt,
+        calldata: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        charge(remaining_gas, self.costs.call_contract.cost(calldata.len()))?;
+        self.handler
+            .call_contract(address, entry_point_selector, calldata, remaining_gas)
+    }
+
+    fn storage_read(
+        &mut self,
+        address_domain: u32,
+        address: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        charge(remaining_gas, self.costs.storage_read.cost(0))?;
+        self.handler
+            .storage_read(address_domain, address, remaining_gas)
+    }
+
+    fn storage_write(
+        &mut self,
+        address_domain: u32,
+        address: Felt,
+        value: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        charge(remaining_gas, self.costs.storage_write.cost(0))?;
+        self.handler
+            .storage_write(address_domain, address, value, remaining_gas)
+    }
+
+    fn emit_event(
+        &mut self,
+        keys: &[Felt],
+        data: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        charge(
+            remaining_gas,
+            self.costs.emit_event.cost(keys.len() + data.len()),
+        )?;
+        self.handler.emit_event(keys, data, remaining_gas)
+    }
+
+    fn send_message_to_l1(
+        &mut self,
+        to_address: Felt,
+        payload: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        charge(
+            remaining_gas,
+            self.costs.send_message_to_l1.cost(payload.len()),
+        )?;
+        self.handler
+            .send_message_to_l1(to_address, payload, remaining_gas)
+    }
+
+    fn keccak(&mut self, input: &[u64], remaining_gas: &mut u64) -> SyscallResult<U256> {
+        charge(
+            remaining_gas,
+            self.costs
+                .keccak
+                .cost(input.len() / KECCAK_FULL_RATE_IN_WORDS),
+        )?;
+        self.handler.keccak(input, remaining_gas)
+    }
+
+    fn secp256k1_new(
+        &mut self,
+        x: U256,
+        y: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256k1Point>> {
+        charge(remaining_gas, self.costs.secp256k1_new.cost(0))?;
+        self.handler.secp256k1_new(x, y, remaining_gas)
+    }
+
+    fn secp256k1_add(
+        &mut self,
+        p0: Secp256k1Point,
+        p1: Secp256k1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256k1Point> {
+        charge(remaining_gas, self.costs.secp256k1_add.cost(0))?;
+        self.handler.secp256k1_add(p0, p1, remaining_gas)
+    }
+
+    fn secp256k1_mul(
+        &mut self,
+        p: Secp256k1Point,
+        m: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256k1Point> {
+        charge(remaining_gas, self.costs.secp256k1_mul.cost(0))?;
+        self.handler.secp256k1_mul(p, m, remaining_gas)
+    }
+
+    fn secp256k1_get_point_from_x(
+        &mut self,
+        x: U256,
+        y_parity: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256k1Point>> {
+        charge(remaining_gas, self.costs.secp256k1_get_point_from_x.cost(0))?;
+        self.handler
+            .secp256k1_get_point_from_x(x, y_parity, remaining_gas)
+    }
+
+    fn secp256k1_get_xy(
+        &mut self,
+        p: Secp256k1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(U256, U256)> {
+        charge(remaining_gas, self.costs.secp256k1_get_xy.cost(0))?;
+        self.handler.secp256k1_get_xy(p, remaining_gas)
+    }
+
+    fn secp256r1_new(
+        &mut self,
+        x: U256,
+        y: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256r1Point>> {
+        charge(remaining_gas, self.costs.secp256r1_new.cost(0))?;
+        self.handler.secp256r1_new(x, y, remaining_gas)
+    }
+
+    fn secp256r1_add(
+        &mut self,
+        p0: Secp256r1Point,
+        p1: Secp256r1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256r1Point> {
+        charge(remaining_gas, self.costs.secp256r1_add.cost(0))?;
+        self.handler.secp256r1_add(p0, p1, remaining_gas)
+    }
+
+    fn secp256r1_mul(
+        &mut self,
+        p: Secp256r1Point,
+        m: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256r1Point> {
+        charge(remaining_gas, self.costs.secp256r1_mul.cost(0))?;
+        self.handler.secp256r1_mul(p, m, remaining_gas)
+    }
+
+    fn secp256r1_get_point_from_x(
+        &mut self,
+        x: U256,
+        y_parity: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256r1Point>> {
+        charge(remaining_gas, self.costs.secp256r1_get_point_from_x.cost(0))?;
+        self.handler
+            .secp256r1_get_point_from_x(x, y_parity, remaining_gas)
+    }
+
+    fn secp256r1_get_xy(
+        &mut self,
+        p: Secp256r1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(U256, U256)> {
+        charge(remaining_gas, self.costs.secp256r1_get_xy.cost(0))?;
+        self.handler.secp256r1_get_xy(p, remaining_gas)
+    }
+
+    fn sha256_process_block(
+        &mut self,
+        state: &mut [u32; 8],
+        block: &[u32; 16],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        charge(remaining_gas, self.costs.sha256_process_block.cost(0))?;
+        self.handler
+            .sha256_process_block(state, block, remaining_gas)
+    }
+
+    fn get_class_hash_at(
+        &mut self,
+        contract_address: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        charge(remaining_gas, self.costs.get_class_hash_at.cost(0))?;
+        self.handler
+            .get_class_hash_at(contract_address, remaining_gas)
+    }
+
+    fn meta_tx_v0(
+        &mut self,
+        address: Felt,
+        entry_point_selector: Felt,
+        calldata: &[Felt],
+        signature: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        charge(remaining_gas, self.costs.meta_tx_v0.cost(calldata.len()))?;
+        self.handler.meta_tx_v0(
+            address,
+            entry_point_selector,
+            calldata,
+            signature,
+            remaining_gas,
+        )
+    }
+
+    #[cfg(feature = "with-cheatcode")]
+    fn cheatcode(&mut self, selector: Felt, input: &[Felt]) -> Vec<Felt> {
+        self.handler.cheatcode(selector, input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::starknet_stub::StubSyscallHandler;
+
+    #[test]
+    fn charge_syscalls() {
+        let costs = SyscallGasCosts::v0_13_0();
+        let mut stub = StubSyscallHandler::default();
+        let mut meter = SyscallGasMeter::new(&mut stub, costs.clone());
+        let mut handler = &mut meter;
+
+        let mut gas = 100_000;
+        handler
+            .storage_write(0, 1.into(), 2.into(), &mut gas)
+            .unwrap();
+        assert_eq!(gas, 100_000 - costs.storage_write.base);
+
+        // Two keccak rounds.
+        let mut gas = 1_000_000;
+        handler
+            .keccak(&[0; 2 * KECCAK_FULL_RATE_IN_WORDS], &mut gas)
+            .unwrap();
+        assert_eq!(gas, 1_000_000 - 2 * costs.keccak.linear_factor);
+
+        // Unaffordable syscalls don't reach the handler.
+        let mut gas = costs.storage_write.base - 1;
+        assert_eq!(
+            handler.storage_write(0, 3.into(), 4.into(), &mut gas),
+            Err(vec![Felt::from_bytes_be_slice(b"Out of gas")])
+        );
+        assert_eq!(gas, costs.storage_write.base - 1);
+
+        assert_eq!(stub.storage.get(&(0, 1.into())), Some(&2.into()));
+        assert_eq!(stub.storage.get(&(0, 3.into())), None);
+    }
+
+    type Meter<'a> = SyscallGasMeter<&'a mut StubSyscallHandler>;
+
+    /// Return the gas charged for a syscall by a meter with the given costs.
+    fn charged(costs: SyscallGasCosts, syscall: fn(&mut &mut Meter, &mut u64)) -> u64 {
+        let mut stub = StubSyscallHandler::default();
+        let mut meter = SyscallGasMeter::new(&mut stub, costs);
+
+        let mut gas = u64::MAX;
+        syscall(&mut &mut meter, &mut gas);
+        u64::MAX - gas
+    }
+
+    #[test]
+    fn charge_per_version() {
+        let versions = ["0.13.0", "0.13.1", "0.13.2"]
+            .map(|version| SyscallGasCosts::for_version(version).unwrap());
+        let charged = |syscall| versions.clone().map(|costs| charged(costs, syscall));
+
+        assert_eq!(
+            charged(|handler, gas| {
+                handler.storage_read(0, 1.into(), gas).unwrap();
+            }),
+            [5000; 3]
+        );
+
+        // Calls and events cost the same regardless of the size of their input.
+        assert_eq!(
+            charged(|handler, gas| {
+                handler.call_contract(1.into(), 2.into(), &[], gas).unwrap();
+            }),
+            [61000; 3]
+        );
+        assert_eq!(
+            charged(|handler, gas| {
+                handler
+                    .call_contract(1.into(), 2.into(), &[Felt::ONE; 100], gas)
+                    .unwrap();
+            }),
+            [61000; 3]
+        );
+        assert_eq!(
+            charged(|handler, gas| {
+                handler
+                    .emit_event(&[Felt::ONE; 10], &[Felt::ONE; 10], gas)
+                    .unwrap();
+            }),
+            [1000; 3]
+        );
+
+        // Keccak is charged per round.
+        assert_eq!(
+            charged(|handler, gas| {
+                handler
+                    .keccak(&[0; 2 * KECCAK_FULL_RATE_IN_WORDS], gas)
+                    .unwrap();
+            }),
+            [360000; 3]
+        );
+
+        // v0.13.1 made secp multiplications pricier.
+        assert_eq!(
+            charged(|handler, gas| {
+                let p = Secp256k1Point {
+                    is_infinity: true,
+                    ..Default::default()
+                };
+                handler
+                    .secp256k1_mul(p, U256 { lo: 2, hi: 0 }, gas)
+                    .unwrap();
+            }),
+            [8133250, 8143270, 8143270]
+        );
+
+        // And v0.13.2 introduced `sha256_process_block`.
+        assert_eq!(
+            charged(|handler, gas| {
+                handler
+                    .sha256_process_block(&mut [0; 8], &[0; 16], gas)
+                    .unwrap();
+            }),
+            [0, 0, 852060]
+        );
+
+        assert_eq!(
+            SyscallGasCosts::for_version("0.13.1.1"),
+            Some(SyscallGasCosts::v0_13_1())
+        );
+        assert_eq!(SyscallGasCosts::for_version("0.13.3"), None);
+    }
+
+    #[test]
+    fn charge_cairo_runner() {
+        // The runner prices the memory holes of secp syscalls differently.
+        fn secp256k1_new(handler: &mut &mut Meter, gas: &mut u64) {
+            let _ = handler.secp256k1_new(U256::default(), U256::default(), gas);
+        }
+        assert_eq!(charged(SyscallGasCosts::v0_13_2(), secp256k1_new), 50350);
+        assert_eq!(
+            charged(SyscallGasCosts::cairo_runner(), secp256k1_new),
+            89950
+        );
+    }
+
+    #[test]
+    fn linear_costs() {
+        let cost = SyscallGasCost::new(10, 3);
+        assert_eq!(cost.cost(0), 10);
+        assert_eq!(cost.cost(4), 22);
+        assert_eq!(SyscallGasCost::new(u64::MAX, 1).cost(1), u64::MAX);
+    }
+}
@@ -0,0 +1,1324 @@
+//! # Syscall recording and replay
+//!
+//! The [`RecordingSyscallHandler`] wraps another syscall handler and records every syscall it
+//! forwards (its arguments, its result or error payload and the gas it consumed) into a
+//! [`SyscallTranscript`], which can be serialized.
+//!
+//! The [`ReplaySyscallHandler`] answers syscalls from a transcript instead, without any state. Every
+//! syscall must match the next recorded one exactly, including the remaining gas when it was made.
+//! On the first divergence the syscall fails with a `REPLAY_DIVERGENCE` error (as do all the
+//! following ones), and [`ReplaySyscallHandler::finish`] will report what went wrong.
+//!
+//! Since contracts may handle syscall errors, a divergence can go unnoticed by looking at the
+//! execution's result alone. Always check [`ReplaySyscallHandler::divergence`] or call
+//! [`ReplaySyscallHandler::finish`] once the execution is done.
+//!
+//! Nested executions (like contract calls) are recorded and replayed as a single syscall.
+
+use super::{
+    ExecutionInfo, ExecutionInfoV2, Secp256k1Point, Secp256r1Point, StarknetSyscallHandler,
+    SyscallResult, U256,
+};
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use starknet_types_core::felt::Felt;
+
+/// A list of syscalls, in the order they were made.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyscallTranscript {
+    pub records: Vec<SyscallRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyscallRecord {
+    pub request: SyscallRequest,
+    pub result: std::result::Result<SyscallResponse, Vec<Felt>>,
+    /// The gas available when the syscall was made.
+    pub remaining_gas: u64,
+    /// The gas consumed by the syscall.
+    pub gas_consumed: u64,
+}
+
+/// The arguments of a syscall.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "syscall", rename_all = "snake_case")]
+pub enum SyscallRequest {
+    GetBlockHash {
+        block_number: u64,
+    },
+    GetExecutionInfo,
+    GetExecutionInfoV2,
+    Deploy {
+        class_hash: Felt,
+        contract_address_salt: Felt,
+        calldata: Vec<Felt>,
+        deploy_from_zero: bool,
+    },
+    ReplaceClass {
+        class_hash: Felt,
+    },
+    LibraryCall {
+        class_hash: Felt,
+        function_selector: Felt,
+        calldata: Vec<Felt>,
+    },
+    CallContract {
+        address: Felt,
+        entry_point_selector: Felt,
+        calldata: Vec<Felt>,
+    },
+    StorageRead {
+        address_domain: u32,
+        address: Felt,
+    },
+    StorageWrite {
+        address_domain: u32,
+        address: Felt,
+        value: Felt,
+    },
+    EmitEvent {
+        keys: Vec<Felt>,
+        data: Vec<Felt>,
+    },
+    SendMessageToL1 {
+        to_address: Felt,
+        payload: Vec<Felt>,
+    },
+    Keccak {
+        input: Vec<u64>,
+    },
+    Secp256k1New {
+        x: U256,
+        y: U256,
+    },
+    Secp256k1Add {
+        p0: Secp256k1Point,
+        p1: Secp256k1Point,
+    },
+    Secp256k1Mul {
+        p: Secp256k1Point,
+        m: U256,
+    },
+    Secp256k1GetPointFromX {
+        x: U256,
+        y_parity: bool,
+    },
+    Secp256k1GetXy {
+        p: Secp256k1Point,
+    },
+    Secp256r1New {
+        x: U256,
+        y: U256,
+    },
+    Secp256r1Add {
+        p0: Secp256r1Point,
+        p1: Secp256r1Point,
+    },
+    Secp256r1Mul {
+        p: Secp256r1Point,
+        m: U256,
+    },
+    Secp256r1GetPointFromX {
+        x: U256,
+        y_parity: bool,
+    },
+    Secp256r1GetXy {
+        p: Secp256r1Point,
+    },
+    Sha256ProcessBlock {
+        state: [u32; 8],
+        block: [u32; 16],
+    },
+    GetClassHashAt {
+        contract_address: Felt,
+    },
+    MetaTxV0 {
+        address: Felt,
+        entry_point_selector: Felt,
+        calldata: Vec<Felt>,
+        signature: Vec<Felt>,
+    },
+    Cheatcode {
+        selector: Felt,
+        input: Vec<Felt>,
+    },
+}
+
+/// The successful result of a syscall.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "syscall", content = "value", rename_all = "snake_case")]
+pub enum SyscallResponse {
+    GetBlockHash(Felt),
+    GetExecutionInfo(ExecutionInfo),
+    GetExecutionInfoV2(ExecutionInfoV2),
+    Deploy(Felt, Vec<Felt>),
+    ReplaceClass,
+    LibraryCall(Vec<Felt>),
+    CallContract(Vec<Felt>),
+    StorageRead(Felt),
+    StorageWrite,
+    EmitEvent,
+    SendMessageToL1,
+    Keccak(U256),
+    Secp256k1New(Option<Secp256k1Point>),
+    Secp256k1Add(Secp256k1Point),
+    Secp256k1Mul(Secp256k1Point),
+    Secp256k1GetPointFromX(Option<Secp256k1Point>),
+    Secp256k1GetXy(U256, U256),
+    Secp256r1New(Option<Secp256r1Point>),
+    Secp256r1Add(Secp256r1Point),
+    Secp256r1Mul(Secp256r1Point),
+    Secp256r1GetPointFromX(Option<Secp256r1Point>),
+    Secp256r1GetXy(U256, U256),
+    /// The updated state.
+    Sha256ProcessBlock([u32; 8]),
+    GetClassHashAt(Felt),
+    MetaTxV0(Vec<Felt>),
+    Cheatcode(Vec<Felt>),
+}
+
+/// A syscall handler which records every syscall forwarded to the inner handler.
+///
+/// Please look at the [module level docs](self).
+#[derive(Debug)]
+pub struct RecordingSyscallHandler<H> {
+    handler: H,
+    transcript: SyscallTranscript,
+}
+
+impl<H> RecordingSyscallHandler<H>
+where
+    H: StarknetSyscallHandler,
+{
+    pub fn new(handler: H) -> Self {
+        Self {
+            handler,
+            transcript: SyscallTranscript::default(),
+        }
+    }
+
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    pub fn transcript(&self) -> &SyscallTranscript {
+        &self.transcript
+    }
+
+    pub fn into_transcript(self) -> SyscallTranscript {
+        self.transcript
+    }
+
+    fn record<T>(
+        &mut self,
+        request: SyscallRequest,
+        remaining_gas: &mut u64,
+        syscall: impl FnOnce(&mut H, &mut u64) -> SyscallResult<T>,
+        into_response: impl FnOnce(T) -> SyscallResponse,
+    ) -> SyscallResult<T>
+    where
+        T: Clone,
+    {
+        let initial_gas = *remaining_gas;
+        let result = syscall(&mut self.handler, remaining_gas);
+
+        self.transcript.records.push(SyscallRecord {
+            request,
+            result: result.clone().map(into_response),
+            remaining_gas: initial_gas,
+            gas_consumed: initial_gas.saturating_sub(*remaining_gas),
+        });
+
+        result
+    }
+}
+
+impl<H> StarknetSyscallHandler for &mut RecordingSyscallHandler<H>
+where
+    H: StarknetSyscallHandler,
+{
+    fn get_block_hash(
+        &mut self,
+        block_number: u64,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        self.record(
+            SyscallRequest::GetBlockHash { block_number },
+            remaining_gas,
+            |handler, gas| handler.get_block_hash(block_number, gas),
+            SyscallResponse::GetBlockHash,
+        )
+    }
+
+    fn get_execution_info(&mut self, remaining_gas: &mut u64) -> SyscallResult<ExecutionInfo> {
+        self.record(
+            SyscallRequest::GetExecutionInfo,
+            remaining_gas,
+            |handler, gas| handler.get_execution_info(gas),
+            SyscallResponse::GetExecutionInfo,
+        )
+    }
+
+    fn get_execution_info_v2(&mut self, remaining_gas: &mut u64) -> SyscallResult<ExecutionInfoV2> {
+        self.record(
+            SyscallRequest::GetExecutionInfoV2,
+            remaining_gas,
+            |handler, gas| handler.get_execution_info_v2(gas),
+            SyscallResponse::GetExecutionInfoV2,
+        )
+    }
+
+    fn deploy(
+        &mut self,
+        class_hash: Felt,
+        contract_address_salt: Felt,
+        calldata: &[Felt],
+        deploy_from_zero: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(Felt, Vec<Felt>)> {
+        self.record(
+            SyscallRequest::Deploy {
+                class_hash,
+                contract_address_salt,
+                calldata: calldata.to_vec(),
+                deploy_from_zero,
+            },
+            remaining_gas,
+            |handler, gas| {
+                handler.deploy(
+                    class_hash,
+                    contract_address_salt,
+                    calldata,
+                    deploy_from_zero,
+                    gas,
+                )
+            },
+            |(address, retdata)| SyscallResponse::Deploy(address, retdata),
+        )
+    }
+
+    fn replace_class(&mut self, class_hash: Felt, remaining_gas: &mut u64) -> SyscallResult<()> {
+        self.record(
+            SyscallRequest::ReplaceClass { class_hash },
+            remaining_gas,
+            |handler, gas| handler.replace_class(class_hash, gas),
+            |()| SyscallResponse::ReplaceClass,
+        )
+    }
+
+    fn library_call(
+        &mut self,
+        class_hash: Felt,
+        function_selector: Felt,
+        calldata: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        self.record(
+            SyscallRequest::LibraryCall {
+                class_hash,
+                function_selector,
+                calldata: calldata.to_vec(),
+            },
+            remaining_gas,
+            |handler, gas| handler.library_call(class_hash, function_selector, calldata, gas),
+            SyscallResponse::LibraryCall,
+        )
+    }
+
+    fn call_contract(
+        &mut self,
+        address: Felt,
+        entry_point_selector: Felt,
+        calldata: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        self.record(
+            SyscallRequest::CallContract {
+                address,
+                entry_point_selector,
+                calldata: calldata.to_vec(),
+            },
+            remaining_gas,
+            |handler, gas| handler.call_contract(address, entry_point_selector, calldata, gas),
+            SyscallResponse::CallContract,
+        )
+    }
+
+    fn storage_read(
+        &mut self,
+        address_domain: u32,
+        address: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        self.record(
+            SyscallRequest::StorageRead {
+                address_domain,
+                address,
+            },
+            remaining_gas,
+            |handler, gas| handler.storage_read(address_domain, address, gas),
+            SyscallResponse::StorageRead,
+        )
+    }
+
+    fn storage_write(
+        &mut self,
+        address_domain: u32,
+        address: Felt,
+        value: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        self.record(
+            SyscallRequest::StorageWrite {
+                address_domain,
+                address,
+                value,
+            },
+            remaining_gas,
+            |handler, gas| handler.storage_write(address_domain, address, value, gas),
+            |()| SyscallResponse::StorageWrite,
+        )
+    }
+
+    fn emit_event(
+        &mut self,
+        keys: &[Felt],
+        data: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        self.record(
+            SyscallRequest::EmitEvent {
+                keys: keys.to_vec(),
+                data: data.to_vec(),
+            },
+            remaining_gas,
+            |handler, gas| handler.emit_event(keys, data, gas),
+            |()| SyscallResponse::EmitEvent,
+        )
+    }
+
+    fn send_message_to_l1(
+        &mut self,
+        to_address: Felt,
+        payload: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        self.record(
+            SyscallRequest::SendMessageToL1 {
+                to_address,
+                payload: payload.to_vec(),
+            },
+            remaining_gas,
+            |handler, gas| handler.send_message_to_l1(to_address, payload, gas),
+            |()| SyscallResponse::SendMessageToL1,
+        )
+    }
+
+    fn keccak(&mut self, input: &[u64], remaining_gas: &mut u64) -> SyscallResult<U256> {
+        self.record(
+            SyscallRequest::Keccak {
+                input: input.to_vec(),
+            },
+            remaining_gas,
+            |handler, gas| handler.keccak(input, gas),
+            SyscallResponse::Keccak,
+        )
+    }
+
+    fn secp256k1_new(
+        &mut self,
+        x: U256,
+        y: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256k1Point>> {
+        self.record(
+            SyscallRequest::Secp256k1New { x, y },
+            remaining_gas,
+            |handler, gas| handler.secp256k1_new(x, y, gas),
+            SyscallResponse::Secp256k1New,
+        )
+    }
+
+    fn secp256k1_add(
+        &mut self,
+        p0: Secp256k1Point,
+        p1: Secp256k1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256k1Point> {
+        self.record(
+            SyscallRequest::Secp256k1Add { p0, p1 },
+            remaining_gas,
+            |handler, gas| handler.secp256k1_add(p0, p1, gas),
+            SyscallResponse::Secp256k1Add,
+        )
+    }
+
+    fn secp256k1_mul(
+        &mut self,
+        p: Secp256k1Point,
+        m: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256k1Point> {
+        self.record(
+            SyscallRequest::Secp256k1Mul { p, m },
+            remaining_gas,
+            |handler, gas| handler.secp256k1_mul(p, m, gas),
+            SyscallResponse::Secp256k1Mul,
+        )
+    }
+
+    fn secp256k1_get_point_from_x(
+        &mut self,
+        x: U256,
+        y_parity: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256k1Point>> {
+        self.record(
+            SyscallRequest::Secp256k1GetPointFromX { x, y_parity },
+            remaining_gas,
+            |handler, gas| handler.secp256k1_get_point_from_x(x, y_parity, gas),
+            SyscallResponse::Secp256k1GetPointFromX,
+        )
+    }
+
+    fn secp256k1_get_xy(
+        &mut self,
+        p: Secp256k1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(U256, U256)> {
+        self.record(
+            SyscallRequest::Secp256k1GetXy { p },
+            remaining_gas,
+            |handler, gas| handler.secp256k1_get_xy(p, gas),
+            |(x, y)| SyscallResponse::Secp256k1GetXy(x, y),
+        )
+    }
+
+    fn secp256r1_new(
+        &mut self,
+        x: U256,
+        y: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256r1Point>> {
+        self.record(
+            SyscallRequest::Secp256r1New { x, y },
+            remaining_gas,
+            |handler, gas| handler.secp256r1_new(x, y, gas),
+            SyscallResponse::Secp256r1New,
+        )
+    }
+
+    fn secp256r1_add(
+        &mut self,
+        p0: Secp256r1Point,
+        p1: Secp256r1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256r1Point> {
+        self.record(
+            SyscallRequest::Secp256r1Add { p0, p1 },
+            remaining_gas,
+            |handler, gas| handler.secp256r1_add(p0, p1, gas),
+            SyscallResponse::Secp256r1Add,
+        )
+    }
+
+    fn secp256r1_mul(
+        &mut self,
+        p: Secp256r1Point,
+        m: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256r1Point> {
+        self.record(
+            SyscallRequest::Secp256r1Mul { p, m },
+            remaining_gas,
+            |handler, gas| handler.secp256r1_mul(p, m, gas),
+            SyscallResponse::Secp256r1Mul,
+        )
+    }
+
+    fn secp256r1_get_point_from_x(
+        &mut self,
+        x: U256,
+        y_parity: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256r1Point>> {
+        self.record(
+            SyscallRequest::Secp256r1GetPointFromX { x, y_parity },
+            remaining_gas,
+            |handler, gas| handler.secp256r1_get_point_from_x(x, y_parity, gas),
+            SyscallResponse::Secp256r1GetPointFromX,
+        )
+    }
+
+    fn secp256r1_get_xy(
+        &mut self,
+        p: Secp256r1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(U256, U256)> {
+        self.record(
+            SyscallRequest::Secp256r1GetXy { p },
+            remaining_gas,
+            |handler, gas| handler.secp256r1_get_xy(p, gas),
+            |(x, y)| SyscallResponse::Secp256r1GetXy(x, y),
+        )
+    }
+
+    fn sha256_process_block(
+        &mut self,
+        state: &mut [u32; 8],
+        block: &[u32; 16],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        let request = SyscallRequest::Sha256ProcessBlock {
+            state: *state,
+            block: *block,
+        };
+
+        self.record(
+            request,
+            remaining_gas,
+            |handler, gas| {
+                handler.sha256_process_block(state, block, gas)?;
+                Ok(*state)
+            },
+            SyscallResponse::Sha256ProcessBlock,
+        )
+        .map(|_| ())
+    }
+
+    fn get_class_hash_at(
+        &mut self,
+        contract_address: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        self.record(
+            SyscallRequest::GetClassHashAt { contract_address },
+            remaining_gas,
+            |handler, gas| handler.get_class_hash_at(contract_address, gas),
+            SyscallResponse::GetClassHashAt,
+        )
+    }
+
+    fn meta_tx_v0(
+        &mut self,
+        address: Felt,
+        entry_point_selector: Felt,
+        calldata: &[Felt],
+        signature: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        self.record(
+            SyscallRequest::MetaTxV0 {
+                address,
+                entry_point_selector,
+                calldata: calldata.to_vec(),
+                signature: signature.to_vec(),
+            },
+            remaining_gas,
+            |handler, gas| {
+                handler.meta_tx_v0(address, entry_point_selector, calldata, signature, gas)
+            },
+            SyscallResponse::MetaTxV0,
+        )
+    }
+
+    #[cfg(feature = "with-cheatcode")]
+    fn cheatcode(&mut self, selector: Felt, input: &[Felt]) -> Vec<Felt> {
+        let output = self.handler.cheatcode(selector, input);
+        self.transcript.records.push(SyscallRecord {
+            request: SyscallRequest::Cheatcode {
+                selector,
+                input: input.to_vec(),
+            },
+            result: Ok(SyscallResponse::Cheatcode(output.clone())),
+            remaining_gas: 0,
+            gas_consumed: 0,
+        });
+
+        output
+    }
+}
+
+/// A syscall handler which replays a transcript.
+///
+/// Please look at the [module level docs](self).
+#[derive(Debug)]
+pub struct ReplaySyscallHandler {
+    transcript: SyscallTranscript,
+    position: usize,
+    divergence: Option<String>,
+}
+
+impl ReplaySyscallHandler {
+    pub fn new(transcript: SyscallTranscript) -> Self {
+        Self {
+            transcript,
+            position: 0,
+            divergence: None,
+        }
+    }
+
+    /// Return the first divergence from the transcript, even if the contract handled its error.
+    pub fn divergence(&self) -> Option<&str> {
+        self.divergence.as_deref()
+    }
+
+    /// Check that the execution matched the transcript and that every syscall was replayed.
+    pub fn finish(&self) -> Result<()> {
+        if let Some(divergence) = &self.divergence {
+            return Err(Error::ReplayDivergence(divergence.clone()));
+        }
+
+        if self.position != self.transcript.records.len() {
+            return Err(Error::ReplayDivergence(format!(
+                "only {} of {} recorded syscalls were replayed",
+                self.position,
+                self.transcript.records.len(),
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn replay<T>(
+        &mut self,
+        request: SyscallRequest,
+        remaining_gas: &mut u64,
+        from_response: impl FnOnce(SyscallResponse) -> Option<T>,
+    ) -> SyscallResult<T> {
+        if self.divergence.is_some() {
+            return Err(divergence_error());
+        }
+
+        let position = self.position;
+        let Some(record) = self.transcript.records.get(position).cloned() else {
+            return Err(self.diverge(format!(
+                "syscall #{position} ({request:?}) was not recorded"
+            )));
+        };
+
+        if record.request != request {
+            return Err(self.diverge(format!(
+                "syscall #{position} was recorded as {:?}, but got {request:?}",
+                record.request,
+            )));
+        }
+        if record.remaining_gas != *remaining_gas {
+            return Err(self.diverge(format!(
+                "syscall #{position} was recorded with {} gas, but got {remaining_gas}",
+                record.remaining_gas,
+            )));
+        }
+
+        self.position += 1;
+        *remaining_gas -= record.gas_consumed.min(*remaining_gas);
+
+        match record.result {
+            Ok(response) => from_response(response).ok_or_else(|| {
+                self.diverge(format!(
+                    "syscall #{position} has a response which doesn't match its request"
+                ))
+            }),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn diverge(&mut self, divergence: String) -> Vec<Felt> {
+        tracing::error!("syscall replay diverged: {divergence}");
+        self.divergence = Some(divergence);
+        divergence_error()
+    }
+}
+
+fn divergence_error() -> Vec<Felt> {
+    vec![Felt::from_bytes_be_slice(b"REPLAY_DIVERGENCE")]
+}
+
+/// Build a closure extracting the payload of a [`SyscallResponse`] variant.
+macro_rules! response {
+    ( $variant:ident ) => {
+        |response| match response {
+            SyscallResponse::$variant => Some(()),
+            _ => None,
+        }
+    };
+    ( $variant:ident ( $field:ident ) ) => {
+        |response| match response {
+            SyscallResponse::$variant($field) => Some($field),
+            _ => None,
+        }
+    };
+    ( $variant:ident ( $($field:ident),+ ) ) => {
+        |response| match response {
+            SyscallResponse::$variant($($field),+) => Some(($($field),+)),
+            _ => None,
+        }
+    };
+}
+
+impl StarknetSyscallHandler for &mut ReplaySyscallHandler {
+    fn get_block_hash(
+        &mut self,
+        block_number: u64,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        self.replay(
+            SyscallRequest::GetBlockHash { block_number },
+            remaining_gas,
+            response!(GetBlockHash(x)),
+        )
+    }
+
+    fn get_execution_info(&mut self, remaining_gas: &mut u64) -> SyscallResult<ExecutionInfo> {
+        self.replay(
+            SyscallRequest::GetExecutionInfo,
+            remaining_gas,
+            response!(GetExecutionInfo(x)),
+        )
+    }
+
+    fn get_execution_info_v2(&mut self, remaining_gas: &mut u64) -> SyscallResult<ExecutionInfoV2> {
+        self.replay(
+            SyscallRequest::GetExecutionInfoV2,
+            remaining_gas,
+            response!(GetExecutionInfoV2(x)),
+        )
+    }
+
+    fn deploy(
+        &mut self,
+        class_hash: Felt,
+        contract_address_salt: Felt,
+        calldata: &[Felt],
+        deploy_from_zero: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(Felt, Vec<Felt>)> {
+        self.replay(
+            SyscallRequest::Deploy {
+                class_hash,
+                contract_address_salt,
+                calldata: calldata.to_vec(),
+                deploy_from_zero,
+            },
+            remaining_gas,
+            response!(Deploy(address, retdata)),
+        )
+    }
+
+    fn replace_class(&mut self, class_hash: Felt, remaining_gas: &mut u64) -> SyscallResult<()> {
+        self.replay(
+            SyscallRequest::ReplaceClass { class_hash },
+            remaining_gas,
+            response!(ReplaceClass),
+        )
+    }
+
+    fn library_call(
+        &mut self,
+        class_hash: Felt,
+        function_selector: Felt,
+        calldata: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        self.replay(
+            SyscallRequest::LibraryCall {
+                class_hash,
+                function_selector,
+                calldata: calldata.to_vec(),
+            },
+            remaining_gas,
+            response!(LibraryCall(x)),
+        )
+    }
+
+    fn call_contract(
+        &mut self,
+        address: Felt,
+        entry_point_selector: Felt,
+        calldata: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        self.replay(
+            SyscallRequest::CallContract {
+                address,
+                entry_point_selector,
+                calldata: calldata.to_vec(),
+            },
+            remaining_gas,
+            response!(CallContract(x)),
+        )
+    }
+
+    fn storage_read(
+        &mut self,
+        address_domain: u32,
+        address: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        self.replay(
+            SyscallRequest::StorageRead {
+                address_domain,
+                address,
+            },
+            remaining_gas,
+            response!(StorageRead(x)),
+        )
+    }
+
+    fn storage_write(
+        &mut self,
+        address_domain: u32,
+        address: Felt,
+        value: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        self.replay(
+            SyscallRequest::StorageWrite {
+                address_domain,
+                address,
+                value,
+            },
+            remaining_gas,
+            response!(StorageWrite),
+        )
+    }
+
+    fn emit_event(
+        &mut self,
+        keys: &[Felt],
+        data: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        self.replay(
+            SyscallRequest::EmitEvent {
+                keys: keys.to_vec(),
+                data: data.to_vec(),
+            },
+            remaining_gas,
+            response!(EmitEvent),
+        )
+    }
+
+    fn send_message_to_l1(
+        &mut self,
+        to_address: Felt,
+        payload: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        self.replay(
+            SyscallRequest::SendMessageToL1 {
+                to_address,
+                payload: payload.to_vec(),
+            },
+            remaining_gas,
+            response!(SendMessageToL1),
+        )
+    }
+
+    fn keccak(&mut self, input: &[u64], remaining_gas: &mut u64) -> SyscallResult<U256> {
+        self.replay(
+            SyscallRequest::Keccak {
+                input: input.to_vec(),
+            },
+            remaining_gas,
+            response!(Keccak(x)),
+        )
+    }
+
+    fn secp256k1_new(
+        &mut self,
+        x: U256,
+        y: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256k1Point>> {
+        self.replay(
+            SyscallRequest::Secp256k1New { x, y },
+            remaining_gas,
+            response!(Secp256k1New(p)),
+        )
+    }
+
+    fn secp256k1_add(
+        &mut self,
+        p0: Secp256k1Point,
+        p1: Secp256k1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256k1Point> {
+        self.replay(
+            SyscallRequest::Secp256k1Add { p0, p1 },
+            remaining_gas,
+            response!(Secp256k1Add(p)),
+        )
+    }
+
+    fn secp256k1_mul(
+        &mut self,
+        p: Secp256k1Point,
+        m: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256k1Point> {
+        self.replay(
+            SyscallRequest::Secp256k1Mul { p, m },
+            remaining_gas,
+            response!(Secp256k1Mul(p)),
+        )
+    }
+
+    fn secp256k1_get_point_from_x(
+        &mut self,
+        x: U256,
+        y_parity: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256k1Point>> {
+        self.replay(
+            SyscallRequest::Secp256k1GetPointFromX { x, y_parity },
+            remaining_gas,
+            response!(Secp256k1GetPointFromX(p)),
+        )
+    }
+
+    fn secp256k1_get_xy(
+        &mut self,
+        p: Secp256k1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(U256, U256)> {
+        self.replay(
+            SyscallRequest::Secp256k1GetXy { p },
+            remaining_gas,
+            response!(Secp256k1GetXy(x, y)),
+        )
+    }
+
+    fn secp256r1_new(
+        &mut self,
+        x: U256,
+        y: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256r1Point>> {
+        self.replay(
+            SyscallRequest::Secp256r1New { x, y },
+            remaining_gas,
+            response!(Secp256r1New(p)),
+        )
+    }
+
+    fn secp256r1_add(
+        &mut self,
+        p0: Secp256r1Point,
+        p1: Secp256r1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256r1Point> {
+        self.replay(
+            SyscallRequest::Secp256r1Add { p0, p1 },
+            remaining_gas,
+            response!(Secp256r1Add(p)),
+        )
+    }
+
+    fn secp256r1_mul(
+        &mut self,
+        p: Secp256r1Point,
+        m: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256r1Point> {
+        self.replay(
+            SyscallRequest::Secp256r1Mul { p, m },
+            remaining_gas,
+            response!(Secp256r1Mul(p)),
+        )
+    }
+
+    fn secp256r1_get_point_from_x(
+        &mut self,
+        x: U256,
+        y_parity: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256r1Point>> {
+        self.replay(
+            SyscallRequest::Secp256r1GetPointFromX { x, y_parity },
+            remaining_gas,
+            response!(Secp256r1GetPointFromX(p)),
+        )
+    }
+
+    fn secp256r1_get_xy(
+        &mut self,
+        p: Secp256r1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(U256, U256)> {
+        self.replay(
+            SyscallRequest::Secp256r1GetXy { p },
+            remaining_gas,
+            response!(Secp256r1GetXy(x, y)),
+        )
+    }
+
+    fn sha256_process_block(
+        &mut self,
+        state: &mut [u32; 8],
+        block: &[u32; 16],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        *state = self.replay(
+            SyscallRequest::Sha256ProcessBlock {
+                state: *state,
+                block: *block,
+            },
+            remaining_gas,
+            response!(Sha256ProcessBlock(x)),
+        )?;
+
+        Ok(())
+    }
+
+    fn get_class_hash_at(
+        &mut self,
+        contract_address: Felt,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        self.replay(
+            SyscallRequest::GetClassHashAt { contract_address },
+            remaining_gas,
+            response!(GetClassHashAt(x)),
+        )
+    }
+
+    fn meta_tx_v0(
+        &mut self,
+        address: Felt,
+        entry_point_selector: Felt,
+        calldata: &[Felt],
+        signature: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        self.replay(
+            SyscallRequest::MetaTxV0 {
+                address,
+                entry_point_selector,
+                calldata: calldata.to_vec(),
+                signature: signature.to_vec(),
+            },
+            remaining_gas,
+            response!(MetaTxV0(x)),
+        )
+    }
+
+    #[cfg(feature = "with-cheatcode")]
+    fn cheatcode(&mut self, selector: Felt, input: &[Felt]) -> Vec<Felt> {
+        self.replay(
+            SyscallRequest::Cheatcode {
+                selector,
+                input: input.to_vec(),
+            },
+            &mut 0,
+            response!(Cheatcode(x)),
+        )
+        .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        executor::{AotContractExecutor, EntryPointKind},
+        starknet_stub::StubSyscallHandler,
+        utils::test::load_starknet_contract,
+        OptLevel,
+    };
+    use cairo_lang_starknet_classes::contract_class::version_id_from_serialized_sierra_program;
+
+    #[test]
+    fn record_and_replay() {
+        let (_, contract) = load_starknet_contract! {
+            #[starknet::interface]
+            trait IReplay<TContractState> {
+                fn run(ref self: TContractState, x: felt252) -> felt252;
+            }
+
+            #[starknet::contract]
+            mod contract {
+                use starknet::{
+                    SyscallResultTrait, emit_event_syscall, get_caller_address,
+                    storage_address_try_from_felt252, storage_read_syscall, storage_write_syscall,
+                };
+
+                #[storage]
+                struct Storage {}
+
+                #[abi(embed_v0)]
+                impl ReplayImpl of super::IReplay<ContractState> {
+                    fn run(ref self: ContractState, x: felt252) -> felt252 {
+                        let key = storage_address_try_from_felt252(1).unwrap();
+                        storage_write_syscall(0, key, x).unwrap_syscall();
+                        emit_event_syscall(array![x].span(), array![].span()).unwrap_syscall();
+
+                        let caller: felt252 = get_caller_address().into();
+                        storage_read_syscall(0, key).unwrap_syscall() + caller
+                    }
+                }
+            }
+        };
+
+        let (sierra_version, _) =
+            version_id_from_serialized_sierra_program(&contract.sierra_program).unwrap();
+        let executor = AotContractExecutor::new(
+            &contract.extract_sierra_program().unwrap(),
+            &contract.entry_points_by_type,
+            sierra_version,
+            OptLevel::Default,
+        )
+        .unwrap();
+        let selector = Felt::from(&contract.entry_points_by_type.external[0].selector);
+
+        let mut stub = StubSyscallHandler::default();
+        let mut recorder = RecordingSyscallHandler::new(&mut stub);
+        let result = executor
+            .run(
+                selector,
+                EntryPointKind::External,
+                &[5.into()],
+                u64::MAX,
+                None,
+                &mut recorder,
+            )
+            .unwrap();
+        assert_eq!(result.return_values, vec![7.into()]);
+
+        let transcript = recorder.into_transcript();
+        assert_eq!(transcript.records.len(), 4);
+        assert_eq!(
+            transcript.records[0].request,
+            SyscallRequest::StorageWrite {
+                address_domain: 0,
+                address: 1.into(),
+                value: 5.into(),
+            }
+        );
+        assert_eq!(
+            transcript.records[3].result,
+            Ok(SyscallResponse::StorageRead(5.into()))
+        );
+
+        // The transcript survives a serialization roundtrip.
+        let transcript: SyscallTranscript =
+            serde_json::from_str(&serde_json::to_string(&transcript).unwrap()).unwrap();
+
+        let mut replay = ReplaySyscallHandler::new(transcript.clone());
+        let replayed = executor
+            .run(
+                selector,
+                EntryPointKind::External,
+                &[5.into()],
+                u64::MAX,
+                None,
+                &mut replay,
+            )
+            .unwrap();
+        replay.finish().unwrap();
+        assert_eq!(replayed, result);
+
+        // Different arguments lead to a different storage write.
+        let mut replay = ReplaySyscallHandler::new(transcript.clone());
+        let replayed = executor
+            .run(
+                selector,
+                EntryPointKind::External,
+                &[6.into()],
+                u64::MAX,
+                None,
+                &mut replay,
+            )
+            .unwrap();
+        assert!(replayed.failure_flag);
+        assert!(matches!(replay.finish(), Err(Error::ReplayDivergence(_))));
+
+        // So does a different amount of gas.
+        let mut replay = ReplaySyscallHandler::new(transcript);
+        executor
+            .run(
+                selector,
+                EntryPointKind::External,
+                &[5.into()],
+                u64::MAX - 1,
+                None,
+                &mut replay,
+            )
+            .unwrap();
+        assert!(matches!(replay.finish(), Err(Error::ReplayDivergence(_))));
+    }
+
+    #[test]
+    fn replay_caught_divergence() {
+        let (_, contract) = load_starknet_contract! {
+            #[starknet::interface]
+            trait IReplay<TContractState> {
+                fn run(ref self: TContractState, x: felt252) -> felt252;
+            }
+
+            #[starknet::contract]
+            mod contract {
+                use starknet::{storage_address_try_from_felt252, storage_write_syscall};
+
+                #[storage]
+                struct Storage {}
+
+                #[abi(embed_v0)]
+                impl ReplayImpl of super::IReplay<ContractState> {
+                    fn run(ref self: ContractState, x: felt252) -> felt252 {
+                        let key = storage_address_try_from_felt252(1).unwrap();
+                        match storage_write_syscall(0, key, x) {
+                            Ok(()) => 1,
+                            Err(_) => 0,
+                        }
+                    }
+                }
+            }
+        };
+
+        let (sierra_version, _) =
+            version_id_from_serialized_sierra_program(&contract.sierra_program).unwrap();
+        let executor = AotContractExecutor::new(
+            &contract.extract_sierra_program().unwrap(),
+            &contract.entry_points_by_type,
+            sierra_version,
+            OptLevel::Default,
+        )
+        .unwrap();
+        let selector = Felt::from(&contract.entry_points_by_type.external[0].selector);
+
+        let mut stub = StubSyscallHandler::default();
+        let mut recorder = RecordingSyscallHandler::new(&mut stub);
+        executor
+            .run(
+                selector,
+                EntryPointKind::External,
+                &[5.into()],
+                u64::MAX,
+                None,
+                &mut recorder,
+            )
+            .unwrap();
+        let transcript = recorder.into_transcript();
+
+        let mut replay = ReplaySyscallHandler::new(transcript);
+        assert_eq!(replay.divergence(), None);
+
+        // The contract handles the divergence error, so the execution succeeds.
+        let replayed = executor
+            .run(
+                selector,
+                EntryPointKind::External,
+                &[6.into()],
+                u64::MAX,
+                None,
+                &mut replay,
+            )
+            .unwrap();
+        assert!(!replayed.failure_flag);
+        assert_eq!(replayed.return_values, vec![0.into()]);
+
+        assert!(replay.divergence().is_some());
+        assert!(matches!(replay.finish(), Err(Error::ReplayDivergence(_))));
+    }
+}
@@ -17,6 +17,19 @@ use num_traits::Zero;
 use starknet_types_core::felt::Felt;
 use tracing::instrument;
 
+pub use self::{
+    account::{
+        AccountTransaction, AccountTransactionKind, PhaseExecution, TransactionPhase,
+        TransactionReceipt, TransactionStatus, L1_DATA_GAS, L1_GAS, L2_GAS, VALIDATED,
+    },
+    env::{calculate_contract_address, StarknetEnv},
+    state::{JournaledState, OrderedEvent, OrderedL2ToL1Message, StateDiff},
+};
+
+mod account;
+mod env;
+mod state;
+
 /// A (somewhat) usable implementation of the starknet syscall handler trait.
 ///
 /// Currently gas is not deducted.
@@ -26,6 +39,15 @@ pub struct StubSyscallHandler {
     pub events: Vec<StubEvent>,
     pub execution_info: ExecutionInfoV2,
     pub logs: HashMap<Felt, ContractLogs>,
+    pub l2_to_l1_messages: Vec<L2ToL1Message>,
+    /// The call depth recorded along with emitted events and messages. The stub doesn't execute
+    /// nested calls, so it's only changed manually.
+    pub call_depth: usize,
+    /// Return data of `call_contract` syscalls mocked by the `mock_call` cheatcode, indexed by
+    /// contract address and entry point selector.
+    pub mocked_calls: HashMap<(Felt, Felt), Vec<Felt>>,
+    /// Events registered by the `expect_event` cheatcode which haven't been emitted yet.
+    pub expected_events: Vec<StubEvent>,
 }
 
 impl Default for StubSyscallHandler {
@@ -59,24 +81,131 @@ impl Default for StubSyscallHandler {
                 entry_point_selector: 4.into(),
             },
             logs: HashMap::new(),
+            l2_to_l1_messages: Vec::new(),
+            call_depth: 0,
+            mocked_calls: HashMap::new(),
+            expected_events: Vec::new(),
         }
     }
 }
 
 /// Event emitted by the emit_event syscall.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StubEvent {
     pub keys: Vec<Felt>,
     pub data: Vec<Felt>,
 }
 
+/// Message sent by the send_message_to_l1 syscall, as its destination address and payload.
+pub type L2ToL1Message = (Felt, Vec<Felt>);
+
+/// An event or message along with where it was emitted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry<T> {
+    /// The position of the entry among all the entries of its kind, from every contract.
+    pub order: usize,
+    /// The call depth of the emitting frame, which is zero for the entry point.
+    pub depth: usize,
+    pub content: T,
+}
+
+/// The events and messages emitted by a contract, in order.
 #[derive(Debug, Default, Clone)]
 pub struct ContractLogs {
-    pub events: VecDeque<StubEvent>,
-    pub l2_to_l1_messages: VecDeque<L2ToL1Message>,
+    pub events: VecDeque<LogEntry<StubEvent>>,
+    pub l2_to_l1_messages: VecDeque<LogEntry<L2ToL1Message>>,
+}
+
+impl ContractLogs {
+    /// Remove and return the oldest event.
+    pub fn pop_log(&mut self) -> Option<LogEntry<StubEvent>> {
+        self.events.pop_front()
+    }
+
+    /// Remove and return the oldest message.
+    pub fn pop_l2_to_l1_message(&mut self) -> Option<LogEntry<L2ToL1Message>> {
+        self.l2_to_l1_messages.pop_front()
+    }
 }
 
-type L2ToL1Message = (Felt, Vec<Felt>);
+impl StubSyscallHandler {
+    /// Remove and return the oldest event emitted by a contract.
+    pub fn pop_log(&mut self, contract_address: Felt) -> Option<LogEntry<StubEvent>> {
+        self.logs.get_mut(&contract_address)?.pop_log()
+    }
+
+    /// Remove and return the oldest message sent by a contract.
+    pub fn pop_l2_to_l1_message(
+        &mut self,
+        contract_address: Felt,
+    ) -> Option<LogEntry<L2ToL1Message>> {
+        self.logs.get_mut(&contract_address)?.pop_l2_to_l1_message()
+    }
+
+    /// Check that every event registered by the `expect_event` cheatcode has been emitted.
+    ///
+    /// The events which haven't been emitted are left in [`Self::expected_events`].
+    pub fn check_expected_events(&self) -> crate::error::Result<()> {
+        if !self.expected_events.is_empty() {
+            return Err(crate::error::Error::ExpectedEventsNotEmitted(
+                self.expected_events.len(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl StubEvent {
+    /// Deserialize an event with the same layout as the output of the `pop_log` cheatcode: the
+    /// keys and then the data, each one prefixed by its length.
+    fn from_cheatcode_input(input: &[Felt]) -> Option<Self> {
+        fn split_span(input: &[Felt]) -> Option<(&[Felt], &[Felt])> {
+            let (len, input) = input.split_first()?;
+            input.split_at_checked(len.to_biguint().try_into().ok()?)
+        }
+
+        let (keys, input) = split_span(input)?;
+        let (data, input) = split_span(input)?;
+        if !input.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            keys: keys.to_vec(),
+            data: data.to_vec(),
+        })
+    }
+}
+
+/// Return the only value of a cheatcode's input, or `None` if it doesn't have exactly one.
+#[cfg(feature = "with-cheatcode")]
+fn cheatcode_value(selector: &str, input: &[Felt]) -> Option<Felt> {
+    match input {
+        [value] => Some(*value),
+        _ => {
+            tracing::warn!("malformed {selector} input: {input:?}");
+            None
+        }
+    }
+}
+
+/// Return the only value of a cheatcode's input as an integer, or `None` if it doesn't have exactly
+/// one or it doesn't fit.
+#[cfg(feature = "with-cheatcode")]
+fn cheatcode_int<T>(selector: &str, input: &[Felt]) -> Option<T>
+where
+    T: TryFrom<BigUint>,
+{
+    let value = cheatcode_value(selector, input)?;
+    match value.to_biguint().try_into() {
+        Ok(value) => Some(value),
+        Err(_) => {
+            tracing::warn!("{selector} value out of range: {value}");
+            None
+        }
+    }
+}
 
 #[derive(PartialEq, Clone, Copy)]
 struct Secp256Point<Curve: SWCurveConfig>(Affine<Curve>);
@@ -352,6 +481,10 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         remaining_gas: &mut u64,
     ) -> crate::starknet::SyscallResult<Vec<Felt>> {
         tracing::debug!("called");
+        if let Some(retdata) = self.mocked_calls.get(&(address, entry_point_selector)) {
+            return Ok(retdata.clone());
+        }
+
         tracing::warn!("unimplemented");
         Ok(vec![])
     }
@@ -391,11 +524,25 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         remaining_gas: &mut u64,
     ) -> crate::starknet::SyscallResult<()> {
         tracing::debug!("called");
-        tracing::warn!("unimplemented but stored");
-        self.events.push(StubEvent {
+        let event = StubEvent {
             keys: keys.to_vec(),
             data: data.to_vec(),
-        });
+        };
+
+        if let Some(index) = self.expected_events.iter().position(|x| x == &event) {
+            self.expected_events.remove(index);
+        }
+
+        self.logs
+            .entry(self.execution_info.contract_address)
+            .or_default()
+            .events
+            .push_back(LogEntry {
+                order: self.events.len(),
+                depth: self.call_depth,
+                content: event.clone(),
+            });
+        self.events.push(event);
         Ok(())
     }
 
@@ -407,7 +554,18 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
         remaining_gas: &mut u64,
     ) -> crate::starknet::SyscallResult<()> {
         tracing::debug!("called");
-        tracing::warn!("unimplemented");
+        let message = (to_address, payload.to_vec());
+
+        self.logs
+            .entry(self.execution_info.contract_address)
+            .or_default()
+            .l2_to_l1_messages
+            .push_back(LogEntry {
+                order: self.l2_to_l1_messages.len(),
+                depth: self.call_depth,
+                content: message.clone(),
+            });
+        self.l2_to_l1_messages.push(message);
         Ok(())
     }
 
@@ -566,80 +724,121 @@ impl StarknetSyscallHandler for &mut StubSyscallHandler {
 
         match selector {
             "set_sequencer_address" => {
-                self.execution_info.block_info.sequencer_address = input[0];
+                if let Some(value) = cheatcode_value(selector, input) {
+                    self.execution_info.block_info.sequencer_address = value;
+                }
                 vec![]
             }
             "set_caller_address" => {
-                self.execution_info.caller_address = input[0];
+                if let Some(value) = cheatcode_value(selector, input) {
+                    self.execution_info.caller_address = value;
+                }
                 vec![]
             }
             "set_contract_address" => {
-                self.execution_info.contract_address = input[0];
+                if let Some(value) = cheatcode_value(selector, input) {
+                    self.execution_info.contract_address = value;
+                }
                 vec![]
             }
             "set_account_contract_address" => {
-                self.execution_info.tx_info.account_contract_address = input[0];
+                if let Some(value) = cheatcode_value(selector, input) {
+                    self.execution_info.tx_info.account_contract_address = value;
+                }
                 vec![]
             }
             "set_transaction_hash" => {
-                self.execution_info.tx_info.transaction_hash = input[0];
+                if let Some(value) = cheatcode_value(selector, input) {
+                    self.execution_info.tx_info.transaction_hash = value;
+                }
                 vec![]
             }
             "set_nonce" => {
-                self.execution_info.tx_info.nonce = input[0];
+                if let Some(value) = cheatcode_value(selector, input) {
+                    self.execution_info.tx_info.nonce = value;
+                }
                 vec![]
             }
             "set_version" => {
-                self.execution_info.tx_info.version = input[0];
+                if let Some(value) = cheatcode_value(selector, input) {
+                    self.execution_info.tx_info.version = value;
+                }
                 vec![]
             }
             "set_chain_id" => {
-                self.execution_info.tx_info.chain_id = input[0];
+                if let Some(value) = cheatcode_value(selector, input) {
+                    self.execution_info.tx_info.chain_id = value;
+                }
                 vec![]
             }
             "set_max_fee" => {
-                let max_fee = input[0].to_biguint().try_into().unwrap();
-                self.execution_info.tx_info.max_fee = max_fee;
+                if let Some(max_fee) = cheatcode_int(selector, input) {
+                    self.execution_info.tx_info.max_fee = max_fee;
+                }
                 vec![]
             }
             "set_block_number" => {
-                let block_number = input[0].to_biguint().try_into().unwrap();
-                self.execution_info.block_info.block_number = block_number;
+                if let Some(block_number) = cheatcode_int(selector, input) {
+                    self.execution_info.block_info.block_number = block_number;
+                }
                 vec![]
             }
             "set_block_timestamp" => {
-                let block_timestamp = input[0].to_biguint().try_into().unwrap();
-                self.execution_info.block_info.block_timestamp = block_timestamp;
+                if let Some(block_timestamp) = cheatcode_int(selector, input) {
+                    self.execution_info.block_info.block_timestamp = block_timestamp;
+                }
                 vec![]
             }
             "set_signature" => {
                 self.execution_info.tx_info.signature = input.to_vec();
                 vec![]
             }
-            "pop_log" => self
-                .logs
-                .get_mut(&input[0])
-                .and_then(|logs| logs.events.pop_front())
-                .map(|mut log| {
-                    let mut serialized_log = Vec::new();
-                    serialized_log.push(log.keys.len().into());
-                    serialized_log.append(&mut log.keys);
-                    serialized_log.push(log.data.len().into());
-                    serialized_log.append(&mut log.data);
-                    serialized_log
-                })
+            "mock_call" => {
+                match input {
+                    [address, selector, retdata @ ..] => {
+                        self.mocked_calls
+                            .insert((*address, *selector), retdata.to_vec());
+                    }
+                    _ => tracing::warn!("malformed mock_call input: {input:?}"),
+                }
+                vec![]
+            }
+            "expect_event" => {
+                match StubEvent::from_cheatcode_input(input) {
+                    Some(event) => self.expected_events.push(event),
+                    None => tracing::warn!("malformed expect_event input: {input:?}"),
+                }
+                vec![]
+            }
+            "pending_expected_events" => vec![self.expected_events.len().into()],
+            "pop_log" => cheatcode_value(selector, input)
+                .and_then(|contract_address| self.pop_log(contract_address))
+                .map(
+                    |LogEntry {
+                         content: mut log, ..
+                     }| {
+                        let mut serialized_log = Vec::new();
+                        serialized_log.push(log.keys.len().into());
+                        serialized_log.append(&mut log.keys);
+                        serialized_log.push(log.data.len().into());
+                        serialized_log.append(&mut log.data);
+                        serialized_log
+                    },
+                )
                 .unwrap_or_default(),
-            "pop_l2_to_l1_message" => self
-                .logs
-                .get_mut(&input[0])
-                .and_then(|logs| logs.l2_to_l1_messages.pop_front())
-                .map(|mut log| {
-                    let mut serialized_log = Vec::new();
-                    serialized_log.push(log.0);
-                    serialized_log.push(log.1.len().into());
-                    serialized_log.append(&mut log.1);
-                    serialized_log
-                })
+            "pop_l2_to_l1_message" => cheatcode_value(selector, input)
+                .and_then(|contract_address| self.pop_l2_to_l1_message(contract_address))
+                .map(
+                    |LogEntry {
+                         content: mut log, ..
+                     }| {
+                        let mut serialized_log = Vec::new();
+                        serialized_log.push(log.0);
+                        serialized_log.push(log.1.len().into());
+                        serialized_log.append(&mut log.1);
+                        serialized_log
+                    },
+                )
                 .unwrap_or_default(),
             _ => vec![],
         }
@@ -1094,4 +1293,193 @@ mod tests {
             )
         )
     }
+
+    #[test]
+    fn test_logs() {
+        let mut test_syscall_handler = StubSyscallHandler::default();
+        let contract_address = test_syscall_handler.execution_info.contract_address;
+
+        (&mut test_syscall_handler)
+            .emit_event(&[1.into()], &[2.into()], &mut 0)
+            .unwrap();
+        test_syscall_handler.call_depth = 1;
+        (&mut test_syscall_handler)
+            .emit_event(&[3.into()], &[], &mut 0)
+            .unwrap();
+        (&mut test_syscall_handler)
+            .send_message_to_l1(4.into(), &[5.into()], &mut 0)
+            .unwrap();
+
+        assert_eq!(
+            test_syscall_handler.pop_log(contract_address),
+            Some(LogEntry {
+                order: 0,
+                depth: 0,
+                content: StubEvent {
+                    keys: vec![1.into()],
+                    data: vec![2.into()],
+                },
+            })
+        );
+        assert_eq!(
+            test_syscall_handler.pop_log(contract_address),
+            Some(LogEntry {
+                order: 1,
+                depth: 1,
+                content: StubEvent {
+                    keys: vec![3.into()],
+                    data: vec![],
+                },
+            })
+        );
+        assert_eq!(test_syscall_handler.pop_log(contract_address), None);
+
+        assert_eq!(
+            test_syscall_handler.pop_l2_to_l1_message(contract_address),
+            Some(LogEntry {
+                order: 0,
+                depth: 1,
+                content: (4.into(), vec![5.into()]),
+            })
+        );
+        assert_eq!(
+            test_syscall_handler.pop_l2_to_l1_message(contract_address),
+            None
+        );
+        assert_eq!(test_syscall_handler.pop_log(Felt::ZERO), None);
+    }
+
+    #[cfg(feature = "with-cheatcode")]
+    #[test]
+    fn test_cheatcode_mock_call_and_expect_event() {
+        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = &mut test_syscall_handler;
+
+        let selector = |name: &str| Felt::from_bytes_be_slice(name.as_bytes());
+
+        test_syscall_handler.cheatcode(selector("mock_call"), &[10.into(), 20.into(), 30.into()]);
+        assert_eq!(
+            test_syscall_handler
+                .call_contract(10.into(), 20.into(), &[], &mut 0)
+                .unwrap(),
+            vec![30.into()]
+        );
+        assert_eq!(
+            test_syscall_handler
+                .call_contract(10.into(), 21.into(), &[], &mut 0)
+                .unwrap(),
+            vec![]
+        );
+
+        test_syscall_handler.cheatcode(
+            selector("expect_event"),
+            &[1.into(), 2.into(), 1.into(), 3.into()],
+        );
+        assert_eq!(
+            test_syscall_handler.cheatcode(selector("pending_expected_events"), &[]),
+            vec![1.into()]
+        );
+        assert!(matches!(
+            test_syscall_handler.check_expected_events(),
+            Err(crate::error::Error::ExpectedEventsNotEmitted(1))
+        ));
+
+        // Malformed inputs are ignored.
+        for input in [
+            &[][..],
+            &[2.into(), 2.into()],
+            &[1.into(), 2.into()],
+            &[1.into(), 2.into(), 2.into(), 3.into()],
+            &[1.into(), 2.into(), 1.into(), 3.into(), 4.into()],
+            &[Felt::MAX],
+        ] {
+            assert_eq!(
+                test_syscall_handler.cheatcode(selector("expect_event"), input),
+                vec![]
+            );
+        }
+        test_syscall_handler.cheatcode(selector("mock_call"), &[10.into()]);
+        assert_eq!(
+            test_syscall_handler.cheatcode(selector("pending_expected_events"), &[]),
+            vec![1.into()]
+        );
+
+        test_syscall_handler
+            .emit_event(&[2.into()], &[4.into()], &mut 0)
+            .unwrap();
+        assert_eq!(
+            test_syscall_handler.cheatcode(selector("pending_expected_events"), &[]),
+            vec![1.into()]
+        );
+
+        test_syscall_handler
+            .emit_event(&[2.into()], &[3.into()], &mut 0)
+            .unwrap();
+        assert_eq!(
+            test_syscall_handler.cheatcode(selector("pending_expected_events"), &[]),
+            vec![0.into()]
+        );
+        test_syscall_handler.check_expected_events().unwrap();
+    }
+
+    #[cfg(feature = "with-cheatcode")]
+    #[test]
+    fn test_cheatcode_malformed_input() {
+        let mut test_syscall_handler = StubSyscallHandler::default();
+        let mut test_syscall_handler = &mut test_syscall_handler;
+
+        let selector = |name: &str| Felt::from_bytes_be_slice(name.as_bytes());
+
+        test_syscall_handler
+            .emit_event(&[1.into()], &[2.into()], &mut 0)
+            .unwrap();
+        test_syscall_handler
+            .send_message_to_l1(3.into(), &[4.into()], &mut 0)
+            .unwrap();
+
+        let contract_address = test_syscall_handler.execution_info.contract_address;
+        let execution_info = test_syscall_handler.execution_info.clone();
+        for name in [
+            "set_sequencer_address",
+            "set_caller_address",
+            "set_contract_address",
+            "set_account_contract_address",
+            "set_transaction_hash",
+            "set_nonce",
+            "set_version",
+            "set_chain_id",
+            "set_max_fee",
+            "set_block_number",
+            "set_block_timestamp",
+            "pop_log",
+            "pop_l2_to_l1_message",
+        ] {
+            for input in [&[][..], &[contract_address, contract_address]] {
+                assert_eq!(
+                    test_syscall_handler.cheatcode(selector(name), input),
+                    vec![],
+                    "{name}({input:?})"
+                );
+            }
+        }
+        for name in ["set_max_fee", "set_block_number", "set_block_timestamp"] {
+            assert_eq!(
+                test_syscall_handler.cheatcode(selector(name), &[Felt::MAX]),
+                vec![]
+            );
+        }
+        assert_eq!(test_syscall_handler.execution_info, execution_info);
+
+        // Nothing was popped.
+        assert!(test_syscall_handler.pop_log(contract_address).is_some());
+        assert!(test_syscall_handler
+            .pop_l2_to_l1_message(contract_address)
+            .is_some());
+
+        test_syscall_handler.cheatcode(selector("set_block_number"), &[7.into()]);
+        assert_eq!(
+            test_syscall_handler.execution_info.block_info.block_number,
+            7
+        );
+    }
 }
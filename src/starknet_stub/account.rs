@@ -0,0 +1,559 @@
+//! # Account transactions
+//!
+//! Runs account transactions on a [`StarknetEnv`] following the same flows as the sequencer:
+//!
+//! - Invoke: `__validate__` and then `__execute__` are called on the sender with the transaction's
+//!   calldata.
+//! - Deploy account: the account's constructor is run, followed by `__validate_deploy__` with the
+//!   class hash, the salt and the constructor calldata.
+//! - Declare: `__validate_declare__` is called on the sender with the class hash, and the class is
+//!   declared once validated.
+//!
+//! The sender's nonce is checked and incremented before validating, and the transaction's fields
+//! (hash, signature, nonce and the fee related fields) are available to the contracts through
+//! `get_execution_info_v2` while it runs.
+//!
+//! When a validation phase fails, the transaction is rejected and the state is left untouched.
+//! When the execution fails, the transaction is reverted instead: only the execution's changes are
+//! undone, keeping the nonce increment. Changes made to the environment before the transaction are
+//! finalized when it starts, so neither case undoes them.
+
+use super::{calculate_contract_address, StarknetEnv, StateDiff};
+use crate::{
+    error::{Error, Result},
+    execution_result::ContractExecutionResult,
+    executor::AotContractExecutor,
+    starknet::{selector_from_name, ResourceBounds, TxV2Info},
+};
+use starknet_types_core::felt::Felt;
+use std::{mem, sync::Arc};
+
+/// The value the validation entry points must return, `'VALID'`.
+pub const VALIDATED: Felt = Felt::from_hex_unchecked("0x56414c4944");
+
+/// The name of the L1 gas resource, `'L1_GAS'`.
+pub const L1_GAS: Felt = Felt::from_hex_unchecked("0x4c315f474153");
+/// The name of the L2 gas resource, `'L2_GAS'`.
+pub const L2_GAS: Felt = Felt::from_hex_unchecked("0x4c325f474153");
+/// The name of the L1 data gas resource, `'L1_DATA'`.
+pub const L1_DATA_GAS: Felt = Felt::from_hex_unchecked("0x4c315f44415441");
+
+/// An account transaction, along with the fields exposed through the transaction info.
+#[derive(Debug, Clone)]
+pub struct AccountTransaction {
+    pub kind: AccountTransactionKind,
+    pub version: Felt,
+    pub transaction_hash: Felt,
+    pub signature: Vec<Felt>,
+    pub nonce: Felt,
+    pub max_fee: u128,
+    pub resource_bounds: Vec<ResourceBounds>,
+    pub tip: u128,
+    pub paymaster_data: Vec<Felt>,
+    pub nonce_data_availability_mode: u32,
+    pub fee_data_availability_mode: u32,
+    pub account_deployment_data: Vec<Felt>,
+    /// The gas available to the transaction, shared by all of its phases.
+    pub gas: u64,
+}
+
+#[derive(Debug, Clone)]
+pub enum AccountTransactionKind {
+    Invoke {
+        sender_address: Felt,
+        calldata: Vec<Felt>,
+    },
+    DeployAccount {
+        class_hash: Felt,
+        contract_address_salt: Felt,
+        constructor_calldata: Vec<Felt>,
+    },
+    Declare {
+        sender_address: Felt,
+        class_hash: Felt,
+        executor: Arc<AotContractExecutor>,
+    },
+}
+
+/// The phases of an account transaction, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransactionPhase {
+    /// The account's constructor, only run by deploy account transactions.
+    Constructor,
+    Validate,
+    /// Only run by invoke transactions.
+    Execute,
+}
+
+/// The outcome of a single transaction phase.
+#[derive(Debug, Clone)]
+pub struct PhaseExecution {
+    pub phase: TransactionPhase,
+    pub gas_consumed: u64,
+    pub result: ContractExecutionResult,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransactionStatus {
+    Succeeded,
+    /// A validation phase (or the account's constructor) failed, nothing has been applied.
+    Rejected,
+    /// The execution failed, only the validation's changes and the nonce increment were applied.
+    Reverted,
+}
+
+#[derive(Debug, Clone)]
+pub struct TransactionReceipt {
+    pub sender_address: Felt,
+    pub status: TransactionStatus,
+    /// The phases which have been run, in order. Phases after a failed one are not run.
+    pub phases: Vec<PhaseExecution>,
+    /// The changes applied by the transaction. Empty if it was rejected.
+    pub state_diff: StateDiff,
+}
+
+impl AccountTransaction {
+    /// Create a version 3 transaction without a signature nor fee bounds, with all the gas
+    /// available.
+    pub fn new(kind: AccountTransactionKind, nonce: Felt) -> Self {
+        Self {
+            kind,
+            version: 3.into(),
+            transaction_hash: Felt::ZERO,
+            signature: Vec::new(),
+            nonce,
+            max_fee: 0,
+            resource_bounds: Vec::new(),
+            tip: 0,
+            paymaster_data: Vec::new(),
+            nonce_data_availability_mode: 0,
+            fee_data_availability_mode: 0,
+            account_deployment_data: Vec::new(),
+            gas: u64::MAX,
+        }
+    }
+
+    /// Return the address of the account sending the transaction.
+    pub fn sender_address(&self) -> Felt {
+        match &self.kind {
+            AccountTransactionKind::Invoke { sender_address, .. }
+            | AccountTransactionKind::Declare { sender_address, .. } => *sender_address,
+            AccountTransactionKind::DeployAccount {
+                class_hash,
+                contract_address_salt,
+                constructor_calldata,
+            } => calculate_contract_address(
+                *contract_address_salt,
+                *class_hash,
+                constructor_calldata,
+                Felt::ZERO,
+            ),
+        }
+    }
+
+    fn tx_info(&self, chain_id: Felt) -> TxV2Info {
+        TxV2Info {
+            version: self.version,
+            account_contract_address: self.sender_address(),
+            max_fee: self.max_fee,
+            signature: self.signature.clone(),
+            transaction_hash: self.transaction_hash,
+            chain_id,
+            nonce: self.nonce,
+            resource_bounds: self.resource_bounds.clone(),
+            tip: self.tip,
+            paymaster_data: self.paymaster_data.clone(),
+            nonce_data_availability_mode: self.nonce_data_availability_mode,
+            fee_data_availability_mode: self.fee_data_availability_mode,
+            account_deployment_data: self.account_deployment_data.clone(),
+        }
+    }
+}
+
+impl TransactionReceipt {
+    /// Return the outcome of a phase, if it has been run.
+    pub fn phase(&self, phase: TransactionPhase) -> Option<&PhaseExecution> {
+        self.phases.iter().find(|x| x.phase == phase)
+    }
+
+    /// Return the gas consumed by all the phases.
+    pub fn gas_consumed(&self) -> u64 {
+        self.phases.iter().map(|x| x.gas_consumed).sum()
+    }
+}
+
+impl StarknetEnv {
+    /// Run an account transaction, finalizing it.
+    ///
+    /// Transactions with a nonce other than the sender's current one are not run. Please look at
+    /// the [module level docs](self) for the flow of every kind of transaction.
+    pub fn execute_transaction(&mut self, tx: &AccountTransaction) -> Result<TransactionReceipt> {
+        let sender_address = tx.sender_address();
+        let nonce = self.state.nonce_at(sender_address);
+        if tx.nonce != nonce {
+            return Err(Error::InvalidNonce {
+                contract_address: sender_address,
+                expected: nonce,
+                actual: tx.nonce,
+            });
+        }
+
+        self.begin_transaction();
+        let tx_info = tx.tx_info(self.tx_info.chain_id);
+        let tx_info = mem::replace(&mut self.tx_info, tx_info);
+        let mut phases = Vec::new();
+        let status = self.run_transaction(tx, sender_address, &mut phases);
+        self.tx_info = tx_info;
+        self.end_transaction();
+
+        let state_diff = match status {
+            Ok(TransactionStatus::Rejected) => {
+                self.state.revert_transaction();
+                StateDiff::default()
+            }
+            Ok(_) => self.state.commit_transaction(),
+            Err(e) => {
+                self.state.revert_transaction();
+                return Err(e);
+            }
+        };
+
+        Ok(TransactionReceipt {
+            sender_address,
+            status: status?,
+            phases,
+            state_diff,
+        })
+    }
+
+    fn run_transaction(
+        &mut self,
+        tx: &AccountTransaction,
+        sender_address: Felt,
+        phases: &mut Vec<PhaseExecution>,
+    ) -> Result<TransactionStatus> {
+        let mut gas = tx.gas;
+        self.state.increment_nonce(sender_address);
+
+        let validate_result = match &tx.kind {
+            AccountTransactionKind::Invoke { calldata, .. } => self.call(
+                Felt::ZERO,
+                sender_address,
+                selector_from_name("__validate__"),
+                calldata,
+                gas,
+            )?,
+            AccountTransactionKind::DeployAccount {
+                class_hash,
+                contract_address_salt,
+                constructor_calldata,
+            } => {
+                let (_, result) = self.deploy_from(
+                    Felt::ZERO,
+                    *class_hash,
+                    *contract_address_salt,
+                    constructor_calldata,
+                    gas,
+                )?;
+                if !record_phase(phases, &mut gas, TransactionPhase::Constructor, result) {
+                    return Ok(TransactionStatus::Rejected);
+                }
+
+                let calldata = [*class_hash, *contract_address_salt]
+                    .into_iter()
+                    .chain(constructor_calldata.iter().copied())
+                    .collect::<Vec<_>>();
+                self.call(
+                    Felt::ZERO,
+                    sender_address,
+                    selector_from_name("__validate_deploy__"),
+                    &calldata,
+                    gas,
+                )?
+            }
+            AccountTransactionKind::Declare { class_hash, .. } => self.call(
+                Felt::ZERO,
+                sender_address,
+                selector_from_name("__validate_declare__"),
+                &[*class_hash],
+                gas,
+            )?,
+        };
+
+        // Validation must also return `'VALID'` to succeed.
+        let is_valid = validate_result.return_values == [VALIDATED];
+        if !record_phase(
+            phases,
+            &mut gas,
+            TransactionPhase::Validate,
+            validate_result,
+        ) {
+            return Ok(TransactionStatus::Rejected);
+        }
+        if !is_valid {
+            return Ok(TransactionStatus::Rejected);
+        }
+
+        match &tx.kind {
+            AccountTransactionKind::Invoke { calldata, .. } => {
+                let result = self.call(
+                    Felt::ZERO,
+                    sender_address,
+                    selector_from_name("__execute__"),
+                    calldata,
+                    gas,
+                )?;
+
+                Ok(
+                    match record_phase(phases, &mut gas, TransactionPhase::Execute, result) {
+                        true => TransactionStatus::Succeeded,
+                        false => TransactionStatus::Reverted,
+                    },
+                )
+            }
+            AccountTransactionKind::DeployAccount { .. } => Ok(TransactionStatus::Succeeded),
+            AccountTransactionKind::Declare {
+                class_hash,
+                executor,
+                ..
+            } => {
+                self.declare(*class_hash, executor.clone());
+                Ok(TransactionStatus::Succeeded)
+            }
+        }
+    }
+}
+
+/// Record the outcome of a phase, deducting the gas it consumed. Returns whether it succeeded.
+fn record_phase(
+    phases: &mut Vec<PhaseExecution>,
+    gas: &mut u64,
+    phase: TransactionPhase,
+    result: ContractExecutionResult,
+) -> bool {
+    let succeeded = !result.failure_flag;
+    let gas_consumed = gas.saturating_sub(result.remaining_gas);
+    *gas = result.remaining_gas;
+
+    phases.push(PhaseExecution {
+        phase,
+        gas_consumed,
+        result,
+    });
+    succeeded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        executor::SierraContractClass, starknet_stub::OrderedEvent,
+        utils::test::load_starknet_contract, OptLevel,
+    };
+
+    fn env() -> (StarknetEnv, Felt, Arc<AotContractExecutor>) {
+        let (_, account) = load_starknet_contract! {
+            #[starknet::contract]
+            mod account {
+                use starknet::{
+                    SyscallResultTrait, emit_event_syscall, storage_address_try_from_felt252,
+                    storage_read_syscall, storage_write_syscall,
+                };
+                use starknet::syscalls::get_execution_info_v2_syscall;
+
+                #[storage]
+                struct Storage {}
+
+                #[constructor]
+                fn constructor(ref self: ContractState, public_key: felt252) {
+                    let key = storage_address_try_from_felt252(1).unwrap();
+                    storage_write_syscall(0, key, public_key).unwrap_syscall();
+                }
+
+                // A toy signature scheme: the signature is the transaction hash plus the key.
+                fn validate() -> felt252 {
+                    let tx_info = get_execution_info_v2_syscall().unwrap_syscall().unbox().tx_info.unbox();
+                    let key = storage_address_try_from_felt252(1).unwrap();
+                    let public_key = storage_read_syscall(0, key).unwrap_syscall();
+                    assert(tx_info.signature.len() == 1, 1);
+                    assert(
+                        *tx_info.signature.at(0) == tx_info.transaction_hash + public_key,
+                        1,
+                    );
+                    0x56414c4944
+                }
+
+                #[external(v0)]
+                fn __validate__(self: @ContractState, _value: felt252) -> felt252 {
+                    validate()
+                }
+
+                #[external(v0)]
+                fn __validate_deploy__(
+                    self: @ContractState, _class_hash: felt252, _salt: felt252, _public_key: felt252,
+                ) -> felt252 {
+                    validate()
+                }
+
+                #[external(v0)]
+                fn __validate_declare__(self: @ContractState, _class_hash: felt252) -> felt252 {
+                    validate()
+                }
+
+                #[external(v0)]
+                fn __execute__(ref self: ContractState, value: felt252) -> (felt252, u64) {
+                    let key = storage_address_try_from_felt252(2).unwrap();
+                    storage_write_syscall(0, key, value).unwrap_syscall();
+                    emit_event_syscall(array![2].span(), array![value].span()).unwrap_syscall();
+                    assert(value != 0, 2);
+
+                    let tx_info = get_execution_info_v2_syscall().unwrap_syscall().unbox().tx_info.unbox();
+                    (tx_info.nonce, *tx_info.resource_bounds.at(0).max_amount)
+                }
+            }
+        };
+
+        let class = SierraContractClass::from_contract_class(&account).unwrap();
+        let executor =
+            Arc::new(AotContractExecutor::from_contract_class(&class, OptLevel::Default).unwrap());
+
+        let mut env = StarknetEnv::default();
+        env.declare(class.class_hash, executor.clone());
+
+        (env, class.class_hash, executor)
+    }
+
+    fn signed(kind: AccountTransactionKind, nonce: Felt, public_key: Felt) -> AccountTransaction {
+        let mut tx = AccountTransaction::new(kind, nonce);
+        tx.transaction_hash = 0x1000.into();
+        tx.signature = vec![tx.transaction_hash + public_key];
+        tx.resource_bounds = vec![ResourceBounds {
+            resource: L2_GAS,
+            max_amount: 100,
+            max_price_per_unit: 5,
+        }];
+        tx
+    }
+
+    #[test]
+    fn account_transactions() {
+        let (mut env, class_hash, executor) = env();
+        let public_key = Felt::from(7);
+
+        // The account can't be deployed with an invalid signature.
+        let deploy_account = |public_key| AccountTransactionKind::DeployAccount {
+            class_hash,
+            contract_address_salt: Felt::ZERO,
+            constructor_calldata: vec![public_key],
+        };
+        let receipt = env
+            .execute_transaction(&signed(deploy_account(public_key), Felt::ZERO, Felt::ONE))
+            .unwrap();
+        assert_eq!(receipt.status, TransactionStatus::Rejected);
+        assert!(
+            receipt
+                .phase(TransactionPhase::Validate)
+                .unwrap()
+                .result
+                .failure_flag
+        );
+        assert_eq!(receipt.state_diff, StateDiff::default());
+        assert_eq!(env.class_hash_at(receipt.sender_address), None);
+
+        let receipt = env
+            .execute_transaction(&signed(deploy_account(public_key), Felt::ZERO, public_key))
+            .unwrap();
+        let account = receipt.sender_address;
+        assert_eq!(receipt.status, TransactionStatus::Succeeded);
+        assert_eq!(
+            receipt.phases.iter().map(|x| x.phase).collect::<Vec<_>>(),
+            [TransactionPhase::Constructor, TransactionPhase::Validate]
+        );
+        assert!(receipt.phases.iter().all(|x| x.gas_consumed > 0));
+        assert_eq!(receipt.state_diff.nonces[&account], Felt::ONE);
+        assert_eq!(receipt.state_diff.class_hashes[&account], class_hash);
+        assert_eq!(env.storage_at(account, 1.into()), public_key);
+
+        // Invoke transactions see the transaction info, including the fee related fields.
+        let invoke = |value: u64| AccountTransactionKind::Invoke {
+            sender_address: account,
+            calldata: vec![value.into()],
+        };
+        let receipt = env
+            .execute_transaction(&signed(invoke(3), Felt::ONE, public_key))
+            .unwrap();
+        assert_eq!(receipt.status, TransactionStatus::Succeeded);
+        let execute = receipt.phase(TransactionPhase::Execute).unwrap();
+        assert_eq!(execute.result.return_values, vec![Felt::ONE, 100.into()]);
+        assert_eq!(
+            receipt.gas_consumed(),
+            u64::MAX - execute.result.remaining_gas
+        );
+        assert_eq!(env.storage_at(account, 2.into()), 3.into());
+        assert_eq!(env.state.nonce_at(account), 2.into());
+
+        assert!(matches!(
+            env.execute_transaction(&signed(invoke(3), Felt::ONE, public_key)),
+            Err(Error::InvalidNonce { expected, actual, .. })
+                if expected == 2.into() && actual == Felt::ONE
+        ));
+
+        // Reverted transactions still increment the nonce.
+        let receipt = env
+            .execute_transaction(&signed(invoke(0), 2.into(), public_key))
+            .unwrap();
+        assert_eq!(receipt.status, TransactionStatus::Reverted);
+        assert!(
+            receipt
+                .phase(TransactionPhase::Execute)
+                .unwrap()
+                .result
+                .failure_flag
+        );
+        assert_eq!(
+            receipt.state_diff,
+            StateDiff {
+                nonces: [(account, 3.into())].into(),
+                ..Default::default()
+            }
+        );
+        assert_eq!(env.storage_at(account, 2.into()), 3.into());
+
+        // Declared classes are available once validated.
+        let receipt = env
+            .execute_transaction(&signed(
+                AccountTransactionKind::Declare {
+                    sender_address: account,
+                    class_hash: 0x300.into(),
+                    executor,
+                },
+                3.into(),
+                public_key,
+            ))
+            .unwrap();
+        assert_eq!(receipt.status, TransactionStatus::Succeeded);
+        assert!(receipt.phase(TransactionPhase::Execute).is_none());
+        env.set_contract(0x400.into(), 0x300.into()).unwrap();
+
+        // Rejected transactions don't undo the changes made before them.
+        let receipt = env
+            .execute_transaction(&signed(invoke(5), 4.into(), Felt::ONE))
+            .unwrap();
+        assert_eq!(receipt.status, TransactionStatus::Rejected);
+        assert_eq!(env.class_hash_at(0x400.into()), Some(0x300.into()));
+        assert_eq!(env.class_hash_at(account), Some(class_hash));
+        assert_eq!(env.state.nonce_at(account), 4.into());
+
+        let receipt = env
+            .execute_transaction(&signed(invoke(5), 4.into(), public_key))
+            .unwrap();
+        assert_eq!(
+            receipt.state_diff.events,
+            [OrderedEvent {
+                contract_address: account,
+                keys: vec![2.into()],
+                data: vec![5.into()],
+            }]
+        );
+    }
+}
@@ -0,0 +1,1269 @@
+//! # In-memory Starknet environment
+//!
+//! A local state and class registry which can run several contracts that interact with each other.
+//! Declared classes are kept as [`AotContractExecutor`]s and deployed contracts are tracked by
+//! address, along with their storage.
+//!
+//! The `call_contract`, `library_call`, `meta_tx_v0`, `deploy`, `replace_class` and
+//! `get_class_hash_at` syscalls are routed into the environment, running nested calls with the
+//! correct `caller_address` and `contract_address`. Meta transactions also see the transaction info
+//! of a version 0 invoke sent by the called account. Nested calls receive the caller's remaining
+//! gas, and whatever they consume is deducted from it. The rest of the syscalls behave like in the
+//! [`StubSyscallHandler`].
+//!
+//! The state is kept in a [`JournaledState`]. Every call frame runs within its own checkpoint, so
+//! the storage writes, deployments, events and messages of failed calls are reverted. Top level
+//! calls can also be executed as transactions, which are reverted as a whole when they fail. Top
+//! level calls and deployments made outside of a transaction are finalized once they're done.
+
+use super::{
+    state::{JournaledState, StateDiff},
+    StubEvent, StubSyscallHandler,
+};
+use crate::{
+    error::{Error, Result},
+    execution_result::ContractExecutionResult,
+    executor::{AotContractExecutor, EntryPointKind},
+    starknet::{
+        BlockInfo, ExecutionInfo, ExecutionInfoV2, Secp256k1Point, Secp256r1Point,
+        StarknetSyscallHandler, SyscallResult, TxInfo, TxV2Info, U256,
+    },
+};
+use starknet_types_core::{
+    felt::Felt,
+    hash::{Pedersen, StarkHash},
+};
+use std::{collections::HashMap, mem, sync::Arc};
+use tracing::instrument;
+
+/// The selector of the constructor entry point, `sn_keccak("constructor")`.
+const CONSTRUCTOR_SELECTOR: Felt =
+    Felt::from_hex_unchecked("0x28ffe4ff0f226a9107253e17a904099aa4f63a02a5621de0576e5aa71bc5194");
+
+/// Contract addresses are kept below `2**251 - 256`.
+const L2_ADDRESS_UPPER_BOUND: Felt =
+    Felt::from_hex_unchecked("0x7ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff00");
+
+/// An in-memory Starknet state capable of running multiple contracts.
+///
+/// Please look at the [module level docs](self).
+#[derive(Debug)]
+pub struct StarknetEnv {
+    pub block_info: BlockInfo,
+    pub tx_info: TxV2Info,
+    pub state: JournaledState,
+
+    classes: HashMap<Felt, Arc<AotContractExecutor>>,
+    // The number of call frames currently running.
+    depth: usize,
+    // Whether a transaction is running, which finalizes the changes of its calls by itself.
+    in_transaction: bool,
+
+    // Used to implement the syscalls which don't depend on the state.
+    stub: StubSyscallHandler,
+}
+
+impl Default for StarknetEnv {
+    fn default() -> Self {
+        let stub = StubSyscallHandler::default();
+
+        Self {
+            block_info: stub.execution_info.block_info,
+            tx_info: stub.execution_info.tx_info.clone(),
+            state: JournaledState::default(),
+            classes: HashMap::new(),
+            depth: 0,
+            in_transaction: false,
+            stub,
+        }
+    }
+}
+
+impl StarknetEnv {
+    /// Declare a class, making it available for deployments and library calls.
+    ///
+    /// Declaring an already declared class hash replaces its executor.
+    pub fn declare(&mut self, class_hash: Felt, executor: impl Into<Arc<AotContractExecutor>>) {
+        self.classes.insert(class_hash, executor.into());
+    }
+
+    /// Deploy a declared class from the zero address, running its constructor (if any).
+    ///
+    /// Returns the address of the new contract along with the constructor's result. If the
+    /// constructor fails, the contract will not be deployed.
+    pub fn deploy(
+        &mut self,
+        class_hash: Felt,
+        contract_address_salt: Felt,
+        calldata: &[Felt],
+        gas: u64,
+    ) -> Result<(Felt, ContractExecutionResult)> {
+        let result = self.deploy_from(Felt::ZERO, class_hash, contract_address_salt, calldata, gas);
+        self.finalize_outside_transaction();
+        result
+    }
+
+    /// Place a declared class at the given address without running its constructor.
+    pub fn set_contract(&mut self, contract_address: Felt, class_hash: Felt) -> Result<()> {
+        if !self.classes.contains_key(&class_hash) {
+            return Err(Error::ClassNotDeclared(class_hash));
+        }
+
+        self.state.set_class_hash_at(contract_address, class_hash);
+        Ok(())
+    }
+
+    /// Return the class hash of the contract deployed at the given address.
+    pub fn class_hash_at(&self, contract_address: Felt) -> Option<Felt> {
+        self.state.class_hash_at(contract_address)
+    }
+
+    /// Read a storage slot. Uninitialized slots are zero.
+    pub fn storage_at(&self, contract_address: Felt, key: Felt) -> Felt {
+        self.state.storage_at(contract_address, key)
+    }
+
+    /// Write a storage slot.
+    pub fn set_storage_at(&mut self, contract_address: Felt, key: Felt, value: Felt) {
+        self.state.set_storage_at(contract_address, key, value);
+    }
+
+    /// Call an entry point of a deployed contract.
+    ///
+    /// Unless it's part of a transaction, the call's changes are finalized once it's done.
+    pub fn call(
+        &mut self,
+        caller_address: Felt,
+        contract_address: Felt,
+        entry_point_selector: Felt,
+        calldata: &[Felt],
+        gas: u64,
+    ) -> Result<ContractExecutionResult> {
+        let class_hash = self
+            .class_hash_at(contract_address)
+            .ok_or(Error::ContractNotDeployed(contract_address))?;
+
+        let result = self.run_frame(
+            CallFrame {
+                class_hash,
+                caller_address,
+                contract_address,
+                entry_point_selector,
+            },
+            EntryPointKind::External,
+            calldata,
+            gas,
+        );
+        self.finalize_outside_transaction();
+        result
+    }
+
+    /// Call an entry point of a deployed contract as a transaction.
+    ///
+    /// The changes made before the transaction (deployments, storage writes...) are finalized
+    /// first. If the call succeeds, the transaction is committed and its changes are returned.
+    /// Otherwise only the call's changes are reverted and the state diff is empty.
+    pub fn execute(
+        &mut self,
+        caller_address: Felt,
+        contract_address: Felt,
+        entry_point_selector: Felt,
+        calldata: &[Felt],
+        gas: u64,
+    ) -> Result<(ContractExecutionResult, StateDiff)> {
+        self.begin_transaction();
+        let result = self.call(
+            caller_address,
+            contract_address,
+            entry_point_selector,
+            calldata,
+            gas,
+        );
+        self.end_transaction();
+
+        match result {
+            Ok(result) if !result.failure_flag => Ok((result, self.state.commit_transaction())),
+            result => {
+                self.state.revert_transaction();
+                Ok((result?, StateDiff::default()))
+            }
+        }
+    }
+
+    /// Begin a transaction, finalizing the changes made before it. Until it ends, top level calls
+    /// and deployments are left for the transaction to commit or revert.
+    pub(super) fn begin_transaction(&mut self) {
+        self.state.begin_transaction();
+        self.in_transaction = true;
+    }
+
+    /// Stop running a transaction, whose changes must then be committed or reverted.
+    pub(super) fn end_transaction(&mut self) {
+        self.in_transaction = false;
+    }
+
+    /// Finalize the changes of a top level call or deployment which isn't part of a transaction,
+    /// so that the journal doesn't grow without bounds.
+    fn finalize_outside_transaction(&mut self) {
+        if !self.in_transaction {
+            self.state.begin_transaction();
+        }
+    }
+
+    pub(super) fn deploy_from(
+        &mut self,
+        deployer_address: Felt,
+        class_hash: Felt,
+        contract_address_salt: Felt,
+        calldata: &[Felt],
+        gas: u64,
+    ) -> Result<(Felt, ContractExecutionResult)> {
+        if !self.classes.contains_key(&class_hash) {
+            return Err(Error::ClassNotDeclared(class_hash));
+        }
+
+        let contract_address = calculate_contract_address(
+            contract_address_salt,
+            class_hash,
+            calldata,
+            deployer_address,
+        );
+        if self.class_hash_at(contract_address).is_some() {
+            return Err(Error::ContractAlreadyDeployed(contract_address));
+        }
+
+        self.state.checkpoint();
+        self.state.set_class_hash_at(contract_address, class_hash);
+
+        let result = match self.run_frame(
+            CallFrame {
+                class_hash,
+                caller_address: deployer_address,
+                contract_address,
+                entry_point_selector: CONSTRUCTOR_SELECTOR,
+            },
+            EntryPointKind::Constructor,
+            calldata,
+            gas,
+        ) {
+            // Classes without a constructor can be deployed as long as there's no calldata.
+            Err(Error::SelectorNotFound) if calldata.is_empty() => Ok(ContractExecutionResult {
+                remaining_gas: gas,
+                ..Default::default()
+            }),
+            result => result,
+        };
+
+        match result {
+            Ok(result) if !result.failure_flag => {
+                self.state.commit();
+                Ok((contract_address, result))
+            }
+            result => {
+                self.state.rollback();
+                Ok((contract_address, result?))
+            }
+        }
+    }
+
+    fn run_frame(
+        &mut self,
+        frame: CallFrame,
+        kind: EntryPointKind,
+        calldata: &[Felt],
+        gas: u64,
+    ) -> Result<ContractExecutionResult> {
+        // The executor is cloned out of the registry since the handler borrows the whole state.
+        let executor = self
+            .classes
+            .get(&frame.class_hash)
+            .cloned()
+            .ok_or(Error::ClassNotDeclared(frame.class_hash))?;
+
+        let depth = self.depth;
+        self.depth += 1;
+
+        self.state.checkpoint();
+        let result = executor.run(
+            frame.entry_point_selector,
+            kind,
+            calldata,
+            gas,
+            None,
+            EnvSyscallHandler {
+                env: self,
+                frame,
+                depth,
+            },
+        );
+        self.depth = depth;
+
+        match result {
+            Ok(ref result) if !result.failure_flag => self.state.commit(),
+            _ => self.state.rollback(),
+        }
+        result
+    }
+
+    /// Run a nested call, deducting the gas it consumed from the caller.
+    fn run_nested(
+        &mut self,
+        frame: CallFrame,
+        calldata: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        let result = self
+            .run_frame(frame, EntryPointKind::External, calldata, *remaining_gas)
+            .map_err(syscall_error)?;
+
+        nested_result(result, remaining_gas)
+    }
+}
+
+/// Compute the address of a contract deployed by `deployer_address`, as Starknet does.
+pub fn calculate_contract_address(
+    salt: Felt,
+    class_hash: Felt,
+    constructor_calldata: &[Felt],
+    deployer_address: Felt,
+) -> Felt {
+    let address = Pedersen::hash_array(&[
+        Felt::from_bytes_be_slice(b"STARKNET_CONTRACT_ADDRESS"),
+        deployer_address,
+        salt,
+        class_hash,
+        Pedersen::hash_array(constructor_calldata),
+    ]);
+
+    // The felt prime is lower than twice the bound, so a single subtraction is enough.
+    match address >= L2_ADDRESS_UPPER_BOUND {
+        true => address - L2_ADDRESS_UPPER_BOUND,
+        false => address,
+    }
+}
+
+/// The contract being executed by a syscall handler.
+#[derive(Debug, Clone, Copy)]
+struct CallFrame {
+    class_hash: Felt,
+    caller_address: Felt,
+    contract_address: Felt,
+    entry_point_selector: Felt,
+}
+
+/// The syscall handler of a single call frame within a [`StarknetEnv`].
+#[derive(Debug)]
+struct EnvSyscallHandler<'a> {
+    env: &'a mut StarknetEnv,
+    frame: CallFrame,
+    depth: usize,
+}
+
+impl EnvSyscallHandler<'_> {
+    fn execution_info(&self) -> ExecutionInfoV2 {
+        ExecutionInfoV2 {
+            block_info: self.env.block_info,
+            tx_info: self.env.tx_info.clone(),
+            caller_address: self.frame.caller_address,
+            contract_address: self.frame.contract_address,
+            entry_point_selector: self.frame.entry_point_selector,
+        }
+    }
+
+    fn call_contract_as(
+        &mut self,
+        caller_address: Felt,
+        address: Felt,
+        entry_point_selector: Felt,
+        calldata: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        let class_hash = self
+            .env
+            .class_hash_at(address)
+            .ok_or_else(|| syscall_error(Error::ContractNotDeployed(address)))?;
+
+        self.env.run_nested(
+            CallFrame {
+                class_hash,
+                caller_address,
+                contract_address: address,
+                entry_point_selector,
+            },
+            calldata,
+            remaining_gas,
+        )
+    }
+}
+
+impl StarknetSyscallHandler for EnvSyscallHandler<'_> {
+    #[instrument(skip(self))]
+    fn get_block_hash(
+        &mut self,
+        block_number: u64,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        (&mut self.env.stub).get_block_hash(block_number, remaining_gas)
+    }
+
+    #[instrument(skip(self))]
+    fn get_execution_info(&mut self, _remaining_gas: &mut u64) -> SyscallResult<ExecutionInfo> {
+        tracing::debug!("called");
+        let execution_info = self.execution_info();
+
+        Ok(ExecutionInfo {
+            block_info: execution_info.block_info,
+            tx_info: TxInfo {
+                version: execution_info.tx_info.version,
+                account_contract_address: execution_info.tx_info.account_contract_address,
+                max_fee: execution_info.tx_info.max_fee,
+                signature: execution_info.tx_info.signature,
+                transaction_hash: execution_info.tx_info.transaction_hash,
+                chain_id: execution_info.tx_info.chain_id,
+                nonce: execution_info.tx_info.nonce,
+            },
+            caller_address: execution_info.caller_address,
+            contract_address: execution_info.contract_address,
+            entry_point_selector: execution_info.entry_point_selector,
+        })
+    }
+
+    #[instrument(skip(self))]
+    fn get_execution_info_v2(
+        &mut self,
+        _remaining_gas: &mut u64,
+    ) -> SyscallResult<ExecutionInfoV2> {
+        tracing::debug!("called");
+        Ok(self.execution_info())
+    }
+
+    #[instrument(skip(self))]
+    fn deploy(
+        &mut self,
+        class_hash: Felt,
+        contract_address_salt: Felt,
+        calldata: &[Felt],
+        deploy_from_zero: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(Felt, Vec<Felt>)> {
+        tracing::debug!("called");
+        let deployer_address = match deploy_from_zero {
+            true => Felt::ZERO,
+            false => self.frame.contract_address,
+        };
+
+        let (contract_address, result) = self
+            .env
+            .deploy_from(
+                deployer_address,
+                class_hash,
+                contract_address_salt,
+                calldata,
+                *remaining_gas,
+            )
+            .map_err(syscall_error)?;
+
+        Ok((contract_address, nested_result(result, remaining_gas)?))
+    }
+
+    #[instrument(skip(self))]
+    fn replace_class(&mut self, class_hash: Felt, _remaining_gas: &mut u64) -> SyscallResult<()> {
+        tracing::debug!("called");
+        self.env
+            .set_contract(self.frame.contract_address, class_hash)
+            .map_err(syscall_error)
+    }
+
+    #[instrument(skip(self))]
+    fn library_call(
+        &mut self,
+        class_hash: Felt,
+        function_selector: Felt,
+        calldata: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        tracing::debug!("called");
+        // Library calls run the class' code within the current contract's context.
+        self.env.run_nested(
+            CallFrame {
+                class_hash,
+                entry_point_selector: function_selector,
+                ..self.frame
+            },
+            calldata,
+            remaining_gas,
+        )
+    }
+
+    #[instrument(skip(self))]
+    fn call_contract(
+        &mut self,
+        address: Felt,
+        entry_point_selector: Felt,
+        calldata: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        tracing::debug!("called");
+        self.call_contract_as(
+            self.frame.contract_address,
+            address,
+            entry_point_selector,
+            calldata,
+            remaining_gas,
+        )
+    }
+
+    #[instrument(skip(self))]
+    fn storage_read(
+        &mut self,
+        address_domain: u32,
+        address: Felt,
+        _remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        tracing::debug!("called");
+        check_address_domain(address_domain)?;
+
+        Ok(self.env.storage_at(self.frame.contract_address, address))
+    }
+
+    #[instrument(skip(self))]
+    fn storage_write(
+        &mut self,
+        address_domain: u32,
+        address: Felt,
+        value: Felt,
+        _remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        tracing::debug!("called");
+        check_address_domain(address_domain)?;
+
+        self.env
+            .set_storage_at(self.frame.contract_address, address, value);
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    fn emit_event(
+        &mut self,
+        keys: &[Felt],
+        data: &[Felt],
+        _remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        tracing::debug!("called");
+        self.env.state.emit_event(
+            self.frame.contract_address,
+            self.depth,
+            StubEvent {
+                keys: keys.to_vec(),
+                data: data.to_vec(),
+            },
+        );
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    fn send_message_to_l1(
+        &mut self,
+        to_address: Felt,
+        payload: &[Felt],
+        _remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        tracing::debug!("called");
+        self.env.state.send_message_to_l1(
+            self.frame.contract_address,
+            self.depth,
+            to_address,
+            payload.to_vec(),
+        );
+        Ok(())
+    }
+
+    fn keccak(&mut self, input: &[u64], remaining_gas: &mut u64) -> SyscallResult<U256> {
+        (&mut self.env.stub).keccak(input, remaining_gas)
+    }
+
+    fn secp256k1_new(
+        &mut self,
+        x: U256,
+        y: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256k1Point>> {
+        (&mut self.env.stub).secp256k1_new(x, y, remaining_gas)
+    }
+
+    fn secp256k1_add(
+        &mut self,
+        p0: Secp256k1Point,
+        p1: Secp256k1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256k1Point> {
+        (&mut self.env.stub).secp256k1_add(p0, p1, remaining_gas)
+    }
+
+    fn secp256k1_mul(
+        &mut self,
+        p: Secp256k1Point,
+        m: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256k1Point> {
+        (&mut self.env.stub).secp256k1_mul(p, m, remaining_gas)
+    }
+
+    fn secp256k1_get_point_from_x(
+        &mut self,
+        x: U256,
+        y_parity: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256k1Point>> {
+        (&mut self.env.stub).secp256k1_get_point_from_x(x, y_parity, remaining_gas)
+    }
+
+    fn secp256k1_get_xy(
+        &mut self,
+        p: Secp256k1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(U256, U256)> {
+        (&mut self.env.stub).secp256k1_get_xy(p, remaining_gas)
+    }
+
+    fn secp256r1_new(
+        &mut self,
+        x: U256,
+        y: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256r1Point>> {
+        (&mut self.env.stub).secp256r1_new(x, y, remaining_gas)
+    }
+
+    fn secp256r1_add(
+        &mut self,
+        p0: Secp256r1Point,
+        p1: Secp256r1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256r1Point> {
+        (&mut self.env.stub).secp256r1_add(p0, p1, remaining_gas)
+    }
+
+    fn secp256r1_mul(
+        &mut self,
+        p: Secp256r1Point,
+        m: U256,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Secp256r1Point> {
+        (&mut self.env.stub).secp256r1_mul(p, m, remaining_gas)
+    }
+
+    fn secp256r1_get_point_from_x(
+        &mut self,
+        x: U256,
+        y_parity: bool,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Option<Secp256r1Point>> {
+        (&mut self.env.stub).secp256r1_get_point_from_x(x, y_parity, remaining_gas)
+    }
+
+    fn secp256r1_get_xy(
+        &mut self,
+        p: Secp256r1Point,
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<(U256, U256)> {
+        (&mut self.env.stub).secp256r1_get_xy(p, remaining_gas)
+    }
+
+    fn sha256_process_block(
+        &mut self,
+        state: &mut [u32; 8],
+        block: &[u32; 16],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<()> {
+        (&mut self.env.stub).sha256_process_block(state, block, remaining_gas)
+    }
+
+    #[instrument(skip(self))]
+    fn get_class_hash_at(
+        &mut self,
+        contract_address: Felt,
+        _remaining_gas: &mut u64,
+    ) -> SyscallResult<Felt> {
+        tracing::debug!("called");
+        Ok(self.env.class_hash_at(contract_address).unwrap_or_default())
+    }
+
+    #[instrument(skip(self))]
+    fn meta_tx_v0(
+        &mut self,
+        address: Felt,
+        entry_point_selector: Felt,
+        calldata: &[Felt],
+        signature: &[Felt],
+        remaining_gas: &mut u64,
+    ) -> SyscallResult<Vec<Felt>> {
+        tracing::debug!("called");
+
+        // The call runs as a version 0 invoke transaction sent by the account, like Starknet does.
+        let chain_id = self.env.tx_info.chain_id;
+        let tx_info = TxV2Info {
+            version: Felt::ZERO,
+            account_contract_address: address,
+            max_fee: 0,
+            signature: signature.to_vec(),
+            transaction_hash: Pedersen::hash_array(&[
+                Felt::from_bytes_be_slice(b"invoke"),
+                Felt::ZERO,
+                address,
+                entry_point_selector,
+                Pedersen::hash_array(calldata),
+                Felt::ZERO,
+                chain_id,
+            ]),
+            chain_id,
+            nonce: Felt::ZERO,
+            resource_bounds: Vec::new(),
+            tip: 0,
+            paymaster_data: Vec::new(),
+            nonce_data_availability_mode: 0,
+            fee_data_availability_mode: 0,
+            account_deployment_data: Vec::new(),
+        };
+        let tx_info = mem::replace(&mut self.env.tx_info, tx_info);
+        let result = self.call_contract_as(
+            Felt::ZERO,
+            address,
+            entry_point_selector,
+            calldata,
+            remaining_gas,
+        );
+        self.env.tx_info = tx_info;
+
+        result
+    }
+
+    #[cfg(feature = "with-cheatcode")]
+    fn cheatcode(&mut self, _selector: Felt, _input: &[Felt]) -> Vec<Felt> {
+        tracing::warn!("unimplemented");
+        Vec::new()
+    }
+}
+
+/// Convert the result of a nested call into the syscall's result, deducting its gas usage.
+///
+/// Failed calls propagate their panic data with `ENTRYPOINT_FAILED` appended, like Starknet does.
+fn nested_result(
+    result: ContractExecutionResult,
+    remaining_gas: &mut u64,
+) -> SyscallResult<Vec<Felt>> {
+    *remaining_gas = result.remaining_gas;
+
+    let mut return_values = result.return_values;
+    if result.failure_flag {
+        return_values.push(Felt::from_bytes_be_slice(b"ENTRYPOINT_FAILED"));
+        Err(return_values)
+    } else {
+        Ok(return_values)
+    }
+}
+
+/// Only the address domain zero is supported, like in Starknet.
+fn check_address_domain(address_domain: u32) -> SyscallResult<()> {
+    match address_domain {
+        0 => Ok(()),
+        _ => Err(vec![Felt::from_bytes_be_slice(
+            b"UNSUPPORTED_ADDRESS_DOMAIN",
+        )]),
+    }
+}
+
+/// Convert an environment error into the panic data of a syscall.
+fn syscall_error(error: Error) -> Vec<Felt> {
+    let reason: &[u8] = match error {
+        Error::ContractNotDeployed(_) => b"CONTRACT_NOT_DEPLOYED",
+        Error::ContractAlreadyDeployed(_) => b"CONTRACT_ALREADY_DEPLOYED",
+        Error::ClassNotDeclared(_) => b"CLASS_HASH_NOT_FOUND",
+        Error::SelectorNotFound => b"ENTRYPOINT_NOT_FOUND",
+        error => {
+            tracing::error!("nested call failed: {error}");
+            return super::encode_str_as_felts(&error.to_string());
+        }
+    };
+
+    vec![Felt::from_bytes_be_slice(reason)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        starknet_stub::{LogEntry, OrderedEvent},
+        utils::test::load_starknet_contract,
+        OptLevel,
+    };
+    use cairo_lang_starknet::contract::starknet_keccak;
+    use cairo_lang_starknet_classes::contract_class::{
+        version_id_from_serialized_sierra_program, ContractClass,
+    };
+
+    fn selector(name: &str) -> Felt {
+        Felt::from_bytes_be_slice(&starknet_keccak(name.as_bytes()).to_bytes_be())
+    }
+
+    fn compile(contract: &ContractClass) -> AotContractExecutor {
+        let (sierra_version, _) =
+            version_id_from_serialized_sierra_program(&contract.sierra_program).unwrap();
+
+        AotContractExecutor::new(
+            &contract.extract_sierra_program().unwrap(),
+            &contract.entry_points_by_type,
+            sierra_version,
+            OptLevel::Default,
+        )
+        .unwrap()
+    }
+
+    fn env() -> (StarknetEnv, Felt, Felt) {
+        let (_, counter) = load_starknet_contract! {
+            #[starknet::interface]
+            trait ICounter<TContractState> {
+                fn increment(ref self: TContractState, amount: felt252) -> felt252;
+                fn increment_and_fail(ref self: TContractState, amount: felt252);
+                fn caller(self: @TContractState) -> starknet::ContractAddress;
+            }
+
+            #[starknet::contract]
+            mod counter {
+                use starknet::{
+                    ContractAddress, SyscallResultTrait, emit_event_syscall, get_caller_address,
+                    storage_address_try_from_felt252, storage_read_syscall, storage_write_syscall,
+                };
+
+                #[storage]
+                struct Storage {}
+
+                #[constructor]
+                fn constructor(ref self: ContractState, initial: felt252) {
+                    let key = storage_address_try_from_felt252(1).unwrap();
+                    storage_write_syscall(0, key, initial).unwrap_syscall();
+                }
+
+                #[abi(embed_v0)]
+                impl CounterImpl of super::ICounter<ContractState> {
+                    fn increment(ref self: ContractState, amount: felt252) -> felt252 {
+                        let key = storage_address_try_from_felt252(1).unwrap();
+                        let value = storage_read_syscall(0, key).unwrap_syscall() + amount;
+                        storage_write_syscall(0, key, value).unwrap_syscall();
+                        emit_event_syscall(array![1].span(), array![value].span()).unwrap_syscall();
+                        value
+                    }
+
+                    fn increment_and_fail(ref self: ContractState, amount: felt252) {
+                        CounterImpl::increment(ref self, amount);
+                        core::panic_with_felt252(42);
+                    }
+
+                    fn caller(self: @ContractState) -> ContractAddress {
+                        get_caller_address()
+                    }
+                }
+            }
+        };
+        let (_, proxy) = load_starknet_contract! {
+            use starknet::{ClassHash, ContractAddress};
+
+            #[starknet::interface]
+            trait IProxy<TContractState> {
+                fn forward(
+                    ref self: TContractState,
+                    target: ContractAddress,
+                    selector: felt252,
+                    calldata: Array<felt252>,
+                ) -> Span<felt252>;
+                fn try_forward(
+                    ref self: TContractState,
+                    target: ContractAddress,
+                    selector: felt252,
+                    calldata: Array<felt252>,
+                ) -> bool;
+                fn forward_library(
+                    ref self: TContractState,
+                    class_hash: ClassHash,
+                    selector: felt252,
+                    calldata: Array<felt252>,
+                ) -> Span<felt252>;
+                fn spawn(
+                    ref self: TContractState,
+                    class_hash: ClassHash,
+                    salt: felt252,
+                    calldata: Array<felt252>,
+                ) -> ContractAddress;
+            }
+
+            #[starknet::contract]
+            mod proxy {
+                use starknet::{
+                    ClassHash, ContractAddress, SyscallResultTrait, call_contract_syscall,
+                    deploy_syscall, library_call_syscall,
+                };
+
+                #[storage]
+                struct Storage {}
+
+                #[abi(embed_v0)]
+                impl ProxyImpl of super::IProxy<ContractState> {
+                    fn forward(
+                        ref self: ContractState,
+                        target: ContractAddress,
+                        selector: felt252,
+                        calldata: Array<felt252>,
+                    ) -> Span<felt252> {
+                        call_contract_syscall(target, selector, calldata.span()).unwrap_syscall()
+                    }
+
+                    fn try_forward(
+                        ref self: ContractState,
+                        target: ContractAddress,
+                        selector: felt252,
+                        calldata: Array<felt252>,
+                    ) -> bool {
+                        call_contract_syscall(target, selector, calldata.span()).is_ok()
+                    }
+
+                    fn forward_library(
+                        ref self: ContractState,
+                        class_hash: ClassHash,
+                        selector: felt252,
+                        calldata: Array<felt252>,
+                    ) -> Span<felt252> {
+                        library_call_syscall(class_hash, selector, calldata.span()).unwrap_syscall()
+                    }
+
+                    fn spawn(
+                        ref self: ContractState,
+                        class_hash: ClassHash,
+                        salt: felt252,
+                        calldata: Array<felt252>,
+                    ) -> ContractAddress {
+                        let (address, _) = deploy_syscall(class_hash, salt, calldata.span(), false)
+                            .unwrap_syscall();
+                        address
+                    }
+                }
+            }
+        };
+
+        let counter_hash = Felt::from(0x100);
+        let proxy_hash = Felt::from(0x200);
+
+        let mut env = StarknetEnv::default();
+        env.declare(counter_hash, compile(&counter));
+        env.declare(proxy_hash, compile(&proxy));
+
+        (env, counter_hash, proxy_hash)
+    }
+
+    #[test]
+    fn nested_calls() {
+        let (mut env, counter_hash, proxy_hash) = env();
+
+        let (counter, result) = env
+            .deploy(counter_hash, Felt::ZERO, &[10.into()], u64::MAX)
+            .unwrap();
+        assert!(!result.failure_flag);
+        assert_eq!(
+            counter,
+            calculate_contract_address(Felt::ZERO, counter_hash, &[10.into()], Felt::ZERO)
+        );
+        assert_eq!(env.storage_at(counter, 1.into()), 10.into());
+
+        // Classes without a constructor can be deployed without calldata only.
+        let (proxy, _) = env.deploy(proxy_hash, Felt::ZERO, &[], u64::MAX).unwrap();
+        assert!(matches!(
+            env.deploy(proxy_hash, Felt::ONE, &[Felt::ONE], u64::MAX),
+            Err(Error::SelectorNotFound)
+        ));
+        assert_eq!(env.class_hash_at(proxy), Some(proxy_hash));
+
+        // Calls see the proxy as their caller, and consume the caller's gas.
+        let result = env
+            .call(
+                Felt::ZERO,
+                proxy,
+                selector("forward"),
+                &[counter, selector("increment"), 1.into(), 5.into()],
+                u64::MAX,
+            )
+            .unwrap();
+        assert!(!result.failure_flag);
+        assert_eq!(result.return_values, vec![1.into(), 15.into()]);
+        assert!(result.remaining_gas < u64::MAX);
+        assert_eq!(env.storage_at(counter, 1.into()), 15.into());
+        assert_eq!(
+            env.state.logs(counter).unwrap().events,
+            [LogEntry {
+                order: 0,
+                depth: 1,
+                content: StubEvent {
+                    keys: vec![1.into()],
+                    data: vec![15.into()],
+                },
+            }]
+        );
+
+        let result = env
+            .call(
+                Felt::ZERO,
+                proxy,
+                selector("forward"),
+                &[counter, selector("caller"), 0.into()],
+                u64::MAX,
+            )
+            .unwrap();
+        assert_eq!(result.return_values, vec![1.into(), proxy]);
+
+        // Library calls run within the proxy's storage.
+        let result = env
+            .call(
+                Felt::ZERO,
+                proxy,
+                selector("forward_library"),
+                &[counter_hash, selector("increment"), 1.into(), 7.into()],
+                u64::MAX,
+            )
+            .unwrap();
+        assert_eq!(result.return_values, vec![1.into(), 7.into()]);
+        assert_eq!(env.storage_at(proxy, 1.into()), 7.into());
+        assert_eq!(env.storage_at(counter, 1.into()), 15.into());
+
+        // Deployments from contracts use the contract as the deployer.
+        let result = env
+            .call(
+                Felt::ZERO,
+                proxy,
+                selector("spawn"),
+                &[counter_hash, 3.into(), 1.into(), 20.into()],
+                u64::MAX,
+            )
+            .unwrap();
+        let spawned = calculate_contract_address(3.into(), counter_hash, &[20.into()], proxy);
+        assert_eq!(result.return_values, vec![spawned]);
+        assert_eq!(env.class_hash_at(spawned), Some(counter_hash));
+        assert_eq!(env.storage_at(spawned, 1.into()), 20.into());
+
+        // Failures are propagated to the caller.
+        let result = env
+            .call(
+                Felt::ZERO,
+                proxy,
+                selector("forward"),
+                &[0xdead.into(), selector("increment"), 1.into(), 1.into()],
+                u64::MAX,
+            )
+            .unwrap();
+        assert!(result.failure_flag);
+        assert_eq!(
+            result.return_values,
+            vec![Felt::from_bytes_be_slice(b"CONTRACT_NOT_DEPLOYED")]
+        );
+
+        // There isn't enough gas for the nested call.
+        let result = env
+            .call(
+                Felt::ZERO,
+                proxy,
+                selector("forward"),
+                &[counter, selector("increment"), 1.into(), 1.into()],
+                50_000,
+            )
+            .unwrap();
+        assert!(result.failure_flag);
+        assert_eq!(env.storage_at(counter, 1.into()), 15.into());
+
+        assert!(matches!(
+            env.call(
+                Felt::ZERO,
+                0xdead.into(),
+                selector("increment"),
+                &[],
+                u64::MAX
+            ),
+            Err(Error::ContractNotDeployed(_))
+        ));
+    }
+
+    #[test]
+    fn constructor_selector() {
+        assert_eq!(CONSTRUCTOR_SELECTOR, selector("constructor"));
+    }
+
+    #[test]
+    fn revert_failed_calls() {
+        let (mut env, counter_hash, proxy_hash) = env();
+
+        let (counter, _) = env
+            .deploy(counter_hash, Felt::ZERO, &[10.into()], u64::MAX)
+            .unwrap();
+        let (proxy, _) = env.deploy(proxy_hash, Felt::ZERO, &[], u64::MAX).unwrap();
+
+        // The whole transaction is reverted, but not the deployments made before it.
+        let (result, state_diff) = env
+            .execute(
+                Felt::ZERO,
+                proxy,
+                selector("forward"),
+                &[counter, selector("increment_and_fail"), 1.into(), 5.into()],
+                u64::MAX,
+            )
+            .unwrap();
+        assert!(result.failure_flag);
+        assert_eq!(
+            result.return_values,
+            vec![42.into(), Felt::from_bytes_be_slice(b"ENTRYPOINT_FAILED")]
+        );
+        assert_eq!(state_diff, StateDiff::default());
+        assert_eq!(env.class_hash_at(counter), Some(counter_hash));
+        assert_eq!(env.class_hash_at(proxy), Some(proxy_hash));
+        assert_eq!(env.storage_at(counter, 1.into()), 10.into());
+        assert!(env.state.logs(counter).unwrap().events.is_empty());
+
+        // Only the failed nested call is reverted.
+        env.call(
+            Felt::ZERO,
+            counter,
+            selector("increment"),
+            &[1.into()],
+            u64::MAX,
+        )
+        .unwrap();
+        let (result, state_diff) = env
+            .execute(
+                Felt::ZERO,
+                proxy,
+                selector("try_forward"),
+                &[counter, selector("increment_and_fail"), 1.into(), 5.into()],
+                u64::MAX,
+            )
+            .unwrap();
+        assert!(!result.failure_flag);
+        assert_eq!(result.return_values, vec![Felt::ZERO]);
+        assert_eq!(env.storage_at(counter, 1.into()), 11.into());
+        // The previous call isn't part of the transaction.
+        assert_eq!(state_diff, StateDiff::default());
+
+        // Calls outside of a transaction are finalized right away.
+        for _ in 0..2 {
+            env.call(
+                Felt::ZERO,
+                counter,
+                selector("increment"),
+                &[1.into()],
+                u64::MAX,
+            )
+            .unwrap();
+        }
+        assert_eq!(env.storage_at(counter, 1.into()), 13.into());
+        assert_eq!(env.state.state_diff(), StateDiff::default());
+
+        // Successful transactions only report their own changes.
+        let (result, state_diff) = env
+            .execute(
+                Felt::ZERO,
+                proxy,
+                selector("forward"),
+                &[counter, selector("increment"), 1.into(), 2.into()],
+                u64::MAX,
+            )
+            .unwrap();
+        assert!(!result.failure_flag);
+        assert_eq!(
+            state_diff,
+            StateDiff {
+                storage: [(counter, [(1.into(), 15.into())].into())].into(),
+                events: vec![OrderedEvent {
+                    contract_address: counter,
+                    keys: vec![1.into()],
+                    data: vec![15.into()],
+                }],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn meta_tx_v0() {
+        let (_, tx_reader) = load_starknet_contract! {
+            use starknet::ContractAddress;
+
+            #[starknet::interface]
+            trait ITxReader<TContractState> {
+                fn read(
+                    self: @TContractState,
+                ) -> (ContractAddress, felt252, ContractAddress, Span<felt252>, felt252);
+            }
+
+            #[starknet::contract]
+            mod tx_reader {
+                use starknet::{ContractAddress, SyscallResultTrait};
+                use starknet::syscalls::get_execution_info_v2_syscall;
+
+                #[storage]
+                struct Storage {}
+
+                #[abi(embed_v0)]
+                impl TxReaderImpl of super::ITxReader<ContractState> {
+                    fn read(
+                        self: @ContractState,
+                    ) -> (ContractAddress, felt252, ContractAddress, Span<felt252>, felt252) {
+                        let info = get_execution_info_v2_syscall().unwrap_syscall().unbox();
+                        let tx_info = info.tx_info.unbox();
+                        (
+                            info.caller_address,
+                            tx_info.version,
+                            tx_info.account_contract_address,
+                            tx_info.signature,
+                            tx_info.transaction_hash,
+                        )
+                    }
+                }
+            }
+        };
+
+        let (mut env, _, proxy_hash) = env();
+        env.declare(0x300.into(), compile(&tx_reader));
+        let (tx_reader, _) = env.deploy(0x300.into(), 0.into(), &[], u64::MAX).unwrap();
+        let tx_info = env.tx_info.clone();
+
+        let mut handler = EnvSyscallHandler {
+            env: &mut env,
+            frame: CallFrame {
+                class_hash: proxy_hash,
+                caller_address: Felt::ZERO,
+                contract_address: 1.into(),
+                entry_point_selector: Felt::ZERO,
+            },
+            depth: 0,
+        };
+        let return_values = handler
+            .meta_tx_v0(tx_reader, selector("read"), &[], &[7.into()], &mut u64::MAX)
+            .unwrap();
+        assert_eq!(
+            return_values,
+            vec![
+                Felt::ZERO,
+                Felt::ZERO,
+                tx_reader,
+                1.into(),
+                7.into(),
+                Pedersen::hash_array(&[
+                    Felt::from_bytes_be_slice(b"invoke"),
+                    Felt::ZERO,
+                    tx_reader,
+                    selector("read"),
+                    Pedersen::hash_array(&[]),
+                    Felt::ZERO,
+                    tx_info.chain_id,
+                ]),
+            ]
+        );
+
+        // The transaction info is restored afterwards.
+        assert_eq!(env.tx_info, tx_info);
+    }
+}
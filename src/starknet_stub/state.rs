@@ -0,0 +1,461 @@
+//! # Journaled Starknet state
+//!
+//! Every change to the state is recorded in a journal along with the value it replaced, which
+//! allows undoing changes back to a checkpoint. Checkpoints are nested: a [`StarknetEnv`] opens one
+//! before every call frame, committing it when the call succeeds and rolling it back when it fails.
+//!
+//! The journal is kept until the transaction is finalized, either by committing it (which returns
+//! its [`StateDiff`]) or by reverting it completely. Beginning a transaction finalizes the changes
+//! made before it, so they're neither reverted nor reported along with it.
+//!
+//! [`StarknetEnv`]: super::StarknetEnv
+
+use super::{ContractLogs, LogEntry, StubEvent};
+use serde::{Deserialize, Serialize};
+use starknet_types_core::felt::Felt;
+use std::collections::{BTreeMap, HashMap};
+
+/// The contract state (storage, nonces and class hashes) along with the emitted events and
+/// messages, journaling every change.
+#[derive(Debug, Default, Clone)]
+pub struct JournaledState {
+    storage: HashMap<Felt, HashMap<Felt, Felt>>,
+    nonces: HashMap<Felt, Felt>,
+    class_hashes: HashMap<Felt, Felt>,
+    logs: HashMap<Felt, ContractLogs>,
+    num_events: usize,
+    num_messages: usize,
+
+    journal: Vec<JournalEntry>,
+    checkpoints: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+enum JournalEntry {
+    Storage {
+        contract_address: Felt,
+        key: Felt,
+        previous: Option<Felt>,
+    },
+    Nonce {
+        contract_address: Felt,
+        previous: Option<Felt>,
+    },
+    ClassHash {
+        contract_address: Felt,
+        previous: Option<Felt>,
+    },
+    Event {
+        contract_address: Felt,
+        event: StubEvent,
+    },
+    Message {
+        contract_address: Felt,
+        to_address: Felt,
+        payload: Vec<Felt>,
+    },
+}
+
+/// The changes made by a transaction.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateDiff {
+    /// The modified storage slots, by contract address.
+    pub storage: BTreeMap<Felt, BTreeMap<Felt, Felt>>,
+    /// The modified nonces, by contract address.
+    pub nonces: BTreeMap<Felt, Felt>,
+    /// The deployed or replaced classes, by contract address.
+    pub class_hashes: BTreeMap<Felt, Felt>,
+    /// The emitted events, in order.
+    pub events: Vec<OrderedEvent>,
+    /// The messages sent to L1, in order.
+    pub l2_to_l1_messages: Vec<OrderedL2ToL1Message>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderedEvent {
+    pub contract_address: Felt,
+    pub keys: Vec<Felt>,
+    pub data: Vec<Felt>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderedL2ToL1Message {
+    pub from_address: Felt,
+    pub to_address: Felt,
+    pub payload: Vec<Felt>,
+}
+
+impl JournaledState {
+    /// Read a storage slot. Uninitialized slots are zero.
+    pub fn storage_at(&self, contract_address: Felt, key: Felt) -> Felt {
+        self.storage
+            .get(&contract_address)
+            .and_then(|storage| storage.get(&key))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Return the full storage of a contract.
+    pub fn storage(&self, contract_address: Felt) -> Option<&HashMap<Felt, Felt>> {
+        self.storage.get(&contract_address)
+    }
+
+    pub fn set_storage_at(&mut self, contract_address: Felt, key: Felt, value: Felt) {
+        let previous = self
+            .storage
+            .entry(contract_address)
+            .or_default()
+            .insert(key, value);
+
+        self.journal.push(JournalEntry::Storage {
+            contract_address,
+            key,
+            previous,
+        });
+    }
+
+    /// Return the nonce of a contract. Unknown contracts have a zero nonce.
+    pub fn nonce_at(&self, contract_address: Felt) -> Felt {
+        self.nonces
+            .get(&contract_address)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_nonce_at(&mut self, contract_address: Felt, nonce: Felt) {
+        let previous = self.nonces.insert(contract_address, nonce);
+        self.journal.push(JournalEntry::Nonce {
+            contract_address,
+            previous,
+        });
+    }
+
+    /// Increment the nonce of a contract, returning the previous one.
+    pub fn increment_nonce(&mut self, contract_address: Felt) -> Felt {
+        let nonce = self.nonce_at(contract_address);
+        self.set_nonce_at(contract_address, nonce + Felt::ONE);
+        nonce
+    }
+
+    /// Return the class hash of the contract deployed at the given address.
+    pub fn class_hash_at(&self, contract_address: Felt) -> Option<Felt> {
+        self.class_hashes.get(&contract_address).copied()
+    }
+
+    pub fn set_class_hash_at(&mut self, contract_address: Felt, class_hash: Felt) {
+        let previous = self.class_hashes.insert(contract_address, class_hash);
+        self.journal.push(JournalEntry::ClassHash {
+            contract_address,
+            previous,
+        });
+    }
+
+    /// Return the events and messages emitted by a contract.
+    pub fn logs(&self, contract_address: Felt) -> Option<&ContractLogs> {
+        self.logs.get(&contract_address)
+    }
+
+    /// Record an event emitted by a contract from a call frame at the given depth.
+    pub fn emit_event(&mut self, contract_address: Felt, depth: usize, event: StubEvent) {
+        self.logs
+            .entry(contract_address)
+            .or_default()
+            .events
+            .push_back(LogEntry {
+                order: self.num_events,
+                depth,
+                content: event.clone(),
+            });
+        self.num_events += 1;
+        self.journal.push(JournalEntry::Event {
+            contract_address,
+            event,
+        });
+    }
+
+    /// Record a message sent by a contract from a call frame at the given depth.
+    pub fn send_message_to_l1(
+        &mut self,
+        contract_address: Felt,
+        depth: usize,
+        to_address: Felt,
+        payload: Vec<Felt>,
+    ) {
+        self.logs
+            .entry(contract_address)
+            .or_default()
+            .l2_to_l1_messages
+            .push_back(LogEntry {
+                order: self.num_messages,
+                depth,
+                content: (to_address, payload.clone()),
+            });
+        self.num_messages += 1;
+        self.journal.push(JournalEntry::Message {
+            contract_address,
+            to_address,
+            payload,
+        });
+    }
+
+    /// Open a new (nested) checkpoint.
+    pub fn checkpoint(&mut self) {
+        self.checkpoints.push(self.journal.len());
+    }
+
+    /// Close the innermost checkpoint, keeping its changes. They will still be reverted if an
+    /// enclosing checkpoint is rolled back.
+    pub fn commit(&mut self) {
+        self.checkpoints.pop();
+    }
+
+    /// Close the innermost checkpoint, undoing every change made since it was opened.
+    pub fn rollback(&mut self) {
+        if let Some(len) = self.checkpoints.pop() {
+            self.undo_until(len);
+        }
+    }
+
+    /// Begin a new transaction, finalizing every change made so far.
+    pub fn begin_transaction(&mut self) {
+        self.journal.clear();
+        self.checkpoints.clear();
+    }
+
+    /// Finalize the transaction, returning its changes.
+    pub fn commit_transaction(&mut self) -> StateDiff {
+        let state_diff = self.state_diff();
+        self.begin_transaction();
+        state_diff
+    }
+
+    /// Undo every change made by the transaction.
+    pub fn revert_transaction(&mut self) {
+        self.undo_until(0);
+        self.checkpoints.clear();
+    }
+
+    /// Return the changes made by the transaction so far.
+    ///
+    /// Values which have been overwritten back to their original value are not included.
+    pub fn state_diff(&self) -> StateDiff {
+        let mut storage = BTreeMap::<Felt, BTreeMap<Felt, Option<Felt>>>::new();
+        let mut nonces = BTreeMap::new();
+        let mut class_hashes = BTreeMap::new();
+        let mut state_diff = StateDiff::default();
+
+        // Keep the value of every modified entry before the transaction started.
+        for entry in &self.journal {
+            match entry {
+                JournalEntry::Storage {
+                    contract_address,
+                    key,
+                    previous,
+                } => {
+                    storage
+                        .entry(*contract_address)
+                        .or_default()
+                        .entry(*key)
+                        .or_insert(*previous);
+                }
+                JournalEntry::Nonce {
+                    contract_address,
+                    previous,
+                } => {
+                    nonces.entry(*contract_address).or_insert(*previous);
+                }
+                JournalEntry::ClassHash {
+                    contract_address,
+                    previous,
+                } => {
+                    class_hashes.entry(*contract_address).or_insert(*previous);
+                }
+                JournalEntry::Event {
+                    contract_address,
+                    event,
+                } => state_diff.events.push(OrderedEvent {
+                    contract_address: *contract_address,
+                    keys: event.keys.clone(),
+                    data: event.data.clone(),
+                }),
+                JournalEntry::Message {
+                    contract_address,
+                    to_address,
+                    payload,
+                } => state_diff.l2_to_l1_messages.push(OrderedL2ToL1Message {
+                    from_address: *contract_address,
+                    to_address: *to_address,
+                    payload: payload.clone(),
+                }),
+            }
+        }
+
+        for (contract_address, keys) in storage {
+            let updates = keys
+                .into_iter()
+                .filter_map(|(key, original)| {
+                    let value = self.storage_at(contract_address, key);
+                    (original.unwrap_or_default() != value).then_some((key, value))
+                })
+                .collect::<BTreeMap<_, _>>();
+
+            if !updates.is_empty() {
+                state_diff.storage.insert(contract_address, updates);
+            }
+        }
+        state_diff.nonces = nonces
+            .into_iter()
+            .filter_map(|(contract_address, original)| {
+                let value = self.nonce_at(contract_address);
+                (original.unwrap_or_default() != value).then_some((contract_address, value))
+            })
+            .collect();
+        state_diff.class_hashes = class_hashes
+            .into_iter()
+            .filter_map(|(contract_address, original)| {
+                let value = self.class_hash_at(contract_address)?;
+                (original != Some(value)).then_some((contract_address, value))
+            })
+            .collect();
+
+        state_diff
+    }
+
+    fn undo_until(&mut self, len: usize) {
+        fn restore(map: &mut HashMap<Felt, Felt>, key: Felt, previous: Option<Felt>) {
+            match previous {
+                Some(value) => map.insert(key, value),
+                None => map.remove(&key),
+            };
+        }
+
+        while self.journal.len() > len {
+            let Some(entry) = self.journal.pop() else {
+                break;
+            };
+
+            match entry {
+                JournalEntry::Storage {
+                    contract_address,
+                    key,
+                    previous,
+                } => restore(
+                    self.storage.entry(contract_address).or_default(),
+                    key,
+                    previous,
+                ),
+                JournalEntry::Nonce {
+                    contract_address,
+                    previous,
+                } => restore(&mut self.nonces, contract_address, previous),
+                JournalEntry::ClassHash {
+                    contract_address,
+                    previous,
+                } => restore(&mut self.class_hashes, contract_address, previous),
+                JournalEntry::Event {
+                    contract_address, ..
+                } => {
+                    if let Some(logs) = self.logs.get_mut(&contract_address) {
+                        logs.events.pop_back();
+                    }
+                    self.num_events -= 1;
+                }
+                JournalEntry::Message {
+                    contract_address, ..
+                } => {
+                    if let Some(logs) = self.logs.get_mut(&contract_address) {
+                        logs.l2_to_l1_messages.pop_back();
+                    }
+                    self.num_messages -= 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_checkpoints() {
+        let mut state = JournaledState::default();
+        let (a, b) = (Felt::from(1), Felt::from(2));
+
+        state.set_storage_at(a, 10.into(), 100.into());
+
+        state.checkpoint();
+        state.set_storage_at(a, 10.into(), 101.into());
+        state.set_class_hash_at(b, 0x200.into());
+        state.emit_event(
+            b,
+            1,
+            StubEvent {
+                keys: vec![1.into()],
+                data: vec![],
+            },
+        );
+
+        state.checkpoint();
+        state.set_storage_at(b, 20.into(), 200.into());
+        state.send_message_to_l1(b, 2, 5.into(), vec![6.into()]);
+        state.increment_nonce(b);
+        state.rollback();
+
+        assert_eq!(state.storage_at(b, 20.into()), Felt::ZERO);
+        assert_eq!(state.nonce_at(b), Felt::ZERO);
+        assert!(state.logs(b).unwrap().l2_to_l1_messages.is_empty());
+        state.commit();
+
+        assert_eq!(state.storage_at(a, 10.into()), 101.into());
+        assert_eq!(state.class_hash_at(b), Some(0x200.into()));
+        assert_eq!(state.logs(b).unwrap().events.len(), 1);
+
+        // Writes restoring the original value are not part of the diff.
+        state.set_storage_at(a, 11.into(), 1.into());
+        state.set_storage_at(a, 11.into(), 0.into());
+
+        assert_eq!(
+            state.state_diff(),
+            StateDiff {
+                storage: [(a, [(10.into(), 101.into())].into())].into(),
+                nonces: BTreeMap::new(),
+                class_hashes: [(b, 0x200.into())].into(),
+                events: vec![OrderedEvent {
+                    contract_address: b,
+                    keys: vec![1.into()],
+                    data: vec![],
+                }],
+                l2_to_l1_messages: vec![],
+            }
+        );
+
+        state.revert_transaction();
+        assert_eq!(state.storage_at(a, 10.into()), Felt::ZERO);
+        assert_eq!(state.class_hash_at(b), None);
+        assert!(state.logs(b).unwrap().events.is_empty());
+        assert_eq!(state.state_diff(), StateDiff::default());
+
+        // Committed transactions can't be reverted anymore.
+        state.set_nonce_at(a, 3.into());
+        let state_diff = state.commit_transaction();
+        assert_eq!(state_diff.nonces, [(a, 3.into())].into());
+
+        state.revert_transaction();
+        assert_eq!(state.nonce_at(a), 3.into());
+
+        // Changes made before beginning a transaction are kept when it's reverted.
+        state.set_storage_at(b, 30.into(), 300.into());
+        state.begin_transaction();
+        state.set_storage_at(b, 30.into(), 301.into());
+        state.set_storage_at(b, 31.into(), 310.into());
+        assert_eq!(
+            state.state_diff().storage,
+            [(b, [(30.into(), 301.into()), (31.into(), 310.into())].into())].into()
+        );
+
+        state.revert_transaction();
+        assert_eq!(state.storage_at(b, 30.into()), 300.into());
+        assert_eq!(state.storage_at(b, 31.into()), Felt::ZERO);
+    }
+}
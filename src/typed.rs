@@ -0,0 +1,968 @@
+//! # Typed bindings
+//!
+//! Conversions between Rust types and [`Value`], and typed wrappers around the executors'
+//! `invoke_dynamic` methods.
+//!
+//! Rust types are converted using the [`IntoValue`] and [`FromValue`] traits, which are
+//! implemented for the primitive types (felts, integers, booleans, `u256`, arrays, options,
+//! results, tuples...) and can be derived for custom structs and enums:
+//!
+//! ```ignore
+//! #[derive(IntoValue, FromValue)]
+//! struct Point {
+//!     x: u32,
+//!     y: u32,
+//! }
+//!
+//! let function = executor.bind::<(Point, Point), Point>(function_id)?;
+//! let result = function.invoke((Point { x: 1, y: 2 }, Point { x: 3, y: 4 }), None)?;
+//! ```
+//!
+//! Binding a function validates its signature against the program registry once, so that type
+//! mismatches are reported before running anything. Builtins, the syscall handler and
+//! zero-sized parameters are handled automatically and are not part of the arguments.
+
+pub use cairo_native_derive::{FromValue, IntoValue};
+
+use self::__private::{check_enum, check_struct, enum_value, enum_variant, struct_value};
+use crate::{
+    error::{Error, Result},
+    execution_result::{BuiltinStats, ExecutionResult},
+    executor::{AotNativeExecutor, JitNativeExecutor},
+    starknet::{StarknetSyscallHandler, U256},
+    types::TypeBuilder,
+    values::Value,
+};
+use cairo_lang_sierra::{
+    extensions::{
+        core::{CoreLibfunc, CoreType, CoreTypeConcrete},
+        starknet::StarknetTypeConcrete,
+    },
+    ids::{ConcreteTypeId, FunctionId},
+    program_registry::ProgramRegistry,
+};
+use starknet_types_core::felt::Felt;
+use std::marker::PhantomData;
+
+/// A Rust type which can be converted into a [`Value`].
+pub trait IntoValue {
+    /// Convert the Rust value into a [`Value`].
+    fn into_value(self) -> Value;
+
+    /// Check whether the Sierra type is compatible with this Rust type.
+    fn check_type(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+    ) -> Result<()>;
+}
+
+/// A Rust type which can be built from a [`Value`].
+pub trait FromValue: Sized {
+    /// Convert a [`Value`] back into the Rust value.
+    fn from_value(value: Value) -> Result<Self>;
+
+    /// Check whether the Sierra type is compatible with this Rust type.
+    fn check_type(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+    ) -> Result<()>;
+}
+
+/// A list of arguments, implemented for tuples of [`IntoValue`] types.
+pub trait IntoArgs {
+    /// Convert every argument into a [`Value`].
+    fn into_values(self) -> Vec<Value>;
+
+    /// Check whether the (non-builtin) parameter types are compatible with the arguments.
+    fn check_types(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_ids: &[ConcreteTypeId],
+    ) -> Result<()>;
+}
+
+/// Executors which can invoke functions using [`Value`]s.
+pub trait DynamicExecutor {
+    fn program_registry(&self) -> &ProgramRegistry<CoreType, CoreLibfunc>;
+
+    fn invoke_dynamic(
+        &self,
+        function_id: &FunctionId,
+        args: &[Value],
+        gas: Option<u64>,
+    ) -> Result<ExecutionResult>;
+
+    fn invoke_dynamic_with_syscall_handler(
+        &self,
+        function_id: &FunctionId,
+        args: &[Value],
+        gas: Option<u64>,
+        syscall_handler: impl StarknetSyscallHandler,
+    ) -> Result<ExecutionResult>;
+}
+
+impl DynamicExecutor for AotNativeExecutor {
+    fn program_registry(&self) -> &ProgramRegistry<CoreType, CoreLibfunc> {
+        AotNativeExecutor::program_registry(self)
+    }
+
+    fn invoke_dynamic(
+        &self,
+        function_id: &FunctionId,
+        args: &[Value],
+        gas: Option<u64>,
+    ) -> Result<ExecutionResult> {
+        AotNativeExecutor::invoke_dynamic(self, function_id, args, gas)
+    }
+
+    fn invoke_dynamic_with_syscall_handler(
+        &self,
+        function_id: &FunctionId,
+        args: &[Value],
+        gas: Option<u64>,
+        syscall_handler: impl StarknetSyscallHandler,
+    ) -> Result<ExecutionResult> {
+        AotNativeExecutor::invoke_dynamic_with_syscall_handler(
+            self,
+            function_id,
+            args,
+            gas,
+            syscall_handler,
+        )
+    }
+}
+
+impl DynamicExecutor for JitNativeExecutor<'_> {
+    fn program_registry(&self) -> &ProgramRegistry<CoreType, CoreLibfunc> {
+        JitNativeExecutor::program_registry(self)
+    }
+
+    fn invoke_dynamic(
+        &self,
+        function_id: &FunctionId,
+        args: &[Value],
+        gas: Option<u64>,
+    ) -> Result<ExecutionResult> {
+        JitNativeExecutor::invoke_dynamic(self, function_id, args, gas)
+    }
+
+    fn invoke_dynamic_with_syscall_handler(
+        &self,
+        function_id: &FunctionId,
+        args: &[Value],
+        gas: Option<u64>,
+        syscall_handler: impl StarknetSyscallHandler,
+    ) -> Result<ExecutionResult> {
+        JitNativeExecutor::invoke_dynamic_with_syscall_handler(
+            self,
+            function_id,
+            args,
+            gas,
+            syscall_handler,
+        )
+    }
+}
+
+/// The result of a typed invocation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypedExecutionResult<T> {
+    pub remaining_gas: Option<u64>,
+    pub return_value: T,
+    pub builtin_stats: BuiltinStats,
+}
+
+impl<T> TypedExecutionResult<T>
+where
+    T: FromValue,
+{
+    fn from_execution_result(result: ExecutionResult) -> Result<Self> {
+        Ok(Self {
+            remaining_gas: result.remaining_gas,
+            return_value: T::from_value(result.return_value)?,
+            builtin_stats: result.builtin_stats,
+        })
+    }
+}
+
+/// A function whose signature has been validated against `Args` and `Ret`.
+#[derive(Debug)]
+pub struct TypedFunction<'e, E, Args, Ret> {
+    executor: &'e E,
+    function_id: FunctionId,
+    phantom: PhantomData<fn(Args) -> Ret>,
+}
+
+impl<'e, E, Args, Ret> TypedFunction<'e, E, Args, Ret>
+where
+    E: DynamicExecutor,
+    Args: IntoArgs,
+    Ret: FromValue,
+{
+    /// Validate the function's signature and bind it.
+    pub fn new(executor: &'e E, function_id: &FunctionId) -> Result<Self> {
+        let registry = executor.program_registry();
+        let signature = &registry.get_function(function_id)?.signature;
+
+        // Builtins (including the syscall handler) and zero-sized types are not provided by the
+        // caller.
+        let mut param_types = Vec::with_capacity(signature.param_types.len());
+        for type_id in &signature.param_types {
+            let type_info = registry.get_type(type_id)?;
+            if !type_info.is_builtin() && !type_info.is_zst(registry)? {
+                param_types.push(type_id.clone());
+            }
+        }
+        Args::check_types(registry, &param_types)?;
+
+        // Functions without a non-builtin return type return unit, which is checked when parsing
+        // the result.
+        if let Some(type_id) = signature.ret_types.last() {
+            if !registry.get_type(type_id)?.is_builtin() {
+                Ret::check_type(registry, type_id)?;
+            }
+        }
+
+        Ok(Self {
+            executor,
+            function_id: function_id.clone(),
+            phantom: PhantomData,
+        })
+    }
+
+    pub fn function_id(&self) -> &FunctionId {
+        &self.function_id
+    }
+
+    /// Execute the function with the given arguments.
+    pub fn invoke(&self, args: Args, gas: Option<u64>) -> Result<TypedExecutionResult<Ret>> {
+        TypedExecutionResult::from_execution_result(self.executor.invoke_dynamic(
+            &self.function_id,
+            &args.into_values(),
+            gas,
+        )?)
+    }
+
+    /// Execute the function with the given arguments and syscall handler.
+    pub fn invoke_with_syscall_handler(
+        &self,
+        args: Args,
+        gas: Option<u64>,
+        syscall_handler: impl StarknetSyscallHandler,
+    ) -> Result<TypedExecutionResult<Ret>> {
+        TypedExecutionResult::from_execution_result(
+            self.executor.invoke_dynamic_with_syscall_handler(
+                &self.function_id,
+                &args.into_values(),
+                gas,
+                syscall_handler,
+            )?,
+        )
+    }
+}
+
+/// Helpers used by the derive macros' generated code.
+#[doc(hidden)]
+pub mod __private {
+    use super::resolve_type;
+    use crate::{
+        error::{Error, Result},
+        values::Value,
+    };
+    use cairo_lang_sierra::extensions::core::{CoreLibfunc, CoreType, CoreTypeConcrete};
+
+    pub use cairo_lang_sierra::ids::ConcreteTypeId;
+
+    pub type ProgramRegistry =
+        cairo_lang_sierra::program_registry::ProgramRegistry<CoreType, CoreLibfunc>;
+
+    pub fn struct_value(fields: Vec<Value>) -> Value {
+        Value::Struct {
+            fields,
+            debug_name: None,
+        }
+    }
+
+    pub fn enum_value(tag: usize, payload: Value) -> Value {
+        Value::Enum {
+            tag,
+            value: Box::new(payload),
+            debug_name: None,
+        }
+    }
+
+    /// Check that the type is a struct with `num_members` members and return their types.
+    pub fn check_struct<'a>(
+        registry: &'a ProgramRegistry,
+        type_id: &ConcreteTypeId,
+        num_members: usize,
+    ) -> Result<&'a [ConcreteTypeId]> {
+        match resolve_type(registry, type_id)? {
+            CoreTypeConcrete::Struct(info) if info.members.len() == num_members => {
+                Ok(&info.members)
+            }
+            _ => Err(type_mismatch(
+                type_id,
+                &format!("a struct with {num_members} members"),
+            )),
+        }
+    }
+
+    /// Check that the type is an enum with `num_variants` variants and return their types.
+    pub fn check_enum<'a>(
+        registry: &'a ProgramRegistry,
+        type_id: &ConcreteTypeId,
+        num_variants: usize,
+    ) -> Result<&'a [ConcreteTypeId]> {
+        match resolve_type(registry, type_id)? {
+            CoreTypeConcrete::Enum(info) if info.variants.len() == num_variants => {
+                Ok(&info.variants)
+            }
+            _ => Err(type_mismatch(
+                type_id,
+                &format!("an enum with {num_variants} variants"),
+            )),
+        }
+    }
+
+    /// Extract the fields of a struct value, which must have exactly `N` fields.
+    pub fn struct_fields<const N: usize>(value: Value, name: &str) -> Result<[Value; N]> {
+        match value {
+            Value::Struct { fields, .. } => fields
+                .try_into()
+                .map_err(|_| Error::UnexpectedValue(name.to_string())),
+            _ => Err(Error::UnexpectedValue(name.to_string())),
+        }
+    }
+
+    /// Extract the tag and payload of an enum value with `num_variants` variants.
+    pub fn enum_variant(value: Value, num_variants: usize, name: &str) -> Result<(usize, Value)> {
+        match value {
+            Value::Enum { tag, value, .. } if tag < num_variants => Ok((tag, *value)),
+            _ => Err(Error::UnexpectedValue(name.to_string())),
+        }
+    }
+
+    pub(super) fn type_mismatch(type_id: &ConcreteTypeId, expected: &str) -> Error {
+        Error::TypeMismatch(format!(
+            "expected {expected}, found '{}'",
+            type_id
+                .debug_name
+                .as_ref()
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| type_id.id.to_string())
+        ))
+    }
+}
+
+/// Return the concrete type, skipping snapshots and non-zero wrappers since they share their
+/// inner type's value representation.
+fn resolve_type<'a>(
+    registry: &'a ProgramRegistry<CoreType, CoreLibfunc>,
+    type_id: &ConcreteTypeId,
+) -> Result<&'a CoreTypeConcrete> {
+    let mut type_info = registry.get_type(type_id)?;
+    loop {
+        type_info = match type_info {
+            CoreTypeConcrete::Snapshot(info) => registry.get_type(&info.ty)?,
+            CoreTypeConcrete::NonZero(info) => registry.get_type(&info.ty)?,
+            type_info => return Ok(type_info),
+        };
+    }
+}
+
+fn check_scalar(
+    registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    type_id: &ConcreteTypeId,
+    expected: &str,
+    is_valid: impl FnOnce(&CoreTypeConcrete) -> bool,
+) -> Result<()> {
+    if is_valid(resolve_type(registry, type_id)?) {
+        Ok(())
+    } else {
+        Err(__private::type_mismatch(type_id, expected))
+    }
+}
+
+macro_rules! impl_scalar {
+    ( $( $ty:ty => $variant:ident($pattern:pat); )+ ) => {
+        $(
+            impl IntoValue for $ty {
+                fn into_value(self) -> Value {
+                    Value::$variant(self)
+                }
+
+                fn check_type(
+                    registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+                    type_id: &ConcreteTypeId,
+                ) -> Result<()> {
+                    check_scalar(registry, type_id, stringify!($ty), |x| matches!(x, $pattern))
+                }
+            }
+
+            impl FromValue for $ty {
+                fn from_value(value: Value) -> Result<Self> {
+                    match value {
+                        Value::$variant(x) => Ok(x),
+                        _ => Err(Error::UnexpectedValue(stringify!($ty).to_string())),
+                    }
+                }
+
+                fn check_type(
+                    registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+                    type_id: &ConcreteTypeId,
+                ) -> Result<()> {
+                    <$ty as IntoValue>::check_type(registry, type_id)
+                }
+            }
+        )+
+    };
+}
+
+impl_scalar! {
+    Felt => Felt252(
+        CoreTypeConcrete::Felt252(_)
+            | CoreTypeConcrete::Starknet(
+                StarknetTypeConcrete::ClassHash(_)
+                    | StarknetTypeConcrete::ContractAddress(_)
+                    | StarknetTypeConcrete::StorageBaseAddress(_)
+                    | StarknetTypeConcrete::StorageAddress(_)
+            )
+    );
+    u8 => Uint8(CoreTypeConcrete::Uint8(_));
+    u16 => Uint16(CoreTypeConcrete::Uint16(_));
+    u32 => Uint32(CoreTypeConcrete::Uint32(_));
+    u64 => Uint64(CoreTypeConcrete::Uint64(_));
+    u128 => Uint128(CoreTypeConcrete::Uint128(_));
+    i8 => Sint8(CoreTypeConcrete::Sint8(_));
+    i16 => Sint16(CoreTypeConcrete::Sint16(_));
+    i32 => Sint32(CoreTypeConcrete::Sint32(_));
+    i64 => Sint64(CoreTypeConcrete::Sint64(_));
+    i128 => Sint128(CoreTypeConcrete::Sint128(_));
+}
+
+/// Values are passed through unchecked.
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+
+    fn check_type(
+        _registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        _type_id: &ConcreteTypeId,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Values are passed through unchecked.
+impl FromValue for Value {
+    fn from_value(value: Value) -> Result<Self> {
+        Ok(value)
+    }
+
+    fn check_type(
+        _registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        _type_id: &ConcreteTypeId,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Booleans are enums whose variants are `False` and `True`, in that order.
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        enum_value(self as usize, struct_value(Vec::new()))
+    }
+
+    fn check_type(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+    ) -> Result<()> {
+        for variant in check_enum(registry, type_id, 2)? {
+            <() as IntoValue>::check_type(registry, variant)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> Result<Self> {
+        let (tag, _) = enum_variant(value, 2, "bool")?;
+        Ok(tag != 0)
+    }
+
+    fn check_type(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+    ) -> Result<()> {
+        <bool as IntoValue>::check_type(registry, type_id)
+    }
+}
+
+/// `u256` is a struct with its low and high halves, in that order.
+impl IntoValue for U256 {
+    fn into_value(self) -> Value {
+        struct_value(vec![Value::Uint128(self.lo), Value::Uint128(self.hi)])
+    }
+
+    fn check_type(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+    ) -> Result<()> {
+        for member in check_struct(registry, type_id, 2)? {
+            <u128 as IntoValue>::check_type(registry, member)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromValue for U256 {
+    fn from_value(value: Value) -> Result<Self> {
+        let [lo, hi] = __private::struct_fields::<2>(value, "u256")?;
+        Ok(Self {
+            lo: u128::from_value(lo)?,
+            hi: u128::from_value(hi)?,
+        })
+    }
+
+    fn check_type(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+    ) -> Result<()> {
+        <U256 as IntoValue>::check_type(registry, type_id)
+    }
+}
+
+fn check_array(
+    registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    type_id: &ConcreteTypeId,
+    check_elem: impl FnOnce(&ConcreteTypeId) -> Result<()>,
+) -> Result<()> {
+    match resolve_type(registry, type_id)? {
+        CoreTypeConcrete::Array(info) => check_elem(&info.ty),
+        _ => Err(__private::type_mismatch(type_id, "an array")),
+    }
+}
+
+impl<T> IntoValue for Vec<T>
+where
+    T: IntoValue,
+{
+    fn into_value(self) -> Value {
+        Value::Array(self.into_iter().map(T::into_value).collect())
+    }
+
+    fn check_type(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+    ) -> Result<()> {
+        check_array(registry, type_id, |elem_ty| {
+            T::check_type(registry, elem_ty)
+        })
+    }
+}
+
+impl<T> FromValue for Vec<T>
+where
+    T: FromValue,
+{
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Array(values) => values.into_iter().map(T::from_value).collect(),
+            _ => Err(Error::UnexpectedValue("Array".to_string())),
+        }
+    }
+
+    fn check_type(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+    ) -> Result<()> {
+        check_array(registry, type_id, |elem_ty| {
+            T::check_type(registry, elem_ty)
+        })
+    }
+}
+
+/// Options are enums whose variants are `Some(T)` and `None`, in that order.
+impl<T> IntoValue for Option<T>
+where
+    T: IntoValue,
+{
+    fn into_value(self) -> Value {
+        match self {
+            Some(value) => enum_value(0, value.into_value()),
+            None => enum_value(1, struct_value(Vec::new())),
+        }
+    }
+
+    fn check_type(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+    ) -> Result<()> {
+        let variants = check_enum(registry, type_id, 2)?;
+        T::check_type(registry, &variants[0])?;
+        <() as IntoValue>::check_type(registry, &variants[1])
+    }
+}
+
+impl<T> FromValue for Option<T>
+where
+    T: FromValue,
+{
+    fn from_value(value: Value) -> Result<Self> {
+        match enum_variant(value, 2, "Option")? {
+            (0, payload) => Ok(Some(T::from_value(payload)?)),
+            _ => Ok(None),
+        }
+    }
+
+    fn check_type(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+    ) -> Result<()> {
+        let variants = check_enum(registry, type_id, 2)?;
+        T::check_type(registry, &variants[0])?;
+        <() as FromValue>::check_type(registry, &variants[1])
+    }
+}
+
+/// Results are enums whose variants are `Ok(T)` and `Err(E)`, in that order. This includes the
+/// results of functions which may panic, where the error is a `(Panic, Array<felt252>)` tuple.
+impl<T, E> IntoValue for std::result::Result<T, E>
+where
+    T: IntoValue,
+    E: IntoValue,
+{
+    fn into_value(self) -> Value {
+        match self {
+            Ok(value) => enum_value(0, value.into_value()),
+            Err(error) => enum_value(1, error.into_value()),
+        }
+    }
+
+    fn check_type(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+    ) -> Result<()> {
+        let variants = check_enum(registry, type_id, 2)?;
+        T::check_type(registry, &variants[0])?;
+        E::check_type(registry, &variants[1])
+    }
+}
+
+impl<T, E> FromValue for std::result::Result<T, E>
+where
+    T: FromValue,
+    E: FromValue,
+{
+    fn from_value(value: Value) -> Result<Self> {
+        match enum_variant(value, 2, "Result")? {
+            (0, payload) => Ok(Ok(T::from_value(payload)?)),
+            (_, payload) => Ok(Err(E::from_value(payload)?)),
+        }
+    }
+
+    fn check_type(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+    ) -> Result<()> {
+        let variants = check_enum(registry, type_id, 2)?;
+        T::check_type(registry, &variants[0])?;
+        E::check_type(registry, &variants[1])
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        struct_value(Vec::new())
+    }
+
+    fn check_type(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+    ) -> Result<()> {
+        check_struct(registry, type_id, 0).map(|_| ())
+    }
+}
+
+impl FromValue for () {
+    fn from_value(value: Value) -> Result<Self> {
+        let [] = __private::struct_fields::<0>(value, "()")?;
+        Ok(())
+    }
+
+    fn check_type(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+    ) -> Result<()> {
+        check_struct(registry, type_id, 0).map(|_| ())
+    }
+}
+
+impl IntoArgs for () {
+    fn into_values(self) -> Vec<Value> {
+        Vec::new()
+    }
+
+    fn check_types(
+        _registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_ids: &[ConcreteTypeId],
+    ) -> Result<()> {
+        check_arity(type_ids, 0)
+    }
+}
+
+fn check_arity(type_ids: &[ConcreteTypeId], num_args: usize) -> Result<()> {
+    if type_ids.len() == num_args {
+        Ok(())
+    } else {
+        Err(Error::TypeMismatch(format!(
+            "expected {num_args} arguments, found {} parameters",
+            type_ids.len()
+        )))
+    }
+}
+
+/// Tuples are converted into structs when used as values, and into an argument list when used as
+/// arguments.
+macro_rules! impl_tuple {
+    ( $num:literal; $( $ty:ident $var:ident $idx:tt ),+ ) => {
+        impl<$($ty),+> IntoValue for ($($ty,)+)
+        where
+            $($ty: IntoValue),+
+        {
+            fn into_value(self) -> Value {
+                struct_value(vec![$(self.$idx.into_value()),+])
+            }
+
+            fn check_type(
+                registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+                type_id: &ConcreteTypeId,
+            ) -> Result<()> {
+                let members = check_struct(registry, type_id, $num)?;
+                $($ty::check_type(registry, &members[$idx])?;)+
+                Ok(())
+            }
+        }
+
+        impl<$($ty),+> FromValue for ($($ty,)+)
+        where
+            $($ty: FromValue),+
+        {
+            fn from_value(value: Value) -> Result<Self> {
+                let [$($var),+] = __private::struct_fields::<$num>(value, "tuple")?;
+                Ok(($($ty::from_value($var)?,)+))
+            }
+
+            fn check_type(
+                registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+                type_id: &ConcreteTypeId,
+            ) -> Result<()> {
+                let members = check_struct(registry, type_id, $num)?;
+                $($ty::check_type(registry, &members[$idx])?;)+
+                Ok(())
+            }
+        }
+
+        impl<$($ty),+> IntoArgs for ($($ty,)+)
+        where
+            $($ty: IntoValue),+
+        {
+            fn into_values(self) -> Vec<Value> {
+                vec![$(self.$idx.into_value()),+]
+            }
+
+            fn check_types(
+                registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+                type_ids: &[ConcreteTypeId],
+            ) -> Result<()> {
+                check_arity(type_ids, $num)?;
+                $(<$ty as IntoValue>::check_type(registry, &type_ids[$idx])?;)+
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_tuple!(1; A a 0);
+impl_tuple!(2; A a 0, B b 1);
+impl_tuple!(3; A a 0, B b 1, C c 2);
+impl_tuple!(4; A a 0, B b 1, C c 2, D d 3);
+impl_tuple!(5; A a 0, B b 1, C c 2, D d 3, E e 4);
+impl_tuple!(6; A a 0, B b 1, C c 2, D d 3, E e 4, F f 5);
+impl_tuple!(7; A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6);
+impl_tuple!(8; A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7);
+impl_tuple!(9; A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8);
+impl_tuple!(10; A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8, J j 9);
+impl_tuple!(11; A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8, J j 9, K k 10);
+impl_tuple!(12; A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8, J j 9, K k 10, L l 11);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{context::NativeContext, utils::test::load_cairo, OptLevel};
+    use cairo_lang_sierra::program::Program;
+
+    #[derive(Debug, PartialEq, IntoValue, FromValue)]
+    struct Point {
+        x: u32,
+        y: u32,
+    }
+
+    #[derive(Debug, PartialEq, IntoValue, FromValue)]
+    enum Shape {
+        Empty,
+        Dot(Point),
+        Segment { from: Point, to: Point },
+    }
+
+    fn find_function_id(program: &Program, module_name: &str, name: &str) -> FunctionId {
+        let entry_point = format!("{0}::{0}::{1}", module_name, name);
+        program
+            .funcs
+            .iter()
+            .find(|f| f.id.debug_name.as_deref() == Some(entry_point.as_str()))
+            .unwrap()
+            .id
+            .clone()
+    }
+
+    #[test]
+    fn derive_roundtrip() {
+        let shape = Shape::Segment {
+            from: Point { x: 1, y: 2 },
+            to: Point { x: 3, y: 4 },
+        };
+
+        let value = shape.into_value();
+        assert_eq!(
+            value,
+            Value::Enum {
+                tag: 2,
+                value: Box::new(Value::Struct {
+                    fields: vec![
+                        Value::Struct {
+                            fields: vec![Value::Uint32(1), Value::Uint32(2)],
+                            debug_name: None,
+                        },
+                        Value::Struct {
+                            fields: vec![Value::Uint32(3), Value::Uint32(4)],
+                            debug_name: None,
+                        },
+                    ],
+                    debug_name: None,
+                }),
+                debug_name: None,
+            },
+        );
+        assert_eq!(
+            Shape::from_value(value).unwrap(),
+            Shape::Segment {
+                from: Point { x: 1, y: 2 },
+                to: Point { x: 3, y: 4 },
+            },
+        );
+        assert!(Point::from_value(Value::Uint32(0)).is_err());
+    }
+
+    #[test]
+    fn invoke_typed() {
+        let (module_name, program) = load_cairo! {
+            #[derive(Drop)]
+            struct Point {
+                x: u32,
+                y: u32,
+            }
+
+            #[derive(Drop)]
+            enum Shape {
+                Empty,
+                Dot: Point,
+                Segment: (Point, Point),
+            }
+
+            fn run_test(shape: Shape, scale: u32) -> Option<Point> {
+                match shape {
+                    Shape::Empty => Option::None,
+                    Shape::Dot(p) => Option::Some(Point { x: p.x * scale, y: p.y * scale }),
+                    Shape::Segment((a, b)) => Option::Some(Point {
+                        x: (b.x - a.x) * scale,
+                        y: (b.y - a.y) * scale,
+                    }),
+                }
+            }
+        };
+
+        let context = NativeContext::new();
+        let module = context
+            .compile(&program, false, Some(Default::default()))
+            .unwrap();
+        let executor = JitNativeExecutor::from_native_module(module, OptLevel::default()).unwrap();
+        let function_id = find_function_id(&program, &module_name, "run_test");
+
+        let function = executor
+            .bind::<(Shape, u32), std::result::Result<(Option<Point>,), ((), Vec<Felt>)>>(
+                &function_id,
+            )
+            .unwrap();
+
+        let result = function
+            .invoke(
+                (
+                    Shape::Segment {
+                        from: Point { x: 1, y: 2 },
+                        to: Point { x: 4, y: 8 },
+                    },
+                    2,
+                ),
+                Some(u64::MAX),
+            )
+            .unwrap();
+        assert_eq!(result.return_value, Ok((Some(Point { x: 6, y: 12 }),)));
+
+        let result = function.invoke((Shape::Empty, 2), Some(u64::MAX)).unwrap();
+        assert_eq!(result.return_value, Ok((None,)));
+
+        // Overflow panics.
+        let result = function
+            .invoke((Shape::Dot(Point { x: 2, y: 1 }), u32::MAX), Some(u64::MAX))
+            .unwrap();
+        assert!(result.return_value.is_err());
+    }
+
+    #[test]
+    fn bind_type_mismatch() {
+        let (module_name, program) = load_cairo! {
+            fn run_test(a: felt252, b: u64) -> u64 {
+                let _ = a;
+                b
+            }
+        };
+
+        let context = NativeContext::new();
+        let module = context
+            .compile(&program, false, Some(Default::default()))
+            .unwrap();
+        let executor = JitNativeExecutor::from_native_module(module, OptLevel::default()).unwrap();
+        let function_id = find_function_id(&program, &module_name, "run_test");
+
+        assert!(matches!(
+            executor.bind::<(Felt, u32), u64>(&function_id),
+            Err(Error::TypeMismatch(_)),
+        ));
+        assert!(matches!(
+            executor.bind::<(Felt,), u64>(&function_id),
+            Err(Error::TypeMismatch(_)),
+        ));
+        assert!(matches!(
+            executor.bind::<(Felt, u64), Felt>(&function_id),
+            Err(Error::TypeMismatch(_)),
+        ));
+
+        let result = executor
+            .invoke::<(Felt, u64), u64>(&function_id, (Felt::ONE, 42), None)
+            .unwrap();
+        assert_eq!(result.return_value, 42);
+    }
+}
@@ -204,7 +204,7 @@ fn build_drop<'ctx>(
 ) -> Result<Region<'ctx>> {
     let location = Location::unknown(context);
     if metadata.get::<ReallocBindingsMeta>().is_none() {
-        metadata.insert(ReallocBindingsMeta::new(context, module));
+        metadata.insert(ReallocBindingsMeta::new(context, module)?);
     }
 
     let value_ty = registry.build_type(context, module, metadata, info.self_ty())?;
@@ -83,7 +83,7 @@ fn build_dup<'ctx>(
 ) -> Result<Region<'ctx>> {
     let location = Location::unknown(context);
     if metadata.get::<ReallocBindingsMeta>().is_none() {
-        metadata.insert(ReallocBindingsMeta::new(context, module));
+        metadata.insert(ReallocBindingsMeta::new(context, module)?);
     }
 
     let inner_ty = registry.get_type(&info.ty)?;
@@ -178,7 +178,7 @@ fn build_drop<'ctx>(
 ) -> Result<Region<'ctx>> {
     let location = Location::unknown(context);
     if metadata.get::<ReallocBindingsMeta>().is_none() {
-        metadata.insert(ReallocBindingsMeta::new(context, module));
+        metadata.insert(ReallocBindingsMeta::new(context, module)?);
     }
 
     let inner_ty = registry.build_type(context, module, metadata, &info.ty)?;
@@ -205,7 +205,7 @@ pub fn build_sha256_state_handle<'ctx>(
 ) -> Result<Type<'ctx>> {
     let location = Location::unknown(context);
     if metadata.get::<ReallocBindingsMeta>().is_none() {
-        metadata.insert(ReallocBindingsMeta::new(context, module));
+        metadata.insert(ReallocBindingsMeta::new(context, module)?);
     }
 
     DupOverridesMeta::register_with(context, module, registry, metadata, info.self_ty(), |_| {
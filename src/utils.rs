@@ -20,6 +20,7 @@ use melior::{
 };
 use num_bigint::{BigInt, BigUint, Sign};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use starknet_types_core::felt::Felt;
 use std::sync::{Arc, LazyLock};
 use std::{
@@ -175,6 +176,11 @@ pub fn decode_error_message(data: &[u8]) -> String {
     })
 }
 
+/// Return the hex-encoded SHA-256 of a compiled shared library, as stored alongside it.
+pub(crate) fn library_checksum(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
 /// Return the layout for an integer of arbitrary width.
 ///
 /// This assumes the platform's maximum (effective) alignment is 16 bytes, and that every integer
@@ -3,14 +3,13 @@
 //! A Rusty interface to provide parameters to cairo-native entry point calls.
 
 use crate::{
+    allocator::{native_free, native_malloc},
     error::{panic::ToNativeAssertError, CompilerError, Error},
-    native_assert, native_panic,
+    native_assert,
     runtime::FeltDict,
     starknet::{Secp256k1Point, Secp256r1Point},
     types::TypeBuilder,
-    utils::{
-        felt252_bigint, get_integer_layout, layout_repeat, libc_free, libc_malloc, RangeExt, PRIME,
-    },
+    utils::{felt252_bigint, get_integer_layout, layout_repeat, RangeExt, PRIME},
 };
 use bumpalo::Bump;
 use cairo_lang_sierra::{
@@ -28,7 +27,7 @@ use num_bigint::{BigInt, BigUint, Sign};
 use num_traits::{Euclid, One};
 use starknet_types_core::felt::Felt;
 use std::{
-    alloc::{alloc, Layout},
+    alloc::Layout,
     collections::HashMap,
     ffi::c_void,
     mem::forget,
@@ -37,6 +36,9 @@ use std::{
     slice,
 };
 
+mod felt_serde;
+mod validation;
+
 /// A Value is a value that can be passed to either the JIT engine or a compiled program as an argument or received as a result.
 ///
 /// They map to the cairo/sierra types.
@@ -214,7 +216,14 @@ impl Value {
                     ptr
                 }
 
-                Self::Bytes31(_) => native_panic!("todo: allocate type Bytes31"),
+                Self::Bytes31(data) => {
+                    let ptr = arena.alloc_layout(get_integer_layout(248)).cast();
+
+                    let mut bytes = [0u8; 32];
+                    bytes[..31].copy_from_slice(data);
+                    ptr.cast::<[u8; 32]>().as_mut().copy_from_slice(&bytes);
+                    ptr
+                }
                 Self::Array(data) => {
                     if let CoreTypeConcrete::Array(info) = Self::resolve_type(ty, registry)? {
                         let elem_ty = registry.get_type(&info.ty)?;
@@ -229,9 +238,10 @@ impl Value {
                         let ptr: *mut () = match len {
                             0 => std::ptr::null_mut(),
                             _ => {
-                                let ptr: *mut () =
-                                    libc_malloc(elem_layout.size() * data.len() + refcount_offset)
-                                        .cast();
+                                let ptr: *mut () = native_malloc(
+                                    elem_layout.size() * data.len() + refcount_offset,
+                                )
+                                .cast();
 
                                 // Write reference count.
                                 ptr.cast::<(u32, u32)>().write((1, len));
@@ -255,7 +265,7 @@ impl Value {
                         let ptr_ptr = if ptr.is_null() {
                             null_mut()
                         } else {
-                            let ptr_ptr: *mut *mut () = libc_malloc(8).cast();
+                            let ptr_ptr: *mut *mut () = native_malloc(8).cast();
                             ptr_ptr.write(ptr);
                             ptr_ptr
                         };
@@ -296,6 +306,14 @@ impl Value {
                     fields: members, ..
                 } => {
                     if let CoreTypeConcrete::Struct(info) = Self::resolve_type(ty, registry)? {
+                        if members.len() != info.members.len() {
+                            return Err(Error::UnexpectedValue(format!(
+                                "expected value of type {:?} but got a struct with {} fields",
+                                type_id.debug_name,
+                                members.len()
+                            )));
+                        }
+
                         let mut layout: Option<Layout> = None;
                         let mut data = Vec::with_capacity(info.members.len());
 
@@ -358,9 +376,12 @@ impl Value {
                 }
                 Self::Enum { tag, value, .. } => {
                     if let CoreTypeConcrete::Enum(info) = Self::resolve_type(ty, registry)? {
-                        native_assert!(*tag < info.variants.len(), "Variant index out of range.");
-
-                        let payload_type_id = &info.variants[*tag];
+                        let payload_type_id = info.variants.get(*tag).ok_or_else(|| {
+                            Error::UnexpectedValue(format!(
+                                "expected value of type {:?} but got variant index {tag}",
+                                type_id.debug_name
+                            ))
+                        })?;
                         let payload = value.to_ptr(
                             arena,
                             registry,
@@ -376,12 +397,12 @@ impl Value {
                         let ptr = arena.alloc_layout(layout).cast::<()>().as_ptr();
 
                         match tag_layout.size() {
-                            0 => native_panic!("An enum without variants cannot be instantiated."),
+                            0 => return Err(Error::make_unsupported_type(type_id)),
                             1 => *ptr.cast::<u8>() = *tag as u8,
                             2 => *ptr.cast::<u16>() = *tag as u16,
                             4 => *ptr.cast::<u32>() = *tag as u32,
                             8 => *ptr.cast::<u64>() = *tag as u64,
-                            _ => native_panic!("reached the maximum size for an enum"),
+                            _ => return Err(Error::make_unsupported_type(type_id)),
                         }
 
                         std::ptr::copy_nonoverlapping(
@@ -409,19 +430,21 @@ impl Value {
                         // implementations (if any) for the value type. This is required to be able to drop
                         // the dictionary automatically when their reference count drops to zero.
                         let drop_fn = find_dict_drop_override(&info.ty);
+                        let mappings = HashMap::with_capacity(map.len());
                         let mut value_map = FeltDict {
-                            mappings: HashMap::with_capacity(map.len()),
-
-                            layout: elem_layout,
+                            // The elements' storage must be able to hold as many elements as the
+                            // mappings can before reallocating.
                             elements: if map.is_empty() {
                                 null_mut()
                             } else {
-                                alloc(Layout::from_size_align_unchecked(
-                                    elem_layout.pad_to_align().size() * map.len(),
-                                    elem_layout.align(),
-                                ))
+                                native_malloc(
+                                    elem_layout.pad_to_align().size() * mappings.capacity(),
+                                )
                                 .cast()
                             },
+                            mappings,
+
+                            layout: elem_layout,
 
                             drop_fn,
 
@@ -544,13 +567,22 @@ impl Value {
 
                     ptr
                 }
-                Self::Secp256K1Point { .. } => native_panic!("todo: allocate type Secp256K1Point"),
-                Self::Secp256R1Point { .. } => native_panic!("todo: allocate type Secp256R1Point"),
-                Self::Null => {
-                    native_panic!(
-                        "unimplemented: null is meant as return value for nullable for now"
-                    )
+                Self::Secp256K1Point(point) => {
+                    let ptr = arena.alloc_layout(Layout::new::<Secp256k1Point>()).cast();
+                    ptr.cast::<Secp256k1Point>().write(*point);
+
+                    ptr
+                }
+                Self::Secp256R1Point(point) => {
+                    let ptr = arena.alloc_layout(Layout::new::<Secp256r1Point>()).cast();
+                    ptr.cast::<Secp256r1Point>().write(*point);
+
+                    ptr
                 }
+                Self::Null => Err(Error::UnexpectedValue(format!(
+                    "expected value of type {:?} but got a null, which is only valid as a nullable argument",
+                    type_id.debug_name
+                )))?,
                 Self::IntRange { x, y } => {
                     if let CoreTypeConcrete::IntRange(info) = Self::resolve_type(ty, registry)? {
                         let inner = registry.get_type(&info.ty)?;
@@ -578,9 +610,10 @@ impl Value {
 
                         NonNull::new_unchecked(ptr).cast()
                     } else {
-                        native_panic!(
-                            "an IntRange value should always have an IntRange CoreTypeConcrete"
-                        )
+                        Err(Error::UnexpectedValue(format!(
+                            "expected value of type {:?} but got an int range",
+                            type_id.debug_name
+                        )))?
                     }
                 }
             }
@@ -694,8 +727,8 @@ impl Value {
                             }
 
                             // Free array storage.
-                            libc_free(array_ptr.byte_sub(refcount_offset).cast());
-                            libc_free(array_ptr_ptr.cast());
+                            native_free(array_ptr.byte_sub(refcount_offset).cast());
+                            native_free(array_ptr_ptr.cast());
                         }
 
                         array_value
@@ -708,7 +741,7 @@ impl Value {
                     let value = Self::from_ptr(inner, &info.ty, registry, should_drop)?;
 
                     if should_drop {
-                        libc_free(inner.as_ptr().cast());
+                        native_free(inner.as_ptr().cast());
                     }
 
                     value
@@ -748,7 +781,7 @@ impl Value {
                 CoreTypeConcrete::Uint64(_) => Self::Uint64(*ptr.cast::<u64>().as_ref()),
                 CoreTypeConcrete::Uint128(_) => Self::Uint128(*ptr.cast::<u128>().as_ref()),
                 CoreTypeConcrete::Uint128MulGuarantee(_) => {
-                    native_panic!("todo: implement uint128mulguarantee from_ptr")
+                    return Err(Error::make_unsupported_type(type_id))
                 }
                 CoreTypeConcrete::Sint8(_) => Self::Sint8(*ptr.cast::<i8>().as_ref()),
                 CoreTypeConcrete::Sint16(_) => Self::Sint16(*ptr.cast::<i16>().as_ref()),
@@ -771,14 +804,14 @@ impl Value {
                         )?;
 
                         if should_drop {
-                            libc_free(inner_ptr.cast());
+                            native_free(inner_ptr.cast());
                         }
 
                         value
                     }
                 }
                 CoreTypeConcrete::Uninitialized(_) => {
-                    native_panic!("todo: implement uninit from_ptr or ignore the return value")
+                    return Err(Error::make_unsupported_type(type_id))
                 }
                 CoreTypeConcrete::Enum(info) => {
                     let tag_layout = crate::utils::get_integer_layout(match info.variants.len() {
@@ -790,7 +823,7 @@ impl Value {
                     let tag_value = match info.variants.len() {
                         0 => {
                             // An enum without variants is basically the `!` (never) type in Rust.
-                            native_panic!("An enum without variants is not a valid type.")
+                            return Err(Error::make_unsupported_type(type_id));
                         }
                         1 => 0,
                         _ => match tag_layout.size() {
@@ -798,7 +831,7 @@ impl Value {
                             2 => *ptr.cast::<u16>().as_ref() as usize,
                             4 => *ptr.cast::<u32>().as_ref() as usize,
                             8 => *ptr.cast::<u64>().as_ref() as usize,
-                            _ => native_panic!("reached the maximum size for an enum"),
+                            _ => return Err(Error::make_unsupported_type(type_id)),
                         },
                     };
 
@@ -899,7 +932,7 @@ impl Value {
                     }
                 }
                 CoreTypeConcrete::Felt252DictEntry(_) => {
-                    native_panic!("unimplemented: should be impossible to return")
+                    return Err(Error::make_unsupported_type(type_id))
                 }
                 CoreTypeConcrete::Pedersen(_)
                 | CoreTypeConcrete::Poseidon(_)
@@ -909,7 +942,7 @@ impl Value {
                 | CoreTypeConcrete::EcOp(_)
                 | CoreTypeConcrete::GasBuiltin(_)
                 | CoreTypeConcrete::SegmentArena(_) => {
-                    native_panic!("handled before: {:?}", type_id)
+                    return Err(Error::make_unsupported_type(type_id))
                 }
                 // Does it make sense for programs to return this? Should it be implemented
                 CoreTypeConcrete::Starknet(selector) => match selector {
@@ -924,7 +957,7 @@ impl Value {
                         Self::Felt252(data)
                     }
                     StarknetTypeConcrete::System(_) => {
-                        native_panic!("should be handled before")
+                        return Err(Error::make_unsupported_type(type_id))
                     }
                     StarknetTypeConcrete::Secp256Point(info) => match info {
                         Secp256PointTypeConcrete::K1(_) => {
@@ -937,10 +970,10 @@ impl Value {
                         }
                     },
                     StarknetTypeConcrete::Sha256StateHandle(_) => {
-                        native_panic!("todo: implement Sha256StateHandle from_ptr")
+                        return Err(Error::make_unsupported_type(type_id))
                     }
                 },
-                CoreTypeConcrete::Span(_) => native_panic!("implement span from_ptr"),
+                CoreTypeConcrete::Span(_) => return Err(Error::make_unsupported_type(type_id)),
                 CoreTypeConcrete::Snapshot(info) => {
                     Self::from_ptr(ptr, &info.ty, registry, should_drop)?
                 }
@@ -949,7 +982,7 @@ impl Value {
                     Self::Bytes31(data)
                 }
 
-                CoreTypeConcrete::Const(_) => native_panic!("implement const from_ptr"),
+                CoreTypeConcrete::Const(_) => return Err(Error::make_unsupported_type(type_id)),
                 CoreTypeConcrete::BoundedInt(info) => {
                     let mut data = BigInt::from_biguint(
                         Sign::Plus,
@@ -986,7 +1019,9 @@ impl Value {
                 }
                 CoreTypeConcrete::Coupon(_)
                 | CoreTypeConcrete::Circuit(_)
-                | CoreTypeConcrete::RangeCheck96(_) => native_panic!("implement from_ptr"),
+                | CoreTypeConcrete::RangeCheck96(_) => {
+                    return Err(Error::make_unsupported_type(type_id))
+                }
                 CoreTypeConcrete::IntRange(info) => {
                     let member = registry.get_type(&info.ty)?;
                     let member_layout = member.layout(registry)?;
@@ -1016,8 +1051,8 @@ impl Value {
                         y: y.into(),
                     }
                 }
-                CoreTypeConcrete::Blake(_) => native_panic!("Implement from_ptr for Blake type"),
-                CoreTypeConcrete::QM31(_) => native_panic!("Implement from_ptr for QM31 type"),
+                CoreTypeConcrete::Blake(_) => return Err(Error::make_unsupported_type(type_id)),
+                CoreTypeConcrete::QM31(_) => return Err(Error::make_unsupported_type(type_id)),
             }
         })
     }
@@ -1242,6 +1277,28 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_from_ptr_unsupported_type() {
+        let program = ProgramParser::new()
+            .parse(
+                "type felt252 = felt252;
+                type Uninit = Uninitialized<felt252>;",
+            )
+            .unwrap();
+
+        let registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(&program).unwrap();
+
+        let mut data = [0u64; 4];
+        let error = Value::from_ptr(
+            NonNull::from(&mut data).cast(),
+            &program.type_declarations[1].id,
+            &registry,
+            false,
+        )
+        .unwrap_err();
+        assert!(matches!(error, Error::UnsupportedType(name) if name == "Uninit"));
+    }
+
     #[test]
     fn test_to_jit_uint8() {
         let program = ProgramParser::new().parse("type u8 = u8;").unwrap();
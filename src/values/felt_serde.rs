@@ -0,0 +1,392 @@
+//! # Cairo `Serde` encoding
+//!
+//! Conversions between [`Value`]s and the felt encoding produced by Cairo's `Serde` trait, which
+//! is the one used for contract calldata and return data:
+//!   - Felts, integers, bounded ints and `bytes31` are encoded as a single felt. Negative integers
+//!     are encoded as their felt negation.
+//!   - Structs are encoded as their members, in order.
+//!   - Enums are encoded as the variant index followed by its payload.
+//!   - Arrays (and spans) are encoded as their length followed by their elements.
+//!
+//! It follows that `u256` is encoded as its low and high halves, `ByteArray` as its full words
+//! array followed by the pending word and its length, and `Option<T>` as either `0, T` or `1`.
+
+use super::Value;
+use crate::error::{Error, Result};
+use cairo_lang_sierra::{
+    extensions::{
+        core::{CoreLibfunc, CoreType, CoreTypeConcrete},
+        starknet::StarknetTypeConcrete,
+    },
+    ids::ConcreteTypeId,
+    program_registry::ProgramRegistry,
+};
+use num_bigint::{BigInt, BigUint};
+use starknet_types_core::felt::Felt;
+use std::slice;
+
+impl Value {
+    /// Encode the value using Cairo's `Serde` rules.
+    pub fn serialize_to_felts(&self) -> Result<Vec<Felt>> {
+        let mut felts = Vec::new();
+        self.serialize_into(&mut felts)?;
+        Ok(felts)
+    }
+
+    /// Decode a value of the given type from its Cairo `Serde` encoding.
+    ///
+    /// Every felt must be consumed by the value.
+    pub fn deserialize_from_felts(
+        felts: &[Felt],
+        type_id: &ConcreteTypeId,
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    ) -> Result<Self> {
+        let mut iter = felts.iter();
+        let value = Self::deserialize_from(&mut iter, type_id, registry)?;
+
+        if iter.len() != 0 {
+            return Err(Error::FeltSerialization(format!(
+                "{} trailing felts after deserializing '{}'",
+                iter.len(),
+                type_name(type_id),
+            )));
+        }
+
+        Ok(value)
+    }
+
+    /// Decode consecutive values of the given types from their Cairo `Serde` encoding, such as the
+    /// arguments of a function.
+    ///
+    /// Every felt must be consumed by the values.
+    pub fn deserialize_seq_from_felts<'a>(
+        felts: &[Felt],
+        type_ids: impl IntoIterator<Item = &'a ConcreteTypeId>,
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    ) -> Result<Vec<Self>> {
+        let mut iter = felts.iter();
+        let values = type_ids
+            .into_iter()
+            .map(|type_id| Self::deserialize_from(&mut iter, type_id, registry))
+            .collect::<Result<Vec<_>>>()?;
+
+        if iter.len() != 0 {
+            return Err(Error::FeltSerialization(format!(
+                "{} trailing felts after deserializing {} values",
+                iter.len(),
+                values.len(),
+            )));
+        }
+
+        Ok(values)
+    }
+
+    fn serialize_into(&self, felts: &mut Vec<Felt>) -> Result<()> {
+        match self {
+            Self::Felt252(value) => felts.push(*value),
+            Self::Bytes31(data) => felts.push(Felt::from_bytes_le_slice(data)),
+            Self::Uint8(value) => felts.push((*value).into()),
+            Self::Uint16(value) => felts.push((*value).into()),
+            Self::Uint32(value) => felts.push((*value).into()),
+            Self::Uint64(value) => felts.push((*value).into()),
+            Self::Uint128(value) => felts.push((*value).into()),
+            Self::Sint8(value) => felts.push((*value).into()),
+            Self::Sint16(value) => felts.push((*value).into()),
+            Self::Sint32(value) => felts.push((*value).into()),
+            Self::Sint64(value) => felts.push((*value).into()),
+            Self::Sint128(value) => felts.push((*value).into()),
+            Self::BoundedInt { value, .. } => felts.push(*value),
+            Self::Array(values) => {
+                felts.push(values.len().into());
+                for value in values {
+                    value.serialize_into(felts)?;
+                }
+            }
+            Self::Struct { fields, .. } => {
+                for field in fields {
+                    field.serialize_into(felts)?;
+                }
+            }
+            Self::Enum { tag, value, .. } => {
+                felts.push((*tag).into());
+                value.serialize_into(felts)?;
+            }
+            Self::Felt252Dict { .. }
+            | Self::EcPoint(..)
+            | Self::EcState(..)
+            | Self::Secp256K1Point(_)
+            | Self::Secp256R1Point(_)
+            | Self::IntRange { .. }
+            | Self::Null => {
+                return Err(Error::FeltSerialization(format!(
+                    "value {self:?} has no Serde encoding"
+                )))
+            }
+        }
+
+        Ok(())
+    }
+
+    fn deserialize_from(
+        felts: &mut slice::Iter<Felt>,
+        type_id: &ConcreteTypeId,
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    ) -> Result<Self> {
+        Ok(match registry.get_type(type_id)? {
+            CoreTypeConcrete::Felt252(_)
+            | CoreTypeConcrete::Starknet(
+                StarknetTypeConcrete::ClassHash(_)
+                | StarknetTypeConcrete::ContractAddress(_)
+                | StarknetTypeConcrete::StorageBaseAddress(_)
+                | StarknetTypeConcrete::StorageAddress(_),
+            ) => Self::Felt252(next_felt(felts, type_id)?),
+            CoreTypeConcrete::Bytes31(_) => {
+                let data = next_felt(felts, type_id)?.to_bytes_le();
+                if data[31] != 0 {
+                    return Err(out_of_range(type_id));
+                }
+
+                Self::Bytes31(data[..31].try_into().map_err(|_| out_of_range(type_id))?)
+            }
+            CoreTypeConcrete::Uint8(_) => Self::Uint8(next_unsigned(felts, type_id)?),
+            CoreTypeConcrete::Uint16(_) => Self::Uint16(next_unsigned(felts, type_id)?),
+            CoreTypeConcrete::Uint32(_) => Self::Uint32(next_unsigned(felts, type_id)?),
+            CoreTypeConcrete::Uint64(_) => Self::Uint64(next_unsigned(felts, type_id)?),
+            CoreTypeConcrete::Uint128(_) => Self::Uint128(next_unsigned(felts, type_id)?),
+            CoreTypeConcrete::Sint8(_) => Self::Sint8(next_signed(felts, type_id)?),
+            CoreTypeConcrete::Sint16(_) => Self::Sint16(next_signed(felts, type_id)?),
+            CoreTypeConcrete::Sint32(_) => Self::Sint32(next_signed(felts, type_id)?),
+            CoreTypeConcrete::Sint64(_) => Self::Sint64(next_signed(felts, type_id)?),
+            CoreTypeConcrete::Sint128(_) => Self::Sint128(next_signed(felts, type_id)?),
+            CoreTypeConcrete::BoundedInt(info) => {
+                let value = next_felt(felts, type_id)?;
+
+                let int_value = value.to_bigint();
+                if int_value < info.range.lower || int_value >= info.range.upper {
+                    return Err(out_of_range(type_id));
+                }
+
+                Self::BoundedInt {
+                    value,
+                    range: info.range.clone(),
+                }
+            }
+            CoreTypeConcrete::Array(info) => {
+                let len: usize = next_unsigned(felts, type_id)?;
+
+                // Avoid trusting the length prefix when reserving memory.
+                let mut values = Vec::with_capacity(len.min(felts.len()));
+                for _ in 0..len {
+                    values.push(Self::deserialize_from(felts, &info.ty, registry)?);
+                }
+
+                Self::Array(values)
+            }
+            CoreTypeConcrete::Struct(info) => Self::Struct {
+                fields: info
+                    .members
+                    .iter()
+                    .map(|member_ty| Self::deserialize_from(felts, member_ty, registry))
+                    .collect::<Result<_>>()?,
+                debug_name: type_id.debug_name.as_ref().map(|x| x.to_string()),
+            },
+            CoreTypeConcrete::Enum(info) => {
+                let tag: usize = next_unsigned(felts, type_id)?;
+                let payload_ty = info.variants.get(tag).ok_or_else(|| {
+                    Error::FeltSerialization(format!(
+                        "variant index {tag} out of range for '{}'",
+                        type_name(type_id),
+                    ))
+                })?;
+
+                Self::Enum {
+                    tag,
+                    value: Box::new(Self::deserialize_from(felts, payload_ty, registry)?),
+                    debug_name: type_id.debug_name.as_ref().map(|x| x.to_string()),
+                }
+            }
+            CoreTypeConcrete::Snapshot(info) => Self::deserialize_from(felts, &info.ty, registry)?,
+            CoreTypeConcrete::Box(info) => Self::deserialize_from(felts, &info.ty, registry)?,
+            CoreTypeConcrete::NonZero(info) => Self::deserialize_from(felts, &info.ty, registry)?,
+            _ => {
+                return Err(Error::FeltSerialization(format!(
+                    "type '{}' has no Serde encoding",
+                    type_name(type_id),
+                )))
+            }
+        })
+    }
+}
+
+fn type_name(type_id: &ConcreteTypeId) -> String {
+    type_id
+        .debug_name
+        .as_ref()
+        .map(|x| x.to_string())
+        .unwrap_or_else(|| type_id.id.to_string())
+}
+
+fn out_of_range(type_id: &ConcreteTypeId) -> Error {
+    Error::FeltSerialization(format!("value out of range for '{}'", type_name(type_id)))
+}
+
+fn next_felt(felts: &mut slice::Iter<Felt>, type_id: &ConcreteTypeId) -> Result<Felt> {
+    felts.next().copied().ok_or_else(|| {
+        Error::FeltSerialization(format!(
+            "unexpected end of input while deserializing '{}'",
+            type_name(type_id),
+        ))
+    })
+}
+
+fn next_unsigned<T>(felts: &mut slice::Iter<Felt>, type_id: &ConcreteTypeId) -> Result<T>
+where
+    T: TryFrom<BigUint>,
+{
+    T::try_from(next_felt(felts, type_id)?.to_biguint()).map_err(|_| out_of_range(type_id))
+}
+
+fn next_signed<T>(felts: &mut slice::Iter<Felt>, type_id: &ConcreteTypeId) -> Result<T>
+where
+    T: TryFrom<BigInt>,
+{
+    T::try_from(next_felt(felts, type_id)?.to_bigint()).map_err(|_| out_of_range(type_id))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::test::{load_cairo, run_program};
+
+    /// Extract the value from a `PanicResult::Ok`, if the function could panic.
+    fn unwrap_panic_result(value: Value) -> Value {
+        match value {
+            Value::Enum {
+                tag: 0,
+                value,
+                debug_name: Some(debug_name),
+            } if debug_name.starts_with("core::panics::PanicResult") => match *value {
+                Value::Struct { mut fields, .. } => fields.remove(0),
+                _ => panic!("invalid panic result"),
+            },
+            value => value,
+        }
+    }
+
+    #[test]
+    fn serde_matches_cairo() {
+        let program = load_cairo! {
+            use core::serde::Serde;
+
+            #[derive(Drop, Serde)]
+            enum Shape {
+                Empty,
+                Dot: (u8, i16),
+                Named: ByteArray,
+            }
+
+            #[derive(Drop, Serde)]
+            struct Data {
+                a: u256,
+                b: Array<u32>,
+                c: Option<i8>,
+                d: Option<i8>,
+                e: Array<Shape>,
+            }
+
+            fn make() -> Data {
+                Data {
+                    a: u256 { low: 5, high: 7 },
+                    b: array![1, 2, 3],
+                    c: Option::Some(-3),
+                    d: Option::None,
+                    e: array![
+                        Shape::Empty,
+                        Shape::Dot((255, -1000)),
+                        Shape::Named("a byte array longer than thirty-one bytes"),
+                    ],
+                }
+            }
+
+            fn serialize() -> Array<felt252> {
+                let mut output = array![];
+                make().serialize(ref output);
+                output
+            }
+        };
+
+        let value = unwrap_panic_result(run_program(&program, "make", &[]).return_value);
+        let felts = match unwrap_panic_result(run_program(&program, "serialize", &[]).return_value)
+        {
+            Value::Array(felts) => felts
+                .into_iter()
+                .map(|x| match x {
+                    Value::Felt252(x) => x,
+                    _ => panic!("expected a felt"),
+                })
+                .collect::<Vec<_>>(),
+            _ => panic!("expected an array"),
+        };
+
+        assert_eq!(value.serialize_to_felts().unwrap(), felts);
+
+        let registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(&program.1).unwrap();
+        let type_id = &program
+            .1
+            .type_declarations
+            .iter()
+            .find(|x| {
+                x.id.debug_name
+                    .as_ref()
+                    .is_some_and(|name| !name.starts_with('@') && name.ends_with("::Data"))
+            })
+            .unwrap()
+            .id;
+
+        assert_eq!(
+            Value::deserialize_from_felts(&felts, type_id, &registry).unwrap(),
+            value,
+        );
+
+        // Truncated and oversized inputs.
+        assert!(matches!(
+            Value::deserialize_from_felts(&felts[..felts.len() - 1], type_id, &registry),
+            Err(Error::FeltSerialization(_)),
+        ));
+        assert!(matches!(
+            Value::deserialize_from_felts(
+                &[felts.as_slice(), &[Felt::ONE]].concat(),
+                type_id,
+                &registry
+            ),
+            Err(Error::FeltSerialization(_)),
+        ));
+
+        // Consecutive values share the same input.
+        assert_eq!(
+            Value::deserialize_seq_from_felts(
+                &[felts.as_slice(), &felts].concat(),
+                [type_id, type_id],
+                &registry
+            )
+            .unwrap(),
+            [value.clone(), value],
+        );
+        assert!(matches!(
+            Value::deserialize_seq_from_felts(&felts, [type_id, type_id], &registry),
+            Err(Error::FeltSerialization(_)),
+        ));
+        assert!(matches!(
+            Value::deserialize_seq_from_felts(&felts, [], &registry),
+            Err(Error::FeltSerialization(_)),
+        ));
+    }
+
+    #[test]
+    fn serialize_unsupported() {
+        assert!(matches!(
+            Value::Null.serialize_to_felts(),
+            Err(Error::FeltSerialization(_)),
+        ));
+    }
+}
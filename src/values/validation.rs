@@ -0,0 +1,364 @@
+//! # Value validation
+//!
+//! Checks that a [`Value`] matches a concrete type before it's marshalled into the memory layout
+//! expected by the compiled program. Marshalling assumes the value matches its type, therefore
+//! mismatched values must be rejected beforehand.
+//!
+//! Errors contain the path to the offending part of the value, for example
+//! `arg[2].fields[1].tag`.
+
+use super::Value;
+use crate::{
+    error::{Error, Result},
+    starknet::{Secp256k1Point, Secp256r1Point},
+};
+use cairo_lang_sierra::{
+    extensions::{
+        core::{CoreLibfunc, CoreType, CoreTypeConcrete},
+        starknet::{secp256::Secp256PointTypeConcrete, StarknetTypeConcrete},
+    },
+    ids::ConcreteTypeId,
+    program_registry::ProgramRegistry,
+};
+use starknet_types_core::felt::Felt;
+use std::fmt::{self, Write};
+
+impl Value {
+    /// Check that the value matches the given type.
+    pub fn validate(
+        &self,
+        type_id: &ConcreteTypeId,
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    ) -> Result<()> {
+        self.validate_at(&mut String::from("value"), type_id, registry)
+    }
+
+    /// Check that the value matches the given type, using `path` as the root of the error paths.
+    pub(crate) fn validate_at(
+        &self,
+        path: &mut String,
+        type_id: &ConcreteTypeId,
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    ) -> Result<()> {
+        let ty = registry.get_type(type_id)?;
+
+        match (self, ty) {
+            // Wrappers which share their inner type's value.
+            (_, CoreTypeConcrete::Snapshot(info) | CoreTypeConcrete::Box(info)) => {
+                self.validate_at(path, &info.ty, registry)?
+            }
+            (_, CoreTypeConcrete::NonZero(info)) => {
+                self.validate_at(path, &info.ty, registry)?;
+                if self.is_zero() {
+                    return Err(invalid_value(path, "expected a non-zero value"));
+                }
+            }
+            (Self::Null, CoreTypeConcrete::Nullable(_)) => {}
+            (_, CoreTypeConcrete::Nullable(info)) => self.validate_at(path, &info.ty, registry)?,
+
+            (
+                Self::Felt252(_),
+                CoreTypeConcrete::Felt252(_)
+                | CoreTypeConcrete::Starknet(
+                    StarknetTypeConcrete::ClassHash(_)
+                    | StarknetTypeConcrete::ContractAddress(_)
+                    | StarknetTypeConcrete::StorageBaseAddress(_)
+                    | StarknetTypeConcrete::StorageAddress(_),
+                ),
+            )
+            | (Self::Bytes31(_), CoreTypeConcrete::Bytes31(_))
+            | (Self::Uint8(_), CoreTypeConcrete::Uint8(_))
+            | (Self::Uint16(_), CoreTypeConcrete::Uint16(_))
+            | (Self::Uint32(_), CoreTypeConcrete::Uint32(_))
+            | (Self::Uint64(_), CoreTypeConcrete::Uint64(_))
+            | (Self::Uint128(_), CoreTypeConcrete::Uint128(_))
+            | (Self::Sint8(_), CoreTypeConcrete::Sint8(_))
+            | (Self::Sint16(_), CoreTypeConcrete::Sint16(_))
+            | (Self::Sint32(_), CoreTypeConcrete::Sint32(_))
+            | (Self::Sint64(_), CoreTypeConcrete::Sint64(_))
+            | (Self::Sint128(_), CoreTypeConcrete::Sint128(_))
+            | (Self::EcPoint(..), CoreTypeConcrete::EcPoint(_))
+            | (Self::EcState(..), CoreTypeConcrete::EcState(_))
+            | (
+                Self::Secp256K1Point(Secp256k1Point { .. }),
+                CoreTypeConcrete::Starknet(StarknetTypeConcrete::Secp256Point(
+                    Secp256PointTypeConcrete::K1(_),
+                )),
+            )
+            | (
+                Self::Secp256R1Point(Secp256r1Point { .. }),
+                CoreTypeConcrete::Starknet(StarknetTypeConcrete::Secp256Point(
+                    Secp256PointTypeConcrete::R1(_),
+                )),
+            ) => {}
+
+            (Self::BoundedInt { value, .. }, CoreTypeConcrete::BoundedInt(info)) => {
+                let value = value.to_bigint();
+                if value < info.range.lower || value >= info.range.upper {
+                    return Err(invalid_value(
+                        path,
+                        &format!(
+                            "value {value} is out of range [{}, {})",
+                            info.range.lower, info.range.upper
+                        ),
+                    ));
+                }
+            }
+
+            (Self::Array(values), CoreTypeConcrete::Array(info)) => {
+                if u32::try_from(values.len()).is_err() {
+                    return Err(invalid_value(path, "too many elements"));
+                }
+
+                for (idx, value) in values.iter().enumerate() {
+                    with_segment(path, format_args!("[{idx}]"), |path| {
+                        value.validate_at(path, &info.ty, registry)
+                    })?;
+                }
+            }
+            (Self::Struct { fields, .. }, CoreTypeConcrete::Struct(info)) => {
+                if fields.len() != info.members.len() {
+                    return with_segment(path, format_args!(".fields"), |path| {
+                        Err(invalid_value(
+                            path,
+                            &format!(
+                                "expected {} fields, found {}",
+                                info.members.len(),
+                                fields.len()
+                            ),
+                        ))
+                    });
+                }
+
+                for (idx, (field, member_ty)) in fields.iter().zip(&info.members).enumerate() {
+                    with_segment(path, format_args!(".fields[{idx}]"), |path| {
+                        field.validate_at(path, member_ty, registry)
+                    })?;
+                }
+            }
+            (Self::Enum { tag, value, .. }, CoreTypeConcrete::Enum(info)) => {
+                let Some(payload_ty) = info.variants.get(*tag) else {
+                    return with_segment(path, format_args!(".tag"), |path| {
+                        Err(invalid_value(
+                            path,
+                            &format!(
+                                "variant index {tag} is out of range for an enum with {} variants",
+                                info.variants.len()
+                            ),
+                        ))
+                    });
+                };
+
+                with_segment(path, format_args!(".value"), |path| {
+                    value.validate_at(path, payload_ty, registry)
+                })?;
+            }
+            (Self::Felt252Dict { value: map, .. }, CoreTypeConcrete::Felt252Dict(info)) => {
+                for (key, value) in map {
+                    with_segment(path, format_args!("[{key}]"), |path| {
+                        value.validate_at(path, &info.ty, registry)
+                    })?;
+                }
+            }
+            (Self::IntRange { x, y }, CoreTypeConcrete::IntRange(info)) => {
+                with_segment(path, format_args!(".x"), |path| {
+                    x.validate_at(path, &info.ty, registry)
+                })?;
+                with_segment(path, format_args!(".y"), |path| {
+                    y.validate_at(path, &info.ty, registry)
+                })?;
+            }
+
+            _ => {
+                return Err(invalid_value(
+                    path,
+                    &format!(
+                        "expected a value of type '{}', found {}",
+                        type_id
+                            .debug_name
+                            .as_ref()
+                            .map(|x| x.to_string())
+                            .unwrap_or_else(|| type_id.id.to_string()),
+                        self.kind_name(),
+                    ),
+                ))
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_zero(&self) -> bool {
+        match self {
+            Self::Felt252(value) | Self::BoundedInt { value, .. } => *value == Felt::ZERO,
+            Self::Uint8(value) => *value == 0,
+            Self::Uint16(value) => *value == 0,
+            Self::Uint32(value) => *value == 0,
+            Self::Uint64(value) => *value == 0,
+            Self::Uint128(value) => *value == 0,
+            Self::Sint8(value) => *value == 0,
+            Self::Sint16(value) => *value == 0,
+            Self::Sint32(value) => *value == 0,
+            Self::Sint64(value) => *value == 0,
+            Self::Sint128(value) => *value == 0,
+            Self::Struct { fields, .. } => !fields.is_empty() && fields.iter().all(Self::is_zero),
+            _ => false,
+        }
+    }
+
+    fn kind_name(&self) -> &'static str {
+        match self {
+            Self::Felt252(_) => "a felt252",
+            Self::Bytes31(_) => "a bytes31",
+            Self::Array(_) => "an array",
+            Self::Struct { .. } => "a struct",
+            Self::Enum { .. } => "an enum",
+            Self::Felt252Dict { .. } => "a felt252 dict",
+            Self::Uint8(_) => "a u8",
+            Self::Uint16(_) => "a u16",
+            Self::Uint32(_) => "a u32",
+            Self::Uint64(_) => "a u64",
+            Self::Uint128(_) => "a u128",
+            Self::Sint8(_) => "an i8",
+            Self::Sint16(_) => "an i16",
+            Self::Sint32(_) => "an i32",
+            Self::Sint64(_) => "an i64",
+            Self::Sint128(_) => "an i128",
+            Self::EcPoint(..) => "an ec point",
+            Self::EcState(..) => "an ec state",
+            Self::Secp256K1Point(_) => "a secp256k1 point",
+            Self::Secp256R1Point(_) => "a secp256r1 point",
+            Self::BoundedInt { .. } => "a bounded int",
+            Self::IntRange { .. } => "an int range",
+            Self::Null => "a null",
+        }
+    }
+}
+
+/// Append a segment to the path while running `f`.
+fn with_segment<T>(
+    path: &mut String,
+    segment: fmt::Arguments,
+    f: impl FnOnce(&mut String) -> T,
+) -> T {
+    let len = path.len();
+    // Writing into a `String` never fails.
+    let _ = path.write_fmt(segment);
+    let result = f(path);
+    path.truncate(len);
+
+    result
+}
+
+fn invalid_value(path: &str, reason: &str) -> Error {
+    Error::InvalidValue {
+        path: path.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cairo_lang_sierra::{extensions::utils::Range, ProgramParser};
+
+    /// Parse the program and return its registry and its last declared type.
+    fn registry_and_type(
+        program: &str,
+    ) -> (ProgramRegistry<CoreType, CoreLibfunc>, ConcreteTypeId) {
+        let program = ProgramParser::new().parse(program).unwrap();
+        let type_id = program.type_declarations.last().unwrap().id.clone();
+
+        (
+            ProgramRegistry::<CoreType, CoreLibfunc>::new(&program).unwrap(),
+            type_id,
+        )
+    }
+
+    fn error_path(result: Result<()>) -> String {
+        match result {
+            Err(Error::InvalidValue { path, .. }) => path,
+            result => panic!("expected an invalid value error, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn validate_nested() {
+        let (registry, type_id) = registry_and_type(
+            "type u8 = u8;
+            type felt252 = felt252;
+            type Unit = Struct<ut@Tuple>;
+            type Option = Enum<ut@Option, u8, Unit>;
+            type Pair = Struct<ut@Pair, felt252, Option>;
+            type Array = Array<Pair>;",
+        );
+
+        let pair = |tag, payload| Value::Struct {
+            fields: vec![
+                Value::Felt252(Felt::ONE),
+                Value::Enum {
+                    tag,
+                    value: Box::new(payload),
+                    debug_name: None,
+                },
+            ],
+            debug_name: None,
+        };
+        let unit = || Value::Struct {
+            fields: vec![],
+            debug_name: None,
+        };
+
+        Value::Array(vec![pair(0, Value::Uint8(1)), pair(1, unit())])
+            .validate(&type_id, &registry)
+            .unwrap();
+
+        assert_eq!(
+            error_path(
+                Value::Array(vec![pair(0, Value::Uint8(1)), pair(2, unit())])
+                    .validate(&type_id, &registry)
+            ),
+            "value[1].fields[1].tag",
+        );
+        assert_eq!(
+            error_path(Value::Array(vec![pair(0, Value::Uint16(1))]).validate(&type_id, &registry)),
+            "value[0].fields[1].value",
+        );
+        assert_eq!(
+            error_path(
+                Value::Array(vec![Value::Struct {
+                    fields: vec![Value::Felt252(Felt::ONE)],
+                    debug_name: None,
+                }])
+                .validate(&type_id, &registry)
+            ),
+            "value[0].fields",
+        );
+    }
+
+    #[test]
+    fn validate_bounded_int_and_non_zero() {
+        let (registry, type_id) = registry_and_type(
+            "type BoundedInt = BoundedInt<0, 510>;
+            type NonZero = NonZero<BoundedInt>;",
+        );
+
+        let bounded_int = |value: u32| Value::BoundedInt {
+            value: Felt::from(value),
+            range: Range {
+                lower: 0.into(),
+                upper: 511.into(),
+            },
+        };
+
+        bounded_int(16).validate(&type_id, &registry).unwrap();
+        assert_eq!(
+            error_path(bounded_int(511).validate(&type_id, &registry)),
+            "value"
+        );
+        assert_eq!(
+            error_path(bounded_int(0).validate(&type_id, &registry)),
+            "value"
+        );
+    }
+}
@@ -35,7 +35,7 @@ use cairo_lang_utils::Upcast;
 use cairo_native::{
     context::NativeContext,
     execution_result::{ContractExecutionResult, ExecutionResult},
-    executor::{AotContractExecutor, JitNativeExecutor},
+    executor::{AotContractExecutor, EntryPointKind, JitNativeExecutor},
     starknet::{DummySyscallHandler, StarknetSyscallHandler},
     utils::{find_entry_point_by_idx, HALF_PRIME, PRIME},
     OptLevel, Value,
@@ -462,6 +462,7 @@ pub fn run_native_starknet_aot_contract(
         // substract ENTRY_POINT_COST so gas matches
         .run(
             Felt::from(selector),
+            EntryPointKind::External,
             args,
             u64::MAX - ENTRY_POINT_COST as u64,
             None,